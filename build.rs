@@ -1,13 +1,42 @@
 fn main() {
+    // Cargo sets the `cfg` of the build script for the host, use the target
+    // configuration instead so that cross compilation works.
+    let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap();
     let mut build = cc::Build::new();
     build.file("src/registers/registers.S");
-    #[cfg(target_arch = "x86_64")]
-    build.define("UNWIND_ARCH_X86_64", "");
-    #[cfg(target_arch = "aarch64")]
-    build.define("UNWIND_ARCH_AARCH64", "");
-    #[cfg(target_os = "linux")]
-    build.define("UNWIND_OS_LINUX", "");
-    #[cfg(target_os = "macos")]
-    build.define("UNWIND_OS_MACOS", "");
+    match arch.as_str() {
+        "x86" => build.define("UNWIND_ARCH_X86", ""),
+        "arm" => build.define("UNWIND_ARCH_ARM", ""),
+        "x86_64" => build.define("UNWIND_ARCH_X86_64", ""),
+        "aarch64" => build.define("UNWIND_ARCH_AARCH64", ""),
+        "riscv64" => build.define("UNWIND_ARCH_RISCV64", ""),
+        _ => &mut build,
+    };
+    match os.as_str() {
+        "linux" => build.define("UNWIND_OS_LINUX", ""),
+        "macos" => build.define("UNWIND_OS_MACOS", ""),
+        _ => &mut build,
+    };
     build.compile("registers");
+    println!("cargo:rerun-if-changed=src/registers/registers.S");
+
+    // Functions with signed return addresses for tests/pauth.rs, linked into
+    // the tests only.
+    if arch == "aarch64" && os == "linux" {
+        cc::Build::new()
+            .file("tests/pauth.c")
+            .flag("-mbranch-protection=standard")
+            .cargo_metadata(false)
+            .compile("pauth");
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        println!("cargo:rustc-link-arg-tests={}/libpauth.a", out_dir);
+        println!("cargo:rerun-if-changed=tests/pauth.c");
+    }
+
+    // The C++ library loaded by tests/itanium.rs must resolve the `_Unwind_*`
+    // functions to the ones of the test executable.
+    if std::env::var_os("CARGO_FEATURE_ITANIUM_ABI").is_some() && os == "linux" {
+        println!("cargo:rustc-link-arg-tests=-rdynamic");
+    }
 }
@@ -0,0 +1,22 @@
+fn main() {
+    // The API only exists on Linux.
+    if std::env::var("CARGO_CFG_TARGET_OS").unwrap() != "linux" {
+        return;
+    }
+    let config = cbindgen::Config::from_file("cbindgen.toml").unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/lib.rs")
+        .generate()
+        .expect("generate libunwind.h");
+    // The header is generated out of the source tree, tests/c_api.rs checks
+    // that the one in include/ is up to date. It is only rewritten on demand.
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    bindings.write_to_file(out_dir.join("include/libunwind.h"));
+    if std::env::var_os("UNWIND_CAPI_UPDATE_HEADER").is_some() {
+        bindings.write_to_file("include/libunwind.h");
+    }
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-env-changed=UNWIND_CAPI_UPDATE_HEADER");
+}
@@ -0,0 +1,150 @@
+/* The libunwind compatible C API of the unwind crate. */
+
+#ifndef UNWIND_LIBUNWIND_H
+#define UNWIND_LIBUNWIND_H
+
+/* Generated by capi/build.rs with cbindgen, don't edit. */
+
+#include <stdint.h>
+#define UNW_ALIGNED(n) __attribute__((aligned(n)))
+
+// No error.
+#define UNW_ESUCCESS 0
+
+// Unspecified (general) error.
+#define UNW_EUNSPEC -6540
+
+// Out of memory.
+#define UNW_ENOMEM -6541
+
+// Bad register number.
+#define UNW_EBADREG -6542
+
+// Attempt to write a read-only register.
+#define UNW_EREADONLYREG -6543
+
+// Stop unwinding.
+#define UNW_ESTOPUNWIND -6544
+
+// Invalid IP.
+#define UNW_EINVALIDIP -6545
+
+// Bad frame.
+#define UNW_EBADFRAME -6546
+
+// Unsupported operation or bad value.
+#define UNW_EINVAL -6547
+
+// Unwind info has unsupported version.
+#define UNW_EBADVERSION -6548
+
+// No unwind info found.
+#define UNW_ENOINFO -6549
+
+// A flag of `unw_init_local2`, the ucontext was passed to a signal handler.
+#define UNW_INIT_SIGNAL_FRAME 1
+
+// The size of `unw_context_t` in 64-bit words.
+#define UNW_CONTEXT_SIZE 128
+
+// The size of `unw_cursor_t` in 64-bit words.
+#define UNW_CURSOR_SIZE 136
+
+// The registers saved by `unw_getcontext`.
+typedef struct UNW_ALIGNED(16) unw_context_t {
+  uint64_t data[UNW_CONTEXT_SIZE];
+} unw_context_t;
+
+// The state of an unwinding, the registers of the current frame.
+typedef struct UNW_ALIGNED(16) unw_cursor_t {
+  uint64_t data[UNW_CURSOR_SIZE];
+} unw_cursor_t;
+
+// A DWARF register number, or `UNW_REG_IP` or `UNW_REG_SP`.
+typedef int unw_regnum_t;
+
+// A machine word, the type of register values.
+typedef uintptr_t unw_word_t;
+
+// The function containing the IP of a frame, see `unw_get_proc_info`.
+typedef struct unw_proc_info_t {
+  // Address of the first instruction of the function.
+  unw_word_t start_ip;
+  // Address right after the last instruction of the function.
+  unw_word_t end_ip;
+  // Language-specific data area, 0 if none.
+  unw_word_t lsda;
+  // Personality routine, 0 if none.
+  unw_word_t handler;
+  // Always 0.
+  unw_word_t gp;
+  // Always 0.
+  unw_word_t flags;
+  // Always 0.
+  uint32_t format;
+  // Always 0.
+  uint32_t unwind_info_size;
+  // Always 0.
+  unw_word_t unwind_info;
+  // Always 0.
+  unw_word_t extra;
+} unw_proc_info_t;
+
+// The program counter.
+#define UNW_REG_IP -1
+
+// The stack pointer.
+#define UNW_REG_SP -2
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Saves the registers of the caller in `context`. Returns UNW_ESUCCESS.
+int unw_getcontext(struct unw_context_t *context);
+
+// Initializes `cursor` to unwind from the registers saved in `context` by
+// `unw_getcontext`. Returns UNW_ESUCCESS.
+int unw_init_local(struct unw_cursor_t *cursor, struct unw_context_t *context);
+
+// Initializes `cursor` to unwind from `ucontext`, the `ucontext_t` passed
+// to a signal handler or filled by `getcontext(3)`. With the
+// UNW_INIT_SIGNAL_FRAME flag, the IP of the first frame is the instruction
+// that was interrupted by the signal. Without it, the IP is a return
+// address, and its function is looked up at the call before it.
+//
+// Returns UNW_ESUCCESS, or UNW_EINVAL if `flags` is unknown or `ucontext`
+// is invalid.
+int unw_init_local2(struct unw_cursor_t *cursor, void *ucontext, int flags);
+
+// Unwinds `cursor` to the caller of its current frame. Returns 1 on
+// success, 0 if the current frame is the last one, or UNW_EBADFRAME if the
+// unwind information can't be used.
+int unw_step(struct unw_cursor_t *cursor);
+
+// Stores register `regnum` of the current frame of `cursor` in `value`.
+// Returns UNW_ESUCCESS, or UNW_EBADREG if there is no such register.
+int unw_get_reg(struct unw_cursor_t *cursor, unw_regnum_t regnum, unw_word_t *value);
+
+// Sets register `regnum` of the current frame of `cursor` to `value`.
+// Setting UNW_REG_IP also pops the arguments pushed at the current IP, as
+// the code at `value` expects, e.g. a landing pad.
+//
+// Returns UNW_ESUCCESS, or UNW_EBADREG if there is no such register.
+int unw_set_reg(struct unw_cursor_t *cursor, unw_regnum_t regnum, unw_word_t value);
+
+// Stores the function containing the IP of the current frame of `cursor`
+// in `info`. Returns UNW_ESUCCESS, or UNW_ENOINFO if it has no unwind
+// information.
+int unw_get_proc_info(struct unw_cursor_t *cursor, struct unw_proc_info_t *info);
+
+// Stores the IPs of at most `size` frames of the current stack in
+// `buffer`, starting with the caller of `unw_backtrace`. Returns the number
+// of IPs stored.
+int unw_backtrace(void **buffer, int size);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* UNWIND_LIBUNWIND_H */
@@ -0,0 +1,268 @@
+//! A C API compatible with the local unwinding API of LLVM libunwind and
+//! nongnu libunwind (`unw_getcontext`, `unw_init_local`, `unw_step`, ...),
+//! built on [UnwindCursor] and [Registers] of the unwind crate.
+//!
+//! The crate builds a shared and a static library, and `include/libunwind.h`
+//! is generated from this file by build.rs. Like the unwind crate, stepping
+//! doesn't allocate or take locks, so it can be used in signal handlers.
+//!
+//! Register numbers are the DWARF register numbers, [UNW_REG_IP] and
+//! [UNW_REG_SP] select the program counter and the stack pointer.
+
+#![cfg(target_os = "linux")]
+#![allow(non_camel_case_types, clippy::missing_safety_doc)]
+
+use libc::{c_int, c_void};
+use std::arch::naked_asm;
+use std::mem::{align_of, size_of};
+use unwind::{unwind_init_registers, RegisterFile, Registers, UnwindCursor};
+
+/// A machine word, the type of register values.
+pub type unw_word_t = usize;
+
+/// A DWARF register number, or `UNW_REG_IP` or `UNW_REG_SP`.
+pub type unw_regnum_t = c_int;
+
+/// No error.
+pub const UNW_ESUCCESS: c_int = 0;
+/// Unspecified (general) error.
+pub const UNW_EUNSPEC: c_int = -6540;
+/// Out of memory.
+pub const UNW_ENOMEM: c_int = -6541;
+/// Bad register number.
+pub const UNW_EBADREG: c_int = -6542;
+/// Attempt to write a read-only register.
+pub const UNW_EREADONLYREG: c_int = -6543;
+/// Stop unwinding.
+pub const UNW_ESTOPUNWIND: c_int = -6544;
+/// Invalid IP.
+pub const UNW_EINVALIDIP: c_int = -6545;
+/// Bad frame.
+pub const UNW_EBADFRAME: c_int = -6546;
+/// Unsupported operation or bad value.
+pub const UNW_EINVAL: c_int = -6547;
+/// Unwind info has unsupported version.
+pub const UNW_EBADVERSION: c_int = -6548;
+/// No unwind info found.
+pub const UNW_ENOINFO: c_int = -6549;
+
+/// The program counter.
+pub const UNW_REG_IP: unw_regnum_t = -1;
+/// The stack pointer.
+pub const UNW_REG_SP: unw_regnum_t = -2;
+
+/// A flag of `unw_init_local2`, the ucontext was passed to a signal handler.
+pub const UNW_INIT_SIGNAL_FRAME: c_int = 1;
+
+/// The size of `unw_context_t` in 64-bit words.
+pub const UNW_CONTEXT_SIZE: usize = 128;
+/// The size of `unw_cursor_t` in 64-bit words.
+pub const UNW_CURSOR_SIZE: usize = 136;
+
+/// The registers saved by `unw_getcontext`.
+#[repr(C, align(16))]
+pub struct unw_context_t {
+    data: [u64; UNW_CONTEXT_SIZE],
+}
+
+/// The state of an unwinding, the registers of the current frame.
+#[repr(C, align(16))]
+pub struct unw_cursor_t {
+    data: [u64; UNW_CURSOR_SIZE],
+}
+
+/// The function containing the IP of a frame, see `unw_get_proc_info`.
+#[repr(C)]
+pub struct unw_proc_info_t {
+    /// Address of the first instruction of the function.
+    pub start_ip: unw_word_t,
+    /// Address right after the last instruction of the function.
+    pub end_ip: unw_word_t,
+    /// Language-specific data area, 0 if none.
+    pub lsda: unw_word_t,
+    /// Personality routine, 0 if none.
+    pub handler: unw_word_t,
+    /// Always 0.
+    pub gp: unw_word_t,
+    /// Always 0.
+    pub flags: unw_word_t,
+    /// Always 0.
+    pub format: u32,
+    /// Always 0.
+    pub unwind_info_size: u32,
+    /// Always 0.
+    pub unwind_info: unw_word_t,
+    /// Always 0.
+    pub extra: unw_word_t,
+}
+
+/// What `unw_cursor_t` holds.
+struct Cursor {
+    registers: Registers,
+    cursor: UnwindCursor,
+}
+
+const _: () = assert!(size_of::<Registers>() <= size_of::<unw_context_t>());
+const _: () = assert!(align_of::<Registers>() <= align_of::<unw_context_t>());
+const _: () = assert!(size_of::<Cursor>() <= size_of::<unw_cursor_t>());
+const _: () = assert!(align_of::<Cursor>() <= align_of::<unw_cursor_t>());
+
+impl unw_cursor_t {
+    unsafe fn init(cursor: *mut unw_cursor_t, registers: Registers) {
+        (cursor as *mut Cursor).write(Cursor {
+            registers,
+            cursor: UnwindCursor::new(),
+        });
+    }
+
+    unsafe fn get<'a>(cursor: *mut unw_cursor_t) -> &'a mut Cursor {
+        &mut *(cursor as *mut Cursor)
+    }
+}
+
+/// Converts a register number to the one of the unwind crate.
+fn regnum(regnum: unw_regnum_t) -> usize {
+    // UNW_REG_IP and UNW_REG_SP are usize::MAX and usize::MAX - 1.
+    regnum as isize as usize
+}
+
+fn error_code(err: &unwind::Error) -> c_int {
+    match err {
+        unwind::Error::InvalidRegister(_) => UNW_EBADREG,
+        unwind::Error::InvalidUcontext => UNW_EINVAL,
+        _ => UNW_EBADFRAME,
+    }
+}
+
+/// Saves the registers of the caller in `context`. Returns UNW_ESUCCESS.
+#[no_mangle]
+#[unsafe(naked)]
+pub unsafe extern "C" fn unw_getcontext(context: *mut unw_context_t) -> c_int {
+    // A tail call, so that the registers are the ones of the caller, which
+    // `unwind_init_registers` returns 0 to.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    naked_asm!("jmp {}", sym unwind_init_registers);
+    #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+    naked_asm!("b {}", sym unwind_init_registers);
+    #[cfg(target_arch = "riscv64")]
+    naked_asm!("tail {}", sym unwind_init_registers);
+}
+
+/// Initializes `cursor` to unwind from the registers saved in `context` by
+/// `unw_getcontext`. Returns UNW_ESUCCESS.
+#[no_mangle]
+pub unsafe extern "C" fn unw_init_local(cursor: *mut unw_cursor_t, context: *mut unw_context_t) -> c_int {
+    unw_cursor_t::init(cursor, *(context as *const Registers));
+    UNW_ESUCCESS
+}
+
+/// Initializes `cursor` to unwind from `ucontext`, the `ucontext_t` passed
+/// to a signal handler or filled by `getcontext(3)`. With the
+/// UNW_INIT_SIGNAL_FRAME flag, the IP of the first frame is the instruction
+/// that was interrupted by the signal. Without it, the IP is a return
+/// address, and its function is looked up at the call before it.
+///
+/// Returns UNW_ESUCCESS, or UNW_EINVAL if `flags` is unknown or `ucontext`
+/// is invalid.
+#[no_mangle]
+pub unsafe extern "C" fn unw_init_local2(cursor: *mut unw_cursor_t, ucontext: *mut c_void, flags: c_int) -> c_int {
+    if flags & !UNW_INIT_SIGNAL_FRAME != 0 {
+        return UNW_EINVAL;
+    }
+    match Registers::from_ucontext(ucontext) {
+        Some(registers) => {
+            unw_cursor_t::init(cursor, registers);
+            let c = unw_cursor_t::get(cursor);
+            c.cursor.set_pc_is_exact(flags & UNW_INIT_SIGNAL_FRAME != 0);
+            UNW_ESUCCESS
+        }
+        None => UNW_EINVAL,
+    }
+}
+
+/// Unwinds `cursor` to the caller of its current frame. Returns 1 on
+/// success, 0 if the current frame is the last one, or UNW_EBADFRAME if the
+/// unwind information can't be used.
+#[no_mangle]
+pub unsafe extern "C" fn unw_step(cursor: *mut unw_cursor_t) -> c_int {
+    let c = unw_cursor_t::get(cursor);
+    match c.cursor.step(&mut c.registers) {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(err) => error_code(&err),
+    }
+}
+
+/// Stores register `regnum` of the current frame of `cursor` in `value`.
+/// Returns UNW_ESUCCESS, or UNW_EBADREG if there is no such register.
+#[no_mangle]
+pub unsafe extern "C" fn unw_get_reg(cursor: *mut unw_cursor_t, regnum: unw_regnum_t, value: *mut unw_word_t) -> c_int {
+    let c = unw_cursor_t::get(cursor);
+    match c.registers.get(self::regnum(regnum)) {
+        Some(v) => {
+            *value = v as unw_word_t;
+            UNW_ESUCCESS
+        }
+        None => UNW_EBADREG,
+    }
+}
+
+/// Sets register `regnum` of the current frame of `cursor` to `value`.
+/// Setting UNW_REG_IP also pops the arguments pushed at the current IP, as
+/// the code at `value` expects, e.g. a landing pad.
+///
+/// Returns UNW_ESUCCESS, or UNW_EBADREG if there is no such register.
+#[no_mangle]
+pub unsafe extern "C" fn unw_set_reg(cursor: *mut unw_cursor_t, regnum: unw_regnum_t, value: unw_word_t) -> c_int {
+    let c = unw_cursor_t::get(cursor);
+    let result = match self::regnum(regnum) {
+        unwind::arch::UNW_REG_IP => c.cursor.set_ip(&mut c.registers, value as u64),
+        n => c.registers.set(n, value as u64),
+    };
+    match result {
+        Ok(()) => UNW_ESUCCESS,
+        Err(err) => error_code(&err),
+    }
+}
+
+/// Stores the function containing the IP of the current frame of `cursor`
+/// in `info`. Returns UNW_ESUCCESS, or UNW_ENOINFO if it has no unwind
+/// information.
+#[no_mangle]
+pub unsafe extern "C" fn unw_get_proc_info(cursor: *mut unw_cursor_t, info: *mut unw_proc_info_t) -> c_int {
+    let c = unw_cursor_t::get(cursor);
+    let Ok(Some(proc_info)) = c.cursor.proc_info(&c.registers) else {
+        return UNW_ENOINFO;
+    };
+    info.write(unw_proc_info_t {
+        start_ip: proc_info.start_ip as unw_word_t,
+        end_ip: proc_info.end_ip as unw_word_t,
+        lsda: proc_info.lsda as unw_word_t,
+        handler: proc_info.personality as unw_word_t,
+        gp: 0,
+        flags: 0,
+        format: 0,
+        unwind_info_size: 0,
+        unwind_info: 0,
+        extra: 0,
+    });
+    UNW_ESUCCESS
+}
+
+/// Stores the IPs of at most `size` frames of the current stack in
+/// `buffer`, starting with the caller of `unw_backtrace`. Returns the number
+/// of IPs stored.
+#[no_mangle]
+#[inline(never)]
+pub unsafe extern "C" fn unw_backtrace(buffer: *mut *mut c_void, size: c_int) -> c_int {
+    let mut registers = Registers::default();
+    unwind_init_registers(&mut registers);
+    let mut cursor = UnwindCursor::new();
+    let mut n = 0;
+    // Step directly, so that we skip `unw_backtrace`.
+    while n < size && matches!(cursor.step(&mut registers), Ok(true)) {
+        *buffer.add(n as usize) = registers.pc() as *mut c_void;
+        n += 1;
+    }
+    n
+}
@@ -0,0 +1,148 @@
+// Exercises the C API, built and run by c_api.rs.
+#define _GNU_SOURCE
+#include <libunwind.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <ucontext.h>
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                 \
+            exit(1);                                                        \
+        }                                                                   \
+    } while (0)
+
+int main(void);
+
+// Returns whether a frame of `cursor` or its callers is in `f`.
+static int find_function(unw_cursor_t *cursor, void *f) {
+    for (;;) {
+        unw_proc_info_t info;
+        if (unw_get_proc_info(cursor, &info) == UNW_ESUCCESS &&
+            info.start_ip == (unw_word_t)f) {
+            return 1;
+        }
+        int ret = unw_step(cursor);
+        CHECK(ret >= 0);
+        if (ret == 0) {
+            return 0;
+        }
+    }
+}
+
+__attribute__((noinline)) static void test_init_local(void) {
+    unw_context_t context;
+    unw_cursor_t cursor;
+    CHECK(unw_getcontext(&context) == UNW_ESUCCESS);
+    CHECK(unw_init_local(&cursor, &context) == UNW_ESUCCESS);
+
+    unw_word_t ip, sp, v;
+    CHECK(unw_get_reg(&cursor, UNW_REG_IP, &ip) == UNW_ESUCCESS);
+    CHECK(unw_get_reg(&cursor, UNW_REG_SP, &sp) == UNW_ESUCCESS);
+    CHECK(ip > (unw_word_t)test_init_local);
+    CHECK(sp <= (unw_word_t)&context);
+    CHECK(unw_get_reg(&cursor, 10000, &v) == UNW_EBADREG);
+    CHECK(unw_set_reg(&cursor, 10000, 0) == UNW_EBADREG);
+
+    // The first frame is the one calling unw_getcontext.
+    unw_proc_info_t info;
+    CHECK(unw_get_proc_info(&cursor, &info) == UNW_ESUCCESS);
+    CHECK(info.start_ip == (unw_word_t)test_init_local);
+    CHECK(info.start_ip < ip && ip < info.end_ip);
+
+    CHECK(unw_step(&cursor) == 1);
+    CHECK(unw_get_reg(&cursor, UNW_REG_SP, &v) == UNW_ESUCCESS);
+    CHECK(v > sp);
+    CHECK(find_function(&cursor, (void *)main));
+}
+
+__attribute__((noinline)) static void test_set_reg(void) {
+    unw_context_t context;
+    unw_cursor_t cursor;
+    unw_getcontext(&context);
+    unw_init_local(&cursor, &context);
+
+    unw_word_t sp, v;
+    CHECK(unw_get_reg(&cursor, UNW_REG_SP, &sp) == UNW_ESUCCESS);
+    CHECK(unw_set_reg(&cursor, UNW_REG_SP, sp + 16) == UNW_ESUCCESS);
+    CHECK(unw_get_reg(&cursor, UNW_REG_SP, &v) == UNW_ESUCCESS);
+    CHECK(v == sp + 16);
+    CHECK(unw_set_reg(&cursor, UNW_REG_IP, (unw_word_t)test_set_reg + 1) == UNW_ESUCCESS);
+    CHECK(unw_get_reg(&cursor, UNW_REG_IP, &v) == UNW_ESUCCESS);
+    CHECK(v == (unw_word_t)test_set_reg + 1);
+}
+
+__attribute__((noinline)) static void test_init_local2_flags(void) {
+    ucontext_t ucontext;
+    unw_cursor_t cursor;
+    unw_proc_info_t info;
+    CHECK(getcontext(&ucontext) == 0);
+
+    // Without UNW_INIT_SIGNAL_FRAME, the IP is a return address, so an IP at
+    // the start of a function is looked up in the code before it.
+    CHECK(unw_init_local2(&cursor, &ucontext, 0) == UNW_ESUCCESS);
+    CHECK(unw_set_reg(&cursor, UNW_REG_IP, (unw_word_t)test_init_local2_flags) == UNW_ESUCCESS);
+    CHECK(unw_get_proc_info(&cursor, &info) != UNW_ESUCCESS ||
+          info.start_ip != (unw_word_t)test_init_local2_flags);
+
+    CHECK(unw_init_local2(&cursor, &ucontext, UNW_INIT_SIGNAL_FRAME) == UNW_ESUCCESS);
+    CHECK(unw_set_reg(&cursor, UNW_REG_IP, (unw_word_t)test_init_local2_flags) == UNW_ESUCCESS);
+    CHECK(unw_get_proc_info(&cursor, &info) == UNW_ESUCCESS);
+    CHECK(info.start_ip == (unw_word_t)test_init_local2_flags);
+}
+
+static volatile int signal_result = -1;
+
+static void signal_handler(int sig, siginfo_t *info, void *ucontext) {
+    (void)sig;
+    (void)info;
+    unw_cursor_t cursor;
+    if (unw_init_local2(&cursor, ucontext, 2) != UNW_EINVAL ||
+        unw_init_local2(&cursor, ucontext, UNW_INIT_SIGNAL_FRAME) != UNW_ESUCCESS) {
+        signal_result = 0;
+        return;
+    }
+    // The handler is skipped, the first frame is the interrupted one.
+    signal_result = find_function(&cursor, (void *)main);
+}
+
+__attribute__((noinline)) static void test_init_local2(void) {
+    struct sigaction action;
+    memset(&action, 0, sizeof(action));
+    action.sa_sigaction = signal_handler;
+    action.sa_flags = SA_SIGINFO;
+    CHECK(sigaction(SIGUSR1, &action, NULL) == 0);
+    CHECK(raise(SIGUSR1) == 0);
+    CHECK(signal_result == 1);
+}
+
+__attribute__((noinline)) static void test_backtrace(void) {
+    void *buffer[64];
+    int n = unw_backtrace(buffer, 64);
+    CHECK(n > 2 && n <= 64);
+    // The first entry is in the caller.
+    unw_context_t context;
+    unw_cursor_t cursor;
+    unw_getcontext(&context);
+    unw_init_local(&cursor, &context);
+    unw_proc_info_t info;
+    CHECK(unw_get_proc_info(&cursor, &info) == UNW_ESUCCESS);
+    CHECK(info.start_ip < (unw_word_t)buffer[0] && (unw_word_t)buffer[0] < info.end_ip);
+
+    CHECK(unw_backtrace(buffer, 1) == 1);
+    CHECK(unw_backtrace(buffer, 0) == 0);
+}
+
+int main(void) {
+    test_init_local();
+    test_set_reg();
+    test_init_local2_flags();
+    test_init_local2();
+    test_backtrace();
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Checks that the checked-in header is the one generated by build.rs.
+#[test]
+fn test_header() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let generated = PathBuf::from(env!("OUT_DIR")).join("include/libunwind.h");
+    assert!(
+        std::fs::read(manifest_dir.join("include/libunwind.h")).unwrap() == std::fs::read(generated).unwrap(),
+        "include/libunwind.h is out of date, run `UNWIND_CAPI_UPDATE_HEADER=1 cargo build -p unwind-capi`"
+    );
+}
+
+/// Builds c_api.c against the header and the shared library, and runs it.
+/// The test needs a C compiler, it fails without one.
+#[test]
+fn test_c_api() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Cargo builds the libraries next to the test, in target/<profile>/deps.
+    let exe = std::env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap();
+    let program = lib_dir.join(format!("c_api-{}", std::process::id()));
+    let status = Command::new("cc")
+        .args(["-O1", "-g", "-Wall", "-Werror", "-o"])
+        .arg(&program)
+        .arg(manifest_dir.join("tests/c_api.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lunwind_capi")
+        .status()
+        .expect("can't run cc");
+    assert!(status.success());
+    // Cargo's LD_LIBRARY_PATH, which takes precedence over the rpath, also
+    // has target/<profile>, where `cargo build` leaves an older library.
+    let output = Command::new(&program).env("LD_LIBRARY_PATH", lib_dir).output().unwrap();
+    std::fs::remove_file(&program).unwrap();
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"ok\n");
+}
@@ -17,7 +17,9 @@ fn main() {
     }
 
     // Block until the signal handler finishes executing.
-    loop {}
+    loop {
+        std::thread::park();
+    }
 }
 
 #[no_mangle]
@@ -35,10 +35,10 @@ fn main() {
 fn frequency(v: i64) -> libc::itimerval {
     let interval = 1e6 as i64 / v;
     let it_interval = libc::timeval {
-        tv_sec: interval / 1e6 as i64,
+        tv_sec: (interval / 1e6 as i64) as _,
         tv_usec: (interval % 1e6 as i64) as _,
     };
-    let it_value = it_interval.clone();
+    let it_value = it_interval;
     libc::itimerval { it_interval, it_value }
 }
 
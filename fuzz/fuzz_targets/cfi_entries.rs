@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::fuzzing::{Entries, SliceAddressSpace};
+
+fuzz_target!(|data: &[u8]| {
+    let space = SliceAddressSpace::new(0x10000, data);
+    for _ in Entries::new(&space, space.start(), space.end()) {}
+    for _ in Entries::debug_frame(&space, space.start(), space.end()) {}
+});
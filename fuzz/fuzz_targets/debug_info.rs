@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::fuzzing::{package_sections, DebugSections, UnitIndex};
+
+fuzz_target!(|data: &[u8]| {
+    // .debug_info in the first half, the other sections in the second one.
+    let (info, rest) = data.split_at(data.len() / 2);
+    let sections = DebugSections {
+        info,
+        abbrev: rest,
+        str: rest,
+        line: rest,
+        line_str: rest,
+        ranges: rest,
+        rnglists: rest,
+        addr: rest,
+        str_offsets: rest,
+        cu_index: rest,
+    };
+    let pc = u64::from_ne_bytes(data.get(..8).map_or([0; 8], |b| b.try_into().unwrap()));
+    if let Ok(index) = UnitIndex::new(&sections) {
+        if let Some(unit) = index.find(pc) {
+            let _ = index.frames(&sections, unit, Some(&sections), pc);
+        }
+    }
+    let _ = package_sections(&sections, pc);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::symbolize::demangle;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = demangle(data, false);
+    let _ = demangle(data, true);
+});
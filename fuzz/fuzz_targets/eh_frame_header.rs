@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::fuzzing::{EhFrameHeader, SliceAddressSpace};
+
+const BASE: u64 = 0x10000;
+
+fuzz_target!(|data: &[u8]| {
+    let space = SliceAddressSpace::new(BASE, data);
+    if let Ok(header) = EhFrameHeader::decode(&space, space.start(), space.end()) {
+        // Probe a few targets across the address space.
+        for target in [0, BASE, BASE + data.len() as u64 / 2, space.end(), u64::MAX] {
+            let _ = header.search(&space, target, space.end());
+        }
+    }
+});
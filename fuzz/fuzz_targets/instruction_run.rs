@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::arch::{Aarch64, MAX_REGISTER_COUNT};
+use unwind::fuzzing::{run, CfiEntry, Entries, SliceAddressSpace};
+use unwind::GenericRegisters;
+
+fuzz_target!(|data: &[u8]| {
+    // The first 8 bytes select the pc offset inside the FDE, the rest is .eh_frame.
+    if data.len() < 8 {
+        return;
+    }
+    let (offset, eh_frame) = data.split_at(8);
+    let offset = u64::from_ne_bytes(offset.try_into().unwrap());
+    let space = SliceAddressSpace::new(0x10000, eh_frame);
+    for entry in Entries::new(&space, space.start(), space.end()).map_while(Result::ok) {
+        if let CfiEntry::FdeCie((fde, cie)) = entry {
+            // aarch64 has the most registers, and the only vendor opcode.
+            let pc = fde.pc_start.wrapping_add(offset);
+            let _ = run::<_, GenericRegisters<Aarch64>, MAX_REGISTER_COUNT>(&space, pc, &fde, &cie);
+        }
+    }
+});
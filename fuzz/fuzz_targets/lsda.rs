@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::fuzzing::SliceAddressSpace;
+use unwind::Lsda;
+
+fuzz_target!(|data: &[u8]| {
+    let space = SliceAddressSpace::new(0x10000, data);
+    if let Ok(lsda) = Lsda::decode(&space, space.start(), 0x20000, space.end()) {
+        for call_site in lsda.call_sites(&space).flatten() {
+            lsda.actions(&space, &call_site).for_each(drop);
+        }
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::symbolize::ModuleMap;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(map) = ModuleMap::decode(data) {
+        assert_eq!(ModuleMap::decode(&map.encode()).unwrap(), map);
+    }
+});
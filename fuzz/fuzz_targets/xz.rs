@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unwind::fuzzing::xz_decompress;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = xz_decompress(data);
+});
@@ -0,0 +1,445 @@
+//! Descriptions of the architectures whose stacks can be unwound.
+//!
+//! The in-process unwinder only deals with the architecture it was compiled
+//! for, through [Registers](crate::Registers). Stacks of other processes,
+//! core dumps or stack snapshots may come from any supported architecture,
+//! which is picked at runtime by instantiating the DWARF unwinder with one of
+//! the types here, see [GenericRegisters](crate::GenericRegisters).
+//!
+//! All supported architectures are little-endian, and define the CFA as the
+//! value of the stack pointer in the caller right before the call, so the
+//! caller's stack pointer is restored to the CFA unless the CFI says
+//! otherwise.
+
+use std::fmt::Debug;
+use std::ops::Range;
+
+pub use crate::registers::consts::*;
+
+/// An architecture, as seen by the DWARF unwinder.
+///
+/// Registers are identified by their DWARF register numbers, as defined by
+/// the psABI of the architecture and listed by the `UNW_*` constants.
+pub trait Arch: Debug + Default + Copy + Eq + Send + Sync + 'static {
+    /// The smallest unsigned integer type holding every DWARF register number
+    /// of the architecture, which keeps register rules compact.
+    type RegisterNum: Debug + Default + Copy + Eq + Into<usize> + TryFrom<usize>;
+
+    /// Highest DWARF register number.
+    const MAX_REGISTER_NUM: usize;
+
+    /// Number of DWARF register numbers up to [Arch::MAX_REGISTER_NUM] that
+    /// name a register, which bounds the rules a frame can have.
+    const REGISTER_COUNT: usize;
+
+    /// Size of a pointer in bytes, 4 or 8.
+    const POINTER_SIZE: u8;
+
+    /// The stack pointer.
+    const SP: usize;
+
+    /// The register that holds the return address on entry to a function.
+    /// The CIE names the return address column, this is what compilers use.
+    const RA: usize;
+
+    /// The program counter, if it has a DWARF register number.
+    const PC: Option<usize>;
+
+    /// The pseudo register telling whether the return address is signed,
+    /// toggled by DW_CFA_AARCH64_negate_ra_state.
+    const RA_SIGN_STATE: Option<usize> = None;
+
+    /// Floating-point registers, read and written as f64.
+    const FLOAT_REGISTERS: Range<usize> = 0..0;
+
+    /// Vector registers, read and written as u128. They can share their
+    /// numbers with [Arch::FLOAT_REGISTERS], which then alias their low bits.
+    const VECTOR_REGISTERS: Range<usize> = 0..0;
+
+    /// Other names of registers, e.g. "fp" and "lr" for x29 and x30 on
+    /// aarch64, accepted by [Arch::register_number].
+    const REGISTER_ALIASES: &'static [(&'static str, usize)] = &[];
+
+    /// Returns whether `n` is a general register, pseudo registers included.
+    fn valid_register(n: usize) -> bool;
+
+    /// Returns the name of register `n`, as used by the assembler of the
+    /// architecture. [UNW_REG_IP] and [UNW_REG_SP] are named too.
+    fn register_name(n: usize) -> Option<&'static str>;
+
+    /// Returns the DWARF register number of the register named `name`,
+    /// ignoring case. The inverse of [Arch::register_name], which also knows
+    /// [Arch::REGISTER_ALIASES].
+    fn register_number(name: &str) -> Option<usize> {
+        if let Some(&(_, n)) = Self::REGISTER_ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        {
+            return Some(n);
+        }
+        (0..=Self::MAX_REGISTER_NUM).find(|&n| Self::register_name(n).is_some_and(|s| s.eq_ignore_ascii_case(name)))
+    }
+
+    #[inline]
+    fn valid_float_register(n: usize) -> bool {
+        Self::FLOAT_REGISTERS.contains(&n)
+    }
+
+    #[inline]
+    fn valid_vector_register(n: usize) -> bool {
+        Self::VECTOR_REGISTERS.contains(&n)
+    }
+
+    /// Returns whether `n` names any register of the architecture.
+    #[inline]
+    fn valid_any_register(n: usize) -> bool {
+        n <= Self::MAX_REGISTER_NUM
+            && (Self::valid_register(n) || Self::valid_float_register(n) || Self::valid_vector_register(n))
+    }
+
+    /// Turns a return address restored from a frame whose return address is
+    /// signed into a plain code address.
+    #[inline]
+    fn strip_return_address(address: u64) -> u64 {
+        address
+    }
+}
+
+/// 32-bit x86, with the register numbers used by .eh_frame on Linux.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct X86;
+
+impl Arch for X86 {
+    type RegisterNum = u8;
+    const MAX_REGISTER_NUM: usize = UNW_X86_MAX_REG_NUM;
+    const REGISTER_COUNT: usize = 9;
+    const POINTER_SIZE: u8 = 4;
+    const SP: usize = UNW_X86_ESP;
+    const RA: usize = UNW_X86_EIP;
+    const PC: Option<usize> = Some(UNW_X86_EIP);
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        n <= UNW_X86_EIP
+    }
+
+    fn register_name(n: usize) -> Option<&'static str> {
+        const NAMES: [&str; 9] = ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "eip"];
+        match n {
+            UNW_REG_IP => Some("eip"),
+            UNW_REG_SP => Some("esp"),
+            _ => NAMES.get(n).copied(),
+        }
+    }
+}
+
+/// x86_64, also known as AMD64.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct X86_64;
+
+impl Arch for X86_64 {
+    type RegisterNum = u8;
+    const MAX_REGISTER_NUM: usize = UNW_X86_64_MAX_REG_NUM;
+    const REGISTER_COUNT: usize = 33;
+    const POINTER_SIZE: u8 = 8;
+    const SP: usize = UNW_X86_64_RSP;
+    const RA: usize = UNW_X86_64_RIP;
+    const PC: Option<usize> = Some(UNW_X86_64_RIP);
+    const VECTOR_REGISTERS: Range<usize> = UNW_X86_64_XMM0..UNW_X86_64_XMM15 + 1;
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        n <= UNW_X86_64_RIP
+    }
+
+    fn register_name(n: usize) -> Option<&'static str> {
+        const NAMES: [&str; 17] = [
+            "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14",
+            "r15", "rip",
+        ];
+        const XMM: [&str; 16] = [
+            "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12",
+            "xmm13", "xmm14", "xmm15",
+        ];
+        match n {
+            UNW_REG_IP => Some("rip"),
+            UNW_REG_SP => Some("rsp"),
+            UNW_X86_64_XMM0..=UNW_X86_64_XMM15 => Some(XMM[n - UNW_X86_64_XMM0]),
+            _ => NAMES.get(n).copied(),
+        }
+    }
+}
+
+/// 64-bit ARM. q0 ~ q31 share the numbers of d0 ~ d31.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Aarch64;
+
+impl Arch for Aarch64 {
+    type RegisterNum = u8;
+    const MAX_REGISTER_NUM: usize = UNW_ARM64_MAX_REG_NUM;
+    const REGISTER_COUNT: usize = 66;
+    const POINTER_SIZE: u8 = 8;
+    const SP: usize = UNW_ARM64_SP;
+    const RA: usize = UNW_ARM64_LR;
+    const PC: Option<usize> = Some(UNW_ARM64_PC);
+    const RA_SIGN_STATE: Option<usize> = Some(UNW_ARM64_RA_SIGN_STATE);
+    const FLOAT_REGISTERS: Range<usize> = UNW_ARM64_D0..UNW_ARM64_D31 + 1;
+    const VECTOR_REGISTERS: Range<usize> = UNW_ARM64_D0..UNW_ARM64_D31 + 1;
+    const REGISTER_ALIASES: &'static [(&'static str, usize)] = &[("fp", UNW_ARM64_FP), ("lr", UNW_ARM64_LR)];
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        n <= UNW_ARM64_PC || n == UNW_ARM64_RA_SIGN_STATE
+    }
+
+    fn register_name(n: usize) -> Option<&'static str> {
+        const X: [&str; 31] = [
+            "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15",
+            "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30",
+        ];
+        const V: [&str; 32] = [
+            "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "v10", "v11", "v12", "v13", "v14", "v15",
+            "v16", "v17", "v18", "v19", "v20", "v21", "v22", "v23", "v24", "v25", "v26", "v27", "v28", "v29", "v30",
+            "v31",
+        ];
+        match n {
+            UNW_REG_IP | UNW_ARM64_PC => Some("pc"),
+            UNW_REG_SP | UNW_ARM64_SP => Some("sp"),
+            UNW_ARM64_RA_SIGN_STATE => Some("ra_sign_state"),
+            UNW_ARM64_D0..=UNW_ARM64_D31 => Some(V[n - UNW_ARM64_D0]),
+            _ => X.get(n).copied(),
+        }
+    }
+
+    /// Strips the pointer authentication code. Unwinding the current process
+    /// uses the size of its address space, other processes are assumed to
+    /// use the default 48-bit one.
+    #[inline]
+    fn strip_return_address(address: u64) -> u64 {
+        #[cfg(target_arch = "aarch64")]
+        return crate::utils::strip_pac(address);
+        #[cfg(not(target_arch = "aarch64"))]
+        return crate::utils::strip_pac_with_va_bits(address, crate::utils::DEFAULT_VA_BITS);
+    }
+}
+
+/// 32-bit ARM. Only the DWARF unwinder is generic, .ARM.exidx tables are
+/// only used in process.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Arm;
+
+impl Arch for Arm {
+    type RegisterNum = u16;
+    const MAX_REGISTER_NUM: usize = UNW_ARM_MAX_REG_NUM;
+    const REGISTER_COUNT: usize = 48;
+    const POINTER_SIZE: u8 = 4;
+    const SP: usize = UNW_ARM_SP;
+    const RA: usize = UNW_ARM_LR;
+    const PC: Option<usize> = Some(UNW_ARM_PC);
+    const FLOAT_REGISTERS: Range<usize> = UNW_ARM_D0..UNW_ARM_D31 + 1;
+    const REGISTER_ALIASES: &'static [(&'static str, usize)] = &[
+        ("fp", UNW_ARM_R11),
+        ("ip", UNW_ARM_R12),
+        ("sp", UNW_ARM_SP),
+        ("lr", UNW_ARM_LR),
+        ("pc", UNW_ARM_PC),
+    ];
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        n <= UNW_ARM_R15
+    }
+
+    fn register_name(n: usize) -> Option<&'static str> {
+        const R: [&str; 16] = [
+            "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr", "pc",
+        ];
+        const D: [&str; 32] = [
+            "d0", "d1", "d2", "d3", "d4", "d5", "d6", "d7", "d8", "d9", "d10", "d11", "d12", "d13", "d14", "d15",
+            "d16", "d17", "d18", "d19", "d20", "d21", "d22", "d23", "d24", "d25", "d26", "d27", "d28", "d29", "d30",
+            "d31",
+        ];
+        match n {
+            UNW_REG_IP => Some("pc"),
+            UNW_REG_SP => Some("sp"),
+            UNW_ARM_D0..=UNW_ARM_D31 => Some(D[n - UNW_ARM_D0]),
+            _ => R.get(n).copied(),
+        }
+    }
+}
+
+/// 64-bit RISC-V with the D extension. The program counter has no DWARF
+/// register number.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Riscv64;
+
+impl Arch for Riscv64 {
+    type RegisterNum = u8;
+    const MAX_REGISTER_NUM: usize = UNW_RISCV_MAX_REG_NUM;
+    const REGISTER_COUNT: usize = 64;
+    const POINTER_SIZE: u8 = 8;
+    const SP: usize = UNW_RISCV_SP;
+    const RA: usize = UNW_RISCV_RA;
+    const PC: Option<usize> = None;
+    const FLOAT_REGISTERS: Range<usize> = UNW_RISCV_F0..UNW_RISCV_F31 + 1;
+    const REGISTER_ALIASES: &'static [(&'static str, usize)] = &[
+        ("pc", UNW_REG_IP),
+        ("zero", 0),
+        ("ra", 1),
+        ("sp", 2),
+        ("gp", 3),
+        ("tp", 4),
+        ("t0", 5),
+        ("t1", 6),
+        ("t2", 7),
+        ("s0", 8),
+        ("fp", 8),
+        ("s1", 9),
+        ("a0", 10),
+        ("a1", 11),
+        ("a2", 12),
+        ("a3", 13),
+        ("a4", 14),
+        ("a5", 15),
+        ("a6", 16),
+        ("a7", 17),
+        ("s2", 18),
+        ("s3", 19),
+        ("s4", 20),
+        ("s5", 21),
+        ("s6", 22),
+        ("s7", 23),
+        ("s8", 24),
+        ("s9", 25),
+        ("s10", 26),
+        ("s11", 27),
+        ("t3", 28),
+        ("t4", 29),
+        ("t5", 30),
+        ("t6", 31),
+    ];
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        n <= UNW_RISCV_X31
+    }
+
+    /// Integer registers are named x0 ~ x31, their ABI names are aliases.
+    fn register_name(n: usize) -> Option<&'static str> {
+        const X: [&str; 32] = [
+            "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15",
+            "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30",
+            "x31",
+        ];
+        const F: [&str; 32] = [
+            "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12", "f13", "f14", "f15",
+            "f16", "f17", "f18", "f19", "f20", "f21", "f22", "f23", "f24", "f25", "f26", "f27", "f28", "f29", "f30",
+            "f31",
+        ];
+        match n {
+            UNW_REG_IP => Some("pc"),
+            UNW_REG_SP => Some("x2"),
+            UNW_RISCV_F0..=UNW_RISCV_F31 => Some(F[n - UNW_RISCV_F0]),
+            _ => X.get(n).copied(),
+        }
+    }
+}
+
+/// The architecture of the current process.
+#[cfg(target_arch = "x86")]
+pub type NativeArch = X86;
+#[cfg(target_arch = "x86_64")]
+pub type NativeArch = X86_64;
+#[cfg(target_arch = "aarch64")]
+pub type NativeArch = Aarch64;
+#[cfg(target_arch = "arm")]
+pub type NativeArch = Arm;
+#[cfg(target_arch = "riscv64")]
+pub type NativeArch = Riscv64;
+
+/// The largest [Arch::REGISTER_COUNT] of all supported architectures.
+pub const MAX_REGISTER_COUNT: usize = max(
+    max(X86::REGISTER_COUNT, X86_64::REGISTER_COUNT),
+    max(
+        max(Aarch64::REGISTER_COUNT, Arm::REGISTER_COUNT),
+        Riscv64::REGISTER_COUNT,
+    ),
+);
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<A: Arch>() {
+        let count = (0..=A::MAX_REGISTER_NUM).filter(|&n| A::valid_any_register(n)).count();
+        assert_eq!(count, A::REGISTER_COUNT, "{:?}", A::default());
+        assert!(A::REGISTER_COUNT <= MAX_REGISTER_COUNT);
+        assert!(A::valid_register(A::SP));
+        assert!(A::valid_register(A::RA));
+        assert!(A::PC.is_none_or(A::valid_register));
+        assert!(A::RA_SIGN_STATE.is_none_or(A::valid_register));
+        assert!(!A::valid_any_register(A::MAX_REGISTER_NUM + 1));
+        assert!(A::RegisterNum::try_from(A::MAX_REGISTER_NUM).is_ok());
+        for n in 0..=A::MAX_REGISTER_NUM {
+            assert_eq!(
+                A::register_name(n).is_some(),
+                A::valid_any_register(n),
+                "{:?}: {}",
+                A::default(),
+                n
+            );
+            if let Some(name) = A::register_name(n) {
+                assert_eq!(A::register_number(name), Some(n));
+            }
+        }
+        assert!(A::register_name(UNW_REG_IP).is_some());
+        assert_eq!(A::register_name(UNW_REG_SP), A::register_name(A::SP));
+    }
+
+    #[test]
+    fn test_arch() {
+        check::<X86>();
+        check::<X86_64>();
+        check::<Aarch64>();
+        check::<Arm>();
+        check::<Riscv64>();
+        assert_eq!(MAX_REGISTER_COUNT, Aarch64::REGISTER_COUNT);
+    }
+
+    #[test]
+    fn test_register_names() {
+        assert_eq!(X86_64::register_name(UNW_X86_64_RBP), Some("rbp"));
+        assert_eq!(X86_64::register_number("RBP"), Some(UNW_X86_64_RBP));
+        assert_eq!(X86_64::register_name(UNW_X86_64_XMM15), Some("xmm15"));
+        assert_eq!(X86::register_name(UNW_REG_IP), Some("eip"));
+        assert_eq!(Aarch64::register_name(UNW_ARM64_X29), Some("x29"));
+        assert_eq!(Aarch64::register_number("fp"), Some(UNW_ARM64_X29));
+        assert_eq!(Aarch64::register_number("lr"), Some(UNW_ARM64_LR));
+        assert_eq!(Aarch64::register_name(UNW_ARM64_D0 + 8), Some("v8"));
+        assert_eq!(Aarch64::register_name(40), None);
+        assert_eq!(Arm::register_number("lr"), Some(UNW_ARM_LR));
+        assert_eq!(Arm::register_name(UNW_ARM_D31), Some("d31"));
+        assert_eq!(Riscv64::register_number("ra"), Some(UNW_RISCV_RA));
+        assert_eq!(Riscv64::register_number("s0"), Some(UNW_RISCV_X8));
+        assert_eq!(Riscv64::register_number("pc"), Some(UNW_REG_IP));
+        assert_eq!(Riscv64::register_name(UNW_REG_IP), Some("pc"));
+        assert_eq!(X86_64::register_number("x29"), None);
+    }
+
+    #[test]
+    fn test_strip_return_address() {
+        assert_eq!(
+            X86_64::strip_return_address(0x9c35 << 48 | 0x1000),
+            0x9c35 << 48 | 0x1000
+        );
+        assert_eq!(Aarch64::strip_return_address(0x9c35 << 48 | 0x1000), 0x1000);
+    }
+}
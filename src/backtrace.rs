@@ -0,0 +1,152 @@
+//! A facade mirroring the tracing API of the [backtrace] crate, `trace`,
+//! `trace_unsynchronized` and `Frame`, on top of [UnwindCursor].
+//!
+//! Code calling `backtrace::trace` switches to this implementation with a
+//! `use unwind::backtrace;`. Addresses are resolved into symbols with
+//! `backtrace::resolve(frame.ip(), ...)`, since `backtrace::resolve_frame`
+//! only takes the frames of the backtrace crate.
+//!
+//! Unlike the backtrace crate, tracing neither allocates nor takes locks, so
+//! `trace` can be called from signal handlers. As with [UnwindCursor], the
+//! frames of shared libraries are only traced with the `trace-shared-libs`
+//! feature.
+//!
+//! [backtrace]: https://docs.rs/backtrace
+
+use crate::{unwind_init_registers, Registers, UnwindCursor};
+use libc::c_void;
+use std::fmt;
+
+/// A frame of a backtrace, yielded to the closure of [trace].
+#[derive(Clone)]
+pub struct Frame {
+    ip: u64,
+    registers: Registers,
+    // The PC to look the function of the frame up with.
+    lookup_pc: u64,
+    cursor: UnwindCursor,
+}
+
+impl Frame {
+    /// Returns the current instruction pointer of this frame, the return
+    /// address in all but the first frame and frames interrupted by a signal.
+    #[inline]
+    pub fn ip(&self) -> *mut c_void {
+        self.ip as usize as *mut c_void
+    }
+
+    /// Returns the stack pointer of this frame, the value of `_Unwind_GetCFA`
+    /// the backtrace crate returns: the canonical frame address of the frame
+    /// it called with libgcc, or that of this frame with the `itanium-abi`
+    /// feature, which replaces `_Unwind_GetCFA`.
+    pub fn sp(&self) -> *mut c_void {
+        if cfg!(feature = "itanium-abi") {
+            if let Ok(Some(cfa)) = self.cursor.cfa(&self.registers) {
+                return cfa as usize as *mut c_void;
+            }
+        }
+        self.registers.sp() as usize as *mut c_void
+    }
+
+    /// Returns the starting address of the function of this frame, or the
+    /// instruction pointer if it has no unwind information.
+    pub fn symbol_address(&self) -> *mut c_void {
+        match self.cursor.proc_info_at(self.lookup_pc) {
+            Ok(Some(info)) => info.start_ip as usize as *mut c_void,
+            _ => self.ip(),
+        }
+    }
+
+    /// Returns the address the object containing this frame is loaded at,
+    /// which the addresses in its ELF file are relative to.
+    pub fn module_base_address(&self) -> Option<*mut c_void> {
+        self.cursor
+            .module_base_at(self.lookup_pc)
+            .map(|base| base as usize as *mut c_void)
+    }
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field("ip", &self.ip())
+            .field("symbol_address", &self.symbol_address())
+            .finish()
+    }
+}
+
+/// Inspects the current call-stack, passing all active frames into the closure
+/// provided to calculate a stack trace, starting with the caller of `trace`.
+///
+/// The closure's return value is an indication of whether the backtrace should
+/// continue. A return value of `false` will terminate the backtrace and return
+/// immediately. The backtrace also ends at the first frame that can't be
+/// unwound.
+#[inline(never)]
+pub fn trace<F: FnMut(&Frame) -> bool>(cb: F) {
+    trace_caller(cb)
+}
+
+/// Same as [trace], which takes no lock either.
+///
+/// # Safety
+///
+/// It is safe to call, and only unsafe like `backtrace::trace_unsynchronized`
+/// to be a drop-in replacement for it.
+#[inline(never)]
+pub unsafe fn trace_unsynchronized<F: FnMut(&Frame) -> bool>(cb: F) {
+    trace_caller(cb)
+}
+
+/// Traces from the caller of the function this is inlined into.
+#[inline(always)]
+fn trace_caller<F: FnMut(&Frame) -> bool>(mut cb: F) {
+    let mut registers = Registers::default();
+    unsafe {
+        unwind_init_registers(&mut registers as _);
+    }
+    let mut cursor = UnwindCursor::new();
+    // Step directly, so that we skip the current function.
+    while let Ok(true) = cursor.step(&mut registers) {
+        let frame = Frame {
+            ip: registers.pc(),
+            registers,
+            lookup_pc: cursor.lookup_pc(&registers),
+            cursor: cursor.clone(),
+        };
+        if !cb(&frame) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let mut frames = vec![];
+        trace(|frame| {
+            frames.push(frame.clone());
+            true
+        });
+        // The first frame is the caller of `trace`.
+        assert_eq!(frames[0].symbol_address() as usize, test_trace as fn() as usize);
+        assert!(frames.windows(2).all(|w| w[0].sp() <= w[1].sp()));
+
+        let base = frames[0].module_base_address().unwrap();
+        let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+        assert_ne!(unsafe { libc::dladdr(frames[0].ip(), &mut info) }, 0);
+        assert_eq!(base, info.dli_fbase);
+
+        let mut n = 0;
+        unsafe {
+            trace_unsynchronized(|_| {
+                n += 1;
+                n < 2
+            });
+        }
+        assert_eq!(n, 2);
+    }
+}
@@ -1,16 +1,34 @@
 use crate::dwarf;
-use crate::dyld::{sections, SectionInfo};
-use crate::registers::Registers;
+use crate::dyld::{find_section, sections, SectionInfo};
+#[cfg(target_arch = "arm")]
+use crate::ehabi;
+use crate::registers::{Registers, UNW_REG_IP, UNW_REG_SP};
 
 /// `UnwindCursor` is used to trace the stack with [Registers].
 ///
 /// `UnwindCursor` is highly platform-dependent. On Linux we use
-/// the .eh_frame section (in DWARF format) to restore registers.
+/// the .eh_frame section (in DWARF format) to restore registers, or
+/// the .ARM.exidx section on arm. With the `symbolize` feature, the
+/// .debug_frame sections registered by `Symbolizer::register_debug_frames`
+/// describe the code neither covers.
 ///
 /// [Registers]: crate::registers::Registers
+#[derive(Clone)]
 pub struct UnwindCursor {
-    sections: &'static [SectionInfo],
-    first_step: bool,
+    // The objects to look PCs up in, or `None` to find them with
+    // `dl_iterate_phdr` at each step.
+    sections: Option<&'static [SectionInfo]>,
+    // Whether the PC of the current frame is the exact instruction to look up,
+    // rather than a return address. This is the case for the first frame and
+    // for frames interrupted by a signal.
+    exact_pc: bool,
+}
+
+impl Default for UnwindCursor {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UnwindCursor {
@@ -18,39 +36,301 @@ impl UnwindCursor {
     #[inline]
     pub fn new() -> Self {
         Self {
-            sections: sections(),
-            first_step: true,
+            sections: Some(sections()),
+            exact_pc: true,
+        }
+    }
+
+    /// Creates an `UnwindCursor` that finds the object containing each PC as
+    /// it steps, rather than among the objects loaded when the first cursor
+    /// was created.
+    ///
+    /// Objects loaded later with `dlopen`, and shared libraries whatever the
+    /// `trace-shared-libs` feature says, are found. But stepping takes the
+    /// lock of the dynamic loader, which is not async-signal-safe.
+    pub fn uncached() -> Self {
+        Self {
+            sections: None,
+            exact_pc: true,
         }
     }
 
+    /// Returns whether the PC of the current frame is the exact instruction
+    /// that was executing, rather than a return address.
+    #[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), allow(unused))]
+    #[inline]
+    pub(crate) fn pc_is_exact(&self) -> bool {
+        self.exact_pc
+    }
+
+    /// Sets whether the PC of the current frame is the exact instruction that
+    /// was executing, or a return address looked up at the call before it,
+    /// e.g. for registers saved by `getcontext(3)`. New cursors start with an
+    /// exact PC.
+    #[inline]
+    pub fn set_pc_is_exact(&mut self, exact: bool) {
+        self.exact_pc = exact;
+    }
+
     /// Attempts to restore the parent function's register state based on the
     /// current register state.
     ///
     /// On Linux, the recovery rules for registers are described in the
-    /// .eh_frame section.
+    /// .eh_frame section. On arm, objects use the .ARM.exidx section instead,
+    /// .eh_frame is only used for those without it.
     pub fn step(&mut self, registers: &mut Registers) -> crate::Result<bool> {
-        let mut pc = registers.pc();
+        let pc = self.lookup_pc(registers);
         if pc == 0 {
             return Ok(false);
         }
-        if self.first_step {
-            self.first_step = false;
-        } else {
-            // Usually when we step for the first time, the PC points to the actual
-            // position that was interrupted by the signal. But then we'll use `return
-            // address` to set the PC. So from now on we need to subtract 1 from the
-            // PC to get the correct position before the call instruction.
-            pc -= 1;
-        }
-        for s in self.sections {
-            if s.contains(pc) {
-                return match dwarf::step(pc, s, registers) {
-                    Ok(_) => Ok(true),
-                    Err(dwarf::DwarfError::FDENotFound) => Ok(false),
+        with_section(self.sections, pc, |s| {
+            #[cfg(target_arch = "arm")]
+            if s.arm_exidx_len != 0 {
+                return match ehabi::step(pc, s, registers) {
+                    Ok(()) => {
+                        self.exact_pc = false;
+                        Ok(true)
+                    }
+                    Err(ehabi::EhabiError::EntryNotFound | ehabi::EhabiError::CantUnwind) => Ok(false),
                     Err(err) => Err(err.into()),
                 };
             }
+            match dwarf::step(pc, s, registers) {
+                Ok(is_signal_frame) => {
+                    // The PC restored from a signal frame is the interrupted
+                    // instruction, not a return address.
+                    self.exact_pc = is_signal_frame;
+                    Ok(true)
+                }
+                Err(dwarf::DwarfError::FDENotFound) => self.step_debug_frame(pc, registers),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .unwrap_or_else(|| self.step_debug_frame(pc, registers))
+    }
+
+    /// Restores the registers of the caller from the registered .debug_frame
+    /// sections, for code without other unwind information.
+    #[cfg(feature = "symbolize")]
+    fn step_debug_frame(&mut self, pc: u64, registers: &mut Registers) -> crate::Result<bool> {
+        match dwarf::step_debug_frame(pc, registers) {
+            Ok(is_signal_frame) => {
+                self.exact_pc = is_signal_frame;
+                Ok(true)
+            }
+            Err(dwarf::DwarfError::FDENotFound) => Ok(false),
+            Err(err) => Err(err.into()),
         }
+    }
+
+    #[cfg(not(feature = "symbolize"))]
+    #[inline(always)]
+    fn step_debug_frame(&mut self, _pc: u64, _registers: &mut Registers) -> crate::Result<bool> {
         Ok(false)
     }
+
+    /// Returns the function containing the PC of the current frame, with its
+    /// LSDA and personality routine, or `None` if it has no FDE.
+    pub fn proc_info(&self, registers: &Registers) -> crate::Result<Option<dwarf::ProcInfo>> {
+        self.proc_info_at(self.lookup_pc(registers))
+    }
+
+    /// Returns the function containing `pc`, see [proc_info].
+    ///
+    /// [proc_info]: UnwindCursor::proc_info
+    pub(crate) fn proc_info_at(&self, pc: u64) -> crate::Result<Option<dwarf::ProcInfo>> {
+        with_section(self.sections, pc, |s| {
+            if s.eh_frame_hdr_len == 0 {
+                return Ok(None);
+            }
+            match dwarf::proc_info(pc, s) {
+                Ok(info) => Ok(Some(info)),
+                Err(dwarf::DwarfError::FDENotFound) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .unwrap_or(Ok(None))
+    }
+
+    /// Returns the landing pad exceptions unwinding through the current frame
+    /// go to, which tells whether the frame runs cleanups or catches them, or
+    /// `None` if there is none.
+    pub fn landing_pad(&self, registers: &Registers) -> crate::Result<Option<dwarf::LandingPad>> {
+        let pc = self.lookup_pc(registers);
+        with_section(self.sections, pc, |s| {
+            if s.eh_frame_hdr_len == 0 {
+                return Ok(None);
+            }
+            match dwarf::landing_pad(pc, s) {
+                Ok(landing_pad) => Ok(landing_pad),
+                Err(dwarf::DwarfError::FDENotFound) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .unwrap_or(Ok(None))
+    }
+
+    /// Returns the CFA of the current frame, the SP of its caller before the
+    /// call, or `None` if it has no FDE.
+    pub fn cfa(&self, registers: &Registers) -> crate::Result<Option<u64>> {
+        let pc = self.lookup_pc(registers);
+        with_section(self.sections, pc, |s| {
+            if s.eh_frame_hdr_len == 0 {
+                return Ok(None);
+            }
+            match dwarf::cfa(pc, s, registers) {
+                Ok(cfa) => Ok(Some(cfa)),
+                Err(dwarf::DwarfError::FDENotFound) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .unwrap_or(Ok(None))
+    }
+
+    /// Sets the PC of the current frame to `ip`, e.g. to transfer control to
+    /// a landing pad.
+    ///
+    /// If outgoing arguments were pushed at the current PC (recorded by
+    /// DW_CFA_GNU_args_size), the SP is adjusted to pop them, as the code at
+    /// `ip` expects. The CFA computed by [step] already includes them, so
+    /// this is the only place they are applied.
+    ///
+    /// [step]: UnwindCursor::step
+    pub fn set_ip(&self, registers: &mut Registers, ip: u64) -> crate::Result<()> {
+        let pc = self.lookup_pc(registers);
+        // Objects without .eh_frame (unwound with .ARM.exidx) never record it.
+        let args_size = with_section(self.sections, pc, |s| match s.eh_frame_hdr_len {
+            0 => Err(dwarf::DwarfError::FDENotFound),
+            _ => dwarf::args_size(pc, s),
+        });
+        match args_size.unwrap_or(Err(dwarf::DwarfError::FDENotFound)) {
+            Ok(size) => registers[UNW_REG_SP] = registers.sp().wrapping_add(size),
+            Err(dwarf::DwarfError::FDENotFound) => {}
+            Err(err) => return Err(err.into()),
+        }
+        registers[UNW_REG_IP] = ip;
+        Ok(())
+    }
+
+    /// Returns the address the object containing `pc` is loaded at, which
+    /// the addresses in its ELF file are relative to.
+    #[cfg_attr(not(feature = "backtrace-compat"), allow(unused))]
+    pub(crate) fn module_base_at(&self, pc: u64) -> Option<u64> {
+        with_section(self.sections, pc, |s| s.base)
+    }
+
+    /// Returns the PC used to look up the unwind information of the current
+    /// frame.
+    pub(crate) fn lookup_pc(&self, registers: &Registers) -> u64 {
+        let pc = registers.pc();
+        // The lowest bit of a code address selects the Thumb state on arm.
+        #[cfg(target_arch = "arm")]
+        let pc = pc & !1;
+        if self.exact_pc || pc == 0 {
+            pc
+        } else {
+            // Usually when we step for the first time, the PC points to the actual
+            // position that was interrupted by the signal. But then we'll use `return
+            // address` to set the PC. So we need to subtract 1 from the PC to get the
+            // correct position before the call instruction, unless we just stepped
+            // through a signal frame.
+            pc - 1
+        }
+    }
+}
+
+/// Calls `f` with the sections of the object containing `pc`, among
+/// `sections` or, if `None`, among the objects loaded now.
+///
+/// The sections are found and used in place, which keeps copies of them off
+/// the stack of signal handlers in the cached case.
+#[inline]
+fn with_section<T>(sections: Option<&'static [SectionInfo]>, pc: u64, f: impl FnOnce(&SectionInfo) -> T) -> Option<T> {
+    match sections {
+        Some(sections) => sections.iter().find(|s| s.contains(pc)).map(f),
+        None => with_found_section(pc, f),
+    }
+}
+
+#[inline(never)]
+fn with_found_section<T>(pc: u64, f: impl FnOnce(&SectionInfo) -> T) -> Option<T> {
+    find_section(pc).as_ref().map(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::consts::*;
+    use crate::dwarf::testing::EhFrameBuilder;
+    #[cfg(target_arch = "aarch64")]
+    use crate::registers::{UNW_ARM64_LR as RA, UNW_ARM64_SP as SP};
+    #[cfg(target_arch = "arm")]
+    use crate::registers::{UNW_ARM_LR as RA, UNW_ARM_SP as SP};
+    #[cfg(target_arch = "riscv64")]
+    use crate::registers::{UNW_RISCV_RA as RA, UNW_RISCV_SP as SP};
+    #[cfg(target_arch = "x86_64")]
+    use crate::registers::{UNW_X86_64_RIP as RA, UNW_X86_64_RSP as SP};
+    #[cfg(target_arch = "x86")]
+    use crate::registers::{UNW_X86_EIP as RA, UNW_X86_ESP as SP};
+
+    /// Trace a fake section with three adjacent functions: a trampoline at
+    /// +0x1000 whose CIE has the augmentation `trampoline`, and two functions
+    /// at +0x1100 and +0x1200 with CFA = SP+16 and CFA = SP+32. The trampoline
+    /// returns to +0x1200, the first instruction of the last function.
+    /// Returns the SP of each frame.
+    fn trace_section(trampoline: &str) -> Vec<u64> {
+        let mut mem = Vec::with_capacity(4096);
+        let base = mem.as_ptr() as u64;
+        let mut b = EhFrameBuilder::default();
+        let cie_trampoline = b.cie(trampoline, 1, -8, RA as u64, &[DW_CFA_OFFSET | RA as u8, 1]);
+        let cie = b.cie("", 1, -8, RA as u64, &[DW_CFA_OFFSET | RA as u8, 1]);
+        b.fde(cie_trampoline, base + 0x1000, 0x100, &[DW_CFA_DEF_CFA, SP as u8, 16]);
+        b.fde(cie, base + 0x1100, 0x100, &[DW_CFA_DEF_CFA, SP as u8, 16]);
+        b.fde(cie, base + 0x1200, 0x100, &[DW_CFA_DEF_CFA, SP as u8, 32]);
+        b.terminate();
+        let header = b.header(base);
+        mem.extend_from_slice(&header);
+        mem.extend_from_slice(&b.data);
+        assert_eq!(mem.as_ptr() as u64, base);
+
+        let sections = Box::leak(Box::new([SectionInfo {
+            base,
+            text: base + 0x1000,
+            text_len: 0x300,
+            eh_frame_hdr: base,
+            eh_frame_hdr_len: header.len() as u64,
+            #[cfg(target_arch = "arm")]
+            arm_exidx: 0,
+            #[cfg(target_arch = "arm")]
+            arm_exidx_len: 0,
+            max_addr: base + mem.len() as u64,
+        }]));
+        let mut cursor = UnwindCursor {
+            sections: Some(sections),
+            exact_pc: true,
+        };
+
+        // Return addresses are at CFA-8, zero terminates the trace.
+        let stack = [0, base + 0x1200, 0, 0, 0, 0u64];
+        let mut registers = Registers::default();
+        registers[UNW_REG_IP] = base + 0x1010;
+        registers[UNW_REG_SP] = stack.as_ptr() as u64;
+        let mut sps = vec![registers.sp() - stack.as_ptr() as u64];
+        while cursor.step(&mut registers).unwrap() {
+            sps.push(registers.sp() - stack.as_ptr() as u64);
+        }
+        sps
+    }
+
+    #[test]
+    fn test_step_return_address() {
+        // The return address is looked up as PC-1, inside the middle function.
+        assert_eq!(trace_section(""), vec![0, 16, 32]);
+    }
+
+    #[test]
+    fn test_step_signal_frame() {
+        // The PC restored from a signal frame is looked up as is.
+        assert_eq!(trace_section("S"), vec![0, 16, 48]);
+    }
 }
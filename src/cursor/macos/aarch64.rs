@@ -14,6 +14,13 @@ use crate::Result;
 /// [Registers]: crate::registers::Registers
 pub struct UnwindCursor;
 
+impl Default for UnwindCursor {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UnwindCursor {
     /// Creates a new `UnwindCursor`.
     #[inline]
@@ -0,0 +1,210 @@
+use crate::dwarf::{load_with_protect, DwarfError};
+use std::{mem, ptr};
+
+/// `AddressSpace` abstracts the memory the DWARF parsers read from.
+///
+/// When unwinding the current process we read the mapped sections directly
+/// through `LocalAddressSpace`. [SliceAddressSpace] instead maps a byte slice
+/// to a virtual address range and refuses to read anything outside of it,
+/// which makes it possible to parse untrusted or offline section data.
+///
+/// Memory made of several regions, like the sections of an object and a
+/// stack from a core dump, can be implemented by picking the region that
+/// contains the address and loading from a [SliceAddressSpace] over it.
+pub trait AddressSpace {
+    /// Load the value at the `address`.
+    fn load<T: Primitive>(&self, address: u64) -> Result<T, DwarfError>;
+
+    /// Size of a pointer of the architecture the data belongs to, 4 or 8.
+    fn pointer_size(&self) -> u8;
+
+    /// Load the pointer at the `address`, zero-extended to 64 bits.
+    #[inline]
+    fn load_pointer(&self, address: u64) -> Result<u64, DwarfError> {
+        match self.pointer_size() {
+            4 => self.load::<u32>(address).map(u64::from),
+            _ => self.load::<u64>(address),
+        }
+    }
+
+    /// Returns the address at `offset` in the thread-local storage block of
+    /// the object containing `address`, for DW_OP_form_tls_address. Address
+    /// spaces have no thread-local storage unless they implement it.
+    #[inline]
+    fn tls_address(&self, address: u64, offset: u64) -> Result<u64, DwarfError> {
+        let _ = offset;
+        Err(DwarfError::NoThreadLocalStorage(address))
+    }
+
+    /// Truncate an address computed with 64-bit arithmetic to the size of a
+    /// pointer, so that it wraps around like on the architecture.
+    #[inline]
+    fn truncate_pointer(&self, address: u64) -> u64 {
+        match self.pointer_size() {
+            4 => address & 0xffff_ffff,
+            _ => address,
+        }
+    }
+}
+
+/// Size of a pointer of the current process.
+pub const NATIVE_POINTER_SIZE: u8 = mem::size_of::<usize>() as u8;
+
+/// Types that can be loaded from an [AddressSpace].
+///
+/// Every bit pattern must be a valid value of the type, so this is only
+/// implemented for plain numeric types.
+pub trait Primitive: Copy + private::Sealed {}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+            impl Primitive for $t {}
+        )*
+    };
+}
+
+impl_primitive!(u8, u16, u32, u64, u128, i8, i16, i32, i64, f64);
+
+/// The address space of the current process.
+///
+/// Reads are checked by `can_access` if the `mem-protect` feature is enabled.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LocalAddressSpace;
+
+impl AddressSpace for LocalAddressSpace {
+    #[inline]
+    fn load<T: Primitive>(&self, address: u64) -> Result<T, DwarfError> {
+        load_with_protect(address)
+    }
+
+    #[inline]
+    fn pointer_size(&self) -> u8 {
+        NATIVE_POINTER_SIZE
+    }
+
+    /// Uses the block of the calling thread, which is the one unwound. The
+    /// object is looked up with `dl_iterate_phdr`, which takes the lock of
+    /// the dynamic loader.
+    #[cfg(target_os = "linux")]
+    fn tls_address(&self, address: u64, offset: u64) -> Result<u64, DwarfError> {
+        crate::dyld::tls_address(address, offset).ok_or(DwarfError::NoThreadLocalStorage(address))
+    }
+}
+
+/// An address space backed by a byte slice, which is mapped to the virtual
+/// address range [base, base + data.len()).
+#[derive(Debug, Copy, Clone)]
+pub struct SliceAddressSpace<'a> {
+    base: u64,
+    data: &'a [u8],
+    pointer_size: u8,
+}
+
+impl<'a> SliceAddressSpace<'a> {
+    /// Creates a new `SliceAddressSpace` whose first byte lives at `base`,
+    /// holding data of the current architecture.
+    #[inline]
+    pub fn new(base: u64, data: &'a [u8]) -> Self {
+        Self {
+            base,
+            data,
+            pointer_size: NATIVE_POINTER_SIZE,
+        }
+    }
+
+    /// Sets the size of a pointer, e.g. 4 to parse the data of a 32-bit
+    /// process. Panics if `pointer_size` is neither 4 nor 8.
+    #[inline]
+    pub fn with_pointer_size(mut self, pointer_size: u8) -> Self {
+        assert!(pointer_size == 4 || pointer_size == 8);
+        self.pointer_size = pointer_size;
+        self
+    }
+
+    /// Returns the address of the first byte.
+    #[inline]
+    pub fn start(&self) -> u64 {
+        self.base
+    }
+
+    /// Returns the address after the last byte.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.base.saturating_add(self.data.len() as u64)
+    }
+}
+
+impl AddressSpace for SliceAddressSpace<'_> {
+    #[inline]
+    fn load<T: Primitive>(&self, address: u64) -> Result<T, DwarfError> {
+        let offset = address
+            .checked_sub(self.base)
+            .ok_or(DwarfError::UnreadableAddress(address))? as usize;
+        match offset.checked_add(mem::size_of::<T>()) {
+            Some(end) if end <= self.data.len() => {
+                // SAFETY: the range has been checked above, and `Primitive` types
+                // accept any bit pattern.
+                Ok(unsafe { ptr::read_unaligned(self.data.as_ptr().add(offset) as *const T) })
+            }
+            _ => Err(DwarfError::UnreadableAddress(address)),
+        }
+    }
+
+    #[inline]
+    fn pointer_size(&self) -> u8 {
+        self.pointer_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_address_space() {
+        let data = [0x01u8, 0x02, 0x03, 0x04, 0x05];
+        let space = SliceAddressSpace::new(0x1000, &data);
+        assert_eq!(space.start(), 0x1000);
+        assert_eq!(space.end(), 0x1005);
+        assert_eq!(space.load::<u8>(0x1000).unwrap(), 0x01);
+        assert_eq!(space.load::<u8>(0x1004).unwrap(), 0x05);
+        assert_eq!(
+            space.load::<u32>(0x1001).unwrap(),
+            u32::from_ne_bytes([0x02, 0x03, 0x04, 0x05])
+        );
+        assert!(matches!(
+            space.load::<u8>(0xfff),
+            Err(DwarfError::UnreadableAddress(0xfff))
+        ));
+        assert!(matches!(
+            space.load::<u8>(0x1005),
+            Err(DwarfError::UnreadableAddress(0x1005))
+        ));
+        assert!(matches!(
+            space.load::<u32>(0x1002),
+            Err(DwarfError::UnreadableAddress(0x1002))
+        ));
+        assert!(space.load::<u64>(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_load_pointer() {
+        let data = 0x1122_3344_5566_7788u64.to_le_bytes();
+        let space = SliceAddressSpace::new(0x1000, &data).with_pointer_size(8);
+        assert_eq!(space.load_pointer(0x1000).unwrap(), 0x1122_3344_5566_7788);
+        assert_eq!(space.truncate_pointer(u64::MAX), u64::MAX);
+        assert!(space.load_pointer(0x1004).is_err());
+
+        let space = space.with_pointer_size(4);
+        assert_eq!(space.load_pointer(0x1000).unwrap(), 0x5566_7788);
+        assert_eq!(space.load_pointer(0x1004).unwrap(), 0x1122_3344);
+        assert_eq!(space.truncate_pointer(u64::MAX), 0xffff_ffff);
+        assert!(space.load_pointer(0x1005).is_err());
+    }
+}
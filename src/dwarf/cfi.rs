@@ -1,6 +1,20 @@
+use crate::dwarf::address_space::AddressSpace;
 use crate::dwarf::consts::DW_EH_PE_OMIT;
 use crate::dwarf::encoding::*;
-use crate::dwarf::{load_with_protect as load, DwarfError};
+use crate::dwarf::DwarfError;
+
+/// The section CIEs and FDEs are read from, which tells how CIEs are told
+/// apart from FDEs and how FDEs point to their CIE.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CfiSection {
+    /// .eh_frame, where CIE ids are 0 and CIE pointers are relative to
+    /// themselves.
+    EhFrame,
+    /// .debug_frame starting at the given address, where CIE ids are all
+    /// ones and CIE pointers are offsets in the section.
+    #[cfg_attr(not(any(feature = "symbolize", feature = "fuzzing")), allow(unused))]
+    DebugFrame(u64),
+}
 
 #[derive(Debug, Default, Copy, Clone)]
 pub struct CommonInformationEntry {
@@ -17,98 +31,107 @@ pub struct CommonInformationEntry {
     pub is_signal_frame: bool,
     pub fdes_have_augmentation_data: bool,
     pub return_address_register: u8,
-    #[cfg(target_arch = "aarch64")]
     pub addresses_signed_with_b_key: bool,
 }
 
 impl CommonInformationEntry {
-    /// Parse a CIE into a [CommonInformationEntry].
-    pub fn decode(start: u64) -> Result<CommonInformationEntry, DwarfError> {
+    /// Parse a CIE of `section` into a [CommonInformationEntry].
+    ///
+    /// `end` is the end of the section containing the CIE, nothing beyond it
+    /// will be read.
+    pub fn decode<A: AddressSpace>(
+        a: &A,
+        section: CfiSection,
+        start: u64,
+        end: u64,
+    ) -> Result<CommonInformationEntry, DwarfError> {
         let mut loc = start;
-        let mut cie = CommonInformationEntry::default();
-        cie.cie_start = loc;
-        cie.lsda_encoding = DW_EH_PE_OMIT;
+        let mut cie = CommonInformationEntry {
+            cie_start: loc,
+            lsda_encoding: DW_EH_PE_OMIT,
+            ..Default::default()
+        };
 
         // Parse length.
-        let mut length = load::<u32>(loc)? as u64;
-        loc += 4;
-        let mut cie_content_end = loc + length;
-        if length == 0xffffffff {
-            // 0xffffffff means length is really next 8 bytes.
-            length = load::<u64>(loc)?;
-            loc += 8;
-            cie_content_end = loc + length;
-        }
+        let (length, cie_content_end) = decode_length(a, &mut loc, end)?;
         if length == 0 {
             return Err(DwarfError::CIEZeroLength);
         }
+        let dwarf64 = loc - start > 4;
 
-        // CIE ID is always 0.
-        let cie_id = load::<u32>(loc)?;
-        loc += 4;
-        if cie_id != 0 {
-            return Err(DwarfError::CIEIdIsNotZero);
+        // CIE ID is always 0 in .eh_frame, and all ones in .debug_frame.
+        match section {
+            CfiSection::EhFrame => {
+                if read::<A, u32>(a, &mut loc, cie_content_end)? != 0 {
+                    return Err(DwarfError::CIEIdIsNotZero);
+                }
+            }
+            CfiSection::DebugFrame(_) => {
+                let cie_id = read_offset(a, &mut loc, cie_content_end, dwarf64)?;
+                if !is_debug_frame_cie_id(cie_id, dwarf64) {
+                    return Err(DwarfError::InvalidDebugFrameCieId(cie_id));
+                }
+            }
         }
 
-        // Version is always 1 or 3.
-        let version = load::<u8>(loc)?;
-        loc += 1;
-        if version != 1 && version != 3 {
+        // Version is always 1 or 3, or 4 in .debug_frame.
+        let version = read::<A, u8>(a, &mut loc, cie_content_end)?;
+        let debug_frame = matches!(section, CfiSection::DebugFrame(_));
+        if version != 1 && version != 3 && !(debug_frame && version == 4) {
             return Err(DwarfError::CIEInvalidVersion(version));
         }
 
         // Save start of augmentation string and find end.
         let augmentation_str_start = loc;
-        while load::<u8>(loc)? != 0 {
-            loc += 1;
+        while read::<A, u8>(a, &mut loc, cie_content_end)? != 0 {}
+
+        // Version 4 records the size of addresses, which must be the one of
+        // the address space, and of segment selectors, which are unused.
+        if version == 4 {
+            let address_size = read::<A, u8>(a, &mut loc, cie_content_end)?;
+            if address_size != a.pointer_size() {
+                return Err(DwarfError::InvalidAddressSize(address_size));
+            }
+            let _ = read::<A, u8>(a, &mut loc, cie_content_end)?;
         }
-        loc += 1; // skip '\0'.
 
         // Parse code alignment factor.
-        cie.code_align_factor = decode_uleb128(&mut loc, cie_content_end)? as u32;
+        cie.code_align_factor = decode_uleb128(a, &mut loc, cie_content_end)? as u32;
 
         // Parse data alignment factor.
-        cie.data_align_factor = decode_sleb128(&mut loc, cie_content_end)? as i32;
+        cie.data_align_factor = decode_sleb128(a, &mut loc, cie_content_end)? as i32;
 
         // Parse return address register.
         cie.return_address_register = if version == 1 {
-            let r = load::<u8>(loc)?;
-            loc += 1;
-            r
+            read::<A, u8>(a, &mut loc, cie_content_end)?
         } else {
-            let r = decode_uleb128(&mut loc, cie_content_end)?;
-            assert!(r < 255);
+            let r = decode_uleb128(a, &mut loc, cie_content_end)?;
+            if r >= 255 {
+                return Err(DwarfError::InvalidReturnAddressRegisterNumber(r as usize));
+            }
             r as u8
         };
 
         // Parse augmentation data based on augmentation string.
         let mut n = augmentation_str_start;
-        if load::<u8>(n)? == b'z' {
+        if a.load::<u8>(n)? == b'z' {
             // Parse augmentation data length.
-            let _ = decode_uleb128(&mut loc, cie_content_end);
-            while load::<u8>(n)? != 0 {
-                match load::<u8>(n)? {
+            let _ = decode_uleb128(a, &mut loc, cie_content_end)?;
+            loop {
+                match read::<A, u8>(a, &mut n, cie_content_end)? {
+                    0 => break,
                     b'z' => cie.fdes_have_augmentation_data = true,
                     b'P' => {
-                        cie.personality_encoding = load::<u8>(loc)?;
-                        loc += 1;
+                        cie.personality_encoding = read::<A, u8>(a, &mut loc, cie_content_end)?;
                         cie.personality_offset_in_cie = (loc - start) as u8;
-                        cie.personality = decode_pointer(&mut loc, cie_content_end, cie.personality_encoding, 0)?;
-                    }
-                    b'L' => {
-                        cie.lsda_encoding = load::<u8>(loc)?;
-                        loc += 1;
-                    }
-                    b'R' => {
-                        cie.pointer_encoding = load::<u8>(loc)?;
-                        loc += 1;
+                        cie.personality = decode_pointer(a, &mut loc, cie_content_end, cie.personality_encoding, 0)?;
                     }
+                    b'L' => cie.lsda_encoding = read::<A, u8>(a, &mut loc, cie_content_end)?,
+                    b'R' => cie.pointer_encoding = read::<A, u8>(a, &mut loc, cie_content_end)?,
                     b'S' => cie.is_signal_frame = true,
-                    #[cfg(target_arch = "aarch64")]
                     b'B' => cie.addresses_signed_with_b_key = true,
                     _ => {}
                 }
-                n += 1;
             }
         }
 
@@ -129,50 +152,72 @@ pub struct FrameDescriptionEntry {
 }
 
 impl FrameDescriptionEntry {
-    /// Parse a FDE into a [FrameDescriptionEntry] and a [CommonInformationEntry].
-    pub fn decode(start: u64) -> Result<(Self, CommonInformationEntry), DwarfError> {
+    /// Parse a FDE of `section` into a [FrameDescriptionEntry] and a
+    /// [CommonInformationEntry].
+    ///
+    /// `end` is the end of the section containing the FDE, nothing beyond it
+    /// will be read.
+    pub fn decode<A: AddressSpace>(
+        a: &A,
+        section: CfiSection,
+        start: u64,
+        end: u64,
+    ) -> Result<(Self, CommonInformationEntry), DwarfError> {
         let mut loc = start;
-        let mut fde = FrameDescriptionEntry::default();
-        fde.fde_start = loc;
+        let mut fde = FrameDescriptionEntry {
+            fde_start: loc,
+            ..Default::default()
+        };
 
         // Parse length.
-        let mut length = load::<u32>(loc)? as u64;
-        loc += 4;
-        if length == 0xffffffff {
-            // 0xffffffff means length is really next 8 bytes.
-            length = load::<u64>(loc)?;
-            loc += 8;
-        }
+        let (length, next_cfi) = decode_length(a, &mut loc, end)?;
         if length == 0 {
             return Err(DwarfError::FDEZeroLength);
         }
-        let next_cfi = loc + length;
+        let dwarf64 = loc - start > 4;
 
         // Parse related CIE.
-        let cie_ptr = load::<u32>(loc)? as u64;
-        if cie_ptr == 0 {
-            return Err(DwarfError::FDEIsReallyCIE);
-        }
-        let cie_start = loc - cie_ptr;
-        let cie = CommonInformationEntry::decode(cie_start)?;
-        loc += 4;
+        let cie_start = match section {
+            CfiSection::EhFrame => {
+                let cie_ptr = a.load::<u32>(loc)? as u64;
+                if cie_ptr == 0 {
+                    return Err(DwarfError::FDEIsReallyCIE);
+                }
+                let cie_start = loc.checked_sub(cie_ptr).ok_or(DwarfError::InvalidCiePointer(loc))?;
+                let _ = read::<A, u32>(a, &mut loc, next_cfi)?;
+                cie_start
+            }
+            CfiSection::DebugFrame(debug_frame) => {
+                let cie_ptr = read_offset(a, &mut loc, next_cfi, dwarf64)?;
+                if is_debug_frame_cie_id(cie_ptr, dwarf64) {
+                    return Err(DwarfError::FDEIsReallyCIE);
+                }
+                debug_frame
+                    .checked_add(cie_ptr)
+                    .ok_or(DwarfError::InvalidCiePointer(loc))?
+            }
+        };
+        let cie = CommonInformationEntry::decode(a, section, cie_start, end)?;
 
         // Parse pc begin and range.
-        let pc_start = decode_pointer(&mut loc, next_cfi, cie.pointer_encoding, 0)?;
-        let pc_range = decode_pointer(&mut loc, next_cfi, cie.pointer_encoding & 0x0F, 0)?;
+        let pc_start = decode_pointer(a, &mut loc, next_cfi, cie.pointer_encoding, 0)?;
+        let pc_range = decode_pointer(a, &mut loc, next_cfi, cie.pointer_encoding & 0x0F, 0)?;
 
         // Check for augmentation length.
         if cie.fdes_have_augmentation_data {
-            let augmentation_len = decode_uleb128(&mut loc, next_cfi)?;
-            let end_of_augmentation = loc + augmentation_len;
+            let augmentation_len = decode_uleb128(a, &mut loc, next_cfi)?;
+            let end_of_augmentation = match loc.checked_add(augmentation_len) {
+                Some(v) if v <= next_cfi => v,
+                _ => return Err(DwarfError::OutOfBounds(loc)),
+            };
             if cie.lsda_encoding != DW_EH_PE_OMIT {
                 // Peek at value (without indirection).
                 // Zero means no LSDA.
                 let lsda_start = loc;
-                if decode_pointer(&mut loc, next_cfi, cie.lsda_encoding & 0x0F, 0)? != 0 {
+                if decode_pointer(a, &mut loc, end_of_augmentation, cie.lsda_encoding & 0x0F, 0)? != 0 {
                     // Reset pointer and re-parse LSDA address.
                     loc = lsda_start;
-                    fde.lsda = decode_pointer(&mut loc, next_cfi, cie.lsda_encoding, 0)?;
+                    fde.lsda = decode_pointer(a, &mut loc, end_of_augmentation, cie.lsda_encoding, 0)?;
                 }
             }
             loc = end_of_augmentation;
@@ -180,7 +225,7 @@ impl FrameDescriptionEntry {
         fde.fde_length = next_cfi - start;
         fde.fde_instructions = loc;
         fde.pc_start = pc_start;
-        fde.pc_end = pc_start + pc_range;
+        fde.pc_end = pc_start.saturating_add(pc_range);
         Ok((fde, cie))
     }
 
@@ -192,70 +237,101 @@ impl FrameDescriptionEntry {
 }
 
 #[derive(Debug)]
+#[cfg_attr(not(feature = "fuzzing"), allow(unused))]
 pub enum CfiEntry {
     Cie(CommonInformationEntry),
     FdeCie((FrameDescriptionEntry, CommonInformationEntry)),
 }
 
-pub struct Entries {
-    eh_frame: u64,
-    eh_frame_end: u64,
+/// Iterator over the entries of a CFI section, which stops after the first
+/// error.
+pub struct Entries<'a, A: AddressSpace> {
+    a: &'a A,
+    section: CfiSection,
+    loc: u64,
+    end: u64,
 }
 
-impl Entries {
-    pub fn new(eh_frame: u64, eh_frame_len: u64) -> Self {
-        let eh_frame_end = if eh_frame_len == u64::MAX {
-            u64::MAX
-        } else {
-            eh_frame + eh_frame_len
-        };
-        Self { eh_frame, eh_frame_end }
+impl<'a, A: AddressSpace> Entries<'a, A> {
+    /// Iterates over the entries of the .eh_frame section [eh_frame, eh_frame_end).
+    pub fn new(a: &'a A, eh_frame: u64, eh_frame_end: u64) -> Self {
+        Self {
+            a,
+            section: CfiSection::EhFrame,
+            loc: eh_frame,
+            end: eh_frame_end,
+        }
     }
 
-    pub fn next(&mut self) -> Result<Option<CfiEntry>, DwarfError> {
-        let mut loc = self.eh_frame;
-        if loc >= self.eh_frame_end {
+    /// Iterates over the entries of the .debug_frame section [debug_frame, debug_frame_end).
+    #[cfg_attr(not(any(feature = "symbolize", feature = "fuzzing")), allow(unused))]
+    pub fn debug_frame(a: &'a A, debug_frame: u64, debug_frame_end: u64) -> Self {
+        Self {
+            a,
+            section: CfiSection::DebugFrame(debug_frame),
+            loc: debug_frame,
+            end: debug_frame_end,
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<CfiEntry>, DwarfError> {
+        let a = self.a;
+        let mut loc = self.loc;
+        if loc >= self.end {
             return Ok(None);
         }
 
         // Parse length.
-        let mut cfi_length = load::<u32>(loc)? as u64;
-        loc += 4;
-        if cfi_length == 0xffffffff {
-            // 0xffffffff means length is really next 8 bytes.
-            cfi_length = load::<u64>(loc)?;
-            loc += 8;
-        }
+        let (cfi_length, _) = decode_length(a, &mut loc, self.end)?;
         if cfi_length == 0 {
             // Zero terminator.
             return Ok(None);
         }
 
         // Parse CIE ID.
-        let cie_id = load::<u32>(loc)?;
-        if cie_id == 0 {
+        let is_cie = match self.section {
+            CfiSection::EhFrame => a.load::<u32>(loc)? == 0,
+            CfiSection::DebugFrame(_) => {
+                let dwarf64 = loc - self.loc > 4;
+                is_debug_frame_cie_id(read_offset(a, &mut loc, self.end, dwarf64)?, dwarf64)
+            }
+        };
+        if is_cie {
             // Parse CIE.
-            let cie = CommonInformationEntry::decode(self.eh_frame)?;
-            self.eh_frame += cie.cie_length;
+            let cie = CommonInformationEntry::decode(a, self.section, self.loc, self.end)?;
+            self.loc += cie.cie_length;
             Ok(Some(CfiEntry::Cie(cie)))
         } else {
             // Parse FDE & related CIE.
-            let (fde, cie) = FrameDescriptionEntry::decode(self.eh_frame)?;
-            self.eh_frame += fde.fde_length;
+            let (fde, cie) = FrameDescriptionEntry::decode(a, self.section, self.loc, self.end)?;
+            self.loc += fde.fde_length;
             Ok(Some(CfiEntry::FdeCie((fde, cie))))
         }
     }
 }
 
+impl<'a, A: AddressSpace> Iterator for Entries<'a, A> {
+    type Item = Result<CfiEntry, DwarfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.read().transpose();
+        if !matches!(entry, Some(Ok(_))) {
+            // Past the zero terminator or a malformed entry.
+            self.loc = self.end;
+        }
+        entry
+    }
+}
+
 /// Full scan an .eh_frame section to find a FDE for a pc.
-pub fn scan(
+pub fn scan<A: AddressSpace>(
+    a: &A,
     eh_frame: u64,
-    eh_frame_len: u64,
+    eh_frame_end: u64,
     target: u64,
 ) -> Result<(FrameDescriptionEntry, CommonInformationEntry), DwarfError> {
-    let mut entries = Entries::new(eh_frame, eh_frame_len);
-    while let Some(entry) = entries.next()? {
-        match entry {
+    for entry in Entries::new(a, eh_frame, eh_frame_end) {
+        match entry? {
             CfiEntry::Cie(_) => {}
             CfiEntry::FdeCie((fde, cie)) => {
                 if fde.contains(target) {
@@ -266,3 +342,228 @@ pub fn scan(
     }
     Err(DwarfError::FDENotFound)
 }
+
+/// Read a section offset of the 64-bit DWARF format if `dwarf64`, or of the
+/// 32-bit one.
+#[inline]
+fn read_offset<A: AddressSpace>(a: &A, loc: &mut u64, end: u64, dwarf64: bool) -> Result<u64, DwarfError> {
+    if dwarf64 {
+        read::<A, u64>(a, loc, end)
+    } else {
+        read::<A, u32>(a, loc, end).map(u64::from)
+    }
+}
+
+/// Returns whether the CIE id or CIE pointer of an entry of .debug_frame
+/// makes it a CIE, all ones in the format of the entry.
+#[inline]
+fn is_debug_frame_cie_id(id: u64, dwarf64: bool) -> bool {
+    id == if dwarf64 { u64::MAX } else { u64::from(u32::MAX) }
+}
+
+/// Parse the length field of a CIE or FDE, and returns the length and the
+/// address after the entry.
+fn decode_length<A: AddressSpace>(a: &A, loc: &mut u64, end: u64) -> Result<(u64, u64), DwarfError> {
+    let mut length = read::<A, u32>(a, loc, end)? as u64;
+    if length == 0xffffffff {
+        // 0xffffffff means length is really next 8 bytes.
+        length = read::<A, u64>(a, loc, end)?;
+    }
+    match loc.checked_add(length) {
+        Some(entry_end) if entry_end <= end => Ok((length, entry_end)),
+        _ => Err(DwarfError::OutOfBounds(*loc)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::address_space::SliceAddressSpace;
+    use crate::dwarf::testing::{DebugFrameBuilder, EhFrameBuilder};
+
+    const BASE: u64 = 0x10000;
+
+    #[test]
+    fn test_entries() {
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 1, -8, 16, &[0x0c, 0x07, 0x08]);
+        b.fde(cie, 0x1000, 0x100, &[]);
+        b.fde(cie, 0x1100, 0x80, &[]);
+        b.terminate();
+        let space = SliceAddressSpace::new(BASE, &b.data);
+        let mut entries = Entries::new(&space, space.start(), space.end());
+        match entries.next().transpose().unwrap() {
+            Some(CfiEntry::Cie(cie)) => {
+                assert_eq!(cie.code_align_factor, 1);
+                assert_eq!(cie.data_align_factor, -8);
+                assert_eq!(cie.return_address_register, 16);
+                assert!(cie.fdes_have_augmentation_data);
+                assert!(!cie.is_signal_frame);
+            }
+            v => panic!("unexpected entry: {:?}", v),
+        }
+        match entries.next().transpose().unwrap() {
+            Some(CfiEntry::FdeCie((fde, _))) => {
+                assert_eq!(fde.pc_start, 0x1000);
+                assert_eq!(fde.pc_end, 0x1100);
+                assert_eq!(fde.lsda, 0);
+            }
+            v => panic!("unexpected entry: {:?}", v),
+        }
+        assert!(matches!(entries.next().transpose().unwrap(), Some(CfiEntry::FdeCie(_))));
+        assert!(entries.next().transpose().unwrap().is_none());
+
+        let (fde, _) = scan(&space, space.start(), space.end(), 0x1110).unwrap();
+        assert_eq!(fde.pc_start, 0x1100);
+        assert!(matches!(
+            scan(&space, space.start(), space.end(), 0x2000),
+            Err(DwarfError::FDENotFound)
+        ));
+    }
+
+    #[test]
+    fn test_debug_frame_entries() {
+        let mut b = DebugFrameBuilder::default();
+        let cie = b.cie(1, -8, 16, &[0x0c, 0x07, 0x08]);
+        b.fde(cie, 0x1000, 0x100, &[]);
+        b.fde(cie, 0x1100, 0x80, &[]);
+        // A CIE and a FDE of the 64-bit format.
+        let cie64 = b.data.len();
+        let mut body = u64::MAX.to_ne_bytes().to_vec();
+        body.extend_from_slice(&[1, 0, 1, 0x78, 16]);
+        for body in [
+            body,
+            [
+                (cie64 as u64).to_ne_bytes(),
+                0x1200u64.to_ne_bytes(),
+                0x10u64.to_ne_bytes(),
+            ]
+            .concat(),
+        ] {
+            b.data.extend_from_slice(&u32::MAX.to_ne_bytes());
+            b.data.extend_from_slice(&(body.len() as u64).to_ne_bytes());
+            b.data.extend_from_slice(&body);
+        }
+        let space = SliceAddressSpace::new(BASE, &b.data).with_pointer_size(8);
+        let mut entries = Entries::debug_frame(&space, space.start(), space.end());
+        match entries.next().transpose().unwrap() {
+            Some(CfiEntry::Cie(cie)) => {
+                assert_eq!(cie.code_align_factor, 1);
+                assert_eq!(cie.data_align_factor, -8);
+                assert_eq!(cie.return_address_register, 16);
+                assert!(!cie.fdes_have_augmentation_data);
+            }
+            v => panic!("unexpected entry: {:?}", v),
+        }
+        let mut pcs = vec![];
+        while let Some(entry) = entries.next().transpose().unwrap() {
+            if let CfiEntry::FdeCie((fde, cie)) = entry {
+                assert_eq!(cie.return_address_register, 16);
+                pcs.push((fde.pc_start, fde.pc_end));
+            }
+        }
+        assert_eq!(pcs, [(0x1000, 0x1100), (0x1100, 0x1180), (0x1200, 0x1210)]);
+
+        // Addresses of another size.
+        let space = SliceAddressSpace::new(BASE, &b.data).with_pointer_size(4);
+        assert_eq!(
+            CommonInformationEntry::decode(&space, CfiSection::DebugFrame(BASE), BASE, space.end()).unwrap_err(),
+            DwarfError::InvalidAddressSize(8)
+        );
+
+        // The CIEs of .eh_frame have another id.
+        let mut b = EhFrameBuilder::default();
+        b.cie("", 1, -8, 16, &[]);
+        let space = SliceAddressSpace::new(BASE, &b.data);
+        assert_eq!(
+            CommonInformationEntry::decode(&space, CfiSection::DebugFrame(BASE), BASE, space.end()).unwrap_err(),
+            DwarfError::InvalidDebugFrameCieId(0)
+        );
+    }
+
+    #[test]
+    fn test_truncated_entries() {
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 1, -8, 16, &[0x0c, 0x07, 0x08]);
+        b.fde(cie, 0x1000, 0x100, &[]);
+
+        // Every strict prefix of the section must fail gracefully or stop at a
+        // complete entry.
+        for len in 0..b.data.len() {
+            let space = SliceAddressSpace::new(BASE, &b.data[..len]);
+            let entries: Vec<_> = Entries::new(&space, space.start(), space.end()).collect();
+            // Iteration stops after an error.
+            assert!(entries.iter().rev().skip(1).all(Result::is_ok));
+            let _ = scan(&space, space.start(), space.end(), 0x1000);
+        }
+    }
+
+    #[test]
+    fn test_malformed_cie() {
+        // Length past the end of the section.
+        let data = [0xf0u8, 0, 0, 0, 0, 0, 0, 0];
+        let space = SliceAddressSpace::new(BASE, &data);
+        assert!(matches!(
+            CommonInformationEntry::decode(&space, CfiSection::EhFrame, BASE, space.end()),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+
+        // 64-bit length that overflows.
+        let mut data = vec![0xffu8; 4];
+        data.extend_from_slice(&u64::MAX.to_ne_bytes());
+        let space = SliceAddressSpace::new(BASE, &data);
+        assert!(matches!(
+            CommonInformationEntry::decode(&space, CfiSection::EhFrame, BASE, space.end()),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+
+        // Return address register that does not fit in a byte (version 3).
+        let mut body = vec![0, 0, 0, 0, 3, 0, 1, 0x78];
+        leb128::write::unsigned(&mut body, 300).unwrap();
+        let mut data = (body.len() as u32).to_ne_bytes().to_vec();
+        data.extend_from_slice(&body);
+        let space = SliceAddressSpace::new(BASE, &data);
+        assert!(matches!(
+            CommonInformationEntry::decode(&space, CfiSection::EhFrame, BASE, space.end()),
+            Err(DwarfError::InvalidReturnAddressRegisterNumber(300))
+        ));
+
+        // Unterminated augmentation string.
+        let body = [0u8, 0, 0, 0, 1, b'z', b'R'];
+        let mut data = (body.len() as u32).to_ne_bytes().to_vec();
+        data.extend_from_slice(&body);
+        let space = SliceAddressSpace::new(BASE, &data);
+        assert!(matches!(
+            CommonInformationEntry::decode(&space, CfiSection::EhFrame, BASE, space.end()),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_fde() {
+        // CIE pointer before the start of the address space.
+        let mut data = 4u32.to_ne_bytes().to_vec();
+        data.extend_from_slice(&0x100u32.to_ne_bytes());
+        let space = SliceAddressSpace::new(BASE, &data);
+        assert!(FrameDescriptionEntry::decode(&space, CfiSection::EhFrame, BASE, space.end()).is_err());
+
+        // CIE pointer underflow.
+        let space = SliceAddressSpace::new(0, &data);
+        assert!(matches!(
+            FrameDescriptionEntry::decode(&space, CfiSection::EhFrame, 0, space.end()),
+            Err(DwarfError::InvalidCiePointer(4))
+        ));
+
+        // Augmentation data longer than the FDE.
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 1, -8, 16, &[]);
+        let fde = b.fde(cie, 0x1000, 0x100, &[]);
+        let len = b.data.len();
+        b.data[len - 1] = 0x7f;
+        let space = SliceAddressSpace::new(BASE, &b.data);
+        assert!(matches!(
+            FrameDescriptionEntry::decode(&space, CfiSection::EhFrame, BASE + fde as u64, space.end()),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+    }
+}
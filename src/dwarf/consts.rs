@@ -175,7 +175,13 @@ pub const DW_OP_PUSH_OBJECT_ADDRESS: u8 = 0x97;
 pub const DW_OP_CALL2: u8 = 0x98; // 2-byte offset of DIE
 pub const DW_OP_CALL4: u8 = 0x99; // 4-byte offset of DIE
 pub const DW_OP_CALL_REF: u8 = 0x9A; // 4- or 8-byte offset of DIE
+pub const DW_OP_FORM_TLS_ADDRESS: u8 = 0x9B;
+pub const DW_OP_CALL_FRAME_CFA: u8 = 0x9C;
+pub const DW_OP_BIT_PIECE: u8 = 0x9D; // ULEB128 size followed by ULEB128 offset
+pub const DW_OP_IMPLICIT_VALUE: u8 = 0x9E; // ULEB128 size followed by block of that size
+pub const DW_OP_STACK_VALUE: u8 = 0x9F;
 pub const DW_OP_LO_USER: u8 = 0xE0;
+pub const DW_OP_GNU_PUSH_TLS_ADDRESS: u8 = 0xE0;
 pub const DW_OP_APPLE_UNINIT: u8 = 0xF0;
 pub const DW_OP_HI_USER: u8 = 0xFF;
 
@@ -210,3 +216,119 @@ pub const DW_CFA_GNU_WINDOW_SAVE: u8 = 0x2D; // GNU extensions
 pub const DW_CFA_GNU_ARGS_SIZE: u8 = 0x2E;
 pub const DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED: u8 = 0x2F;
 pub const DW_CFA_AARCH64_NEGATE_RA_STATE: u8 = 0x2D; // AARCH64 extensions
+
+// Debug information entries, from version 5 of the DWARF standard and the
+// GNU split DWARF extension to version 4.
+pub const DW_TAG_INLINED_SUBROUTINE: u64 = 0x1D;
+pub const DW_TAG_SUBPROGRAM: u64 = 0x2E;
+pub const DW_TAG_SKELETON_UNIT: u64 = 0x4A;
+
+pub const DW_AT_SIBLING: u64 = 0x01;
+pub const DW_AT_NAME: u64 = 0x03;
+pub const DW_AT_STMT_LIST: u64 = 0x10;
+pub const DW_AT_LOW_PC: u64 = 0x11;
+pub const DW_AT_HIGH_PC: u64 = 0x12;
+pub const DW_AT_COMP_DIR: u64 = 0x1B;
+pub const DW_AT_ABSTRACT_ORIGIN: u64 = 0x31;
+pub const DW_AT_SPECIFICATION: u64 = 0x47;
+pub const DW_AT_RANGES: u64 = 0x55;
+pub const DW_AT_CALL_COLUMN: u64 = 0x57;
+pub const DW_AT_CALL_FILE: u64 = 0x58;
+pub const DW_AT_CALL_LINE: u64 = 0x59;
+pub const DW_AT_LINKAGE_NAME: u64 = 0x6E;
+pub const DW_AT_STR_OFFSETS_BASE: u64 = 0x72;
+pub const DW_AT_ADDR_BASE: u64 = 0x73;
+pub const DW_AT_RNGLISTS_BASE: u64 = 0x74;
+pub const DW_AT_DWO_NAME: u64 = 0x76;
+pub const DW_AT_MIPS_LINKAGE_NAME: u64 = 0x2007;
+pub const DW_AT_GNU_DWO_NAME: u64 = 0x2130;
+pub const DW_AT_GNU_DWO_ID: u64 = 0x2131;
+pub const DW_AT_GNU_RANGES_BASE: u64 = 0x2132;
+pub const DW_AT_GNU_ADDR_BASE: u64 = 0x2133;
+
+pub const DW_FORM_ADDR: u64 = 0x01;
+pub const DW_FORM_BLOCK2: u64 = 0x03;
+pub const DW_FORM_BLOCK4: u64 = 0x04;
+pub const DW_FORM_DATA2: u64 = 0x05;
+pub const DW_FORM_DATA4: u64 = 0x06;
+pub const DW_FORM_DATA8: u64 = 0x07;
+pub const DW_FORM_STRING: u64 = 0x08;
+pub const DW_FORM_BLOCK: u64 = 0x09;
+pub const DW_FORM_BLOCK1: u64 = 0x0A;
+pub const DW_FORM_DATA1: u64 = 0x0B;
+pub const DW_FORM_FLAG: u64 = 0x0C;
+pub const DW_FORM_SDATA: u64 = 0x0D;
+pub const DW_FORM_STRP: u64 = 0x0E;
+pub const DW_FORM_UDATA: u64 = 0x0F;
+pub const DW_FORM_REF_ADDR: u64 = 0x10;
+pub const DW_FORM_REF1: u64 = 0x11;
+pub const DW_FORM_REF2: u64 = 0x12;
+pub const DW_FORM_REF4: u64 = 0x13;
+pub const DW_FORM_REF8: u64 = 0x14;
+pub const DW_FORM_REF_UDATA: u64 = 0x15;
+pub const DW_FORM_INDIRECT: u64 = 0x16;
+pub const DW_FORM_SEC_OFFSET: u64 = 0x17;
+pub const DW_FORM_EXPRLOC: u64 = 0x18;
+pub const DW_FORM_FLAG_PRESENT: u64 = 0x19;
+pub const DW_FORM_STRX: u64 = 0x1A;
+pub const DW_FORM_ADDRX: u64 = 0x1B;
+pub const DW_FORM_REF_SUP4: u64 = 0x1C;
+pub const DW_FORM_STRP_SUP: u64 = 0x1D;
+pub const DW_FORM_DATA16: u64 = 0x1E;
+pub const DW_FORM_LINE_STRP: u64 = 0x1F;
+pub const DW_FORM_REF_SIG8: u64 = 0x20;
+pub const DW_FORM_IMPLICIT_CONST: u64 = 0x21;
+pub const DW_FORM_LOCLISTX: u64 = 0x22;
+pub const DW_FORM_RNGLISTX: u64 = 0x23;
+pub const DW_FORM_REF_SUP8: u64 = 0x24;
+pub const DW_FORM_STRX1: u64 = 0x25;
+pub const DW_FORM_STRX2: u64 = 0x26;
+pub const DW_FORM_STRX3: u64 = 0x27;
+pub const DW_FORM_STRX4: u64 = 0x28;
+pub const DW_FORM_ADDRX1: u64 = 0x29;
+pub const DW_FORM_ADDRX2: u64 = 0x2A;
+pub const DW_FORM_ADDRX3: u64 = 0x2B;
+pub const DW_FORM_ADDRX4: u64 = 0x2C;
+pub const DW_FORM_GNU_ADDR_INDEX: u64 = 0x1F01;
+pub const DW_FORM_GNU_STR_INDEX: u64 = 0x1F02;
+pub const DW_FORM_GNU_REF_ALT: u64 = 0x1F20;
+pub const DW_FORM_GNU_STRP_ALT: u64 = 0x1F21;
+
+// Unit header types.
+pub const DW_UT_COMPILE: u8 = 0x01;
+pub const DW_UT_TYPE: u8 = 0x02;
+pub const DW_UT_PARTIAL: u8 = 0x03;
+pub const DW_UT_SKELETON: u8 = 0x04;
+pub const DW_UT_SPLIT_COMPILE: u8 = 0x05;
+pub const DW_UT_SPLIT_TYPE: u8 = 0x06;
+
+// Line number program opcodes.
+pub const DW_LNS_COPY: u8 = 0x01;
+pub const DW_LNS_ADVANCE_PC: u8 = 0x02;
+pub const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+pub const DW_LNS_SET_FILE: u8 = 0x04;
+pub const DW_LNS_SET_COLUMN: u8 = 0x05;
+pub const DW_LNS_CONST_ADD_PC: u8 = 0x08;
+pub const DW_LNS_FIXED_ADVANCE_PC: u8 = 0x09;
+pub const DW_LNE_END_SEQUENCE: u8 = 0x01;
+pub const DW_LNE_SET_ADDRESS: u8 = 0x02;
+pub const DW_LNE_DEFINE_FILE: u8 = 0x03;
+pub const DW_LNCT_PATH: u64 = 0x01;
+pub const DW_LNCT_DIRECTORY_INDEX: u64 = 0x02;
+
+// Range list entries.
+pub const DW_RLE_END_OF_LIST: u8 = 0x00;
+pub const DW_RLE_BASE_ADDRESSX: u8 = 0x01;
+pub const DW_RLE_STARTX_ENDX: u8 = 0x02;
+pub const DW_RLE_STARTX_LENGTH: u8 = 0x03;
+pub const DW_RLE_OFFSET_PAIR: u8 = 0x04;
+pub const DW_RLE_BASE_ADDRESS: u8 = 0x05;
+pub const DW_RLE_START_END: u8 = 0x06;
+pub const DW_RLE_START_LENGTH: u8 = 0x07;
+
+// Section identifiers of the package file indexes, .debug_cu_index.
+pub const DW_SECT_INFO: u32 = 1;
+pub const DW_SECT_ABBREV: u32 = 3;
+pub const DW_SECT_LINE: u32 = 4;
+pub const DW_SECT_STR_OFFSETS: u32 = 6;
+pub const DW_SECT_RNGLISTS: u32 = 8;
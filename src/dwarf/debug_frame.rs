@@ -0,0 +1,227 @@
+//! The .debug_frame sections registered for the unwinder, which describe
+//! code that .eh_frame doesn't, e.g. of objects built without unwind tables
+//! whose debug information is in a separate file.
+//!
+//! Sections are indexed and registered outside of signal handlers, and
+//! looked up without locks or allocations while stepping.
+
+use crate::dwarf::address_space::SliceAddressSpace;
+use crate::dwarf::cfi::{CfiEntry, CfiSection, CommonInformationEntry, Entries, FrameDescriptionEntry};
+use crate::dwarf::DwarfError;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+/// The maximum number of objects whose .debug_frame can be registered.
+const MAX_DEBUG_FRAMES: usize = 128;
+
+/// The registered sections, filled in order. A section replaced by another
+/// one of the same object or deregistered is leaked, as a signal handler may
+/// be reading it.
+static DEBUG_FRAMES: [AtomicPtr<DebugFrame>; MAX_DEBUG_FRAMES] =
+    [const { AtomicPtr::new(ptr::null_mut()) }; MAX_DEBUG_FRAMES];
+
+/// Takes the place of deregistered sections, so that lookups still go past
+/// it to the ones registered after. Its slot can be reused.
+static REMOVED: DebugFrame = DebugFrame {
+    base: 0,
+    section: &[],
+    fdes: Vec::new(),
+};
+
+#[inline]
+fn removed() -> *mut DebugFrame {
+    &REMOVED as *const DebugFrame as *mut DebugFrame
+}
+
+/// Serializes registrations, lookups don't take it.
+static REGISTER_LOCK: Mutex<()> = Mutex::new(());
+
+/// A .debug_frame section of a loaded object, indexed by FDE.
+#[derive(Debug)]
+pub struct DebugFrame {
+    /// The address the object is loaded at, which the addresses in the
+    /// section are relative to.
+    base: u64,
+    section: &'static [u8],
+    /// The address ranges of the FDEs, sorted, and their offsets in the
+    /// section.
+    fdes: Vec<(u64, u64, usize)>,
+}
+
+impl DebugFrame {
+    /// Indexes `section`, the .debug_frame of the object loaded at `base`.
+    /// Entries after a malformed one are left out.
+    pub fn new(base: u64, section: &'static [u8]) -> Self {
+        let a = SliceAddressSpace::new(section.as_ptr() as u64, section);
+        let mut fdes = vec![];
+        for entry in Entries::debug_frame(&a, a.start(), a.end()).map_while(Result::ok) {
+            if let CfiEntry::FdeCie((fde, _)) = entry {
+                if fde.pc_start < fde.pc_end {
+                    fdes.push((fde.pc_start, fde.pc_end, (fde.fde_start - a.start()) as usize));
+                }
+            }
+        }
+        fdes.sort_unstable();
+        Self { base, section, fdes }
+    }
+
+    /// Returns whether no FDE was found.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.fdes.is_empty()
+    }
+
+    /// Returns the FDE covering `pc`, an address of the object file, and its
+    /// CIE. The section is read where it is mapped, which the addresses of
+    /// their instructions point to.
+    fn search(&self, pc: u64) -> Result<(FrameDescriptionEntry, CommonInformationEntry), DwarfError> {
+        let i = self.fdes.partition_point(|&(start, _, _)| start <= pc);
+        match i.checked_sub(1).map(|i| self.fdes[i]) {
+            Some((_, end, offset)) if pc < end => {
+                let a = SliceAddressSpace::new(self.section.as_ptr() as u64, self.section);
+                FrameDescriptionEntry::decode(
+                    &a,
+                    CfiSection::DebugFrame(a.start()),
+                    a.start() + offset as u64,
+                    a.end(),
+                )
+            }
+            _ => Err(DwarfError::FDENotFound),
+        }
+    }
+}
+
+/// Returns the slot of the section of the object loaded at `base`, if it is
+/// registered. Must be called with `REGISTER_LOCK` held.
+fn find_slot(base: u64) -> Option<&'static AtomicPtr<DebugFrame>> {
+    DEBUG_FRAMES
+        .iter()
+        .take_while(|slot| !slot.load(Ordering::Acquire).is_null())
+        .find(|slot| {
+            let current = slot.load(Ordering::Acquire);
+            current != removed() && unsafe { (*current).base } == base
+        })
+}
+
+/// Registers `debug_frame` for the unwinder, replacing the section of the
+/// object loaded at the same address if any. Returns `false` if too many
+/// are registered.
+///
+/// The section must stay mapped until it is deregistered with
+/// [deregister_debug_frame], e.g. before the object is unloaded.
+pub fn register_debug_frame(debug_frame: DebugFrame) -> bool {
+    let _lock = REGISTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let slot = find_slot(debug_frame.base).or_else(|| {
+        DEBUG_FRAMES.iter().find(|slot| {
+            let current = slot.load(Ordering::Acquire);
+            current.is_null() || current == removed()
+        })
+    });
+    match slot {
+        Some(slot) => {
+            slot.store(Box::into_raw(Box::new(debug_frame)), Ordering::Release);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Deregisters the section of the object loaded at `base`, so that the
+/// unwinder no longer uses it, e.g. before the object is unloaded with
+/// `dlclose`. Returns `false` if none is registered.
+pub fn deregister_debug_frame(base: u64) -> bool {
+    let _lock = REGISTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    match find_slot(base) {
+        Some(slot) => {
+            slot.store(removed(), Ordering::Release);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the FDE covering `pc` among the registered sections, its CIE,
+/// and the address the object is loaded at, which `pc` must be made
+/// relative to when running its instructions.
+pub fn search_debug_frame(pc: u64) -> Result<(FrameDescriptionEntry, CommonInformationEntry, u64), DwarfError> {
+    for slot in &DEBUG_FRAMES {
+        let debug_frame = slot.load(Ordering::Acquire);
+        if debug_frame.is_null() {
+            break;
+        }
+        let debug_frame = unsafe { &*debug_frame };
+        match debug_frame.search(pc.wrapping_sub(debug_frame.base)) {
+            Ok((fde, cie)) => return Ok((fde, cie, debug_frame.base)),
+            Err(DwarfError::FDENotFound) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(DwarfError::FDENotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::testing::DebugFrameBuilder;
+
+    /// Returns a section with FDEs for [0x1000, 0x1100) and [0x2000, 0x2010).
+    fn section() -> &'static [u8] {
+        let mut b = DebugFrameBuilder::default();
+        let cie = b.cie(1, -8, 16, &[]);
+        b.fde(cie, 0x2000, 0x10, &[]);
+        b.fde(cie, 0x1000, 0x100, &[]);
+        b.fde(cie, 0x3000, 0, &[]);
+        Box::leak(b.data.into_boxed_slice())
+    }
+
+    #[test]
+    fn test_search() {
+        let debug_frame = DebugFrame::new(0x7000_0000, section());
+        assert!(!debug_frame.is_empty());
+        let (fde, cie) = debug_frame.search(0x10ff).unwrap();
+        assert_eq!((fde.pc_start, fde.pc_end), (0x1000, 0x1100));
+        assert_eq!(cie.return_address_register, 16);
+        let (fde, _) = debug_frame.search(0x2000).unwrap();
+        assert_eq!((fde.pc_start, fde.pc_end), (0x2000, 0x2010));
+        for pc in [0, 0xfff, 0x1100, 0x2010, 0x3000] {
+            assert_eq!(debug_frame.search(pc).unwrap_err(), DwarfError::FDENotFound);
+        }
+        assert!(DebugFrame::new(0x7000_0000, &[1, 2, 3]).is_empty());
+    }
+
+    #[test]
+    fn test_register() {
+        // Other tests register sections too, at other addresses.
+        const BASE: u64 = 0x7100_0000;
+        assert_eq!(search_debug_frame(BASE + 0x1000).unwrap_err(), DwarfError::FDENotFound);
+        assert!(register_debug_frame(DebugFrame::new(BASE, section())));
+        let (fde, _, base) = search_debug_frame(BASE + 0x1000).unwrap();
+        assert_eq!((fde.pc_start, base), (0x1000, BASE));
+        assert_eq!(search_debug_frame(BASE + 0x1100).unwrap_err(), DwarfError::FDENotFound);
+
+        // Registering the object again replaces its section.
+        assert!(register_debug_frame(DebugFrame::new(BASE, &[])));
+        assert_eq!(search_debug_frame(BASE + 0x1000).unwrap_err(), DwarfError::FDENotFound);
+    }
+
+    #[test]
+    fn test_deregister() {
+        const FIRST: u64 = 0x7300_0000;
+        const SECOND: u64 = 0x7400_0000;
+        assert!(register_debug_frame(DebugFrame::new(FIRST, section())));
+        assert!(register_debug_frame(DebugFrame::new(SECOND, section())));
+        assert!(deregister_debug_frame(FIRST));
+        assert!(!deregister_debug_frame(FIRST));
+        assert_eq!(search_debug_frame(FIRST + 0x1000).unwrap_err(), DwarfError::FDENotFound);
+        // The sections registered after are still found.
+        let (_, _, base) = search_debug_frame(SECOND + 0x1000).unwrap();
+        assert_eq!(base, SECOND);
+
+        // Its slot is reused.
+        assert!(register_debug_frame(DebugFrame::new(FIRST, section())));
+        assert!(search_debug_frame(FIRST + 0x1000).is_ok());
+        assert!(deregister_debug_frame(FIRST));
+        assert!(deregister_debug_frame(SECOND));
+    }
+}
@@ -0,0 +1,1139 @@
+//! Parsers of the DWARF debug information of versions 2 to 5 (.debug_info,
+//! .debug_abbrev, .debug_ranges, .debug_rnglists, ...), which expand an
+//! address into the chain of functions inlined at it.
+//!
+//! Split DWARF is supported both as the GNU extension to version 4 and as
+//! standardized by version 5: the skeleton units of an object point to .dwo
+//! files, whose sections may have been merged into a .dwp package.
+
+use crate::dwarf::address_space::SliceAddressSpace;
+use crate::dwarf::consts::*;
+use crate::dwarf::encoding::{decode_sleb128, decode_uleb128, read};
+use crate::dwarf::line::LineProgram;
+use crate::dwarf::DwarfError;
+use std::ops::Range;
+
+/// How many DW_AT_abstract_origin and DW_AT_specification references are
+/// followed to find the name of a function.
+const MAX_REFERENCES: usize = 16;
+
+/// The DWARF sections of an object file, or of a .dwo or .dwp file whose
+/// sections have the same names with a .dwo suffix. Missing sections are
+/// empty.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DebugSections<'a> {
+    pub info: &'a [u8],
+    pub abbrev: &'a [u8],
+    pub str: &'a [u8],
+    pub line: &'a [u8],
+    pub line_str: &'a [u8],
+    pub ranges: &'a [u8],
+    pub rnglists: &'a [u8],
+    pub addr: &'a [u8],
+    pub str_offsets: &'a [u8],
+    /// The unit index of a .dwp file.
+    pub cu_index: &'a [u8],
+}
+
+/// A function at an address, with the source location of the address in
+/// it: the call site of the next inner function if it was inlined.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SourceFrame<'a> {
+    /// The linkage name of the function, or its plain name if it has none.
+    pub name: Option<&'a [u8]>,
+    pub file: Option<Vec<u8>>,
+    /// 0 if unknown.
+    pub line: u32,
+    /// 0 if unknown.
+    pub column: u32,
+}
+
+/// A cursor over a section, reading with the helpers of `encoding`.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Reader<'a> {
+    data: &'a [u8],
+    space: SliceAddressSpace<'a>,
+    pub loc: u64,
+    pub end: u64,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    pub fn new(data: &'a [u8], loc: u64) -> Self {
+        Self {
+            data,
+            space: SliceAddressSpace::new(0, data),
+            loc,
+            end: data.len() as u64,
+        }
+    }
+
+    #[inline]
+    pub fn u8(&mut self) -> Result<u8, DwarfError> {
+        read(&self.space, &mut self.loc, self.end)
+    }
+
+    #[inline]
+    pub fn u16(&mut self) -> Result<u16, DwarfError> {
+        read(&self.space, &mut self.loc, self.end)
+    }
+
+    #[inline]
+    pub fn u32(&mut self) -> Result<u32, DwarfError> {
+        read(&self.space, &mut self.loc, self.end)
+    }
+
+    #[inline]
+    pub fn u64(&mut self) -> Result<u64, DwarfError> {
+        read(&self.space, &mut self.loc, self.end)
+    }
+
+    #[inline]
+    pub fn uleb(&mut self) -> Result<u64, DwarfError> {
+        decode_uleb128(&self.space, &mut self.loc, self.end)
+    }
+
+    #[inline]
+    pub fn sleb(&mut self) -> Result<i64, DwarfError> {
+        decode_sleb128(&self.space, &mut self.loc, self.end)
+    }
+
+    /// Reads an unsigned value of `size` bytes, e.g. an address or an offset.
+    pub fn sized(&mut self, size: u8) -> Result<u64, DwarfError> {
+        match size {
+            1 => self.u8().map(u64::from),
+            2 => self.u16().map(u64::from),
+            3 => {
+                let (a, b) = (u64::from(self.u16()?), u64::from(self.u8()?));
+                Ok(if cfg!(target_endian = "little") {
+                    a | b << 16
+                } else {
+                    a << 8 | b
+                })
+            }
+            4 => self.u32().map(u64::from),
+            8 => self.u64(),
+            _ => Err(DwarfError::InvalidAddressSize(size)),
+        }
+    }
+
+    pub fn skip(&mut self, len: u64) -> Result<(), DwarfError> {
+        match self.loc.checked_add(len) {
+            Some(next) if next <= self.end => {
+                self.loc = next;
+                Ok(())
+            }
+            _ => Err(DwarfError::OutOfBounds(self.loc)),
+        }
+    }
+
+    pub fn bytes(&mut self, len: u64) -> Result<&'a [u8], DwarfError> {
+        let start = self.loc;
+        self.skip(len)?;
+        Ok(&self.data[start as usize..self.loc as usize])
+    }
+
+    /// Reads a NUL-terminated string, without the NUL.
+    pub fn c_str(&mut self) -> Result<&'a [u8], DwarfError> {
+        let rest = self
+            .data
+            .get(self.loc as usize..self.end as usize)
+            .ok_or(DwarfError::OutOfBounds(self.loc))?;
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(DwarfError::OutOfBounds(self.end))?;
+        self.loc += len as u64 + 1;
+        Ok(&rest[..len])
+    }
+
+    /// Reads the initial length of a unit, and bounds the reader to the
+    /// unit. Returns the size of offsets, 4 in the 32-bit DWARF format and 8
+    /// in the 64-bit one.
+    pub fn unit_length(&mut self) -> Result<u8, DwarfError> {
+        let (len, offset_size) = match self.u32()? {
+            0xffff_ffff => (self.u64()?, 8),
+            len => (u64::from(len), 4),
+        };
+        match self.loc.checked_add(len) {
+            Some(end) if end <= self.end => {
+                self.end = end;
+                Ok(offset_size)
+            }
+            _ => Err(DwarfError::OutOfBounds(self.loc)),
+        }
+    }
+}
+
+/// How the values of a unit, or of a line program header, are encoded.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Format {
+    pub version: u16,
+    pub offset_size: u8,
+    pub address_size: u8,
+}
+
+/// An attribute value, to be resolved against the sections and bases of
+/// its unit.
+#[derive(Debug, Copy, Clone)]
+pub(super) enum Value<'a> {
+    Address(u64),
+    AddressIndex(u64),
+    Constant(u64),
+    Signed(i64),
+    String(&'a [u8]),
+    /// An offset in .debug_str.
+    Strp(u64),
+    /// An offset in .debug_line_str.
+    LineStrp(u64),
+    StrIndex(u64),
+    /// A reference relative to the unit.
+    UnitRef(u64),
+    /// A reference relative to .debug_info.
+    InfoRef(u64),
+    SecOffset(u64),
+    RangeListIndex(u64),
+    /// Values of no interest, e.g. blocks and flags.
+    Other,
+}
+
+impl Value<'_> {
+    #[inline]
+    pub fn constant(&self) -> Option<u64> {
+        match *self {
+            Value::Constant(v) => Some(v),
+            Value::Signed(v) => Some(v as u64),
+            _ => None,
+        }
+    }
+
+    /// Returns a section offset, which were constants before version 4.
+    #[inline]
+    fn offset(&self) -> Option<u64> {
+        match *self {
+            Value::SecOffset(v) | Value::Constant(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a value of `form`, `implicit` being the value of
+/// DW_FORM_implicit_const given by the abbreviation.
+pub(super) fn read_value<'a>(
+    r: &mut Reader<'a>,
+    form: u64,
+    implicit: i64,
+    format: &Format,
+) -> Result<Value<'a>, DwarfError> {
+    Ok(match form {
+        DW_FORM_ADDR => Value::Address(r.sized(format.address_size)?),
+        DW_FORM_ADDRX | DW_FORM_GNU_ADDR_INDEX => Value::AddressIndex(r.uleb()?),
+        DW_FORM_ADDRX1..=DW_FORM_ADDRX4 => Value::AddressIndex(r.sized((form - DW_FORM_ADDRX1 + 1) as u8)?),
+        DW_FORM_DATA1 => Value::Constant(r.sized(1)?),
+        DW_FORM_DATA2 => Value::Constant(r.sized(2)?),
+        DW_FORM_DATA4 => Value::Constant(r.sized(4)?),
+        DW_FORM_DATA8 => Value::Constant(r.sized(8)?),
+        DW_FORM_UDATA => Value::Constant(r.uleb()?),
+        DW_FORM_SDATA => Value::Signed(r.sleb()?),
+        DW_FORM_IMPLICIT_CONST => Value::Signed(implicit),
+        DW_FORM_STRING => Value::String(r.c_str()?),
+        DW_FORM_STRP => Value::Strp(r.sized(format.offset_size)?),
+        DW_FORM_LINE_STRP => Value::LineStrp(r.sized(format.offset_size)?),
+        DW_FORM_STRX | DW_FORM_GNU_STR_INDEX => Value::StrIndex(r.uleb()?),
+        DW_FORM_STRX1..=DW_FORM_STRX4 => Value::StrIndex(r.sized((form - DW_FORM_STRX1 + 1) as u8)?),
+        DW_FORM_REF1 => Value::UnitRef(r.sized(1)?),
+        DW_FORM_REF2 => Value::UnitRef(r.sized(2)?),
+        DW_FORM_REF4 => Value::UnitRef(r.sized(4)?),
+        DW_FORM_REF8 => Value::UnitRef(r.sized(8)?),
+        DW_FORM_REF_UDATA => Value::UnitRef(r.uleb()?),
+        // Version 2 had address sized references.
+        DW_FORM_REF_ADDR if format.version <= 2 => Value::InfoRef(r.sized(format.address_size)?),
+        DW_FORM_REF_ADDR => Value::InfoRef(r.sized(format.offset_size)?),
+        DW_FORM_SEC_OFFSET => Value::SecOffset(r.sized(format.offset_size)?),
+        DW_FORM_RNGLISTX => Value::RangeListIndex(r.uleb()?),
+        DW_FORM_INDIRECT => {
+            let form = r.uleb()?;
+            return read_value(r, form, implicit, format);
+        }
+        // Values of supplementary object files are ignored.
+        DW_FORM_STRP_SUP | DW_FORM_GNU_STRP_ALT | DW_FORM_GNU_REF_ALT => {
+            r.sized(format.offset_size).map(|_| Value::Other)?
+        }
+        DW_FORM_FLAG => r.skip(1).map(|_| Value::Other)?,
+        DW_FORM_FLAG_PRESENT => Value::Other,
+        DW_FORM_REF_SUP4 => r.skip(4).map(|_| Value::Other)?,
+        DW_FORM_REF_SUP8 | DW_FORM_REF_SIG8 => r.skip(8).map(|_| Value::Other)?,
+        DW_FORM_DATA16 => r.skip(16).map(|_| Value::Other)?,
+        DW_FORM_LOCLISTX => r.uleb().map(|_| Value::Other)?,
+        DW_FORM_BLOCK1 => {
+            let len = r.sized(1)?;
+            r.skip(len).map(|_| Value::Other)?
+        }
+        DW_FORM_BLOCK2 => {
+            let len = r.sized(2)?;
+            r.skip(len).map(|_| Value::Other)?
+        }
+        DW_FORM_BLOCK4 => {
+            let len = r.sized(4)?;
+            r.skip(len).map(|_| Value::Other)?
+        }
+        DW_FORM_BLOCK | DW_FORM_EXPRLOC => {
+            let len = r.uleb()?;
+            r.skip(len).map(|_| Value::Other)?
+        }
+        _ => return Err(DwarfError::InvalidForm(form)),
+    })
+}
+
+#[derive(Debug, Copy, Clone)]
+struct AttributeSpec {
+    name: u64,
+    form: u64,
+    implicit: i64,
+}
+
+#[derive(Debug, Clone)]
+struct Abbrev {
+    code: u64,
+    tag: u64,
+    has_children: bool,
+    attributes: Vec<AttributeSpec>,
+}
+
+/// An abbreviation table of .debug_abbrev.
+#[derive(Debug, Clone)]
+struct Abbrevs(Vec<Abbrev>);
+
+impl Abbrevs {
+    fn parse(data: &[u8], offset: u64) -> Result<Self, DwarfError> {
+        let mut r = Reader::new(data, offset);
+        let mut abbrevs = vec![];
+        loop {
+            let code = r.uleb()?;
+            if code == 0 {
+                break;
+            }
+            let tag = r.uleb()?;
+            let has_children = r.u8()? != 0;
+            let mut attributes = vec![];
+            loop {
+                let (name, form) = (r.uleb()?, r.uleb()?);
+                if name == 0 && form == 0 {
+                    break;
+                }
+                let implicit = if form == DW_FORM_IMPLICIT_CONST { r.sleb()? } else { 0 };
+                attributes.push(AttributeSpec { name, form, implicit });
+            }
+            abbrevs.push(Abbrev {
+                code,
+                tag,
+                has_children,
+                attributes,
+            });
+        }
+        Ok(Self(abbrevs))
+    }
+
+    #[inline]
+    fn get(&self, code: u64) -> Option<&Abbrev> {
+        // Codes are usually consecutive from 1.
+        match self.0.get((code as usize).wrapping_sub(1)) {
+            Some(abbrev) if abbrev.code == code => Some(abbrev),
+            _ => self.0.iter().find(|abbrev| abbrev.code == code),
+        }
+    }
+}
+
+/// The header of a unit of .debug_info.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct UnitHeader {
+    offset: u64,
+    end: u64,
+    /// The offset of the first entry, the unit entry.
+    entries: u64,
+    pub format: Format,
+    unit_type: u8,
+    abbrev_offset: u64,
+    dwo_id: Option<u64>,
+}
+
+impl UnitHeader {
+    fn parse(info: &[u8], offset: u64) -> Result<Self, DwarfError> {
+        let mut r = Reader::new(info, offset);
+        let offset_size = r.unit_length()?;
+        let version = r.u16()?;
+        let mut dwo_id = None;
+        let (unit_type, address_size, abbrev_offset) = match version {
+            2..=4 => {
+                let abbrev_offset = r.sized(offset_size)?;
+                (DW_UT_COMPILE, r.u8()?, abbrev_offset)
+            }
+            5 => {
+                let (unit_type, address_size) = (r.u8()?, r.u8()?);
+                let abbrev_offset = r.sized(offset_size)?;
+                match unit_type {
+                    DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => dwo_id = Some(r.u64()?),
+                    // The type signature and offset.
+                    DW_UT_TYPE | DW_UT_SPLIT_TYPE => r.skip(8 + u64::from(offset_size))?,
+                    _ => {}
+                }
+                (unit_type, address_size, abbrev_offset)
+            }
+            _ => return Err(DwarfError::InvalidUnitVersion(version)),
+        };
+        Ok(Self {
+            offset,
+            end: r.end,
+            entries: r.loc,
+            format: Format {
+                version,
+                offset_size,
+                address_size,
+            },
+            unit_type,
+            abbrev_offset,
+            dwo_id,
+        })
+    }
+
+    #[inline]
+    fn contains(&self, offset: u64) -> bool {
+        offset >= self.entries && offset < self.end
+    }
+}
+
+/// The attributes of interest of an entry.
+#[derive(Debug, Default, Copy, Clone)]
+struct Attributes<'a> {
+    name: Option<Value<'a>>,
+    linkage_name: Option<Value<'a>>,
+    low_pc: Option<Value<'a>>,
+    high_pc: Option<Value<'a>>,
+    ranges: Option<Value<'a>>,
+    abstract_origin: Option<Value<'a>>,
+    specification: Option<Value<'a>>,
+    call_file: Option<Value<'a>>,
+    call_line: Option<Value<'a>>,
+    call_column: Option<Value<'a>>,
+    sibling: Option<Value<'a>>,
+    stmt_list: Option<Value<'a>>,
+    comp_dir: Option<Value<'a>>,
+    dwo_name: Option<Value<'a>>,
+    dwo_id: Option<Value<'a>>,
+    addr_base: Option<Value<'a>>,
+    str_offsets_base: Option<Value<'a>>,
+    rnglists_base: Option<Value<'a>>,
+    ranges_base: Option<Value<'a>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry<'a> {
+    tag: u64,
+    has_children: bool,
+    attributes: Attributes<'a>,
+}
+
+/// The bases of the indexes and offsets in the attributes of a unit.
+#[derive(Debug, Default, Copy, Clone)]
+struct Bases {
+    /// The address offsets of range lists are relative to.
+    address: u64,
+    addr: u64,
+    str_offsets: u64,
+    rnglists: u64,
+    /// DW_AT_GNU_ranges_base, for the .debug_ranges offsets of split units.
+    ranges: u64,
+}
+
+/// A compilation unit of .debug_info, with what is needed to read it again.
+#[derive(Debug, Clone)]
+pub struct Unit {
+    header: UnitHeader,
+    bases: Bases,
+    /// The base of the range lists of its split unit, if a skeleton.
+    split_ranges_base: u64,
+    stmt_list: Option<u64>,
+    comp_dir: Option<Vec<u8>>,
+    dwo_name: Option<Vec<u8>>,
+    dwo_id: Option<u64>,
+}
+
+impl Unit {
+    /// Returns the compilation directory, which relative paths of the unit
+    /// are relative to.
+    #[inline]
+    pub fn comp_dir(&self) -> Option<&[u8]> {
+        self.comp_dir.as_deref()
+    }
+
+    /// Returns the path of the .dwo file of a skeleton unit.
+    #[inline]
+    pub fn dwo_name(&self) -> Option<&[u8]> {
+        self.dwo_name.as_deref()
+    }
+
+    /// Returns the id of the split unit of a skeleton unit, its key in .dwp
+    /// files.
+    #[inline]
+    pub fn dwo_id(&self) -> Option<u64> {
+        self.dwo_id
+    }
+
+    /// Returns whether the entries of the unit are in a .dwo file.
+    #[inline]
+    pub fn is_skeleton(&self) -> bool {
+        self.dwo_name.is_some() || self.dwo_id.is_some()
+    }
+}
+
+/// A unit being read, with the sections and bases its attributes are
+/// resolved against.
+pub(super) struct UnitReader<'a> {
+    pub sections: DebugSections<'a>,
+    pub header: UnitHeader,
+    abbrevs: Abbrevs,
+    bases: Bases,
+}
+
+impl<'a> UnitReader<'a> {
+    fn new(sections: DebugSections<'a>, header: UnitHeader, bases: Bases) -> Result<Self, DwarfError> {
+        Ok(Self {
+            sections,
+            header,
+            abbrevs: Abbrevs::parse(sections.abbrev, header.abbrev_offset)?,
+            bases,
+        })
+    }
+
+    /// Reads the unit at `header`, with the bases given by its unit entry.
+    fn with_unit_entry(sections: DebugSections<'a>, header: UnitHeader) -> Result<(Self, Attributes<'a>), DwarfError> {
+        let mut unit = Self::new(sections, header, Bases::default())?;
+        let root = unit.entry_at(header.entries)?;
+        let offset = |value: Option<Value<'_>>| value.and_then(|v| v.offset()).unwrap_or(0);
+        unit.bases.addr = offset(root.addr_base);
+        unit.bases.str_offsets = offset(root.str_offsets_base);
+        unit.bases.rnglists = offset(root.rnglists_base);
+        if let Some(low_pc) = root.low_pc {
+            unit.bases.address = unit.address(low_pc)?.unwrap_or(0);
+        }
+        Ok((unit, root))
+    }
+
+    /// Reads the split unit of `skeleton` in the sections of a .dwo file, or
+    /// the contributions of a .dwp file to them.
+    fn split(sections: &DebugSections<'a>, split: &DebugSections<'a>, skeleton: &Unit) -> Result<Self, DwarfError> {
+        // Addresses and version 4 range lists stay in the object.
+        let sections = DebugSections {
+            addr: sections.addr,
+            ranges: sections.ranges,
+            ..*split
+        };
+        let mut offset = 0;
+        while offset < sections.info.len() as u64 {
+            let header = UnitHeader::parse(sections.info, offset)?;
+            offset = header.end;
+            if header.unit_type != DW_UT_COMPILE && header.unit_type != DW_UT_SPLIT_COMPILE {
+                continue;
+            }
+            // Offset tables of version 5 follow their header.
+            let offset_size = u64::from(header.format.offset_size);
+            let (str_offsets, rnglists) = match header.format.version {
+                5 => (2 * offset_size, offset_size + 8),
+                _ => (0, 0),
+            };
+            let bases = Bases {
+                str_offsets,
+                rnglists,
+                ranges: skeleton.split_ranges_base,
+                ..skeleton.bases
+            };
+            let unit = Self::new(sections, header, bases)?;
+            let dwo_id = match header.dwo_id {
+                Some(dwo_id) => Some(dwo_id),
+                None => unit.entry_at(header.entries)?.dwo_id.and_then(|v| v.constant()),
+            };
+            if skeleton.dwo_id.is_none() || dwo_id == skeleton.dwo_id {
+                return Ok(unit);
+            }
+        }
+        Err(DwarfError::SplitUnitNotFound)
+    }
+
+    /// Returns the indexed unit of a unit entry.
+    fn unit(&self, root: &Attributes<'a>) -> Result<Unit, DwarfError> {
+        let string = |value: Option<Value<'a>>| -> Result<Option<Vec<u8>>, DwarfError> {
+            Ok(match value {
+                Some(value) => self.string(value)?.map(|s| s.to_vec()),
+                None => None,
+            })
+        };
+        Ok(Unit {
+            header: self.header,
+            bases: self.bases,
+            split_ranges_base: root.ranges_base.and_then(|v| v.offset()).unwrap_or(0),
+            stmt_list: root.stmt_list.and_then(|v| v.offset()),
+            comp_dir: string(root.comp_dir)?,
+            dwo_name: string(root.dwo_name)?,
+            dwo_id: self.header.dwo_id.or_else(|| root.dwo_id.and_then(|v| v.constant())),
+        })
+    }
+
+    /// Reads the entry at `r`, `None` for the null entries ending siblings.
+    fn entry(&self, r: &mut Reader<'a>) -> Result<Option<Entry<'a>>, DwarfError> {
+        let code = r.uleb()?;
+        if code == 0 {
+            return Ok(None);
+        }
+        let abbrev = self
+            .abbrevs
+            .get(code)
+            .ok_or(DwarfError::InvalidAbbreviationCode(code))?;
+        let mut attributes = Attributes::default();
+        for spec in &abbrev.attributes {
+            let value = match read_value(r, spec.form, spec.implicit, &self.header.format)? {
+                Value::UnitRef(offset) => Value::InfoRef(self.header.offset.wrapping_add(offset)),
+                value => value,
+            };
+            let attribute = match spec.name {
+                DW_AT_NAME => &mut attributes.name,
+                DW_AT_LINKAGE_NAME | DW_AT_MIPS_LINKAGE_NAME => &mut attributes.linkage_name,
+                DW_AT_LOW_PC => &mut attributes.low_pc,
+                DW_AT_HIGH_PC => &mut attributes.high_pc,
+                DW_AT_RANGES => &mut attributes.ranges,
+                DW_AT_ABSTRACT_ORIGIN => &mut attributes.abstract_origin,
+                DW_AT_SPECIFICATION => &mut attributes.specification,
+                DW_AT_CALL_FILE => &mut attributes.call_file,
+                DW_AT_CALL_LINE => &mut attributes.call_line,
+                DW_AT_CALL_COLUMN => &mut attributes.call_column,
+                DW_AT_SIBLING => &mut attributes.sibling,
+                DW_AT_STMT_LIST => &mut attributes.stmt_list,
+                DW_AT_COMP_DIR => &mut attributes.comp_dir,
+                DW_AT_DWO_NAME | DW_AT_GNU_DWO_NAME => &mut attributes.dwo_name,
+                DW_AT_GNU_DWO_ID => &mut attributes.dwo_id,
+                DW_AT_ADDR_BASE | DW_AT_GNU_ADDR_BASE => &mut attributes.addr_base,
+                DW_AT_STR_OFFSETS_BASE => &mut attributes.str_offsets_base,
+                DW_AT_RNGLISTS_BASE => &mut attributes.rnglists_base,
+                DW_AT_GNU_RANGES_BASE => &mut attributes.ranges_base,
+                _ => continue,
+            };
+            *attribute = Some(value);
+        }
+        Ok(Some(Entry {
+            tag: abbrev.tag,
+            has_children: abbrev.has_children,
+            attributes,
+        }))
+    }
+
+    /// Reads the attributes of the entry at `offset` in .debug_info.
+    fn entry_at(&self, offset: u64) -> Result<Attributes<'a>, DwarfError> {
+        if !self.header.contains(offset) {
+            return Err(DwarfError::InvalidReference(offset));
+        }
+        let mut r = self.reader(offset);
+        match self.entry(&mut r)? {
+            Some(entry) => Ok(entry.attributes),
+            None => Err(DwarfError::InvalidReference(offset)),
+        }
+    }
+
+    #[inline]
+    fn reader(&self, offset: u64) -> Reader<'a> {
+        let mut r = Reader::new(self.sections.info, offset);
+        r.end = self.header.end;
+        r
+    }
+
+    /// Skips the children of an entry whose attributes were just read.
+    fn skip_children(&self, r: &mut Reader<'a>, attributes: &Attributes<'a>) -> Result<(), DwarfError> {
+        if let Some(Value::InfoRef(sibling)) = attributes.sibling {
+            if sibling > r.loc && sibling <= self.header.end {
+                r.loc = sibling;
+                return Ok(());
+            }
+        }
+        let mut depth = 1;
+        while depth > 0 {
+            match self.entry(r)? {
+                Some(entry) if entry.has_children => depth += 1,
+                Some(_) => {}
+                None => depth -= 1,
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn string(&self, value: Value<'a>) -> Result<Option<&'a [u8]>, DwarfError> {
+        let (section, offset) = match value {
+            Value::String(s) => return Ok(Some(s)),
+            Value::Strp(offset) => (self.sections.str, offset),
+            Value::LineStrp(offset) => (self.sections.line_str, offset),
+            Value::StrIndex(index) => {
+                let size = self.header.format.offset_size;
+                let loc = index.wrapping_mul(u64::from(size)).wrapping_add(self.bases.str_offsets);
+                (
+                    self.sections.str,
+                    Reader::new(self.sections.str_offsets, loc).sized(size)?,
+                )
+            }
+            _ => return Ok(None),
+        };
+        Reader::new(section, offset).c_str().map(Some)
+    }
+
+    fn address(&self, value: Value<'a>) -> Result<Option<u64>, DwarfError> {
+        match value {
+            Value::Address(address) => Ok(Some(address)),
+            Value::AddressIndex(index) => self.address_index(index).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn address_index(&self, index: u64) -> Result<u64, DwarfError> {
+        let size = self.header.format.address_size;
+        let loc = index.wrapping_mul(u64::from(size)).wrapping_add(self.bases.addr);
+        Reader::new(self.sections.addr, loc).sized(size)
+    }
+
+    /// Calls `f` with the address ranges of an entry, until it returns true.
+    /// Returns whether it did.
+    fn find_range<F: FnMut(Range<u64>) -> bool>(
+        &self,
+        attributes: &Attributes<'a>,
+        mut f: F,
+    ) -> Result<bool, DwarfError> {
+        if let Some(ranges) = attributes.ranges {
+            return self.find_in_range_list(ranges, f);
+        }
+        let (Some(low_pc), Some(high_pc)) = (attributes.low_pc, attributes.high_pc) else {
+            return Ok(false);
+        };
+        let Some(low) = self.address(low_pc)? else {
+            return Ok(false);
+        };
+        // A length unless it is an address.
+        let high = match (self.address(high_pc)?, high_pc.constant()) {
+            (Some(high), _) => high,
+            (None, Some(len)) => low.wrapping_add(len),
+            (None, None) => return Ok(false),
+        };
+        Ok(f(low..high))
+    }
+
+    fn find_in_range_list<F: FnMut(Range<u64>) -> bool>(&self, value: Value<'a>, mut f: F) -> Result<bool, DwarfError> {
+        let Format {
+            version,
+            offset_size,
+            address_size,
+        } = self.header.format;
+        let mut base = self.bases.address;
+        if version < 5 {
+            let Some(offset) = value.offset() else {
+                return Ok(false);
+            };
+            let mut r = Reader::new(self.sections.ranges, offset.wrapping_add(self.bases.ranges));
+            let max = u64::MAX >> (64 - 8 * u32::from(address_size.clamp(1, 8)));
+            loop {
+                let (start, end) = (r.sized(address_size)?, r.sized(address_size)?);
+                if start == 0 && end == 0 {
+                    return Ok(false);
+                }
+                if start == max {
+                    base = end;
+                } else if f(base.wrapping_add(start)..base.wrapping_add(end)) {
+                    return Ok(true);
+                }
+            }
+        }
+        let offset = match value {
+            Value::RangeListIndex(index) => {
+                let loc = index
+                    .wrapping_mul(u64::from(offset_size))
+                    .wrapping_add(self.bases.rnglists);
+                let offset = Reader::new(self.sections.rnglists, loc).sized(offset_size)?;
+                offset.wrapping_add(self.bases.rnglists)
+            }
+            value => match value.offset() {
+                Some(offset) => offset,
+                None => return Ok(false),
+            },
+        };
+        let mut r = Reader::new(self.sections.rnglists, offset);
+        loop {
+            let range = match r.u8()? {
+                DW_RLE_END_OF_LIST => return Ok(false),
+                DW_RLE_BASE_ADDRESSX => {
+                    base = self.address_index(r.uleb()?)?;
+                    continue;
+                }
+                DW_RLE_BASE_ADDRESS => {
+                    base = r.sized(address_size)?;
+                    continue;
+                }
+                DW_RLE_STARTX_ENDX => self.address_index(r.uleb()?)?..self.address_index(r.uleb()?)?,
+                DW_RLE_STARTX_LENGTH => {
+                    let start = self.address_index(r.uleb()?)?;
+                    start..start.wrapping_add(r.uleb()?)
+                }
+                DW_RLE_OFFSET_PAIR => base.wrapping_add(r.uleb()?)..base.wrapping_add(r.uleb()?),
+                DW_RLE_START_END => r.sized(address_size)?..r.sized(address_size)?,
+                DW_RLE_START_LENGTH => {
+                    let start = r.sized(address_size)?;
+                    start..start.wrapping_add(r.uleb()?)
+                }
+                kind => return Err(DwarfError::InvalidRangeListEntry(kind)),
+            };
+            if f(range) {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Returns the name of a subprogram, following references to the
+    /// entries it completes.
+    fn name(&self, index: &UnitIndex, attributes: &Attributes<'a>) -> Result<Option<&'a [u8]>, DwarfError> {
+        let mut attributes = *attributes;
+        // Where the current entry is, if another unit.
+        let mut other: Option<UnitReader<'a>> = None;
+        for _ in 0..MAX_REFERENCES {
+            let unit = other.as_ref().unwrap_or(self);
+            if let Some(name) = attributes.linkage_name.or(attributes.name) {
+                return unit.string(name);
+            }
+            let Some(Value::InfoRef(offset)) = attributes.abstract_origin.or(attributes.specification) else {
+                return Ok(None);
+            };
+            if !unit.header.contains(offset) {
+                // Only DW_FORM_ref_addr references leave their unit.
+                let target = index
+                    .units
+                    .iter()
+                    .find(|u| u.header.contains(offset))
+                    .ok_or(DwarfError::InvalidReference(offset))?;
+                other = Some(UnitReader::new(self.sections, target.header, target.bases)?);
+            }
+            attributes = other.as_ref().unwrap_or(self).entry_at(offset)?;
+        }
+        Ok(None)
+    }
+
+    /// Returns the subprogram containing `pc`, and the subroutines inlined
+    /// at `pc` in it, outermost first.
+    fn inlined_chain(&self, pc: u64) -> Result<Vec<Attributes<'a>>, DwarfError> {
+        let mut r = self.reader(self.header.entries);
+        let mut chain: Vec<(usize, Attributes<'a>)> = vec![];
+        let mut depth = 0;
+        while r.loc < r.end {
+            let Some(entry) = self.entry(&mut r)? else {
+                // Past the unit, or the subprogram containing `pc`.
+                if depth <= 1 || chain.first().is_some_and(|&(d, _)| depth - 1 <= d) {
+                    break;
+                }
+                depth -= 1;
+                continue;
+            };
+            if entry.tag == DW_TAG_SUBPROGRAM || entry.tag == DW_TAG_INLINED_SUBROUTINE {
+                if self.find_range(&entry.attributes, |range| range.contains(&pc))? {
+                    chain.retain(|&(d, _)| d < depth);
+                    chain.push((depth, entry.attributes));
+                    if !entry.has_children {
+                        break;
+                    }
+                } else if entry.has_children {
+                    self.skip_children(&mut r, &entry.attributes)?;
+                    continue;
+                }
+            }
+            if entry.has_children {
+                depth += 1;
+            }
+        }
+        Ok(chain.into_iter().map(|(_, attributes)| attributes).collect())
+    }
+}
+
+/// An index of the compilation units of .debug_info by their address
+/// ranges.
+#[derive(Debug, Clone, Default)]
+pub struct UnitIndex {
+    units: Vec<Unit>,
+    ranges: Vec<(Range<u64>, usize)>,
+}
+
+impl UnitIndex {
+    pub fn new(sections: &DebugSections<'_>) -> Result<Self, DwarfError> {
+        let mut units = vec![];
+        let mut ranges = vec![];
+        let mut offset = 0;
+        while offset < sections.info.len() as u64 {
+            let header = UnitHeader::parse(sections.info, offset)?;
+            offset = header.end;
+            if !matches!(header.unit_type, DW_UT_COMPILE | DW_UT_PARTIAL | DW_UT_SKELETON) {
+                continue;
+            }
+            let (unit, root) = UnitReader::with_unit_entry(*sections, header)?;
+            let index = units.len();
+            unit.find_range(&root, |range| {
+                // Linkers move the code they discard to address 0.
+                if range.start != 0 && range.start < range.end {
+                    ranges.push((range, index));
+                }
+                false
+            })?;
+            units.push(unit.unit(&root)?);
+        }
+        ranges.sort_unstable_by_key(|(range, _)| range.start);
+        Ok(Self { units, ranges })
+    }
+
+    /// Returns whether there are no compilation units with code.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the compilation unit containing `pc`.
+    pub fn find(&self, pc: u64) -> Option<&Unit> {
+        let i = self.ranges.partition_point(|(range, _)| range.start <= pc);
+        match i.checked_sub(1).map(|i| &self.ranges[i]) {
+            Some((range, index)) if pc < range.end => Some(&self.units[*index]),
+            _ => None,
+        }
+    }
+
+    /// Returns the functions at `pc` in `unit`, the innermost inlined one
+    /// first, with their source locations. The outermost frame has no name
+    /// if no function is found in `unit`, e.g. because it is a skeleton unit
+    /// whose split unit isn't given in `split`.
+    pub fn frames<'a>(
+        &self,
+        sections: &DebugSections<'a>,
+        unit: &Unit,
+        split: Option<&DebugSections<'a>>,
+        pc: u64,
+    ) -> Result<Vec<SourceFrame<'a>>, DwarfError> {
+        let comp_dir = unit.comp_dir();
+        let skeleton = UnitReader::new(*sections, unit.header, unit.bases)?;
+        let lines = match unit.stmt_list {
+            Some(offset) => Some(LineProgram::parse(&skeleton, offset)?),
+            None => None,
+        };
+        let mut frame = SourceFrame::default();
+        if let Some(lines) = &lines {
+            if let Some(row) = lines.find_row(pc)? {
+                frame.file = lines.file(row.file, comp_dir);
+                frame.line = row.line.min(u64::from(u32::MAX)) as u32;
+                frame.column = row.column.min(u64::from(u32::MAX)) as u32;
+            }
+        }
+
+        let split = match split {
+            Some(split) if unit.is_skeleton() => Some(UnitReader::split(sections, split, unit)?),
+            _ => None,
+        };
+        // Call sites of split units are in the file names of .debug_line.dwo.
+        let (entries, call_lines) = match &split {
+            Some(split) if !split.sections.line.is_empty() => (split, Some(LineProgram::parse(split, 0)?)),
+            Some(split) => (split, lines),
+            None => (&skeleton, lines),
+        };
+        let mut frames = vec![];
+        for attributes in entries.inlined_chain(pc)?.iter().rev() {
+            let name = entries.name(self, attributes)?;
+            let constant = |value: Option<Value<'_>>| value.and_then(|v| v.constant()).unwrap_or(0);
+            let next = SourceFrame {
+                name: None,
+                file: match (&call_lines, attributes.call_file.and_then(|v| v.constant())) {
+                    (Some(lines), Some(file)) => lines.file(file, comp_dir),
+                    _ => None,
+                },
+                line: constant(attributes.call_line).min(u64::from(u32::MAX)) as u32,
+                column: constant(attributes.call_column).min(u64::from(u32::MAX)) as u32,
+            };
+            frames.push(SourceFrame {
+                name,
+                ..std::mem::replace(&mut frame, next)
+            });
+        }
+        if frames.is_empty() && frame.line != 0 {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+}
+
+/// Returns the contributions of the split unit `dwo_id` to the sections of
+/// a .dwp file, found in its .debug_cu_index.
+pub fn package_sections<'a>(dwp: &DebugSections<'a>, dwo_id: u64) -> Result<Option<DebugSections<'a>>, DwarfError> {
+    let mut r = Reader::new(dwp.cu_index, 0);
+    // A 4-byte version 2 (GNU), or a 2-byte version 5 and 2 bytes of padding.
+    let version = match r.u32()? {
+        v if v > 0xffff => v >> 16,
+        v => v & 0xffff,
+    };
+    if version != 2 && version != 5 {
+        return Err(DwarfError::InvalidPackageVersion(version));
+    }
+    let (columns, units, slots) = (u64::from(r.u32()?), u64::from(r.u32()?), u64::from(r.u32()?));
+    if !slots.is_power_of_two() {
+        return Ok(None);
+    }
+    let hashes = r.loc;
+    let indexes = hashes + 8 * slots;
+    let ids = indexes + 4 * slots;
+    let offsets = ids + 4 * columns;
+    let sizes = offsets + 4 * columns * units;
+    let u32_at = |loc: u64| Reader::new(dwp.cu_index, loc).u32();
+
+    let mask = slots - 1;
+    let step = ((dwo_id >> 32) & mask) | 1;
+    let mut slot = dwo_id & mask;
+    let mut row = None;
+    for _ in 0..slots {
+        let index = u32_at(indexes + 4 * slot)?;
+        if index == 0 {
+            break;
+        }
+        if Reader::new(dwp.cu_index, hashes + 8 * slot).u64()? == dwo_id {
+            row = Some(u64::from(index) - 1);
+            break;
+        }
+        slot = (slot + step) & mask;
+    }
+    let Some(row) = row.filter(|&row| row < units) else {
+        return Ok(None);
+    };
+
+    let mut sections = DebugSections {
+        str: dwp.str,
+        ..Default::default()
+    };
+    for column in 0..columns {
+        let (section, data) = match u32_at(ids + 4 * column)? {
+            DW_SECT_INFO => (&mut sections.info, dwp.info),
+            DW_SECT_ABBREV => (&mut sections.abbrev, dwp.abbrev),
+            DW_SECT_LINE => (&mut sections.line, dwp.line),
+            DW_SECT_STR_OFFSETS => (&mut sections.str_offsets, dwp.str_offsets),
+            // Of version 2, the id of .debug_macro.dwo.
+            DW_SECT_RNGLISTS if version == 5 => (&mut sections.rnglists, dwp.rnglists),
+            _ => continue,
+        };
+        let cell = 4 * (row * columns + column);
+        let (offset, size) = (u32_at(offsets + cell)? as usize, u32_at(sizes + cell)? as usize);
+        *section = offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end))
+            .ok_or(DwarfError::OutOfBounds(offset as u64))?;
+    }
+    Ok(Some(sections))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a .debug_cu_index of `version` with the columns info, abbrev
+    /// and rnglists, and two units.
+    fn cu_index(version: u16, units: &[(u64, [(u32, u32); 3])]) -> Vec<u8> {
+        let slots = 4;
+        let mut hashes = [0u64; 4];
+        let mut indexes = [0u32; 4];
+        for (row, &(dwo_id, _)) in units.iter().enumerate() {
+            let mut slot = dwo_id & (slots - 1);
+            while indexes[slot as usize] != 0 {
+                slot = (slot + (((dwo_id >> 32) & (slots - 1)) | 1)) & (slots - 1);
+            }
+            hashes[slot as usize] = dwo_id;
+            indexes[slot as usize] = row as u32 + 1;
+        }
+        let mut data = vec![];
+        match version {
+            2 => data.extend_from_slice(&2u32.to_ne_bytes()),
+            _ => data.extend_from_slice(&[version.to_ne_bytes(), [0, 0]].concat()),
+        }
+        for n in [3, units.len() as u32, slots as u32] {
+            data.extend_from_slice(&n.to_ne_bytes());
+        }
+        hashes.iter().for_each(|h| data.extend_from_slice(&h.to_ne_bytes()));
+        indexes.iter().for_each(|i| data.extend_from_slice(&i.to_ne_bytes()));
+        for id in [DW_SECT_INFO, DW_SECT_ABBREV, DW_SECT_RNGLISTS] {
+            data.extend_from_slice(&id.to_ne_bytes());
+        }
+        for field in [0, 1] {
+            for (_, columns) in units {
+                for column in columns {
+                    let value = if field == 0 { column.0 } else { column.1 };
+                    data.extend_from_slice(&value.to_ne_bytes());
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_package_sections() {
+        let info: Vec<u8> = (0..16).collect();
+        let abbrev: Vec<u8> = (16..24).collect();
+        let rnglists: Vec<u8> = (24..28).collect();
+        // Both ids hash to slot 1.
+        let units = [
+            (0x1_0000_0001, [(0, 8), (0, 4), (0, 2)]),
+            (0x5, [(8, 8), (4, 4), (2, 2)]),
+        ];
+        for version in [2, 5] {
+            let index = cu_index(version, &units);
+            let dwp = DebugSections {
+                info: &info,
+                abbrev: &abbrev,
+                rnglists: &rnglists,
+                str: b"str",
+                cu_index: &index,
+                ..Default::default()
+            };
+            let first = package_sections(&dwp, 0x1_0000_0001).unwrap().unwrap();
+            assert_eq!(first.info, &info[..8]);
+            assert_eq!(first.abbrev, &abbrev[..4]);
+            assert_eq!(first.str, b"str");
+            let second = package_sections(&dwp, 0x5).unwrap().unwrap();
+            assert_eq!(second.info, &info[8..]);
+            assert_eq!(second.abbrev, &abbrev[4..]);
+            // The column is of .debug_macro.dwo in version 2.
+            match version {
+                2 => assert!(first.rnglists.is_empty() && second.rnglists.is_empty()),
+                _ => assert_eq!((first.rnglists, second.rnglists), (&rnglists[..2], &rnglists[2..])),
+            }
+            assert!(package_sections(&dwp, 0x9).unwrap().is_none());
+        }
+
+        let mut index = cu_index(5, &units);
+        index[0] = 3;
+        let dwp = DebugSections {
+            cu_index: &index,
+            ..Default::default()
+        };
+        assert_eq!(
+            package_sections(&dwp, 0x5).unwrap_err(),
+            DwarfError::InvalidPackageVersion(3)
+        );
+    }
+
+    #[test]
+    fn test_reader() {
+        let data = [0x01, 0x02, 0x03, 0x85, 0x01, b'a', 0, 0xff, 0xff, 0xff, 0xff];
+        let mut r = Reader::new(&data, 0);
+        let value = r.sized(3).unwrap();
+        assert_eq!(
+            value,
+            if cfg!(target_endian = "little") {
+                0x030201
+            } else {
+                0x010203
+            }
+        );
+        assert_eq!(r.uleb().unwrap(), 0x85);
+        assert_eq!(r.c_str().unwrap(), b"a");
+        assert_eq!(r.sized(5), Err(DwarfError::InvalidAddressSize(5)));
+        // A 64-bit unit length, truncated.
+        assert_eq!(r.unit_length(), Err(DwarfError::OutOfBounds(11)));
+    }
+}
@@ -1,8 +1,39 @@
+use crate::dwarf::address_space::{AddressSpace, Primitive};
 use crate::dwarf::consts::*;
-use crate::dwarf::{load_with_protect as load, DwarfError};
+use crate::dwarf::DwarfError;
+use std::mem;
+
+/// Read a fixed-size value at `loc` and advance `loc`. Reads that would cross
+/// `end` are rejected.
+#[inline]
+pub fn read<A: AddressSpace, T: Primitive>(a: &A, loc: &mut u64, end: u64) -> Result<T, DwarfError> {
+    match loc.checked_add(mem::size_of::<T>() as u64) {
+        Some(next) if next <= end => {
+            let v = a.load::<T>(*loc)?;
+            *loc = next;
+            Ok(v)
+        }
+        _ => Err(DwarfError::OutOfBounds(*loc)),
+    }
+}
+
+/// Read a pointer at `loc`, zero-extended to 64 bits, and advance `loc`.
+#[inline]
+pub fn read_pointer<A: AddressSpace>(a: &A, loc: &mut u64, end: u64) -> Result<u64, DwarfError> {
+    match a.pointer_size() {
+        4 => read::<A, u32>(a, loc, end).map(u64::from),
+        _ => read::<A, u64>(a, loc, end),
+    }
+}
 
 /// Decode a Pointer-Encoding value.
-pub fn decode_pointer(loc: &mut u64, end: u64, enc: u8, datarel_base: u64) -> Result<u64, DwarfError> {
+pub fn decode_pointer<A: AddressSpace>(
+    a: &A,
+    loc: &mut u64,
+    end: u64,
+    enc: u8,
+    datarel_base: u64,
+) -> Result<u64, DwarfError> {
     // Calculate relative offset.
     let offset = match enc & 0b1110000 {
         DW_EH_PE_ABSPTR => 0, // do nothing
@@ -19,91 +50,46 @@ pub fn decode_pointer(loc: &mut u64, end: u64, enc: u8, datarel_base: u64) -> Re
         v => return Err(DwarfError::InvalidPointerEncodingOffset(v)),
     };
 
-    // Get value.
-    let mut res = match enc & 0b1111 {
-        DW_EH_PE_PTR => {
-            let v = load::<u64>(*loc)?;
-            *loc += 8;
-            v + offset
-        }
-        DW_EH_PE_ULEB128 => decode_uleb128(loc, end)? + offset,
-        DW_EH_PE_UDATA2 => {
-            let v = load::<u16>(*loc)? as u64;
-            *loc += 2;
-            v + offset
-        }
-        DW_EH_PE_UDATA4 => {
-            let v = load::<u32>(*loc)? as u64;
-            *loc += 4;
-            v + offset
-        }
-        DW_EH_PE_UDATA8 => {
-            let v = load::<u64>(*loc)?;
-            *loc += 8;
-            v + offset
-        }
-        DW_EH_PE_SLEB128 => {
-            let v = decode_sleb128(loc, end)?;
-            if v > 0 {
-                v as u64 + offset
-            } else {
-                offset - ((-v) as u64)
-            }
-        }
-        DW_EH_PE_SDATA2 => {
-            let v = load::<i16>(*loc)?;
-            *loc += 2;
-            if v > 0 {
-                v as u64 + offset
-            } else {
-                offset - ((-v) as u64)
-            }
-        }
-        DW_EH_PE_SDATA4 => {
-            let v = load::<i32>(*loc)?;
-            *loc += 4;
-            if v > 0 {
-                v as u64 + offset
-            } else {
-                offset - ((-v) as u64)
-            }
-        }
-        DW_EH_PE_SDATA8 => {
-            let v = load::<i64>(*loc)?;
-            *loc += 8;
-            if v > 0 {
-                v as u64 + offset
-            } else {
-                offset - ((-v) as u64)
-            }
-        }
+    // Get value. Signed values are sign-extended, so that adding them to the
+    // offset with wrapping arithmetic gives the right result.
+    let v = match enc & 0b1111 {
+        DW_EH_PE_PTR => read_pointer(a, loc, end)?,
+        DW_EH_PE_ULEB128 => decode_uleb128(a, loc, end)?,
+        DW_EH_PE_UDATA2 => read::<A, u16>(a, loc, end)? as u64,
+        DW_EH_PE_UDATA4 => read::<A, u32>(a, loc, end)? as u64,
+        DW_EH_PE_UDATA8 => read::<A, u64>(a, loc, end)?,
+        DW_EH_PE_SLEB128 => decode_sleb128(a, loc, end)? as u64,
+        DW_EH_PE_SDATA2 => read::<A, i16>(a, loc, end)? as u64,
+        DW_EH_PE_SDATA4 => read::<A, i32>(a, loc, end)? as u64,
+        DW_EH_PE_SDATA8 => read::<A, i64>(a, loc, end)? as u64,
         v => return Err(DwarfError::InvalidPointerEncodingValue(v)),
     };
+    let mut res = a.truncate_pointer(offset.wrapping_add(v));
 
     // Dereference the pointer if necessary.
     if enc & DW_EH_PE_INDIRECT != 0 {
-        res = load::<u64>(res)?;
+        res = a.load_pointer(res)?;
     }
     Ok(res)
 }
 
 /// Read a ULEB128 into a 64-bit word.
-pub fn decode_uleb128(loc: &mut u64, end: u64) -> Result<u64, DwarfError> {
+pub fn decode_uleb128<A: AddressSpace>(a: &A, loc: &mut u64, end: u64) -> Result<u64, DwarfError> {
     let mut res = 0u64;
     let mut bit = 0u64;
     loop {
-        if *loc == end {
+        if *loc >= end {
             return Err(DwarfError::TruncatedUleb128Expression(*loc));
         }
-        let b = (load::<u8>(*loc)? & 0b1111111) as u64;
+        let byte = a.load::<u8>(*loc)?;
+        let b = (byte & 0b1111111) as u64;
         if bit >= 64 || b << bit >> bit != b {
             return Err(DwarfError::MalformedUleb128Expression(*loc));
         }
         res |= b << bit;
         bit += 7;
-        let brk = load::<u8>(*loc)? < 0b10000000;
         *loc += 1;
-        if brk {
+        if byte < 0b10000000 {
             break;
         }
     }
@@ -111,15 +97,18 @@ pub fn decode_uleb128(loc: &mut u64, end: u64) -> Result<u64, DwarfError> {
 }
 
 /// Read a SLEB128 into a 64-bit word.
-pub fn decode_sleb128(loc: &mut u64, end: u64) -> Result<i64, DwarfError> {
+pub fn decode_sleb128<A: AddressSpace>(a: &A, loc: &mut u64, end: u64) -> Result<i64, DwarfError> {
     let mut res = 0i64;
     let mut bit = 0u64;
     let mut byte;
     loop {
-        if *loc == end {
+        if *loc >= end {
             return Err(DwarfError::TruncatedSleb128Expression(*loc));
         }
-        byte = load::<u8>(*loc)?;
+        if bit >= 64 {
+            return Err(DwarfError::MalformedSleb128Expression(*loc));
+        }
+        byte = a.load::<u8>(*loc)?;
         *loc += 1;
         res |= (((byte & 0b1111111) as u64) << bit) as i64;
         bit += 7;
@@ -137,6 +126,7 @@ pub fn decode_sleb128(loc: &mut u64, end: u64) -> Result<i64, DwarfError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dwarf::address_space::{LocalAddressSpace, SliceAddressSpace};
 
     #[test]
     fn test_decode_uleb128() {
@@ -145,7 +135,10 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_uleb128(&mut loc, start + len as u64).unwrap(), 0);
+        assert_eq!(
+            decode_uleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            0
+        );
         assert_eq!(loc - start, len as u64);
 
         let mut buf = Vec::new();
@@ -153,7 +146,10 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_uleb128(&mut loc, start + len as u64).unwrap(), 0x12345678);
+        assert_eq!(
+            decode_uleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            0x12345678
+        );
         assert_eq!(loc - start, len as u64);
 
         let mut buf = Vec::new();
@@ -161,7 +157,10 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_uleb128(&mut loc, start + len as u64).unwrap(), u64::MAX);
+        assert_eq!(
+            decode_uleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            u64::MAX
+        );
         assert_eq!(loc - start, len as u64);
     }
 
@@ -172,7 +171,10 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_sleb128(&mut loc, start + len as u64).unwrap(), 0);
+        assert_eq!(
+            decode_sleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            0
+        );
         assert_eq!(loc - start, len as u64);
 
         let mut buf = Vec::new();
@@ -180,7 +182,10 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_sleb128(&mut loc, start + len as u64).unwrap(), 0x12345678);
+        assert_eq!(
+            decode_sleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            0x12345678
+        );
         assert_eq!(loc - start, len as u64);
 
         let mut buf = Vec::new();
@@ -188,7 +193,10 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_sleb128(&mut loc, start + len as u64).unwrap(), i64::MAX);
+        assert_eq!(
+            decode_sleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            i64::MAX
+        );
         assert_eq!(loc - start, len as u64);
 
         let mut buf = Vec::new();
@@ -196,73 +204,104 @@ mod tests {
         assert_eq!(len, buf.len());
         let start = buf.as_ptr() as u64;
         let mut loc = start;
-        assert_eq!(decode_sleb128(&mut loc, start + len as u64).unwrap(), i64::MIN);
+        assert_eq!(
+            decode_sleb128(&LocalAddressSpace, &mut loc, start + len as u64).unwrap(),
+            i64::MIN
+        );
         assert_eq!(loc - start, len as u64);
     }
 
     #[test]
+    #[cfg(target_pointer_width = "64")]
     fn test_decode_pointer() {
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_PTR;
         let val = u64::MAX;
         let mut loc = &val as *const u64 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap(), val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap(),
+            val
+        );
         assert_eq!(loc, start + 8);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_UDATA2;
         let val = u16::MAX;
         let mut loc = &val as *const u16 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap() as u16, val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap() as u16,
+            val
+        );
         assert_eq!(loc, start + 2);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_UDATA4;
         let val = u32::MAX;
         let mut loc = &val as *const u32 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap() as u32, val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap() as u32,
+            val
+        );
         assert_eq!(loc, start + 4);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8;
         let val = u64::MAX;
         let mut loc = &val as *const u64 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap(), val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap(),
+            val
+        );
         assert_eq!(loc, start + 8);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_SDATA2;
         let val = i16::MAX;
         let mut loc = &val as *const i16 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap() as i16, val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap() as i16,
+            val
+        );
         assert_eq!(loc, start + 2);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_SDATA4;
         let val = i32::MAX;
         let mut loc = &val as *const i32 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap() as i32, val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap() as i32,
+            val
+        );
         assert_eq!(loc, start + 4);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_SDATA8;
         let val = i64::MAX;
         let mut loc = &val as *const i64 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap() as i64, val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap() as i64,
+            val
+        );
         assert_eq!(loc, start + 8);
 
         let enc = DW_EH_PE_PCREL | DW_EH_PE_PTR;
         let val = 0x123;
         let mut loc = &val as *const u64 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0).unwrap(), start + val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0).unwrap(),
+            start + val
+        );
         assert_eq!(loc, start + 8);
 
         let enc = DW_EH_PE_DATAREL | DW_EH_PE_PTR;
         let val = 0x123;
         let mut loc = &val as *const u64 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0x456).unwrap(), val + 0x456);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0x456).unwrap(),
+            val + 0x456
+        );
         assert_eq!(loc, start + 8);
 
         let enc = DW_EH_PE_ABSPTR | DW_EH_PE_PTR | DW_EH_PE_INDIRECT;
@@ -270,17 +309,165 @@ mod tests {
         let loc = &val as *const u64 as u64;
         let mut loc2 = &loc as *const u64 as u64;
         let start = loc2;
-        assert_eq!(decode_pointer(&mut loc2, u64::MAX, enc, 0).unwrap(), val);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc2, u64::MAX, enc, 0).unwrap(),
+            val
+        );
         assert_eq!(loc2, start + 8);
     }
 
+    #[test]
+    fn test_decode_pointer_32bit() {
+        let data = [0x1004u32, 0x8765_4321];
+        let mem: Vec<u8> = data.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let space = SliceAddressSpace::new(0x1000, &mem).with_pointer_size(4);
+
+        let enc = DW_EH_PE_ABSPTR | DW_EH_PE_PTR;
+        let mut loc = 0x1000;
+        assert_eq!(decode_pointer(&space, &mut loc, 0x1008, enc, 0).unwrap(), 0x1004);
+        assert_eq!(loc, 0x1004);
+
+        let enc = DW_EH_PE_ABSPTR | DW_EH_PE_PTR | DW_EH_PE_INDIRECT;
+        let mut loc = 0x1000;
+        assert_eq!(decode_pointer(&space, &mut loc, 0x1008, enc, 0).unwrap(), 0x8765_4321);
+        assert_eq!(loc, 0x1004);
+
+        // Pointer arithmetic wraps around at 32 bits.
+        let enc = DW_EH_PE_PCREL | DW_EH_PE_UDATA4;
+        let mut loc = 0x1004;
+        assert_eq!(decode_pointer(&space, &mut loc, 0x1008, enc, 0).unwrap(), 0x8765_5325);
+        let enc = DW_EH_PE_DATAREL | DW_EH_PE_UDATA4;
+        let mut loc = 0x1004;
+        assert_eq!(
+            decode_pointer(&space, &mut loc, 0x1008, enc, 0x8000_0000).unwrap(),
+            0x0765_4321
+        );
+    }
+
     #[test]
     fn test_decode_pointer_negative_datarel() {
         let enc = DW_EH_PE_DATAREL | DW_EH_PE_SDATA4;
         let val = -1;
         let mut loc = &val as *const i32 as u64;
         let start = loc;
-        assert_eq!(decode_pointer(&mut loc, u64::MAX, enc, 0x456).unwrap(), 0x455);
+        assert_eq!(
+            decode_pointer(&LocalAddressSpace, &mut loc, u64::MAX, enc, 0x456).unwrap(),
+            0x455
+        );
         assert_eq!(loc, start + 4);
     }
+
+    #[test]
+    fn test_read() {
+        let data = [0x01u8, 0x02, 0x03];
+        let space = SliceAddressSpace::new(0x1000, &data);
+        let mut loc = 0x1000;
+        assert_eq!(
+            read::<_, u16>(&space, &mut loc, 0x1003).unwrap(),
+            u16::from_ne_bytes([0x01, 0x02])
+        );
+        assert_eq!(loc, 0x1002);
+        assert!(matches!(
+            read::<_, u16>(&space, &mut loc, 0x1003),
+            Err(DwarfError::OutOfBounds(0x1002))
+        ));
+        assert_eq!(loc, 0x1002);
+        assert_eq!(read::<_, u8>(&space, &mut loc, 0x1003).unwrap(), 0x03);
+
+        // `end` is a stricter bound than the address space.
+        let mut loc = 0x1000;
+        assert!(read::<_, u16>(&space, &mut loc, 0x1001).is_err());
+
+        // Address space is a stricter bound than `end`.
+        let mut loc = 0x1002;
+        assert!(matches!(
+            read::<_, u16>(&space, &mut loc, u64::MAX),
+            Err(DwarfError::UnreadableAddress(0x1002))
+        ));
+
+        // Overflowing `loc` must not wrap around.
+        let mut loc = u64::MAX;
+        assert!(read::<_, u16>(&space, &mut loc, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_decode_leb128_malformed() {
+        // Missing terminator.
+        let data = [0x80u8, 0x80];
+        let space = SliceAddressSpace::new(0x1000, &data);
+        let mut loc = 0x1000;
+        assert!(matches!(
+            decode_uleb128(&space, &mut loc, space.end()),
+            Err(DwarfError::TruncatedUleb128Expression(0x1002))
+        ));
+        let mut loc = 0x1000;
+        assert!(matches!(
+            decode_sleb128(&space, &mut loc, space.end()),
+            Err(DwarfError::TruncatedSleb128Expression(0x1002))
+        ));
+
+        // Too long to fit in 64 bits.
+        let data = [0xffu8; 16];
+        let space = SliceAddressSpace::new(0x1000, &data);
+        let mut loc = 0x1000;
+        assert!(matches!(
+            decode_uleb128(&space, &mut loc, space.end()),
+            Err(DwarfError::MalformedUleb128Expression(_))
+        ));
+        let mut loc = 0x1000;
+        assert!(matches!(
+            decode_sleb128(&space, &mut loc, space.end()),
+            Err(DwarfError::MalformedSleb128Expression(_))
+        ));
+
+        // Empty input.
+        let space = SliceAddressSpace::new(0x1000, &[]);
+        let mut loc = 0x1000;
+        assert!(decode_uleb128(&space, &mut loc, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_decode_pointer_bounds() {
+        let data = 0x1000u64.to_ne_bytes();
+        let space = SliceAddressSpace::new(0x1000, &data);
+
+        let mut loc = 0x1000;
+        let enc = DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8;
+        assert_eq!(decode_pointer(&space, &mut loc, space.end(), enc, 0).unwrap(), 0x1000);
+
+        let mut loc = 0x1000;
+        assert!(matches!(
+            decode_pointer(&space, &mut loc, 0x1004, enc, 0),
+            Err(DwarfError::OutOfBounds(0x1000))
+        ));
+
+        // Indirection is resolved through the address space too.
+        let mut loc = 0x1000;
+        let enc = DW_EH_PE_ABSPTR | DW_EH_PE_UDATA4 | DW_EH_PE_INDIRECT;
+        assert_eq!(
+            decode_pointer(&space, &mut loc, space.end(), enc, 0).unwrap(),
+            u64::from_ne_bytes(data)
+        );
+        let mut loc = 0x1004;
+        assert!(decode_pointer(&space, &mut loc, space.end(), enc, 0).is_err());
+
+        // Relative encodings wrap around instead of overflowing.
+        let data = (-1i32).to_ne_bytes();
+        let space = SliceAddressSpace::new(0, &data);
+        let mut loc = 0;
+        let enc = DW_EH_PE_PCREL | DW_EH_PE_SDATA4;
+        assert_eq!(decode_pointer(&space, &mut loc, space.end(), enc, 0).unwrap(), u64::MAX);
+
+        // Invalid encodings.
+        let mut loc = 0;
+        assert!(matches!(
+            decode_pointer(&space, &mut loc, space.end(), 0x0f, 0),
+            Err(DwarfError::InvalidPointerEncodingValue(0x0f))
+        ));
+        let mut loc = 0;
+        assert!(matches!(
+            decode_pointer(&space, &mut loc, space.end(), DW_EH_PE_DATAREL, 0),
+            Err(DwarfError::InvalidDataRelBase)
+        ));
+    }
 }
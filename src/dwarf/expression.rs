@@ -2,350 +2,881 @@ pub use imp::*;
 
 #[cfg(not(feature = "dwarf-expression"))]
 mod imp {
+    use crate::dwarf::address_space::AddressSpace;
     use crate::dwarf::DwarfError;
-    use crate::registers::Registers;
+    use crate::registers::RegisterFile;
 
     #[inline(always)]
-    pub fn evaluate(_expression: u64, _registers: &Registers, _initial_stack: u64) -> Result<u64, DwarfError> {
+    pub fn evaluate<A: AddressSpace, R: RegisterFile>(
+        _a: &A,
+        _expression: u64,
+        _registers: &R,
+        _cfa: Option<u64>,
+    ) -> Result<u64, DwarfError> {
         Err(DwarfError::DwarfExpressionNotImplemented)
     }
 }
 
 #[cfg(feature = "dwarf-expression")]
 mod imp {
+    use crate::dwarf::address_space::AddressSpace;
     use crate::dwarf::consts::*;
-    use crate::dwarf::encoding::{decode_sleb128, decode_uleb128};
-    use crate::dwarf::{load_with_protect as load, DwarfError};
-    use crate::registers::Registers;
-    use std::ops::{Index, IndexMut};
+    use crate::dwarf::encoding::{decode_sleb128, decode_uleb128, read, read_pointer};
+    use crate::dwarf::DwarfError;
+    use crate::registers::RegisterFile;
+
+    /// Maximum number of values on the evaluation stack.
+    const MAX_STACK_DEPTH: usize = 64;
 
-    pub fn evaluate(expression: u64, registers: &Registers, initial_stack: u64) -> Result<u64, DwarfError> {
+    /// Maximum number of operations executed by a single evaluation. Branches
+    /// can jump backwards, so this is what guarantees termination.
+    const MAX_INSTRUCTIONS: usize = 1024;
+
+    /// A ULEB128 encoded u64 takes at most 10 bytes.
+    const MAX_ULEB128_LEN: u64 = 10;
+
+    /// Evaluate a DWARF expression (DWARF Spec, section 2.5) in the context of
+    /// call frame information, and returns the resulting value.
+    ///
+    /// `expression` points to the ULEB128 length that precedes the expression
+    /// body, as recorded by DW_CFA_def_cfa_expression, DW_CFA_expression and
+    /// DW_CFA_val_expression. For register rules `cfa` is `Some` and it is
+    /// pushed onto the stack before evaluation begins (DWARF Spec, section
+    /// 6.4.2.3), for the CFA itself the stack starts empty.
+    ///
+    /// Both the expression and the memory it dereferences are read through `a`,
+    /// which also resolves the thread-local storage of DW_OP_form_tls_address.
+    pub fn evaluate<A: AddressSpace, R: RegisterFile>(
+        a: &A,
+        expression: u64,
+        registers: &R,
+        cfa: Option<u64>,
+    ) -> Result<u64, DwarfError> {
         let mut loc = expression;
-        let end = expression + decode_uleb128(&mut loc, expression + 20)?; // 20 is a tmp guard.
+        let len = decode_uleb128(a, &mut loc, expression.saturating_add(MAX_ULEB128_LEN))?;
+        let start = loc;
+        let end = start.checked_add(len).ok_or(DwarfError::OutOfBounds(start))?;
         let mut stack = EvaluateStack::default();
-        stack.push(initial_stack);
+        if let Some(cfa) = cfa {
+            stack.push(cfa)?;
+        }
+        // Value assembled by DW_OP_piece & DW_OP_bit_piece, and how many bits
+        // of it have been filled.
+        let mut pieces = 0u64;
+        let mut piece_bits = 0u64;
+        let mut executed = 0;
         while loc < end {
-            let mut u1: u64;
-            let mut s1: i64;
-            let s2: i64; // temporarily remove `mut` to avoid warning
-            let reg: u32; // ditto
-            let opcode = load::<u8>(loc)?;
-            match opcode {
-                DW_OP_ADDR => {
-                    // Push immediate address sized value.
-                    u1 = load::<u64>(loc)?;
-                    loc += 8;
-                    stack.push(u1);
-                }
-                DW_OP_DEREF => {
-                    // Pop stack, dereference, push result.
-                    u1 = stack.pop();
-                    stack.push(load::<u64>(u1)?);
-                }
-                DW_OP_CONST1U => {
-                    // Push immediate 1 byte value.
-                    u1 = load::<u8>(loc)? as u64;
-                    loc += 1;
-                    stack.push(u1);
-                }
-                DW_OP_CONST1S => {
-                    // Push immediate 1 byte signed value.
-                    s1 = load::<i8>(loc)? as i64;
-                    loc += 1;
-                    stack.push(s1 as u64);
-                }
-                DW_OP_CONST2U => {
-                    // Push immediate 2 byte value.
-                    u1 = load::<u16>(loc)? as u64;
-                    loc += 2;
-                    stack.push(u1);
-                }
-                DW_OP_CONST2S => {
-                    // Push immediate 2 byte signed value.
-                    s1 = load::<i16>(loc)? as i64;
-                    loc += 2;
-                    stack.push(s1 as u64);
-                }
-                DW_OP_CONST4U => {
-                    // Push immediate 4 byte value.
-                    u1 = load::<u32>(loc)? as u64;
-                    loc += 4;
-                    stack.push(u1);
-                }
-                DW_OP_CONST4S => {
-                    // Push immediate 4 byte signed value.
-                    s1 = load::<i32>(loc)? as i64;
-                    loc += 4;
-                    stack.push(s1 as u64);
-                }
-                DW_OP_CONST8U => {
-                    // Push immediate 8 byte value.
-                    u1 = load::<u64>(loc)?;
-                    loc += 8;
-                    stack.push(u1);
-                }
-                DW_OP_CONST8S => {
-                    // Push immediate 8 byte signed value.
-                    s1 = load::<i64>(loc)?;
-                    loc += 8;
-                    stack.push(s1 as u64);
-                }
-                DW_OP_CONSTU => {
-                    // Push immediate ULEB128 value.
-                    u1 = decode_uleb128(&mut loc, end)?;
-                    stack.push(u1);
-                }
-                DW_OP_CONSTS => {
-                    // Push immediate SLEB128 value.
-                    s1 = decode_sleb128(&mut loc, end)?;
-                    stack.push(s1 as u64);
-                }
-                DW_OP_DUP => {
-                    // Push top of stack.
-                    u1 = stack.pop();
-                    stack.push(u1);
+            executed += 1;
+            if executed > MAX_INSTRUCTIONS {
+                return Err(DwarfError::ExpressionTooManyInstructions);
+            }
+            let opcode = read::<A, u8>(a, &mut loc, end)?;
+            // Most operations push a single value, which is done after the match.
+            // Those that don't `continue` or `break` instead.
+            let value = match opcode {
+                DW_OP_ADDR => read_pointer(a, &mut loc, end)?,
+                DW_OP_CONST8U => read::<A, u64>(a, &mut loc, end)?,
+                DW_OP_CONST1U => read::<A, u8>(a, &mut loc, end)? as u64,
+                DW_OP_CONST1S => read::<A, i8>(a, &mut loc, end)? as u64,
+                DW_OP_CONST2U => read::<A, u16>(a, &mut loc, end)? as u64,
+                DW_OP_CONST2S => read::<A, i16>(a, &mut loc, end)? as u64,
+                DW_OP_CONST4U => read::<A, u32>(a, &mut loc, end)? as u64,
+                DW_OP_CONST4S => read::<A, i32>(a, &mut loc, end)? as u64,
+                DW_OP_CONST8S => read::<A, i64>(a, &mut loc, end)? as u64,
+                DW_OP_CONSTU => decode_uleb128(a, &mut loc, end)?,
+                DW_OP_CONSTS => decode_sleb128(a, &mut loc, end)? as u64,
+                DW_OP_LIT0..=DW_OP_LIT31 => (opcode - DW_OP_LIT0) as u64,
+                DW_OP_DUP => stack.top(0)?,
+                DW_OP_OVER => stack.top(1)?,
+                DW_OP_PICK => {
+                    let index = read::<A, u8>(a, &mut loc, end)?;
+                    stack.top(index as usize)?
                 }
                 DW_OP_DROP => {
-                    // Pop.
-                    stack.pop();
-                }
-                DW_OP_OVER => {
-                    // Dup second.
-                    u1 = stack.top(1);
-                    stack.push(u1);
-                }
-                DW_OP_PICK => {
-                    // Pick from.
-                    reg = load::<u8>(loc)? as u32;
-                    loc += 1;
-                    u1 = stack.top(reg as usize);
-                    stack.push(u1);
+                    stack.pop()?;
+                    continue;
                 }
                 DW_OP_SWAP => {
                     // Swap top two.
-                    u1 = stack.top(0);
-                    *stack.top_mut(0) = stack.top(1);
-                    *stack.top_mut(1) = u1;
+                    let v1 = stack.pop()?;
+                    let v2 = stack.pop()?;
+                    stack.push(v1)?;
+                    v2
                 }
                 DW_OP_ROT => {
                     // Rotate top three.
-                    u1 = stack.top(0);
-                    *stack.top_mut(0) = stack.top(1);
-                    *stack.top_mut(1) = stack.top(2);
-                    *stack.top_mut(2) = u1;
+                    let v1 = stack.pop()?;
+                    let v2 = stack.pop()?;
+                    let v3 = stack.pop()?;
+                    stack.push(v1)?;
+                    stack.push(v3)?;
+                    v2
                 }
-                DW_OP_XDEREF => {
+                DW_OP_DEREF => {
                     // Pop stack, dereference, push result.
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = load::<u64>(u1)?;
+                    a.load_pointer(stack.pop()?)?
                 }
-                DW_OP_ABS => {
-                    s1 = stack.top(0) as i64;
-                    if s1 < 0 {
-                        *stack.top_mut(0) = (-s1) as u64;
+                DW_OP_XDEREF => {
+                    // Pop address and address space identifier, dereference, push result.
+                    let address = stack.pop()?;
+                    stack.pop()?;
+                    a.load_pointer(address)?
+                }
+                DW_OP_DEREF_SIZE | DW_OP_XDEREF_SIZE => {
+                    // Pop stack, dereference a smaller value, zero-extend and push result.
+                    let size = read::<A, u8>(a, &mut loc, end)?;
+                    let address = stack.pop()?;
+                    if opcode == DW_OP_XDEREF_SIZE {
+                        stack.pop()?;
+                    }
+                    match size {
+                        1 => a.load::<u8>(address)? as u64,
+                        2 => a.load::<u16>(address)? as u64,
+                        4 => a.load::<u32>(address)? as u64,
+                        8 => a.load::<u64>(address)?,
+                        v => return Err(DwarfError::InvalidExpressionDerefSize(v)),
                     }
                 }
-                DW_OP_AND => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) &= u1;
-                }
-                DW_OP_DIV => {
-                    s1 = stack.pop() as i64;
-                    s2 = stack.top(0) as i64;
-                    *stack.top_mut(0) = (s2 / s1) as u64;
-                }
-                DW_OP_MINUS => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) -= u1;
-                }
-                DW_OP_MOD => {
-                    s1 = stack.pop() as i64;
-                    s2 = stack.top(0) as i64;
-                    *stack.top_mut(0) = (s2 % s1) as u64;
-                }
-                DW_OP_MUL => {
-                    s1 = stack.pop() as i64;
-                    s2 = stack.top(0) as i64;
-                    *stack.top_mut(0) = (s2 * s1) as u64;
-                }
-                DW_OP_NEG => {
-                    *stack.top_mut(0) = 0 - stack.top(0);
-                }
-                DW_OP_NOT => {
-                    s1 = stack.top(0) as i64;
-                    *stack.top_mut(0) = (!s1) as u64
-                }
-                DW_OP_OR => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) |= u1;
-                }
-                DW_OP_PLUS => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) += u1;
+                DW_OP_ABS | DW_OP_NEG | DW_OP_NOT => unary(opcode, stack.pop()?),
+                DW_OP_AND | DW_OP_DIV | DW_OP_MINUS | DW_OP_MOD | DW_OP_MUL | DW_OP_OR | DW_OP_PLUS | DW_OP_SHL
+                | DW_OP_SHR | DW_OP_SHRA | DW_OP_XOR | DW_OP_EQ | DW_OP_GE | DW_OP_GT | DW_OP_LE | DW_OP_LT
+                | DW_OP_NE => {
+                    let v1 = stack.pop()?;
+                    let v2 = stack.pop()?;
+                    binary(opcode, v2, v1)?
                 }
                 DW_OP_PLUS_UCONST => {
-                    u1 = decode_uleb128(&mut loc, end)?;
-                    *stack.top_mut(0) += u1;
-                }
-                DW_OP_SHL => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) <<= u1;
-                }
-                DW_OP_SHR => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) >>= u1;
-                }
-                DW_OP_SHRA => {
-                    u1 = stack.pop();
-                    s1 = stack.top(0) as i64;
-                    *stack.top_mut(0) = (s1 >> u1) as u64;
-                }
-                DW_OP_XOR => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) ^= u1;
+                    let v1 = decode_uleb128(a, &mut loc, end)?;
+                    stack.pop()?.wrapping_add(v1)
                 }
                 DW_OP_SKIP => {
-                    s1 = load::<i16>(loc)? as i64;
-                    loc += 2;
-                    loc = ((loc as i64) + s1) as u64;
+                    let offset = read::<A, i16>(a, &mut loc, end)?;
+                    loc = branch(loc, offset, start, end)?;
+                    continue;
                 }
                 DW_OP_BRA => {
-                    s1 = load::<i16>(loc)? as i64;
-                    loc += 2;
-                    if stack.pop() != 0 {
-                        loc = ((loc as i64) + s1) as u64;
+                    let offset = read::<A, i16>(a, &mut loc, end)?;
+                    if stack.pop()? != 0 {
+                        loc = branch(loc, offset, start, end)?;
                     }
+                    continue;
                 }
-                DW_OP_EQ => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = if stack.top(0) == u1 { 1 } else { 0 };
-                }
-                DW_OP_GE => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = if stack.top(0) >= u1 { 1 } else { 0 };
-                }
-                DW_OP_GT => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = if stack.top(0) > u1 { 1 } else { 0 };
-                }
-                DW_OP_LE => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = if stack.top(0) <= u1 { 1 } else { 0 };
-                }
-                DW_OP_LT => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = if stack.top(0) < u1 { 1 } else { 0 };
-                }
-                DW_OP_NE => {
-                    u1 = stack.pop();
-                    *stack.top_mut(0) = if stack.top(0) != u1 { 1 } else { 0 };
-                }
-                DW_OP_LIT0..=DW_OP_LIT31 => {
-                    u1 = (opcode - DW_OP_LIT0) as u64;
-                    stack.push(u1);
+                DW_OP_REG0..=DW_OP_REG31 => register(registers, (opcode - DW_OP_REG0) as u64)?,
+                DW_OP_REGX => register(registers, decode_uleb128(a, &mut loc, end)?)?,
+                DW_OP_BREG0..=DW_OP_BREG31 => {
+                    let reg = (opcode - DW_OP_BREG0) as u64;
+                    let offset = decode_sleb128(a, &mut loc, end)?;
+                    register(registers, reg)?.wrapping_add(offset as u64)
                 }
-                DW_OP_REG0..=DW_OP_REG31 => {
-                    reg = (opcode - DW_OP_REG0) as u32;
-                    if !Registers::valid_register(reg as usize) {
-                        return Err(DwarfError::InvalidExpressionRegisterNumber(reg));
+                DW_OP_BREGX => {
+                    let reg = decode_uleb128(a, &mut loc, end)?;
+                    let offset = decode_sleb128(a, &mut loc, end)?;
+                    register(registers, reg)?.wrapping_add(offset as u64)
+                }
+                DW_OP_NOP => continue,
+                DW_OP_CALL_FRAME_CFA => {
+                    // The CFA is unknown while it is being calculated.
+                    cfa.ok_or(DwarfError::NoWayToCalculateCfa)?
+                }
+                DW_OP_PIECE => {
+                    let bytes = decode_uleb128(a, &mut loc, end)?;
+                    let bits = bytes.checked_mul(8).ok_or(DwarfError::InvalidExpressionPiece)?;
+                    add_piece(&mut pieces, &mut piece_bits, stack.pop()?, bits, 0)?;
+                    continue;
+                }
+                DW_OP_BIT_PIECE => {
+                    let bits = decode_uleb128(a, &mut loc, end)?;
+                    let offset = decode_uleb128(a, &mut loc, end)?;
+                    add_piece(&mut pieces, &mut piece_bits, stack.pop()?, bits, offset)?;
+                    continue;
+                }
+                DW_OP_IMPLICIT_VALUE => {
+                    // The block is the value itself, which ends the expression.
+                    let size = decode_uleb128(a, &mut loc, end)?;
+                    if size > 8 {
+                        return Err(DwarfError::InvalidExpressionPiece);
                     }
-                    stack.push(registers[reg as usize]);
-                }
-                DW_OP_REGX => {
-                    reg = decode_uleb128(&mut loc, end)? as u32;
-                    if !Registers::valid_register(reg as usize) {
-                        return Err(DwarfError::InvalidExpressionRegisterNumber(reg));
+                    let mut bytes = [0u8; 8];
+                    for b in bytes.iter_mut().take(size as usize) {
+                        *b = read::<A, u8>(a, &mut loc, end)?;
                     }
-                    stack.push(registers[reg as usize]);
+                    stack.push(u64::from_le_bytes(bytes))?;
+                    break;
                 }
-                DW_OP_BREG0..=DW_OP_BREG31 => {
-                    reg = (opcode - DW_OP_BREG0) as u32;
-                    if !Registers::valid_register(reg as usize) {
-                        return Err(DwarfError::InvalidExpressionRegisterNumber(reg));
-                    }
-                    s1 = decode_sleb128(&mut loc, end)?;
-                    s1 += registers[reg as usize] as i64;
-                    stack.push(s1 as u64);
+                DW_OP_STACK_VALUE => {
+                    // The top of stack is the value, rather than its location. In CFI
+                    // the caller decides how the result is used, so this simply ends
+                    // the expression.
+                    break;
                 }
-                DW_OP_BREGX => {
-                    reg = decode_uleb128(&mut loc, end)? as u32;
-                    if !Registers::valid_register(reg as usize) {
-                        return Err(DwarfError::InvalidExpressionRegisterNumber(reg));
-                    }
-                    s1 = decode_sleb128(&mut loc, end)?;
-                    s1 += registers[reg as usize] as i64;
-                    stack.push(s1 as u64);
+                DW_OP_FORM_TLS_ADDRESS | DW_OP_GNU_PUSH_TLS_ADDRESS => {
+                    // An offset in the TLS block of the object the expression is in.
+                    let offset = stack.pop()?;
+                    a.tls_address(expression, offset)?
                 }
-                DW_OP_DEREF_SIZE => {
-                    u1 = stack.pop();
-                    match load::<u8>(loc)? {
-                        1 => u1 = load::<u8>(u1)? as u64,
-                        2 => u1 = load::<u16>(u1)? as u64,
-                        4 => u1 = load::<u32>(u1)? as u64,
-                        8 => u1 = load::<u64>(u1)?,
-                        v => return Err(DwarfError::InvalidExpressionDerefSize(v)),
-                    }
-                    loc += 1;
-                    stack.push(u1);
+                DW_OP_FBREG | DW_OP_PUSH_OBJECT_ADDRESS | DW_OP_CALL2 | DW_OP_CALL4 | DW_OP_CALL_REF => {
+                    // These need a frame base, an object or DIEs, which CFI doesn't
+                    // have, and the spec forbids them there (DWARF Spec, section 6.4.2).
+                    return Err(DwarfError::ExpressionNotAllowedInCfi(opcode));
                 }
                 v => return Err(DwarfError::InvalidExpression(v)),
-            }
+            };
+            stack.push(value)?;
+        }
+        if piece_bits > 0 {
+            Ok(pieces)
+        } else {
+            stack.top(0).map(|v| a.truncate_pointer(v))
         }
-        Ok(stack.top(0))
     }
 
-    struct EvaluateStack {
-        len: usize,
-        stack: [u64; 100],
+    /// Apply an unary arithmetic operation to the top of stack.
+    #[inline]
+    fn unary(opcode: u8, v: u64) -> u64 {
+        match opcode {
+            DW_OP_ABS => (v as i64).wrapping_abs() as u64,
+            DW_OP_NEG => (v as i64).wrapping_neg() as u64,
+            _ => !v,
+        }
     }
 
-    impl Default for EvaluateStack {
-        fn default() -> Self {
-            Self {
-                len: 0,
-                stack: [0; 100],
+    /// Apply a binary arithmetic, logical or relational operation, where `v1`
+    /// was the top of stack and `v2` the entry below it.
+    #[inline]
+    fn binary(opcode: u8, v2: u64, v1: u64) -> Result<u64, DwarfError> {
+        let v = match opcode {
+            DW_OP_AND => v2 & v1,
+            DW_OP_DIV => {
+                // Signed division.
+                if v1 == 0 {
+                    return Err(DwarfError::ExpressionDivisionByZero);
+                }
+                (v2 as i64).wrapping_div(v1 as i64) as u64
+            }
+            DW_OP_MINUS => v2.wrapping_sub(v1),
+            DW_OP_MOD => {
+                // Unsigned modulo, the generic type has no sign.
+                if v1 == 0 {
+                    return Err(DwarfError::ExpressionDivisionByZero);
+                }
+                v2 % v1
+            }
+            DW_OP_MUL => v2.wrapping_mul(v1),
+            DW_OP_OR => v2 | v1,
+            DW_OP_PLUS => v2.wrapping_add(v1),
+            DW_OP_SHL => {
+                if v1 >= 64 {
+                    0
+                } else {
+                    v2 << v1
+                }
+            }
+            DW_OP_SHR => {
+                if v1 >= 64 {
+                    0
+                } else {
+                    v2 >> v1
+                }
             }
+            DW_OP_SHRA => ((v2 as i64) >> v1.min(63)) as u64,
+            DW_OP_XOR => v2 ^ v1,
+            // Comparisons are signed operations.
+            DW_OP_EQ => (v2 == v1) as u64,
+            DW_OP_GE => (v2 as i64 >= v1 as i64) as u64,
+            DW_OP_GT => (v2 as i64 > v1 as i64) as u64,
+            DW_OP_LE => (v2 as i64 <= v1 as i64) as u64,
+            DW_OP_LT => ((v2 as i64) < v1 as i64) as u64,
+            _ => (v2 != v1) as u64,
+        };
+        Ok(v)
+    }
+
+    /// Calculate the target of DW_OP_skip and DW_OP_bra, which must be inside
+    /// the expression.
+    #[inline]
+    fn branch(loc: u64, offset: i16, start: u64, end: u64) -> Result<u64, DwarfError> {
+        let target = loc.wrapping_add(offset as i64 as u64);
+        if target < start || target > end {
+            return Err(DwarfError::OutOfBounds(target));
         }
+        Ok(target)
     }
 
-    impl Index<usize> for EvaluateStack {
-        type Output = u64;
+    #[inline]
+    fn register<R: RegisterFile>(registers: &R, reg: u64) -> Result<u64, DwarfError> {
+        if reg > u32::MAX as u64 || !R::valid_register(reg as usize) {
+            return Err(DwarfError::InvalidExpressionRegisterNumber(reg as u32));
+        }
+        Ok(registers[reg as usize])
+    }
 
-        #[inline]
-        fn index(&self, index: usize) -> &Self::Output {
-            &self.stack[index]
+    /// Append `bits` bits of `v`, starting from bit `offset`, to the value
+    /// assembled from pieces.
+    #[inline]
+    fn add_piece(pieces: &mut u64, piece_bits: &mut u64, v: u64, bits: u64, offset: u64) -> Result<(), DwarfError> {
+        match piece_bits.checked_add(bits) {
+            Some(total) if total <= 64 && offset < 64 => {
+                let mask = if bits >= 64 { u64::MAX } else { (1u64 << bits) - 1 };
+                if bits > 0 {
+                    *pieces |= ((v >> offset) & mask) << *piece_bits;
+                }
+                *piece_bits = total;
+                Ok(())
+            }
+            _ => Err(DwarfError::InvalidExpressionPiece),
         }
     }
 
-    impl IndexMut<usize> for EvaluateStack {
-        #[inline]
-        fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-            &mut self.stack[index]
+    struct EvaluateStack {
+        len: usize,
+        stack: [u64; MAX_STACK_DEPTH],
+    }
+
+    impl Default for EvaluateStack {
+        fn default() -> Self {
+            Self {
+                len: 0,
+                stack: [0; MAX_STACK_DEPTH],
+            }
         }
     }
 
     impl EvaluateStack {
         #[inline]
-        fn push(&mut self, v: u64) {
+        fn push(&mut self, v: u64) -> Result<(), DwarfError> {
+            if self.len >= MAX_STACK_DEPTH {
+                return Err(DwarfError::ExpressionStackOverflow);
+            }
             self.stack[self.len] = v;
             self.len += 1;
+            Ok(())
         }
 
         #[inline]
-        fn pop(&mut self) -> u64 {
-            let v = self.stack[self.len - 1];
+        fn pop(&mut self) -> Result<u64, DwarfError> {
+            if self.len == 0 {
+                return Err(DwarfError::ExpressionStackUnderflow);
+            }
             self.len -= 1;
-            v
+            Ok(self.stack[self.len])
         }
 
         #[inline]
-        fn top(&self, n: usize) -> u64 {
-            self.stack[self.len - (n + 1)]
+        fn top(&self, n: usize) -> Result<u64, DwarfError> {
+            if n >= self.len {
+                return Err(DwarfError::ExpressionStackUnderflow);
+            }
+            Ok(self.stack[self.len - (n + 1)])
         }
+    }
+}
 
-        #[inline]
-        fn top_mut(&mut self, n: usize) -> &mut u64 {
-            &mut self.stack[self.len - (n + 1)]
+#[cfg(all(test, feature = "dwarf-expression"))]
+mod tests {
+    use super::*;
+    use crate::dwarf::address_space::SliceAddressSpace;
+    use crate::dwarf::consts::*;
+    use crate::dwarf::DwarfError;
+    use crate::registers::Registers;
+
+    const BASE: u64 = 0x10000;
+    const DATA: u64 = BASE + 0x1000;
+    const CFA: u64 = 0x7fff0000;
+
+    fn uleb(v: u64) -> Vec<u8> {
+        let mut buf = vec![];
+        leb128::write::unsigned(&mut buf, v).unwrap();
+        buf
+    }
+
+    fn sleb(v: i64) -> Vec<u8> {
+        let mut buf = vec![];
+        leb128::write::signed(&mut buf, v).unwrap();
+        buf
+    }
+
+    /// Evaluate the expression body `ops` with memory at `DATA` holding
+    /// `[0x1122334455667788, DATA, u64::MAX]` and registers rN = N * 0x100.
+    fn eval(ops: &[u8], cfa: Option<u64>) -> Result<u64, DwarfError> {
+        eval_with_pointer_size(ops, cfa, 8)
+    }
+
+    fn eval_with_pointer_size(ops: &[u8], cfa: Option<u64>, pointer_size: u8) -> Result<u64, DwarfError> {
+        let mut mem = uleb(ops.len() as u64);
+        mem.extend_from_slice(ops);
+        mem.resize((DATA - BASE) as usize, 0);
+        for v in [0x1122334455667788u64, DATA, u64::MAX] {
+            mem.extend_from_slice(&v.to_ne_bytes());
+        }
+        let space = SliceAddressSpace::new(BASE, &mem).with_pointer_size(pointer_size);
+        let mut registers = Registers::default();
+        for n in 0..8 {
+            registers[n] = n as u64 * 0x100;
+        }
+        evaluate(&space, BASE, &registers, cfa)
+    }
+
+    #[test]
+    fn test_evaluate_32bit() {
+        let data = (DATA as u32).to_ne_bytes();
+        let eval = |ops: &[u8]| eval_with_pointer_size(ops, None, 4);
+        assert_eq!(eval(&[&[DW_OP_ADDR][..], &data].concat()), Ok(DATA));
+        assert_eq!(
+            eval(&[&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF]].concat()),
+            Ok(0x55667788)
+        );
+        assert_eq!(
+            eval(&[&[DW_OP_LIT0, DW_OP_ADDR][..], &data, &[DW_OP_XDEREF]].concat()),
+            Ok(0x55667788)
+        );
+        assert_eq!(
+            eval(&[&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF_SIZE, 4]].concat()),
+            Ok(0x55667788)
+        );
+        // The result wraps around at 32 bits.
+        assert_eq!(eval(&[DW_OP_LIT0, DW_OP_LIT1, DW_OP_MINUS]), Ok(0xffff_ffff));
+        assert_eq!(eval(&[DW_OP_BREG0 + 1, 0x7f]), Ok(0xff));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let data = DATA.to_ne_bytes();
+        let cases: Vec<(&str, Vec<u8>, Result<u64, DwarfError>)> = vec![
+            // Literals and constants.
+            ("lit0", vec![DW_OP_LIT0], Ok(0)),
+            ("lit31", vec![DW_OP_LIT31], Ok(31)),
+            ("addr", [&[DW_OP_ADDR][..], &data].concat(), Ok(DATA)),
+            ("const1u", vec![DW_OP_CONST1U, 0xff], Ok(0xff)),
+            ("const1s", vec![DW_OP_CONST1S, 0xff], Ok(u64::MAX)),
+            (
+                "const2u",
+                [&[DW_OP_CONST2U][..], &0xfffeu16.to_ne_bytes()].concat(),
+                Ok(0xfffe),
+            ),
+            (
+                "const2s",
+                [&[DW_OP_CONST2S][..], &(-2i16).to_ne_bytes()].concat(),
+                Ok(-2i64 as u64),
+            ),
+            (
+                "const4u",
+                [&[DW_OP_CONST4U][..], &0xfffffffeu32.to_ne_bytes()].concat(),
+                Ok(0xfffffffe),
+            ),
+            (
+                "const4s",
+                [&[DW_OP_CONST4S][..], &(-2i32).to_ne_bytes()].concat(),
+                Ok(-2i64 as u64),
+            ),
+            (
+                "const8u",
+                [&[DW_OP_CONST8U][..], &u64::MAX.to_ne_bytes()].concat(),
+                Ok(u64::MAX),
+            ),
+            (
+                "const8s",
+                [&[DW_OP_CONST8S][..], &(-2i64).to_ne_bytes()].concat(),
+                Ok(-2i64 as u64),
+            ),
+            ("constu", [&[DW_OP_CONSTU][..], &uleb(300)].concat(), Ok(300)),
+            (
+                "consts",
+                [&[DW_OP_CONSTS][..], &sleb(-300)].concat(),
+                Ok(-300i64 as u64),
+            ),
+            // Stack operations.
+            ("dup", vec![DW_OP_LIT3, DW_OP_DUP, DW_OP_PLUS], Ok(6)),
+            ("drop", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_DROP], Ok(1)),
+            ("over", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_OVER], Ok(1)),
+            ("pick", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_LIT3, DW_OP_PICK, 2], Ok(1)),
+            ("swap", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_SWAP, DW_OP_MINUS], Ok(1)),
+            ("rot", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_LIT3, DW_OP_ROT], Ok(2)),
+            (
+                "rot third",
+                vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_LIT3, DW_OP_ROT, DW_OP_DROP, DW_OP_DROP],
+                Ok(3),
+            ),
+            // Memory.
+            (
+                "deref",
+                [&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF]].concat(),
+                Ok(0x1122334455667788),
+            ),
+            (
+                "deref twice",
+                [
+                    &[DW_OP_ADDR][..],
+                    &data,
+                    &[DW_OP_PLUS_UCONST, 8, DW_OP_DEREF, DW_OP_DEREF],
+                ]
+                .concat(),
+                Ok(0x1122334455667788),
+            ),
+            (
+                "deref_size 1",
+                [&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF_SIZE, 1]].concat(),
+                Ok(0x88),
+            ),
+            (
+                "deref_size 2",
+                [&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF_SIZE, 2]].concat(),
+                Ok(0x7788),
+            ),
+            (
+                "deref_size 4",
+                [&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF_SIZE, 4]].concat(),
+                Ok(0x55667788),
+            ),
+            (
+                "deref_size 8",
+                [&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF_SIZE, 8]].concat(),
+                Ok(0x1122334455667788),
+            ),
+            (
+                "deref_size 3",
+                [&[DW_OP_ADDR][..], &data, &[DW_OP_DEREF_SIZE, 3]].concat(),
+                Err(DwarfError::InvalidExpressionDerefSize(3)),
+            ),
+            (
+                "xderef",
+                [&[DW_OP_LIT0, DW_OP_ADDR][..], &data, &[DW_OP_XDEREF]].concat(),
+                Ok(0x1122334455667788),
+            ),
+            (
+                "xderef_size",
+                [&[DW_OP_LIT0, DW_OP_ADDR][..], &data, &[DW_OP_XDEREF_SIZE, 1]].concat(),
+                Ok(0x88),
+            ),
+            (
+                "deref unreadable",
+                vec![DW_OP_LIT1, DW_OP_DEREF],
+                Err(DwarfError::UnreadableAddress(1)),
+            ),
+            // Arithmetic and logic.
+            ("abs", [&[DW_OP_CONSTS][..], &sleb(-5), &[DW_OP_ABS]].concat(), Ok(5)),
+            (
+                "abs min",
+                [&[DW_OP_CONST8S][..], &i64::MIN.to_ne_bytes(), &[DW_OP_ABS]].concat(),
+                Ok(i64::MIN as u64),
+            ),
+            ("and", vec![DW_OP_LIT12, DW_OP_LIT10, DW_OP_AND], Ok(8)),
+            (
+                "div",
+                [&[DW_OP_CONSTS][..], &sleb(-12), &[DW_OP_LIT5, DW_OP_DIV]].concat(),
+                Ok(-2i64 as u64),
+            ),
+            (
+                "div by zero",
+                vec![DW_OP_LIT1, DW_OP_LIT0, DW_OP_DIV],
+                Err(DwarfError::ExpressionDivisionByZero),
+            ),
+            (
+                "div overflow",
+                [
+                    &[DW_OP_CONST8S][..],
+                    &i64::MIN.to_ne_bytes(),
+                    &[DW_OP_CONST1S, 0xff, DW_OP_DIV],
+                ]
+                .concat(),
+                Ok(i64::MIN as u64),
+            ),
+            ("minus", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_MINUS], Ok(u64::MAX)),
+            ("mod", vec![DW_OP_LIT17, DW_OP_LIT5, DW_OP_MOD], Ok(2)),
+            (
+                "mod by zero",
+                vec![DW_OP_LIT1, DW_OP_LIT0, DW_OP_MOD],
+                Err(DwarfError::ExpressionDivisionByZero),
+            ),
+            ("mul", vec![DW_OP_LIT6, DW_OP_LIT7, DW_OP_MUL], Ok(42)),
+            ("neg", vec![DW_OP_LIT6, DW_OP_NEG], Ok(-6i64 as u64)),
+            ("not", vec![DW_OP_LIT0, DW_OP_NOT], Ok(u64::MAX)),
+            ("or", vec![DW_OP_LIT12, DW_OP_LIT3, DW_OP_OR], Ok(15)),
+            ("plus", vec![DW_OP_LIT12, DW_OP_LIT3, DW_OP_PLUS], Ok(15)),
+            (
+                "plus overflow",
+                vec![DW_OP_LIT0, DW_OP_NOT, DW_OP_LIT2, DW_OP_PLUS],
+                Ok(1),
+            ),
+            (
+                "plus_uconst",
+                [&[DW_OP_LIT1, DW_OP_PLUS_UCONST][..], &uleb(1000)].concat(),
+                Ok(1001),
+            ),
+            ("shl", vec![DW_OP_LIT1, DW_OP_LIT4, DW_OP_SHL], Ok(16)),
+            (
+                "shl 64",
+                [&[DW_OP_LIT1, DW_OP_CONSTU][..], &uleb(64), &[DW_OP_SHL]].concat(),
+                Ok(0),
+            ),
+            ("shr", vec![DW_OP_LIT16, DW_OP_LIT4, DW_OP_SHR], Ok(1)),
+            (
+                "shr 64",
+                [&[DW_OP_LIT16, DW_OP_CONSTU][..], &uleb(64), &[DW_OP_SHR]].concat(),
+                Ok(0),
+            ),
+            (
+                "shra",
+                [&[DW_OP_CONSTS][..], &sleb(-16), &[DW_OP_LIT2, DW_OP_SHRA]].concat(),
+                Ok(-4i64 as u64),
+            ),
+            (
+                "shra 64",
+                [
+                    &[DW_OP_CONSTS][..],
+                    &sleb(-16),
+                    &[DW_OP_CONSTU],
+                    &uleb(64),
+                    &[DW_OP_SHRA],
+                ]
+                .concat(),
+                Ok(u64::MAX),
+            ),
+            ("xor", vec![DW_OP_LIT12, DW_OP_LIT10, DW_OP_XOR], Ok(6)),
+            // Comparisons are signed.
+            ("eq", vec![DW_OP_LIT1, DW_OP_LIT1, DW_OP_EQ], Ok(1)),
+            ("ne", vec![DW_OP_LIT1, DW_OP_LIT1, DW_OP_NE], Ok(0)),
+            ("lt", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_LT], Ok(1)),
+            ("le", vec![DW_OP_LIT2, DW_OP_LIT2, DW_OP_LE], Ok(1)),
+            ("gt", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_GT], Ok(0)),
+            ("ge", vec![DW_OP_LIT1, DW_OP_LIT2, DW_OP_GE], Ok(0)),
+            ("lt signed", vec![DW_OP_CONST1S, 0xff, DW_OP_LIT0, DW_OP_LT], Ok(1)),
+            // Control flow.
+            (
+                "skip",
+                [&[DW_OP_LIT1, DW_OP_SKIP][..], &1i16.to_ne_bytes(), &[DW_OP_LIT2]].concat(),
+                Ok(1),
+            ),
+            (
+                "bra taken",
+                [
+                    &[DW_OP_LIT1, DW_OP_LIT1, DW_OP_BRA][..],
+                    &1i16.to_ne_bytes(),
+                    &[DW_OP_LIT2],
+                ]
+                .concat(),
+                Ok(1),
+            ),
+            (
+                "bra not taken",
+                [
+                    &[DW_OP_LIT1, DW_OP_LIT0, DW_OP_BRA][..],
+                    &1i16.to_ne_bytes(),
+                    &[DW_OP_LIT2],
+                ]
+                .concat(),
+                Ok(2),
+            ),
+            (
+                "skip to end",
+                [&[DW_OP_LIT1, DW_OP_SKIP][..], &0i16.to_ne_bytes()].concat(),
+                Ok(1),
+            ),
+            (
+                "skip out of bounds",
+                [&[DW_OP_LIT1, DW_OP_SKIP][..], &1i16.to_ne_bytes()].concat(),
+                Err(DwarfError::OutOfBounds(BASE + 6)),
+            ),
+            (
+                "skip before start",
+                [&[DW_OP_SKIP][..], &(-4i16).to_ne_bytes()].concat(),
+                Err(DwarfError::OutOfBounds(BASE)),
+            ),
+            (
+                "infinite loop",
+                [&[DW_OP_SKIP][..], &(-3i16).to_ne_bytes()].concat(),
+                Err(DwarfError::ExpressionTooManyInstructions),
+            ),
+            (
+                // Count down from 10 with a backward branch.
+                "loop",
+                [
+                    &[DW_OP_LIT10, DW_OP_LIT1, DW_OP_MINUS, DW_OP_DUP, DW_OP_BRA][..],
+                    &(-6i16).to_ne_bytes(),
+                ]
+                .concat(),
+                Ok(0),
+            ),
+            // Registers.
+            ("reg", vec![DW_OP_REG0 + 6], Ok(0x600)),
+            ("regx", [&[DW_OP_REGX][..], &uleb(3)].concat(), Ok(0x300)),
+            ("breg", [&[DW_OP_BREG0 + 7][..], &sleb(-8)].concat(), Ok(0x6f8)),
+            ("bregx", [&[DW_OP_BREGX][..], &uleb(2), &sleb(16)].concat(), Ok(0x210)),
+            (
+                "regx invalid",
+                [&[DW_OP_REGX][..], &uleb(1000)].concat(),
+                Err(DwarfError::InvalidExpressionRegisterNumber(1000)),
+            ),
+            // Special operations.
+            ("nop", vec![DW_OP_LIT1, DW_OP_NOP], Ok(1)),
+            (
+                "call_frame_cfa",
+                vec![DW_OP_CALL_FRAME_CFA],
+                Err(DwarfError::NoWayToCalculateCfa),
+            ),
+            (
+                "piece",
+                vec![DW_OP_LIT1, DW_OP_PIECE, 4, DW_OP_LIT2, DW_OP_PIECE, 4],
+                Ok(0x200000001),
+            ),
+            (
+                "bit_piece",
+                vec![DW_OP_LIT12, DW_OP_BIT_PIECE, 2, 2, DW_OP_LIT1, DW_OP_BIT_PIECE, 1, 0],
+                Ok(0b111),
+            ),
+            (
+                "piece too large",
+                vec![DW_OP_LIT1, DW_OP_PIECE, 8, DW_OP_LIT1, DW_OP_PIECE, 1],
+                Err(DwarfError::InvalidExpressionPiece),
+            ),
+            (
+                "implicit_value",
+                [&[DW_OP_IMPLICIT_VALUE, 2][..], &0x1234u16.to_le_bytes(), &[DW_OP_DROP]].concat(),
+                Ok(0x1234),
+            ),
+            ("implicit_value 3", vec![DW_OP_IMPLICIT_VALUE, 3, 1, 2, 3], Ok(0x030201)),
+            (
+                "implicit_value too large",
+                [&[DW_OP_IMPLICIT_VALUE, 9][..], &[0; 9]].concat(),
+                Err(DwarfError::InvalidExpressionPiece),
+            ),
+            ("stack_value", vec![DW_OP_LIT5, DW_OP_STACK_VALUE, DW_OP_DROP], Ok(5)),
+            // The spec forbids the operations that need a frame base, an object
+            // or DIEs in CFI.
+            (
+                "fbreg",
+                vec![DW_OP_FBREG, 0],
+                Err(DwarfError::ExpressionNotAllowedInCfi(DW_OP_FBREG)),
+            ),
+            (
+                "push_object_address",
+                vec![DW_OP_PUSH_OBJECT_ADDRESS],
+                Err(DwarfError::ExpressionNotAllowedInCfi(DW_OP_PUSH_OBJECT_ADDRESS)),
+            ),
+            (
+                "call2",
+                vec![DW_OP_CALL2, 0, 0],
+                Err(DwarfError::ExpressionNotAllowedInCfi(DW_OP_CALL2)),
+            ),
+            (
+                "call4",
+                vec![DW_OP_CALL4, 0, 0, 0, 0],
+                Err(DwarfError::ExpressionNotAllowedInCfi(DW_OP_CALL4)),
+            ),
+            (
+                "call_ref",
+                vec![DW_OP_CALL_REF, 0, 0, 0, 0],
+                Err(DwarfError::ExpressionNotAllowedInCfi(DW_OP_CALL_REF)),
+            ),
+            // A slice has no thread-local storage, see `test_evaluate_tls`.
+            (
+                "form_tls_address",
+                vec![DW_OP_LIT0, DW_OP_FORM_TLS_ADDRESS],
+                Err(DwarfError::NoThreadLocalStorage(BASE)),
+            ),
+            (
+                "gnu_push_tls_address",
+                vec![DW_OP_LIT0, DW_OP_GNU_PUSH_TLS_ADDRESS],
+                Err(DwarfError::NoThreadLocalStorage(BASE)),
+            ),
+            (
+                "form_tls_address underflow",
+                vec![DW_OP_FORM_TLS_ADDRESS],
+                Err(DwarfError::ExpressionStackUnderflow),
+            ),
+            ("unknown", vec![0xff], Err(DwarfError::InvalidExpression(0xff))),
+            // Malformed expressions.
+            ("empty", vec![], Err(DwarfError::ExpressionStackUnderflow)),
+            (
+                "underflow",
+                vec![DW_OP_LIT1, DW_OP_PLUS],
+                Err(DwarfError::ExpressionStackUnderflow),
+            ),
+            (
+                "pick underflow",
+                vec![DW_OP_LIT1, DW_OP_PICK, 1],
+                Err(DwarfError::ExpressionStackUnderflow),
+            ),
+            (
+                "overflow",
+                vec![DW_OP_LIT1; 65],
+                Err(DwarfError::ExpressionStackOverflow),
+            ),
+            (
+                "truncated operand",
+                vec![DW_OP_CONST4U, 1, 2],
+                Err(DwarfError::OutOfBounds(BASE + 2)),
+            ),
+            (
+                "truncated uleb128",
+                vec![DW_OP_CONSTU, 0x80],
+                Err(DwarfError::TruncatedUleb128Expression(BASE + 3)),
+            ),
+        ];
+        for (name, ops, expected) in cases {
+            assert_eq!(eval(&ops, None), expected, "case: {}", name);
         }
     }
+
+    #[test]
+    fn test_evaluate_with_cfa() {
+        // The CFA is pushed before evaluation of register rules.
+        assert_eq!(eval(&[], Some(CFA)), Ok(CFA));
+        assert_eq!(eval(&[DW_OP_LIT8, DW_OP_MINUS], Some(CFA)), Ok(CFA - 8));
+        assert_eq!(eval(&[DW_OP_CALL_FRAME_CFA, DW_OP_PLUS], Some(CFA)), Ok(CFA * 2));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_evaluate_tls() {
+        use crate::dwarf::address_space::LocalAddressSpace;
+
+        thread_local! {
+            static VALUE: std::cell::Cell<u64> = const { std::cell::Cell::new(42) };
+        }
+        // In the executable, whose TLS block the offset is relative to. The
+        // offset is pushed like the CFA of register rules.
+        static FORM_TLS_ADDRESS: [u8; 2] = [1, DW_OP_FORM_TLS_ADDRESS];
+        static GNU_PUSH_TLS_ADDRESS: [u8; 2] = [1, DW_OP_GNU_PUSH_TLS_ADDRESS];
+        let a = &LocalAddressSpace;
+        let registers = Registers::default();
+        let expression = FORM_TLS_ADDRESS.as_ptr() as u64;
+        let block = evaluate(a, expression, &registers, Some(0)).unwrap();
+        let value = VALUE.with(|v| v as *const _ as u64);
+        assert_eq!(evaluate(a, expression, &registers, Some(value - block)), Ok(value));
+        let expression = GNU_PUSH_TLS_ADDRESS.as_ptr() as u64;
+        assert_eq!(evaluate(a, expression, &registers, Some(value - block)), Ok(value));
+
+        // Not in a loaded object.
+        let heap = FORM_TLS_ADDRESS.to_vec();
+        let expression = heap.as_ptr() as u64;
+        assert_eq!(
+            evaluate(a, expression, &registers, Some(0)),
+            Err(DwarfError::NoThreadLocalStorage(expression))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_exact_length() {
+        // Operations after the declared length are never executed.
+        let mut mem = vec![1, DW_OP_LIT1, DW_OP_LIT2];
+        let space = SliceAddressSpace::new(BASE, &mem);
+        assert_eq!(evaluate(&space, BASE, &Registers::default(), None), Ok(1));
+
+        // Declared length past the end of memory.
+        mem[0] = 10;
+        let space = SliceAddressSpace::new(BASE, &mem);
+        assert_eq!(
+            evaluate(&space, BASE, &Registers::default(), None),
+            Err(DwarfError::UnreadableAddress(BASE + 3))
+        );
+
+        // Length that overflows.
+        let mut mem = uleb(u64::MAX);
+        mem.push(DW_OP_LIT1);
+        let space = SliceAddressSpace::new(BASE, &mem);
+        assert_eq!(
+            evaluate(&space, BASE, &Registers::default(), None),
+            Err(DwarfError::OutOfBounds(BASE + 10))
+        );
+    }
 }
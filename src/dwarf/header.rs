@@ -1,22 +1,21 @@
-use crate::dwarf::cfi::{CommonInformationEntry, FrameDescriptionEntry};
+use crate::dwarf::address_space::AddressSpace;
+use crate::dwarf::cfi::{CfiSection, CommonInformationEntry, FrameDescriptionEntry};
 use crate::dwarf::consts::*;
 use crate::dwarf::encoding::*;
 use crate::dwarf::DwarfError;
-use std::mem;
-
-#[repr(C)]
-#[derive(Debug)]
-struct RawEhFrameHeader {
-    version: u8,
-    eh_frame_ptr_enc: u8,
-    fde_count_enc: u8,
-    table_enc: u8,
-    // eh_frame_ptr: encoded
-    // fde_count: encoded
-    // table: [[encoded; 2]] // binary search table of: [ initial_location | address ]
-}
 
 /// Information encoded in the .eh_frame_hdr section.
+///
+/// The layout of .eh_frame_hdr is:
+/// ```text
+/// version:          u8
+/// eh_frame_ptr_enc: u8
+/// fde_count_enc:    u8
+/// table_enc:        u8
+/// eh_frame_ptr:     encoded
+/// fde_count:        encoded
+/// table:            [[encoded; 2]] // binary search table of: [ initial_location | address ]
+/// ```
 #[derive(Debug)]
 pub struct EhFrameHeader {
     pub start: u64,
@@ -28,16 +27,18 @@ pub struct EhFrameHeader {
 }
 
 impl EhFrameHeader {
-    pub fn decode(start: u64, end: u64) -> Result<Self, DwarfError> {
+    pub fn decode<A: AddressSpace>(a: &A, start: u64, end: u64) -> Result<Self, DwarfError> {
         let mut loc = start;
-        let raw = unsafe { mem::transmute::<_, &RawEhFrameHeader>(loc as *const u8) };
-        loc += mem::size_of::<RawEhFrameHeader>() as u64;
-        if raw.version != 1 {
-            return Err(DwarfError::InvalidHeaderVersion(raw.version));
+        let version = read::<A, u8>(a, &mut loc, end)?;
+        if version != 1 {
+            return Err(DwarfError::InvalidHeaderVersion(version));
         }
-        let eh_frame = decode_pointer(&mut loc, end, raw.eh_frame_ptr_enc, start)?;
-        let fde_count = if raw.fde_count_enc != DW_EH_PE_OMIT {
-            decode_pointer(&mut loc, end, raw.fde_count_enc, start)?
+        let eh_frame_ptr_enc = read::<A, u8>(a, &mut loc, end)?;
+        let fde_count_enc = read::<A, u8>(a, &mut loc, end)?;
+        let table_enc = read::<A, u8>(a, &mut loc, end)?;
+        let eh_frame = decode_pointer(a, &mut loc, end, eh_frame_ptr_enc, start)?;
+        let fde_count = if fde_count_enc != DW_EH_PE_OMIT {
+            decode_pointer(a, &mut loc, end, fde_count_enc, start)?
         } else {
             0
         };
@@ -47,11 +48,19 @@ impl EhFrameHeader {
             eh_frame,
             fde_count: fde_count as usize,
             table: loc,
-            table_enc: raw.table_enc,
+            table_enc,
         })
     }
 
-    pub fn search(&self, target: u64) -> Result<(FrameDescriptionEntry, CommonInformationEntry), DwarfError> {
+    /// Binary search the table for the FDE that covers `target`.
+    ///
+    /// `eh_frame_end` bounds the .eh_frame section the table points into.
+    pub fn search<A: AddressSpace>(
+        &self,
+        a: &A,
+        target: u64,
+        eh_frame_end: u64,
+    ) -> Result<(FrameDescriptionEntry, CommonInformationEntry), DwarfError> {
         let &Self {
             start,
             end,
@@ -61,18 +70,28 @@ impl EhFrameHeader {
             ..
         } = self;
         let entry_size = match table_enc & 0b1111 {
-            DW_EH_PE_OMIT => 0,
             DW_EH_PE_UDATA2 | DW_EH_PE_SDATA2 => 4,
             DW_EH_PE_UDATA4 | DW_EH_PE_SDATA4 => 8,
             DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => 16,
             v => return Err(DwarfError::InvalidPointerEncodingSize(v)),
         };
+        if fde_count == 0 {
+            return Err(DwarfError::FDENotFound);
+        }
+        // The whole table must be inside the header.
+        match (fde_count as u64)
+            .checked_mul(entry_size)
+            .and_then(|size| table.checked_add(size))
+        {
+            Some(table_end) if table_end <= end => {}
+            _ => return Err(DwarfError::OutOfBounds(table)),
+        }
         let mut low = 0;
         let mut len = fde_count;
         while len > 1 {
             let mid = low + (len / 2);
-            let mut entry_loc = table + (mid * entry_size) as u64;
-            let entry_target = decode_pointer(&mut entry_loc, end, table_enc, start)?;
+            let mut entry_loc = table + mid as u64 * entry_size;
+            let entry_target = decode_pointer(a, &mut entry_loc, end, table_enc, start)?;
             if entry_target == target {
                 low = mid;
                 break;
@@ -83,10 +102,10 @@ impl EhFrameHeader {
                 len /= 2;
             }
         }
-        let mut entry_loc = table + (low * entry_size) as u64;
-        let _ = decode_pointer(&mut entry_loc, end, table_enc, start)?;
-        let fde = decode_pointer(&mut entry_loc, end, table_enc, start)?;
-        match FrameDescriptionEntry::decode(fde) {
+        let mut entry_loc = table + low as u64 * entry_size;
+        let _ = decode_pointer(a, &mut entry_loc, end, table_enc, start)?;
+        let fde = decode_pointer(a, &mut entry_loc, end, table_enc, start)?;
+        match FrameDescriptionEntry::decode(a, CfiSection::EhFrame, fde, eh_frame_end) {
             Ok((fde, cie)) => {
                 if target < fde.pc_start || target >= fde.pc_end {
                     Err(DwarfError::FDENotFound)
@@ -102,16 +121,18 @@ impl EhFrameHeader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dwarf::address_space::{LocalAddressSpace, SliceAddressSpace};
+    use crate::dwarf::testing::EhFrameBuilder;
     use crate::{unwind_init_registers, Registers};
 
     #[test]
     #[cfg(target_os = "linux")]
     fn test_eh_frame_header_decode() {
         let sects = crate::dyld::sections();
-        assert!(sects.len() > 0);
+        assert!(!sects.is_empty());
         for s in sects {
             let hdr_end = s.eh_frame_hdr + s.eh_frame_hdr_len;
-            let hdr = EhFrameHeader::decode(s.eh_frame_hdr, hdr_end).unwrap();
+            let hdr = EhFrameHeader::decode(&LocalAddressSpace, s.eh_frame_hdr, hdr_end).unwrap();
             assert!(hdr.eh_frame > 0);
         }
     }
@@ -128,8 +149,8 @@ mod tests {
         for s in sects {
             if s.contains(registers.pc()) {
                 let hdr_end = s.eh_frame_hdr + s.eh_frame_hdr_len;
-                let hdr = EhFrameHeader::decode(s.eh_frame_hdr, hdr_end).unwrap();
-                let (fde, _) = hdr.search(registers.pc()).unwrap();
+                let hdr = EhFrameHeader::decode(&LocalAddressSpace, s.eh_frame_hdr, hdr_end).unwrap();
+                let (fde, _) = hdr.search(&LocalAddressSpace, registers.pc(), s.max_addr).unwrap();
                 assert!(fde.contains(registers.pc()));
                 found = true;
                 break;
@@ -137,4 +158,81 @@ mod tests {
         }
         assert!(found);
     }
+
+    #[test]
+    fn test_eh_frame_header_search_slice() {
+        const BASE: u64 = 0x10000;
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 1, -8, 16, &[]);
+        for n in 0..10 {
+            b.fde(cie, 0x1000 + n * 0x100, 0x80, &[]);
+        }
+        b.terminate();
+        let mut data = b.header(BASE);
+        let eh_frame = BASE + data.len() as u64;
+        data.extend_from_slice(&b.data);
+
+        let space = SliceAddressSpace::new(BASE, &data);
+        let hdr = EhFrameHeader::decode(&space, BASE, eh_frame).unwrap();
+        assert_eq!(hdr.eh_frame, eh_frame);
+        assert_eq!(hdr.fde_count, 10);
+        for n in 0..10 {
+            let pc = 0x1000 + n * 0x100;
+            let (fde, _) = hdr.search(&space, pc + 0x10, space.end()).unwrap();
+            assert_eq!(fde.pc_start, pc);
+            assert!(matches!(
+                hdr.search(&space, pc + 0x90, space.end()),
+                Err(DwarfError::FDENotFound)
+            ));
+        }
+        assert!(matches!(
+            hdr.search(&space, 0x10, space.end()),
+            Err(DwarfError::FDENotFound)
+        ));
+
+        // Table pointing outside the header.
+        let hdr = EhFrameHeader::decode(&space, BASE, eh_frame - 1).unwrap();
+        assert!(matches!(
+            hdr.search(&space, 0x1010, space.end()),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+
+        // FDE pointing outside .eh_frame.
+        assert!(hdr.search(&space, 0x1010, eh_frame + 4).is_err());
+    }
+
+    #[test]
+    fn test_eh_frame_header_malformed() {
+        let space = SliceAddressSpace::new(0x1000, &[]);
+        assert!(matches!(
+            EhFrameHeader::decode(&space, 0x1000, 0x1000),
+            Err(DwarfError::OutOfBounds(0x1000))
+        ));
+
+        let data = [2u8, 0, 0, 0];
+        let space = SliceAddressSpace::new(0x1000, &data);
+        assert!(matches!(
+            EhFrameHeader::decode(&space, 0x1000, space.end()),
+            Err(DwarfError::InvalidHeaderVersion(2))
+        ));
+
+        // Truncated eh_frame_ptr.
+        let data = [1u8, DW_EH_PE_UDATA4, DW_EH_PE_OMIT, DW_EH_PE_UDATA4, 0];
+        let space = SliceAddressSpace::new(0x1000, &data);
+        assert!(matches!(
+            EhFrameHeader::decode(&space, 0x1000, space.end()),
+            Err(DwarfError::OutOfBounds(0x1004))
+        ));
+
+        // Huge fde_count.
+        let mut data = vec![1u8, DW_EH_PE_UDATA4, DW_EH_PE_UDATA8, DW_EH_PE_UDATA8];
+        data.extend_from_slice(&0u32.to_ne_bytes());
+        data.extend_from_slice(&u64::MAX.to_ne_bytes());
+        let space = SliceAddressSpace::new(0x1000, &data);
+        let hdr = EhFrameHeader::decode(&space, 0x1000, space.end()).unwrap();
+        assert!(matches!(
+            hdr.search(&space, 0, space.end()),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+    }
 }
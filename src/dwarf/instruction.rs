@@ -1,115 +1,216 @@
+use crate::arch::Arch;
+use crate::dwarf::address_space::AddressSpace;
 use crate::dwarf::cfi::{CommonInformationEntry, FrameDescriptionEntry};
 use crate::dwarf::consts::*;
 use crate::dwarf::encoding::*;
 use crate::dwarf::expression::evaluate;
-use crate::dwarf::{load_with_protect as load, DwarfError};
-use crate::registers::Registers;
-#[cfg(target_arch = "aarch64")]
-use crate::registers::UNW_ARM64_RA_SIGN_STATE;
+use crate::dwarf::DwarfError;
+use crate::registers::RegisterFile;
+use std::mem::MaybeUninit;
 
-const MAX_REGISTER_NUM: usize = 287;
+/// Maximum nesting depth of DW_CFA_remember_state used by [run]. GCC emits
+/// one level for each epilogue in the middle of a function, so real code
+/// rarely goes beyond a couple of levels.
+pub const MAX_REMEMBER_DEPTH: usize = 4;
 
 /// "Run" the DWARF instructions and create the abstract [PrologInfo].
-pub fn run(pc: u64, fde: &FrameDescriptionEntry, cie: &CommonInformationEntry) -> Result<PrologInfo, DwarfError> {
+///
+/// The CFI is interpreted for the architecture of `R`, and the rules of up
+/// to `N` registers are kept, which must be at least its
+/// [Arch::REGISTER_COUNT].
+#[inline]
+pub fn run<A: AddressSpace, R: RegisterFile, const N: usize>(
+    a: &A,
+    pc: u64,
+    fde: &FrameDescriptionEntry,
+    cie: &CommonInformationEntry,
+) -> Result<PrologInfo<R::Arch, N>, DwarfError> {
+    run_with_depth::<A, R, N, MAX_REMEMBER_DEPTH>(a, pc, fde, cie)
+}
+
+/// Same as [run], but DW_CFA_remember_state may nest up to `D` levels.
+/// Deeper nesting fails with [DwarfError::RememberStackOverflow].
+pub fn run_with_depth<A: AddressSpace, R: RegisterFile, const N: usize, const D: usize>(
+    a: &A,
+    pc: u64,
+    fde: &FrameDescriptionEntry,
+    cie: &CommonInformationEntry,
+) -> Result<PrologInfo<R::Arch, N>, DwarfError> {
+    debug_assert!(N >= R::Arch::REGISTER_COUNT);
     let mut result = PrologInfo::default();
-    run_(
+    // DW_CFA_restore in the CIE initial instructions resets to no rule.
+    run_::<A, R, N, D>(
+        a,
         &mut result,
+        &RegisterRules::default(),
         cie,
         cie.cie_instructions,
         cie.cie_start + cie.cie_length,
         u64::MAX,
     )?;
-    run_(
+    // DW_CFA_restore in the FDE resets to the rule set by the CIE.
+    let initial_state = result.saved_registers;
+    run_::<A, R, N, D>(
+        a,
         &mut result,
+        &initial_state,
         cie,
         fde.fde_instructions,
         fde.fde_start + fde.fde_length,
-        pc - fde.pc_start,
+        pc.saturating_sub(fde.pc_start),
     )?;
     Ok(result)
 }
 
 /// Information about a frame layout and registers saved determined
 /// by "running" the DWARF FDE "instructions".
-#[derive(Debug, Copy, Clone)]
-pub struct PrologInfo {
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PrologInfo<T: Arch, const N: usize> {
     pub cfa_register: u32,
     pub cfa_register_offset: i32, // CFA = (cfa_register) + cfa_register_offset
     pub cfa_expression: i64,      // CFA = expression
     pub sp_extra_arg_size: u32,
-    pub saved_registers: [RegisterLocation; MAX_REGISTER_NUM],
+    pub saved_registers: RegisterRules<T, N>,
 }
 
-impl Default for PrologInfo {
-    fn default() -> Self {
-        Self {
-            cfa_register: 0,
-            cfa_register_offset: 0,
-            cfa_expression: 0,
-            sp_extra_arg_size: 0,
-            saved_registers: [RegisterLocation {
-                location: RegisterSavedWhere::Unused,
-                initial_state_saved: false,
-                value: 0,
-            }; MAX_REGISTER_NUM],
-        }
-    }
-}
-
-impl PrologInfo {
-    pub fn cfa(&self, registers: &Registers) -> Result<u64, DwarfError> {
+impl<T: Arch, const N: usize> PrologInfo<T, N> {
+    pub fn cfa<A: AddressSpace, R: RegisterFile>(&self, a: &A, registers: &R) -> Result<u64, DwarfError> {
         if self.cfa_register != 0 {
-            if Registers::valid_register(self.cfa_register as usize) {
-                Ok((registers[self.cfa_register as usize] as i64 + self.cfa_register_offset as i64) as u64)
+            if R::valid_register(self.cfa_register as usize) {
+                let cfa = registers[self.cfa_register as usize].wrapping_add(self.cfa_register_offset as i64 as u64);
+                Ok(a.truncate_pointer(cfa))
             } else {
                 Err(DwarfError::InvalidCfaRegisterNumber(self.cfa_register as usize))
             }
         } else if self.cfa_expression != 0 {
-            evaluate(self.cfa_expression as u64, registers, 0)
+            evaluate(a, self.cfa_expression as u64, registers, None)
         } else {
             Err(DwarfError::NoWayToCalculateCfa)
         }
     }
 
-    pub fn set_register(&mut self, r: usize, new_loc: RegisterSavedWhere, new_v: i64, initial_state: &mut PrologInfo) {
-        self.check_save_register(r, initial_state);
-        self.saved_registers[r].location = new_loc;
-        self.saved_registers[r].value = new_v;
+    pub fn set_register(&mut self, r: usize, new_loc: RegisterSavedWhere, new_v: i64) {
+        self.saved_registers.set(
+            r,
+            RegisterLocation {
+                location: new_loc,
+                value: new_v,
+            },
+        );
     }
 
-    pub fn set_register_location(&mut self, r: usize, new_loc: RegisterSavedWhere, initial_state: &mut PrologInfo) {
-        self.check_save_register(r, initial_state);
-        self.saved_registers[r].location = new_loc;
+    pub fn set_register_location(&mut self, r: usize, new_loc: RegisterSavedWhere) {
+        let value = self.saved_registers.get(r).value;
+        self.set_register(r, new_loc, value);
     }
 
-    #[cfg_attr(target_arch = "x86_64", allow(unused))]
-    pub fn set_register_value(&mut self, r: usize, new_v: i64, initial_state: &mut PrologInfo) {
-        self.check_save_register(r, initial_state);
-        self.saved_registers[r].value = new_v;
+    pub fn set_register_value(&mut self, r: usize, new_v: i64) {
+        let location = self.saved_registers.get(r).location;
+        self.set_register(r, location, new_v);
     }
 
-    pub fn restore_register_to_initial_state(&mut self, r: usize, initial_state: &PrologInfo) {
-        if self.saved_registers[r].initial_state_saved {
-            self.saved_registers[r] = initial_state.saved_registers[r];
+    pub fn restore_register_to_initial_state(&mut self, r: usize, initial_state: &RegisterRules<T, N>) {
+        self.saved_registers.set(r, initial_state.get(r));
+    }
+}
+
+/// Rules of the registers saved by a frame.
+///
+/// This is a sparse map keyed by DWARF register number: only the registers
+/// mentioned by the CFI take an entry, in the order they were first seen.
+/// Functions typically save a handful of registers, so lookups are a short
+/// linear scan. The capacity `N` is the number of registers of the
+/// architecture, so that it can't overflow once register numbers have been
+/// checked.
+#[derive(Debug, Copy, Clone)]
+pub struct RegisterRules<T: Arch, const N: usize> {
+    len: u8,
+    registers: [T::RegisterNum; N],
+    locations: [RegisterSavedWhere; N],
+    values: [i64; N],
+}
+
+impl<T: Arch, const N: usize> Default for RegisterRules<T, N> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            registers: [T::RegisterNum::default(); N],
+            locations: [RegisterSavedWhere::Unused; N],
+            values: [0; N],
         }
-        // Else the register still holds its initial state.
     }
+}
 
-    fn check_save_register(&mut self, r: usize, initial_state: &mut PrologInfo) {
-        if !self.saved_registers[r].initial_state_saved {
-            initial_state.saved_registers[r] = self.saved_registers[r];
-            self.saved_registers[r].initial_state_saved = true;
+impl<T: Arch, const N: usize> RegisterRules<T, N> {
+    /// Returns the rule of register `r`, which is [RegisterSavedWhere::Unused]
+    /// if the CFI never mentioned it.
+    pub fn get(&self, r: usize) -> RegisterLocation {
+        match self.position(r) {
+            Some(i) => RegisterLocation {
+                location: self.locations[i],
+                value: self.values[i],
+            },
+            None => RegisterLocation::default(),
         }
     }
+
+    /// Set the rule of register `r`, which must be a register of the
+    /// architecture.
+    pub fn set(&mut self, r: usize, loc: RegisterLocation) {
+        let i = match self.position(r) {
+            Some(i) => i,
+            None if loc == RegisterLocation::default() => return,
+            None => {
+                let i = self.len as usize;
+                let Ok(r) = T::RegisterNum::try_from(r) else {
+                    unreachable!("invalid register number: {}", r);
+                };
+                self.registers[i] = r;
+                self.len += 1;
+                i
+            }
+        };
+        self.locations[i] = loc.location;
+        self.values[i] = loc.value;
+    }
+
+    /// Iterate over registers that have a rule other than
+    /// [RegisterSavedWhere::Unused].
+    pub fn iter(&self) -> impl Iterator<Item = (usize, RegisterLocation)> + '_ {
+        (0..self.len as usize)
+            .filter(move |&i| self.locations[i] != RegisterSavedWhere::Unused)
+            .map(move |i| {
+                (
+                    self.registers[i].into(),
+                    RegisterLocation {
+                        location: self.locations[i],
+                        value: self.values[i],
+                    },
+                )
+            })
+    }
+
+    #[inline]
+    fn position(&self, r: usize) -> Option<usize> {
+        self.registers[..self.len as usize].iter().position(|&v| v.into() == r)
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct RegisterLocation {
     pub location: RegisterSavedWhere,
-    pub initial_state_saved: bool,
     pub value: i64,
 }
 
+impl Default for RegisterLocation {
+    fn default() -> Self {
+        Self {
+            location: RegisterSavedWhere::Unused,
+            value: 0,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum RegisterSavedWhere {
     Unused,
@@ -121,40 +222,137 @@ pub enum RegisterSavedWhere {
     IsExpression,
 }
 
-pub fn get_saved_register(registers: &Registers, loc: RegisterLocation, cfa: u64) -> Result<u64, DwarfError> {
+/// Recover the value of a general register in the caller frame (DWARF Spec,
+/// section 6.4.1). `registers` holds the state of the current frame.
+pub fn get_saved_register<A: AddressSpace, R: RegisterFile>(
+    a: &A,
+    registers: &R,
+    loc: RegisterLocation,
+    cfa: u64,
+) -> Result<u64, DwarfError> {
     match loc.location {
-        RegisterSavedWhere::InCFA => load::<u64>((cfa as i64 + loc.value) as u64),
-        RegisterSavedWhere::AtExpression => load::<u64>(evaluate(loc.value as u64, registers, cfa)?),
-        RegisterSavedWhere::IsExpression => evaluate(loc.value as u64, registers, cfa),
-        RegisterSavedWhere::InRegister => load::<u64>(loc.value as u64),
+        // offset(N): saved at CFA+N.
+        RegisterSavedWhere::InCFA => a.load_pointer(a.truncate_pointer(cfa.wrapping_add(loc.value as u64))),
+        // val_offset(N): the value is CFA+N.
+        RegisterSavedWhere::OffsetFromCFA => Ok(a.truncate_pointer(cfa.wrapping_add(loc.value as u64))),
+        // register(R): saved in another register.
+        RegisterSavedWhere::InRegister => {
+            let r = loc.value as usize;
+            if !R::valid_register(r) {
+                return Err(DwarfError::InvalidRegisterNumber(r));
+            }
+            Ok(registers[r])
+        }
+        // expression(E): saved at the address computed by E.
+        RegisterSavedWhere::AtExpression => a.load_pointer(evaluate(a, loc.value as u64, registers, Some(cfa))?),
+        // val_expression(E): the value is computed by E.
+        RegisterSavedWhere::IsExpression => evaluate(a, loc.value as u64, registers, Some(cfa)),
+        // undefined: the register has no recoverable value. An undefined return
+        // address marks the outermost frame, which is reported as a zero PC.
         RegisterSavedWhere::Undefined => Ok(0),
-        _ => Err(DwarfError::InvalidRegisterLocation),
+        RegisterSavedWhere::Unused => Err(DwarfError::InvalidRegisterLocation),
     }
 }
 
-pub fn get_saved_float_register(registers: &Registers, loc: RegisterLocation, cfa: u64) -> Result<f64, DwarfError> {
+/// Recover the value of a floating-point register in the caller frame,
+/// see [get_saved_register].
+pub fn get_saved_float_register<A: AddressSpace, R: RegisterFile>(
+    a: &A,
+    registers: &R,
+    loc: RegisterLocation,
+    cfa: u64,
+) -> Result<f64, DwarfError> {
     match loc.location {
-        RegisterSavedWhere::InCFA => load::<f64>((cfa as i64 + loc.value) as u64),
-        RegisterSavedWhere::AtExpression => load::<f64>(evaluate(loc.value as u64, registers, cfa)?),
-        _ => Err(DwarfError::InvalidRegisterLocation),
+        RegisterSavedWhere::InCFA => a.load::<f64>(cfa.wrapping_add(loc.value as u64)),
+        RegisterSavedWhere::OffsetFromCFA => Ok(f64::from_bits(cfa.wrapping_add(loc.value as u64))),
+        RegisterSavedWhere::InRegister => {
+            let r = loc.value as usize;
+            if R::valid_float_register(r) {
+                Ok(registers.float_register(r))
+            } else if R::valid_register(r) {
+                Ok(f64::from_bits(registers[r]))
+            } else {
+                Err(DwarfError::InvalidRegisterNumber(r))
+            }
+        }
+        RegisterSavedWhere::AtExpression => a.load::<f64>(evaluate(a, loc.value as u64, registers, Some(cfa))?),
+        RegisterSavedWhere::IsExpression => Ok(f64::from_bits(evaluate(a, loc.value as u64, registers, Some(cfa))?)),
+        RegisterSavedWhere::Undefined => Ok(0.0),
+        RegisterSavedWhere::Unused => Err(DwarfError::InvalidRegisterLocation),
     }
 }
 
-pub fn get_saved_vector_register(registers: &Registers, loc: RegisterLocation, cfa: u64) -> Result<u128, DwarfError> {
+/// Recover the value of a vector register in the caller frame, see
+/// [get_saved_register]. Values computed from the CFA, general registers or
+/// expressions are zero-extended.
+pub fn get_saved_vector_register<A: AddressSpace, R: RegisterFile>(
+    a: &A,
+    registers: &R,
+    loc: RegisterLocation,
+    cfa: u64,
+) -> Result<u128, DwarfError> {
     match loc.location {
-        RegisterSavedWhere::InCFA => load::<u128>((cfa as i64 + loc.value) as u64),
-        RegisterSavedWhere::AtExpression => load::<u128>(evaluate(loc.value as u64, registers, cfa)?),
-        _ => Err(DwarfError::InvalidRegisterLocation),
+        RegisterSavedWhere::InCFA => a.load::<u128>(cfa.wrapping_add(loc.value as u64)),
+        RegisterSavedWhere::OffsetFromCFA => Ok(cfa.wrapping_add(loc.value as u64) as u128),
+        RegisterSavedWhere::InRegister => {
+            let r = loc.value as usize;
+            if R::valid_vector_register(r) {
+                Ok(registers.vector_register(r))
+            } else if R::valid_register(r) {
+                Ok(registers[r] as u128)
+            } else {
+                Err(DwarfError::InvalidRegisterNumber(r))
+            }
+        }
+        RegisterSavedWhere::AtExpression => a.load::<u128>(evaluate(a, loc.value as u64, registers, Some(cfa))?),
+        RegisterSavedWhere::IsExpression => Ok(evaluate(a, loc.value as u64, registers, Some(cfa))? as u128),
+        RegisterSavedWhere::Undefined => Ok(0),
+        RegisterSavedWhere::Unused => Err(DwarfError::InvalidRegisterLocation),
     }
 }
 
-struct RememberStack {
-    info: PrologInfo,
-    next: *const RememberStack,
+/// Fixed-capacity stack of the states saved by DW_CFA_remember_state, kept
+/// inline to avoid heap allocation. Slots are only initialized when pushed,
+/// so an unused stack costs nothing but its space.
+struct RememberStack<T: Arch, const N: usize, const D: usize> {
+    states: [MaybeUninit<PrologInfo<T, N>>; D],
+    len: usize,
+}
+
+impl<T: Arch, const N: usize, const D: usize> RememberStack<T, N, D> {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            states: [const { MaybeUninit::uninit() }; D],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, info: &PrologInfo<T, N>) -> Result<(), DwarfError> {
+        if self.len == D {
+            return Err(DwarfError::RememberStackOverflow(D));
+        }
+        self.states[self.len].write(*info);
+        self.len += 1;
+        Ok(())
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<&PrologInfo<T, N>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: slots below `len` have been written by `push`.
+        Some(unsafe { self.states[self.len].assume_init_ref() })
+    }
 }
 
-fn run_(
-    result: &mut PrologInfo,
+fn run_<A: AddressSpace, R: RegisterFile, const N: usize, const D: usize>(
+    a: &A,
+    result: &mut PrologInfo<R::Arch, N>,
+    initial_state: &RegisterRules<R::Arch, N>,
     cie: &CommonInformationEntry,
     start: u64,
     end: u64,
@@ -162,200 +360,143 @@ fn run_(
 ) -> Result<(), DwarfError> {
     let mut loc = start;
     let mut code_offset = 0;
-    let mut initial_state = PrologInfo::default();
-    let mut remember_stack: *const RememberStack = std::ptr::null();
+    let mut remember_stack = RememberStack::<R::Arch, N, D>::new();
 
-    // See DWARF Spec, section 6.4.2 for details on unwind opcodes.
-    while loc < end && code_offset < pc_offset {
-        let opcode = load::<u8>(loc)?;
-        loc += 1;
+    // See DWARF Spec, section 6.4.2 for details on unwind opcodes. A row
+    // describes the state before the instruction at its location is executed,
+    // so it applies to `pc_offset` itself.
+    while loc < end && code_offset <= pc_offset {
+        let opcode = read::<A, u8>(a, &mut loc, end)?;
 
         match opcode {
             DW_CFA_NOP => {}
             DW_CFA_SET_LOC => {
-                code_offset = decode_pointer(&mut loc, end, cie.pointer_encoding, 0)?;
+                code_offset = decode_pointer(a, &mut loc, end, cie.pointer_encoding, 0)?;
             }
             DW_CFA_ADVANCE_LOC1 => {
-                code_offset += load::<u8>(loc)? as u64 * cie.code_align_factor as u64;
-                loc += 1;
+                code_offset =
+                    code_offset.saturating_add(read::<A, u8>(a, &mut loc, end)? as u64 * cie.code_align_factor as u64);
             }
             DW_CFA_ADVANCE_LOC2 => {
-                code_offset += load::<u16>(loc)? as u64 * cie.code_align_factor as u64;
-                loc += 2;
+                code_offset =
+                    code_offset.saturating_add(read::<A, u16>(a, &mut loc, end)? as u64 * cie.code_align_factor as u64);
             }
             DW_CFA_ADVANCE_LOC4 => {
-                code_offset += load::<u32>(loc)? as u64 * cie.code_align_factor as u64;
-                loc += 4;
+                code_offset =
+                    code_offset.saturating_add(read::<A, u32>(a, &mut loc, end)? as u64 * cie.code_align_factor as u64);
             }
             DW_CFA_OFFSET_EXTENDED => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                let offset = decode_uleb128(&mut loc, end)? as i64 * cie.data_align_factor as i64;
-                result.set_register(r, RegisterSavedWhere::InCFA, offset, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                let offset = (decode_uleb128(a, &mut loc, end)? as i64).wrapping_mul(cie.data_align_factor as i64);
+                result.set_register(r, RegisterSavedWhere::InCFA, offset);
             }
             DW_CFA_RESTORE_EXTENDED => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                result.restore_register_to_initial_state(r, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                result.restore_register_to_initial_state(r, initial_state);
             }
             DW_CFA_UNDEFINED => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                result.set_register_location(r, RegisterSavedWhere::Undefined, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                result.set_register_location(r, RegisterSavedWhere::Undefined);
             }
             DW_CFA_SAME_VALUE => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
+                let r = read_register::<A, R>(a, &mut loc, end)?;
                 // "same value" means register was stored in frame, but its current
                 // value has not changed, so no need to restore from frame.
                 // We model this as if the register was never saved.
-                result.set_register_location(r, RegisterSavedWhere::Unused, &mut initial_state);
+                result.set_register_location(r, RegisterSavedWhere::Unused);
             }
             DW_CFA_REGISTER => {
-                let r1 = decode_uleb128(&mut loc, end)? as usize;
-                if r1 > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r1));
-                }
-                let r2 = decode_uleb128(&mut loc, end)? as usize;
-                if r2 > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r2));
-                }
-                result.set_register(r1, RegisterSavedWhere::InRegister, r2 as i64, &mut initial_state);
+                let r1 = read_register::<A, R>(a, &mut loc, end)?;
+                let r2 = read_register::<A, R>(a, &mut loc, end)?;
+                result.set_register(r1, RegisterSavedWhere::InRegister, r2 as i64);
             }
             DW_CFA_REMEMBER_STATE => {
-                // Avoid malloc because it needs heap allocation.
-                remember_stack = &RememberStack {
-                    info: *result,
-                    next: remember_stack,
-                } as _;
+                remember_stack.push(result)?;
             }
             DW_CFA_RESTORE_STATE => {
-                if remember_stack == std::ptr::null() {
-                    return Err(DwarfError::NoRememberState);
-                }
-                unsafe {
-                    *result = (*remember_stack).info;
-                    remember_stack = (*remember_stack).next;
-                }
+                *result = *remember_stack.pop().ok_or(DwarfError::NoRememberState)?;
             }
             DW_CFA_DEF_CFA => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
+                let r = read_register::<A, R>(a, &mut loc, end)?;
                 result.cfa_register = r as u32;
-                result.cfa_register_offset = decode_uleb128(&mut loc, end)? as i32;
+                result.cfa_register_offset = decode_uleb128(a, &mut loc, end)? as i32;
             }
             DW_CFA_DEF_CFA_REGISTER => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
+                let r = read_register::<A, R>(a, &mut loc, end)?;
                 result.cfa_register = r as u32;
             }
             DW_CFA_DEF_CFA_OFFSET => {
-                result.cfa_register_offset = decode_uleb128(&mut loc, end)? as i32;
+                result.cfa_register_offset = decode_uleb128(a, &mut loc, end)? as i32;
             }
             DW_CFA_DEF_CFA_EXPRESSION => {
                 result.cfa_register = 0;
                 result.cfa_expression = loc as i64;
-                loc += decode_uleb128(&mut loc, end)?;
+                skip_expression(a, &mut loc, end)?;
             }
             DW_CFA_EXPRESSION => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                result.set_register(r, RegisterSavedWhere::AtExpression, loc as i64, &mut initial_state);
-                loc += decode_uleb128(&mut loc, end)?;
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                result.set_register(r, RegisterSavedWhere::AtExpression, loc as i64);
+                skip_expression(a, &mut loc, end)?;
             }
             DW_CFA_OFFSET_EXTENDED_SF => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                let offset = decode_sleb128(&mut loc, end)? * cie.data_align_factor as i64;
-                result.set_register(r, RegisterSavedWhere::InCFA, offset, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                let offset = decode_sleb128(a, &mut loc, end)?.wrapping_mul(cie.data_align_factor as i64);
+                result.set_register(r, RegisterSavedWhere::InCFA, offset);
             }
             DW_CFA_DEF_CFA_SF => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
+                let r = read_register::<A, R>(a, &mut loc, end)?;
                 result.cfa_register = r as u32;
-                result.cfa_register_offset = (decode_sleb128(&mut loc, end)? * cie.data_align_factor as i64) as i32;
+                result.cfa_register_offset =
+                    (decode_sleb128(a, &mut loc, end)?.wrapping_mul(cie.data_align_factor as i64)) as i32;
             }
             DW_CFA_DEF_CFA_OFFSET_SF => {
-                result.cfa_register_offset = (decode_sleb128(&mut loc, end)? * cie.data_align_factor as i64) as i32;
+                result.cfa_register_offset =
+                    (decode_sleb128(a, &mut loc, end)?.wrapping_mul(cie.data_align_factor as i64)) as i32;
             }
             DW_CFA_VAL_OFFSET => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                let offset = decode_uleb128(&mut loc, end)? as i64 * cie.data_align_factor as i64;
-                result.set_register(r, RegisterSavedWhere::OffsetFromCFA, offset, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                let offset = (decode_uleb128(a, &mut loc, end)? as i64).wrapping_mul(cie.data_align_factor as i64);
+                result.set_register(r, RegisterSavedWhere::OffsetFromCFA, offset);
             }
             DW_CFA_VAL_OFFSET_SF => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                let offset = decode_sleb128(&mut loc, end)? * cie.data_align_factor as i64;
-                result.set_register(r, RegisterSavedWhere::OffsetFromCFA, offset, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                let offset = decode_sleb128(a, &mut loc, end)?.wrapping_mul(cie.data_align_factor as i64);
+                result.set_register(r, RegisterSavedWhere::OffsetFromCFA, offset);
             }
             DW_CFA_VAL_EXPRESSION => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                result.set_register(r, RegisterSavedWhere::IsExpression, loc as i64, &mut initial_state);
-                loc += decode_uleb128(&mut loc, end)?;
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                result.set_register(r, RegisterSavedWhere::IsExpression, loc as i64);
+                skip_expression(a, &mut loc, end)?;
             }
             DW_CFA_GNU_ARGS_SIZE => {
-                result.sp_extra_arg_size = decode_uleb128(&mut loc, end)? as u32;
+                result.sp_extra_arg_size = decode_uleb128(a, &mut loc, end)? as u32;
             }
             DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED => {
-                let r = decode_uleb128(&mut loc, end)? as usize;
-                if r > MAX_REGISTER_NUM {
-                    return Err(DwarfError::InvalidRegisterNumber(r));
-                }
-                let offset = decode_uleb128(&mut loc, end)? as i64 * cie.data_align_factor as i64;
-                result.set_register(r, RegisterSavedWhere::InCFA, -offset, &mut initial_state);
+                let r = read_register::<A, R>(a, &mut loc, end)?;
+                let offset = (decode_uleb128(a, &mut loc, end)? as i64).wrapping_mul(cie.data_align_factor as i64);
+                result.set_register(r, RegisterSavedWhere::InCFA, offset.wrapping_neg());
             }
-            #[cfg(target_arch = "aarch64")]
-            DW_CFA_AARCH64_NEGATE_RA_STATE => {
-                let value = result.saved_registers[UNW_ARM64_RA_SIGN_STATE].value ^ 0x1;
-                result.set_register_value(UNW_ARM64_RA_SIGN_STATE, value, &mut initial_state);
+            // The same opcode is DW_CFA_GNU_window_save on SPARC.
+            DW_CFA_AARCH64_NEGATE_RA_STATE if R::Arch::RA_SIGN_STATE.is_some() => {
+                let r = R::Arch::RA_SIGN_STATE.unwrap();
+                let value = result.saved_registers.get(r).value ^ 0x1;
+                result.set_register_value(r, value);
             }
             _ => {
                 let operand = opcode & 0b111111;
                 match opcode & 0b11000000 {
                     DW_CFA_OFFSET => {
-                        let r = operand as usize;
-                        if r > MAX_REGISTER_NUM {
-                            return Err(DwarfError::InvalidRegisterNumber(r));
-                        }
-                        let offset = decode_uleb128(&mut loc, end)? as i64 * cie.data_align_factor as i64;
-                        result.set_register(r, RegisterSavedWhere::InCFA, offset, &mut initial_state);
+                        let r = check_register::<R>(operand as u64)?;
+                        let offset =
+                            (decode_uleb128(a, &mut loc, end)? as i64).wrapping_mul(cie.data_align_factor as i64);
+                        result.set_register(r, RegisterSavedWhere::InCFA, offset);
                     }
                     DW_CFA_ADVANCE_LOC => {
-                        code_offset += operand as u64 * cie.code_align_factor as u64;
+                        code_offset = code_offset.saturating_add(operand as u64 * cie.code_align_factor as u64);
                     }
                     DW_CFA_RESTORE => {
-                        let r = operand as usize;
-                        if r > MAX_REGISTER_NUM {
-                            return Err(DwarfError::InvalidRegisterNumber(r));
-                        }
-                        result.restore_register_to_initial_state(r, &initial_state);
+                        let r = check_register::<R>(operand as u64)?;
+                        result.restore_register_to_initial_state(r, initial_state);
                     }
                     v => return Err(DwarfError::InvalidInstruction(v)),
                 }
@@ -364,3 +505,368 @@ fn run_(
     }
     Ok(())
 }
+
+/// Read a ULEB128 register number, which must be a register of the architecture.
+#[inline]
+fn read_register<A: AddressSpace, R: RegisterFile>(a: &A, loc: &mut u64, end: u64) -> Result<usize, DwarfError> {
+    check_register::<R>(decode_uleb128(a, loc, end)?)
+}
+
+/// Check that `r` is a register of the architecture, which keeps the number
+/// of rules within [Arch::REGISTER_COUNT].
+#[inline]
+fn check_register<R: RegisterFile>(r: u64) -> Result<usize, DwarfError> {
+    if r > R::Arch::MAX_REGISTER_NUM as u64 || !R::Arch::valid_any_register(r as usize) {
+        return Err(DwarfError::InvalidRegisterNumber(r as usize));
+    }
+    Ok(r as usize)
+}
+
+/// Skip a ULEB128 length prefixed expression, which must end before `end`.
+fn skip_expression<A: AddressSpace>(a: &A, loc: &mut u64, end: u64) -> Result<(), DwarfError> {
+    let len = decode_uleb128(a, loc, end)?;
+    match loc.checked_add(len) {
+        Some(v) if v <= end => {
+            *loc = v;
+            Ok(())
+        }
+        _ => Err(DwarfError::OutOfBounds(*loc)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::{Aarch64, MAX_REGISTER_COUNT, X86_64};
+    use crate::dwarf::address_space::SliceAddressSpace;
+    use crate::dwarf::cfi::CfiEntry;
+    use crate::dwarf::cfi::Entries;
+    use crate::dwarf::testing::EhFrameBuilder;
+    use crate::registers::{GenericRegisters, UNW_ARM64_RA_SIGN_STATE};
+
+    /// The CFI in these tests uses x86_64 register numbers.
+    type Registers = GenericRegisters<X86_64>;
+
+    type Info = PrologInfo<X86_64, MAX_REGISTER_COUNT>;
+
+    fn run_fde(cie_instructions: &[u8], fde_instructions: &[u8], pc: u64) -> Result<Info, DwarfError> {
+        run_fde_with_depth::<Registers, MAX_REMEMBER_DEPTH>(cie_instructions, fde_instructions, pc)
+    }
+
+    fn run_fde_with_depth<R: RegisterFile, const D: usize>(
+        cie_instructions: &[u8],
+        fde_instructions: &[u8],
+        pc: u64,
+    ) -> Result<PrologInfo<R::Arch, MAX_REGISTER_COUNT>, DwarfError> {
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 1, -8, 16, cie_instructions);
+        b.fde(cie, 0x1000, 0x100, fde_instructions);
+        let space = SliceAddressSpace::new(0x10000, &b.data);
+        let mut entries = Entries::new(&space, space.start(), space.end());
+        let _ = entries.next().transpose()?;
+        match entries.next().transpose()? {
+            Some(CfiEntry::FdeCie((fde, cie))) => run_with_depth::<_, R, MAX_REGISTER_COUNT, D>(&space, pc, &fde, &cie),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        // DW_CFA_def_cfa: r7 ofs 8; DW_CFA_offset: r16 at cfa-8
+        let cie = [DW_CFA_DEF_CFA, 7, 8, DW_CFA_OFFSET | 16, 1];
+        // DW_CFA_advance_loc: 1; DW_CFA_def_cfa_offset: 16; DW_CFA_offset: r6 at cfa-16
+        let fde = [DW_CFA_ADVANCE_LOC | 1, DW_CFA_DEF_CFA_OFFSET, 16, DW_CFA_OFFSET | 6, 2];
+        let info = run_fde(&cie, &fde, 0x1000).unwrap();
+        assert_eq!(info.cfa_register, 7);
+        assert_eq!(info.cfa_register_offset, 8);
+        assert_eq!(info.saved_registers.get(16).location, RegisterSavedWhere::InCFA);
+        assert_eq!(info.saved_registers.get(16).value, -8);
+        assert_eq!(info.saved_registers.get(6).location, RegisterSavedWhere::Unused);
+        let info = run_fde(&cie, &fde, 0x1002).unwrap();
+        assert_eq!(info.cfa_register_offset, 16);
+        assert_eq!(info.saved_registers.get(6).location, RegisterSavedWhere::InCFA);
+        assert_eq!(info.saved_registers.get(6).value, -16);
+
+        // DW_CFA_GNU_args_size: 16
+        let info = run_fde(&cie, &[DW_CFA_GNU_ARGS_SIZE, 16], 0x1001).unwrap();
+        assert_eq!(info.sp_extra_arg_size, 16);
+    }
+
+    #[test]
+    fn test_register_rules() {
+        let loc = |location, value| RegisterLocation { location, value };
+        const N: usize = X86_64::REGISTER_COUNT;
+        let mut rules = RegisterRules::<X86_64, N>::default();
+        assert_eq!(rules.get(6), RegisterLocation::default());
+        assert_eq!(rules.iter().count(), 0);
+
+        // Clearing a register without a rule takes no entry.
+        rules.set(3, RegisterLocation::default());
+        assert_eq!(rules.len, 0);
+
+        rules.set(16, loc(RegisterSavedWhere::InCFA, -8));
+        rules.set(6, loc(RegisterSavedWhere::InCFA, -16));
+        rules.set(16, loc(RegisterSavedWhere::InRegister, 6));
+        assert_eq!(rules.len, 2);
+        assert_eq!(rules.get(16), loc(RegisterSavedWhere::InRegister, 6));
+        assert_eq!(
+            rules.iter().collect::<Vec<_>>(),
+            vec![
+                (16, loc(RegisterSavedWhere::InRegister, 6)),
+                (6, loc(RegisterSavedWhere::InCFA, -16)),
+            ]
+        );
+
+        // Cleared rules are skipped.
+        rules.set(6, RegisterLocation::default());
+        assert_eq!(rules.iter().map(|(r, _)| r).collect::<Vec<_>>(), vec![16]);
+
+        // Every register of the architecture fits.
+        for r in 0..N {
+            rules.set(r, loc(RegisterSavedWhere::Undefined, r as i64));
+        }
+        assert_eq!(rules.iter().count(), N);
+        assert_eq!(rules.get(N - 1).value, N as i64 - 1);
+
+        // Registers outside of the architecture are rejected by the CFI parser.
+        assert_eq!(
+            run_fde(&[DW_CFA_UNDEFINED, N as u8], &[], 0x1000).unwrap_err(),
+            DwarfError::InvalidRegisterNumber(N)
+        );
+        // Including the gaps in the numbering.
+        let run_aarch64 = |cie: &[u8]| run_fde_with_depth::<GenericRegisters<Aarch64>, 1>(cie, &[], 0x1000);
+        assert!(run_aarch64(&[DW_CFA_UNDEFINED, 34, DW_CFA_UNDEFINED, 64]).is_ok());
+        assert_eq!(
+            run_aarch64(&[DW_CFA_UNDEFINED, 40]).unwrap_err(),
+            DwarfError::InvalidRegisterNumber(40)
+        );
+    }
+
+    #[test]
+    fn test_remember_state() {
+        // DW_CFA_def_cfa: r7 ofs 8; DW_CFA_offset: r16 at cfa-8
+        let cie = [DW_CFA_DEF_CFA, 7, 8, DW_CFA_OFFSET | 16, 1];
+        // A function with a frame pointer and two epilogues, the first one is
+        // nested in another remembered state.
+        let fde = [
+            // 0x1001: DW_CFA_def_cfa_offset: 16; DW_CFA_offset: r6 at cfa-16
+            DW_CFA_ADVANCE_LOC | 1,
+            DW_CFA_DEF_CFA_OFFSET,
+            16,
+            DW_CFA_OFFSET | 6,
+            2,
+            // 0x1004: DW_CFA_def_cfa_register: r6
+            DW_CFA_ADVANCE_LOC | 3,
+            DW_CFA_DEF_CFA_REGISTER,
+            6,
+            // 0x1008: DW_CFA_remember_state; DW_CFA_offset: r3 at cfa-24
+            DW_CFA_ADVANCE_LOC | 4,
+            DW_CFA_REMEMBER_STATE,
+            DW_CFA_OFFSET | 3,
+            3,
+            // 0x100a: DW_CFA_remember_state; DW_CFA_def_cfa: r7 ofs 8; DW_CFA_restore: r6
+            DW_CFA_ADVANCE_LOC | 2,
+            DW_CFA_REMEMBER_STATE,
+            DW_CFA_DEF_CFA,
+            7,
+            8,
+            DW_CFA_RESTORE | 6,
+            // 0x100c: DW_CFA_restore_state
+            DW_CFA_ADVANCE_LOC | 2,
+            DW_CFA_RESTORE_STATE,
+            // 0x100e: DW_CFA_restore_state
+            DW_CFA_ADVANCE_LOC | 2,
+            DW_CFA_RESTORE_STATE,
+            // 0x1010: DW_CFA_remember_state; DW_CFA_def_cfa: r7 ofs 8
+            DW_CFA_ADVANCE_LOC | 2,
+            DW_CFA_REMEMBER_STATE,
+            DW_CFA_DEF_CFA,
+            7,
+            8,
+            // 0x1012: DW_CFA_restore_state
+            DW_CFA_ADVANCE_LOC | 2,
+            DW_CFA_RESTORE_STATE,
+        ];
+        // (pc, cfa register, cfa offset, r6 saved, r3 saved)
+        let rows = [
+            (0x1000, 7, 8, false, false),
+            (0x1001, 7, 16, true, false),
+            (0x1004, 6, 16, true, false),
+            (0x1008, 6, 16, true, true),
+            (0x100a, 7, 8, false, true),
+            (0x100c, 6, 16, true, true),
+            (0x100e, 6, 16, true, false),
+            (0x1010, 7, 8, true, false),
+            (0x1012, 6, 16, true, false),
+        ];
+        for (pc, cfa_register, cfa_offset, r6, r3) in rows {
+            let info = run_fde(&cie, &fde, pc).unwrap();
+            assert_eq!(info.cfa_register, cfa_register, "pc: {:#x}", pc);
+            assert_eq!(info.cfa_register_offset, cfa_offset, "pc: {:#x}", pc);
+            assert_eq!(
+                info.saved_registers.get(6).location != RegisterSavedWhere::Unused,
+                r6,
+                "pc: {:#x}",
+                pc
+            );
+            assert_eq!(
+                info.saved_registers.get(3).location != RegisterSavedWhere::Unused,
+                r3,
+                "pc: {:#x}",
+                pc
+            );
+            assert_eq!(
+                info.saved_registers.get(16).location,
+                RegisterSavedWhere::InCFA,
+                "pc: {:#x}",
+                pc
+            );
+        }
+    }
+
+    #[test]
+    fn test_remember_state_depth() {
+        let nested = |depth: usize| {
+            let mut fde = vec![DW_CFA_REMEMBER_STATE; depth];
+            fde.extend(vec![DW_CFA_RESTORE_STATE; depth]);
+            fde
+        };
+        assert!(run_fde(&[], &nested(MAX_REMEMBER_DEPTH), 0x1001).is_ok());
+        assert_eq!(
+            run_fde(&[], &nested(MAX_REMEMBER_DEPTH + 1), 0x1001).unwrap_err(),
+            DwarfError::RememberStackOverflow(MAX_REMEMBER_DEPTH)
+        );
+        assert!(run_fde_with_depth::<Registers, 2>(&[], &nested(2), 0x1001).is_ok());
+        assert_eq!(
+            run_fde_with_depth::<Registers, 2>(&[], &nested(3), 0x1001).unwrap_err(),
+            DwarfError::RememberStackOverflow(2)
+        );
+
+        // Restore without remember.
+        assert_eq!(
+            run_fde(
+                &[],
+                &[DW_CFA_REMEMBER_STATE, DW_CFA_RESTORE_STATE, DW_CFA_RESTORE_STATE],
+                0x1001
+            )
+            .unwrap_err(),
+            DwarfError::NoRememberState
+        );
+
+        // The CIE and the FDE have separate stacks.
+        assert_eq!(
+            run_fde(&[DW_CFA_REMEMBER_STATE], &[DW_CFA_RESTORE_STATE], 0x1001).unwrap_err(),
+            DwarfError::NoRememberState
+        );
+    }
+
+    #[test]
+    fn test_run_malformed() {
+        // Truncated operands.
+        assert!(matches!(
+            run_fde(&[DW_CFA_DEF_CFA, 7], &[], 0x1000),
+            Err(DwarfError::TruncatedUleb128Expression(_))
+        ));
+        assert!(matches!(
+            run_fde(&[DW_CFA_ADVANCE_LOC4, 1], &[], 0x1000),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+
+        // Register numbers out of range.
+        assert!(matches!(
+            run_fde(&[DW_CFA_UNDEFINED, 0x9f, 0x02], &[], 0x1000),
+            Err(DwarfError::InvalidRegisterNumber(287))
+        ));
+
+        // Expression longer than the instructions.
+        assert!(matches!(
+            run_fde(&[DW_CFA_DEF_CFA_EXPRESSION, 0x10, DW_OP_LIT0], &[], 0x1000),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+        assert!(matches!(
+            run_fde(&[DW_CFA_EXPRESSION, 6, 0x7f], &[], 0x1000),
+            Err(DwarfError::OutOfBounds(_))
+        ));
+
+        // Unknown opcode.
+        assert!(matches!(
+            run_fde(&[0x3f], &[], 0x1000),
+            Err(DwarfError::InvalidInstruction(_))
+        ));
+        // DW_CFA_AARCH64_negate_ra_state is only known on aarch64.
+        assert!(matches!(
+            run_fde(&[DW_CFA_AARCH64_NEGATE_RA_STATE], &[], 0x1000),
+            Err(DwarfError::InvalidInstruction(_))
+        ));
+        let info = run_fde_with_depth::<GenericRegisters<Aarch64>, 1>(&[DW_CFA_AARCH64_NEGATE_RA_STATE], &[], 0x1000);
+        assert_eq!(info.unwrap().saved_registers.get(UNW_ARM64_RA_SIGN_STATE).value, 1);
+    }
+
+    /// Run `fde_instructions` and returns the memory the rules refer to: the
+    /// .eh_frame section at 0x10000 and `stack` at 0x11000.
+    #[cfg(feature = "dwarf-expression")]
+    fn run_rule(fde_instructions: &[u8], stack: u128) -> (Vec<u8>, Info) {
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 1, -8, 16, &[DW_CFA_DEF_CFA, 7, 16]);
+        b.fde(cie, 0x1000, 0x100, fde_instructions);
+        let mut mem = b.data.clone();
+        mem.resize(0x1000, 0);
+        mem.extend_from_slice(&stack.to_ne_bytes());
+        let space = SliceAddressSpace::new(0x10000, &mem);
+        let mut entries = Entries::new(&space, space.start(), space.start() + b.data.len() as u64);
+        let _ = entries.next().transpose().unwrap();
+        let info = match entries.next().transpose().unwrap() {
+            Some(CfiEntry::FdeCie((fde, cie))) => {
+                run::<_, Registers, MAX_REGISTER_COUNT>(&space, 0x1001, &fde, &cie).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        (mem, info)
+    }
+
+    #[test]
+    #[cfg(feature = "dwarf-expression")]
+    fn test_float_and_vector_rules() {
+        // The stack slot is at CFA-16.
+        const CFA: u64 = 0x11010;
+        let stack = 0x0123456789abcdef_4000000000000000u128;
+        let mut registers = Registers::default();
+        registers[3] = 3f64.to_bits();
+        let cases: Vec<(&str, Vec<u8>, u128)> = vec![
+            ("offset", vec![DW_CFA_OFFSET_EXTENDED, 17, 2], stack),
+            ("val_offset", vec![DW_CFA_VAL_OFFSET, 17, 2], (CFA - 16) as u128),
+            ("register", vec![DW_CFA_REGISTER, 17, 3], 3f64.to_bits() as u128),
+            (
+                "expression",
+                vec![DW_CFA_EXPRESSION, 17, 2, DW_OP_LIT16, DW_OP_MINUS],
+                stack,
+            ),
+            ("val_expression", vec![DW_CFA_VAL_EXPRESSION, 17, 1, DW_OP_LIT5], 5),
+            ("undefined", vec![DW_CFA_UNDEFINED, 17], 0),
+        ];
+        for (name, instructions, expected) in cases {
+            let (mem, info) = run_rule(&instructions, stack);
+            let space = SliceAddressSpace::new(0x10000, &mem);
+            let loc = info.saved_registers.get(17);
+            let v = get_saved_vector_register(&space, &registers, loc, CFA);
+            assert_eq!(v, Ok(expected), "vector case: {}", name);
+            let v = get_saved_float_register(&space, &registers, loc, CFA).map(f64::to_bits);
+            assert_eq!(v, Ok(expected as u64), "float case: {}", name);
+        }
+
+        // Neither a general nor a floating-point/vector register.
+        let space = SliceAddressSpace::new(0x10000, &[]);
+        let loc = RegisterLocation {
+            location: RegisterSavedWhere::InRegister,
+            value: 256,
+        };
+        assert_eq!(
+            get_saved_float_register(&space, &registers, loc, CFA),
+            Err(DwarfError::InvalidRegisterNumber(256))
+        );
+        assert_eq!(
+            get_saved_vector_register(&space, &registers, loc, CFA),
+            Err(DwarfError::InvalidRegisterNumber(256))
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Parser of the line number programs of .debug_line, of versions 2 to 5.
+
+use crate::dwarf::consts::*;
+use crate::dwarf::debug_info::{read_value, Format, Reader, UnitReader};
+use crate::dwarf::DwarfError;
+
+/// A row of the line number matrix.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(super) struct Row {
+    pub address: u64,
+    pub file: u64,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl Row {
+    #[inline]
+    fn new() -> Self {
+        Self {
+            address: 0,
+            file: 1,
+            line: 1,
+            column: 0,
+        }
+    }
+}
+
+/// The header of a line number program, and a reader of its opcodes.
+pub(super) struct LineProgram<'a> {
+    format: Format,
+    minimum_instruction_length: u8,
+    line_base: i8,
+    line_range: u8,
+    opcode_base: u8,
+    standard_opcode_lengths: &'a [u8],
+    directories: Vec<&'a [u8]>,
+    /// Paths and directory indexes.
+    files: Vec<(&'a [u8], u64)>,
+    program: Reader<'a>,
+}
+
+impl<'a> LineProgram<'a> {
+    /// Parses the header of the program at `offset` in the .debug_line of
+    /// `unit`, whose strings are read with those of the unit.
+    pub fn parse(unit: &UnitReader<'a>, offset: u64) -> Result<Self, DwarfError> {
+        let mut r = Reader::new(unit.sections.line, offset);
+        let offset_size = r.unit_length()?;
+        let version = r.u16()?;
+        if !(2..=5).contains(&version) {
+            return Err(DwarfError::InvalidLineVersion(version));
+        }
+        let mut address_size = unit.header.format.address_size;
+        if version >= 5 {
+            address_size = r.u8()?;
+            let _segment_selector_size = r.u8()?;
+        }
+        let header_length = r.sized(offset_size)?;
+        let mut program = r;
+        program.skip(header_length)?;
+        let minimum_instruction_length = r.u8()?;
+        if version >= 4 {
+            let _maximum_operations_per_instruction = r.u8()?;
+        }
+        let _default_is_stmt = r.u8()?;
+        let line_base = r.u8()? as i8;
+        let line_range = r.u8()?;
+        if line_range == 0 {
+            return Err(DwarfError::InvalidLineRange);
+        }
+        let opcode_base = r.u8()?;
+        let standard_opcode_lengths = r.bytes(u64::from(opcode_base.saturating_sub(1)))?;
+        let format = Format {
+            version,
+            offset_size,
+            address_size,
+        };
+
+        let mut directories = vec![];
+        let mut files = vec![];
+        if version >= 5 {
+            directories.extend(entries(unit, &mut r, &format)?.into_iter().map(|(path, _)| path));
+            files = entries(unit, &mut r, &format)?;
+        } else {
+            loop {
+                let directory = r.c_str()?;
+                if directory.is_empty() {
+                    break;
+                }
+                directories.push(directory);
+            }
+            loop {
+                let path = r.c_str()?;
+                if path.is_empty() {
+                    break;
+                }
+                let directory = r.uleb()?;
+                // The modification time and length.
+                r.uleb()?;
+                r.uleb()?;
+                files.push((path, directory));
+            }
+        }
+        Ok(Self {
+            format,
+            minimum_instruction_length,
+            line_base,
+            line_range,
+            opcode_base,
+            standard_opcode_lengths,
+            directories,
+            files,
+            program,
+        })
+    }
+
+    /// Returns the path of a file, joined to its directory and `comp_dir`
+    /// if relative.
+    pub fn file(&self, index: u64, comp_dir: Option<&[u8]>) -> Option<Vec<u8>> {
+        // Files and directories are numbered from 1 before version 5, the
+        // directory 0 being the compilation directory.
+        let (path, directory) = match self.format.version {
+            5 => *self.files.get(index as usize)?,
+            _ => *self.files.get((index as usize).checked_sub(1)?)?,
+        };
+        let directory = match self.format.version {
+            5 => self.directories.get(directory as usize).copied(),
+            _ => (directory as usize)
+                .checked_sub(1)
+                .and_then(|i| self.directories.get(i).copied()),
+        };
+        let mut joined = vec![];
+        for component in [comp_dir, directory, Some(path)].into_iter().flatten() {
+            if component.first() == Some(&b'/') {
+                joined.clear();
+            } else if !joined.is_empty() && joined.last() != Some(&b'/') {
+                joined.push(b'/');
+            }
+            joined.extend_from_slice(component);
+        }
+        Some(joined)
+    }
+
+    /// Runs the program up to the row of `pc`, the last one at or before it
+    /// in its sequence.
+    pub fn find_row(&self, pc: u64) -> Result<Option<Row>, DwarfError> {
+        let mut r = self.program;
+        let min_length = u64::from(self.minimum_instruction_length);
+        let mut row = Row::new();
+        let mut prev: Option<Row> = None;
+        while r.loc < r.end {
+            let opcode = r.u8()?;
+            let end_sequence = if opcode >= self.opcode_base {
+                let adjusted = opcode - self.opcode_base;
+                row.address = row
+                    .address
+                    .wrapping_add(u64::from(adjusted / self.line_range) * min_length);
+                let advance = i64::from(self.line_base) + i64::from(adjusted % self.line_range);
+                row.line = row.line.wrapping_add(advance as u64);
+                false
+            } else {
+                match opcode {
+                    0 => {
+                        let len = r.uleb()?;
+                        let mut extended = r;
+                        r.skip(len)?;
+                        extended.end = r.loc;
+                        match extended.u8()? {
+                            DW_LNE_END_SEQUENCE => true,
+                            DW_LNE_SET_ADDRESS => {
+                                row.address = extended.sized(self.format.address_size)?;
+                                continue;
+                            }
+                            // DW_LNE_define_file is obsolete, other opcodes
+                            // don't change the rows of interest.
+                            _ => continue,
+                        }
+                    }
+                    DW_LNS_COPY => false,
+                    DW_LNS_ADVANCE_PC => {
+                        row.address = row.address.wrapping_add(r.uleb()?.wrapping_mul(min_length));
+                        continue;
+                    }
+                    DW_LNS_ADVANCE_LINE => {
+                        row.line = row.line.wrapping_add(r.sleb()? as u64);
+                        continue;
+                    }
+                    DW_LNS_SET_FILE => {
+                        row.file = r.uleb()?;
+                        continue;
+                    }
+                    DW_LNS_SET_COLUMN => {
+                        row.column = r.uleb()?;
+                        continue;
+                    }
+                    DW_LNS_CONST_ADD_PC => {
+                        let adjusted = 255 - self.opcode_base;
+                        row.address = row
+                            .address
+                            .wrapping_add(u64::from(adjusted / self.line_range) * min_length);
+                        continue;
+                    }
+                    DW_LNS_FIXED_ADVANCE_PC => {
+                        row.address = row.address.wrapping_add(u64::from(r.u16()?));
+                        continue;
+                    }
+                    _ => {
+                        // Flags, or opcodes unknown to us, skipped by their
+                        // number of ULEB128 operands.
+                        let operands = self.standard_opcode_lengths[opcode as usize - 1];
+                        for _ in 0..operands {
+                            r.uleb()?;
+                        }
+                        continue;
+                    }
+                }
+            };
+            if let Some(prev) = prev {
+                if prev.address <= pc && pc < row.address {
+                    return Ok(Some(prev));
+                }
+            }
+            prev = if end_sequence {
+                row = Row::new();
+                None
+            } else {
+                Some(row)
+            };
+        }
+        Ok(None)
+    }
+}
+
+/// Reads the directory or file name entries of a version 5 header.
+fn entries<'a>(unit: &UnitReader<'a>, r: &mut Reader<'a>, format: &Format) -> Result<Vec<(&'a [u8], u64)>, DwarfError> {
+    let mut formats = vec![];
+    for _ in 0..r.u8()? {
+        formats.push((r.uleb()?, r.uleb()?));
+    }
+    let mut entries = vec![];
+    for _ in 0..r.uleb()? {
+        let mut entry: (&[u8], u64) = (b"", 0);
+        for &(content, form) in &formats {
+            let value = read_value(r, form, 0, format)?;
+            match content {
+                DW_LNCT_PATH => entry.0 = unit.string(value)?.unwrap_or_default(),
+                DW_LNCT_DIRECTORY_INDEX => entry.1 = value.constant().unwrap_or(0),
+                _ => {}
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
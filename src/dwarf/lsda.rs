@@ -0,0 +1,433 @@
+use crate::dwarf::address_space::AddressSpace;
+use crate::dwarf::consts::*;
+use crate::dwarf::encoding::*;
+use crate::dwarf::DwarfError;
+
+/// The language-specific data area of a function, which its FDE points at
+/// and its personality routine interprets. GCC, LLVM and rustc emit it in
+/// .gcc_except_table with the layout:
+/// ```text
+/// lpstart_enc:     u8
+/// lpstart:         encoded              // if lpstart_enc != DW_EH_PE_omit
+/// ttype_enc:       u8
+/// ttype_offset:    uleb128              // if ttype_enc != DW_EH_PE_omit
+/// call_site_enc:   u8
+/// call_site_len:   uleb128
+/// call_site_table: [call_site]          // sorted by start
+/// action_table:    [(sleb128, sleb128)] // type filter, offset to next action
+/// type_table:      [encoded]            // indexed backwards from its end
+/// ```
+///
+/// Each call site records the range of the function it covers, relative to
+/// the start of the function, its landing pad, relative to `lpstart`, and the
+/// first of its chain of actions, see [Action].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Lsda {
+    /// Address of the first instruction of the function.
+    pub start_ip: u64,
+    /// Base address of landing pads, the start of the function by default.
+    pub lpstart: u64,
+    /// Encoding of the type table entries.
+    pub ttype_enc: u8,
+    /// End of the type table, 0 if there is none.
+    pub ttype_base: u64,
+    /// Encoding of the call site table entries.
+    pub call_site_enc: u8,
+    /// Address of the call site table.
+    pub call_site_table: u64,
+    /// Address of the action table, right after the call site table.
+    pub action_table: u64,
+    /// Nothing beyond this address is read.
+    pub end: u64,
+}
+
+/// An entry of the call site table.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct CallSite {
+    /// Address of the first instruction covered.
+    pub start: u64,
+    /// Number of bytes covered.
+    pub len: u64,
+    /// Address of the landing pad, 0 if exceptions are not caught here.
+    pub landing_pad: u64,
+    /// One plus the offset of the first action in the action table, 0 if
+    /// the landing pad only runs cleanups.
+    pub action: u64,
+}
+
+impl CallSite {
+    /// Determine whether the target address is covered by the call site.
+    #[inline]
+    pub fn contains(&self, target: u64) -> bool {
+        self.start <= target && target - self.start < self.len
+    }
+}
+
+/// What the landing pad of a call site does with an exception, an entry of
+/// the action table.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+    /// Runs cleanups, e.g. destructors or `Drop` implementations, and resumes
+    /// unwinding.
+    Cleanup,
+    /// Catches exceptions of the type described by the type info at this
+    /// address, 0 for catch-all.
+    Catch(u64),
+    /// Catches exceptions whose type is not in an exception specification,
+    /// a list of uleb128 type table indices terminated by 0 at this address.
+    Filter(u64),
+}
+
+/// The landing pad of a call site, summarizing its actions.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct LandingPad {
+    /// Address of the landing pad.
+    pub address: u64,
+    /// Whether it runs cleanups.
+    pub cleanup: bool,
+    /// Whether it catches some exceptions.
+    pub catch: bool,
+    /// Whether it checks an exception specification.
+    pub filter: bool,
+}
+
+impl Lsda {
+    /// Parse the LSDA at `start` of the function starting at `start_ip`.
+    ///
+    /// The size of the LSDA is not recorded anywhere, `end` is an upper bound
+    /// of it, e.g. the end of the object, nothing beyond it will be read.
+    pub fn decode<A: AddressSpace>(a: &A, start: u64, start_ip: u64, end: u64) -> Result<Self, DwarfError> {
+        let mut loc = start;
+        let lpstart_enc = read::<A, u8>(a, &mut loc, end)?;
+        let lpstart = if lpstart_enc != DW_EH_PE_OMIT {
+            decode_pointer(a, &mut loc, end, lpstart_enc, 0)?
+        } else {
+            start_ip
+        };
+        let ttype_enc = read::<A, u8>(a, &mut loc, end)?;
+        let ttype_base = if ttype_enc != DW_EH_PE_OMIT {
+            let offset = decode_uleb128(a, &mut loc, end)?;
+            loc.checked_add(offset).ok_or(DwarfError::OutOfBounds(loc))?
+        } else {
+            0
+        };
+        let call_site_enc = read::<A, u8>(a, &mut loc, end)?;
+        let call_site_len = decode_uleb128(a, &mut loc, end)?;
+        let action_table = match loc.checked_add(call_site_len) {
+            Some(v) if v <= end => v,
+            _ => return Err(DwarfError::OutOfBounds(loc)),
+        };
+        Ok(Self {
+            start_ip,
+            lpstart,
+            ttype_enc,
+            ttype_base,
+            call_site_enc,
+            call_site_table: loc,
+            action_table,
+            end,
+        })
+    }
+
+    /// Returns the entries of the call site table.
+    pub fn call_sites<'a, A: AddressSpace>(&self, a: &'a A) -> CallSites<'a, A> {
+        CallSites {
+            a,
+            lsda: *self,
+            loc: self.call_site_table,
+        }
+    }
+
+    /// Returns the call site covering `pc`, or `None` if there is none, in
+    /// which case exceptions must not unwind through `pc`.
+    ///
+    /// `pc` is looked up as is, like in [step_eh_frame](crate::step_eh_frame).
+    pub fn call_site<A: AddressSpace>(&self, a: &A, pc: u64) -> Result<Option<CallSite>, DwarfError> {
+        for call_site in self.call_sites(a) {
+            let call_site = call_site?;
+            if call_site.contains(pc) {
+                return Ok(Some(call_site));
+            }
+            if call_site.start > pc {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the actions of the landing pad of `call_site`, in the order
+    /// the personality routine tries them.
+    pub fn actions<'a, A: AddressSpace>(&self, a: &'a A, call_site: &CallSite) -> Actions<'a, A> {
+        let loc = match call_site.action {
+            0 => None,
+            action => Some(self.action_table.wrapping_add(action - 1)),
+        };
+        Actions {
+            a,
+            lsda: *self,
+            loc,
+            cleanup_only: call_site.action == 0 && call_site.landing_pad != 0,
+            // Actions take at least 2 bytes.
+            remaining: self.end.saturating_sub(self.action_table) / 2,
+        }
+    }
+
+    /// Returns the landing pad exceptions unwinding through `pc` go to, or
+    /// `None` if there is none.
+    ///
+    /// `pc` is looked up as is, like in [step_eh_frame](crate::step_eh_frame).
+    pub fn landing_pad<A: AddressSpace>(&self, a: &A, pc: u64) -> Result<Option<LandingPad>, DwarfError> {
+        let call_site = match self.call_site(a, pc)? {
+            Some(v) if v.landing_pad != 0 => v,
+            _ => return Ok(None),
+        };
+        let mut landing_pad = LandingPad {
+            address: call_site.landing_pad,
+            ..Default::default()
+        };
+        for action in self.actions(a, &call_site) {
+            match action? {
+                Action::Cleanup => landing_pad.cleanup = true,
+                Action::Catch(_) => landing_pad.catch = true,
+                Action::Filter(_) => landing_pad.filter = true,
+            }
+        }
+        Ok(Some(landing_pad))
+    }
+
+    /// Returns the size of the type table entries.
+    fn ttype_size<A: AddressSpace>(&self, a: &A) -> Result<u64, DwarfError> {
+        match self.ttype_enc & 0x0f {
+            DW_EH_PE_PTR => Ok(a.pointer_size() as u64),
+            DW_EH_PE_UDATA2 | DW_EH_PE_SDATA2 => Ok(2),
+            DW_EH_PE_UDATA4 | DW_EH_PE_SDATA4 => Ok(4),
+            DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => Ok(8),
+            _ => Err(DwarfError::InvalidPointerEncodingSize(self.ttype_enc)),
+        }
+    }
+}
+
+/// Iterator over the call site table of an [Lsda], which stops after the
+/// first error.
+pub struct CallSites<'a, A: AddressSpace> {
+    a: &'a A,
+    lsda: Lsda,
+    loc: u64,
+}
+
+impl<'a, A: AddressSpace> CallSites<'a, A> {
+    fn read(&mut self) -> Result<CallSite, DwarfError> {
+        let a = self.a;
+        let lsda = &self.lsda;
+        let end = lsda.action_table;
+        let start = decode_pointer(a, &mut self.loc, end, lsda.call_site_enc, 0)?;
+        let len = decode_pointer(a, &mut self.loc, end, lsda.call_site_enc, 0)?;
+        let landing_pad = decode_pointer(a, &mut self.loc, end, lsda.call_site_enc, 0)?;
+        let action = decode_uleb128(a, &mut self.loc, end)?;
+        Ok(CallSite {
+            start: lsda.start_ip.wrapping_add(start),
+            len,
+            landing_pad: if landing_pad != 0 {
+                lsda.lpstart.wrapping_add(landing_pad)
+            } else {
+                0
+            },
+            action,
+        })
+    }
+}
+
+impl<'a, A: AddressSpace> Iterator for CallSites<'a, A> {
+    type Item = Result<CallSite, DwarfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.loc >= self.lsda.action_table {
+            return None;
+        }
+        let call_site = self.read();
+        if call_site.is_err() {
+            self.loc = self.lsda.action_table;
+        }
+        Some(call_site)
+    }
+}
+
+/// Iterator over the chain of actions of a [CallSite], which stops after
+/// the first error.
+pub struct Actions<'a, A: AddressSpace> {
+    a: &'a A,
+    lsda: Lsda,
+    loc: Option<u64>,
+    cleanup_only: bool,
+    // Upper bound of the length of the chain, which malformed data can make
+    // loop.
+    remaining: u64,
+}
+
+impl<'a, A: AddressSpace> Actions<'a, A> {
+    fn read(&mut self, mut loc: u64) -> Result<Action, DwarfError> {
+        let a = self.a;
+        let lsda = &self.lsda;
+        if self.remaining == 0 {
+            return Err(DwarfError::TooManyActions);
+        }
+        self.remaining -= 1;
+        let filter = decode_sleb128(a, &mut loc, lsda.end)?;
+        let next = loc;
+        let offset = decode_sleb128(a, &mut loc, lsda.end)?;
+        self.loc = match offset {
+            0 => None,
+            offset => Some(next.wrapping_add(offset as u64)),
+        };
+        match filter {
+            0 => Ok(Action::Cleanup),
+            _ if lsda.ttype_base == 0 => Err(DwarfError::InvalidTypeFilter(filter)),
+            filter if filter > 0 => {
+                let size = lsda.ttype_size(a)?;
+                let mut entry = (filter as u64)
+                    .checked_mul(size)
+                    .and_then(|v| lsda.ttype_base.checked_sub(v))
+                    .ok_or(DwarfError::InvalidTypeFilter(filter))?;
+                let type_info = decode_pointer(a, &mut entry, lsda.ttype_base, lsda.ttype_enc, 0)?;
+                Ok(Action::Catch(type_info))
+            }
+            filter => Ok(Action::Filter(lsda.ttype_base.wrapping_add(filter.unsigned_abs() - 1))),
+        }
+    }
+}
+
+impl<'a, A: AddressSpace> Iterator for Actions<'a, A> {
+    type Item = Result<Action, DwarfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cleanup_only {
+            self.cleanup_only = false;
+            return Some(Ok(Action::Cleanup));
+        }
+        let loc = self.loc.take()?;
+        Some(self.read(loc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::address_space::SliceAddressSpace;
+
+    /// .gcc_except_table of a non-PIE x86_64 executable built by g++ -O1 from:
+    /// ```text
+    /// int g() {
+    ///     try {
+    ///         Guard guard;
+    ///         f();
+    ///     } catch (int) {
+    ///         return 1;
+    ///     } catch (const char *) {
+    ///         return 2;
+    ///     }
+    ///     return 0;
+    /// }
+    /// ```
+    const GXX_LSDA: [u8; 28] = [
+        0xff, 0x03, 0x19, 0x01, 0x08, 0x06, 0x05, 0x0b, 0x05, 0x2a, 0x05, 0x00, 0x00, 0x02, 0x00, 0x01, 0x7d, 0x00,
+        0x7d, 0x00, 0xc8, 0x3d, 0x40, 0x00, 0xb8, 0x3d, 0x40, 0x00,
+    ];
+    const GXX_LSDA_ADDRESS: u64 = 0x40215c;
+    const GXX_G: u64 = 0x40119d;
+    const GXX_TYPEINFO_INT: u64 = 0x403db8;
+    const GXX_TYPEINFO_CONST_CHAR_P: u64 = 0x403dc8;
+
+    fn actions<A: AddressSpace>(a: &A, lsda: &Lsda, call_site: &CallSite) -> Vec<Action> {
+        lsda.actions(a, call_site).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_gxx_lsda() {
+        let a = SliceAddressSpace::new(GXX_LSDA_ADDRESS, &GXX_LSDA);
+        let lsda = Lsda::decode(&a, a.start(), GXX_G, a.end()).unwrap();
+        assert_eq!(lsda.lpstart, GXX_G);
+        assert_eq!(lsda.ttype_base, a.end());
+
+        let mut call_sites = lsda.call_sites(&a);
+        let first = call_sites.next().unwrap().unwrap();
+        assert_eq!(
+            first,
+            CallSite {
+                start: GXX_G + 6,
+                len: 5,
+                landing_pad: GXX_G + 0xb,
+                action: 5,
+            }
+        );
+        let second = call_sites.next().unwrap().unwrap();
+        assert_eq!(second.start, GXX_G + 0x2a);
+        assert_eq!(second.landing_pad, 0);
+        assert!(call_sites.next().is_none());
+
+        // The destructor runs, then the catch clauses are tried in order.
+        assert_eq!(
+            actions(&a, &lsda, &first),
+            [
+                Action::Cleanup,
+                Action::Catch(GXX_TYPEINFO_INT),
+                Action::Catch(GXX_TYPEINFO_CONST_CHAR_P)
+            ]
+        );
+        assert_eq!(actions(&a, &lsda, &second), []);
+
+        assert_eq!(lsda.call_site(&a, GXX_G + 10).unwrap(), Some(first));
+        assert_eq!(lsda.call_site(&a, GXX_G + 11).unwrap(), None);
+        assert_eq!(
+            lsda.landing_pad(&a, GXX_G + 6).unwrap(),
+            Some(LandingPad {
+                address: GXX_G + 0xb,
+                cleanup: true,
+                catch: true,
+                filter: false,
+            })
+        );
+        assert_eq!(lsda.landing_pad(&a, GXX_G + 0x2a).unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_lsda() {
+        for len in 0..GXX_LSDA.len() {
+            let a = SliceAddressSpace::new(GXX_LSDA_ADDRESS, &GXX_LSDA[..len]);
+            let Ok(lsda) = Lsda::decode(&a, a.start(), GXX_G, a.end()) else {
+                continue;
+            };
+            for call_site in lsda.call_sites(&a).flatten() {
+                lsda.actions(&a, &call_site).for_each(drop);
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        // A dynamic exception specification, `throw(T)`: filter -1 at action
+        // 1, and its list of type indices after the type table.
+        let data = [
+            0xff, 0x00, 0x07, 0x01, 0x04, 0x00, 0x10, 0x20, 0x01, 0x7f, 0x00, 0x01, 0x00,
+        ];
+        let a = SliceAddressSpace::new(0x1000, &data);
+        let lsda = Lsda::decode(&a, a.start(), 0x2000, a.end()).unwrap();
+        assert_eq!(lsda.ttype_base, 0x100a);
+        let call_site = lsda.call_site(&a, 0x2000).unwrap().unwrap();
+        assert_eq!(call_site.landing_pad, 0x2020);
+        assert_eq!(actions(&a, &lsda, &call_site), [Action::Filter(0x100a)]);
+        let landing_pad = lsda.landing_pad(&a, 0x200f).unwrap().unwrap();
+        assert!(landing_pad.filter && !landing_pad.catch && !landing_pad.cleanup);
+    }
+
+    #[test]
+    fn test_action_loop() {
+        // A cleanup action whose next action is itself.
+        let data = [0xff, 0xff, 0x01, 0x04, 0x00, 0x10, 0x20, 0x01, 0x00, 0x7f];
+        let a = SliceAddressSpace::new(0x1000, &data);
+        let lsda = Lsda::decode(&a, a.start(), 0x2000, a.end()).unwrap();
+        let call_site = lsda.call_site(&a, 0x2000).unwrap().unwrap();
+        let actions: Vec<_> = lsda.actions(&a, &call_site).collect();
+        assert_eq!(actions, [Ok(Action::Cleanup), Err(DwarfError::TooManyActions)]);
+        assert_eq!(lsda.landing_pad(&a, 0x2000), Err(DwarfError::TooManyActions));
+    }
+}
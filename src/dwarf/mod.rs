@@ -1,22 +1,53 @@
+use crate::arch::{Arch, NativeArch, MAX_REGISTER_COUNT};
 use crate::dyld::SectionInfo;
-#[cfg(target_arch = "aarch64")]
-use crate::registers::UNW_ARM64_RA_SIGN_STATE;
-use crate::registers::{Registers, UNW_REG_IP, UNW_REG_SP};
+use crate::registers::{RegisterFile, Registers, UNW_REG_IP, UNW_REG_SP};
 #[cfg(feature = "mem-protect")]
 use crate::utils::can_access;
 use crate::utils::load;
+use address_space::LocalAddressSpace;
+pub use address_space::{AddressSpace, Primitive, SliceAddressSpace};
 use cfi::{CommonInformationEntry, FrameDescriptionEntry};
+#[cfg(feature = "symbolize")]
+use debug_frame::search_debug_frame;
+#[cfg(feature = "symbolize")]
+pub use debug_frame::{deregister_debug_frame, register_debug_frame, DebugFrame};
+#[cfg(feature = "symbolize")]
+pub use debug_info::{package_sections, DebugSections, SourceFrame, Unit, UnitIndex};
 use header::EhFrameHeader;
 use instruction::{get_saved_float_register, get_saved_register, get_saved_vector_register, RegisterSavedWhere};
+pub use lsda::{Action, Actions, CallSite, CallSites, LandingPad, Lsda};
 
+mod address_space;
 mod cfi;
-mod consts;
+pub(crate) mod consts;
+#[cfg(feature = "symbolize")]
+mod debug_frame;
+#[cfg(feature = "symbolize")]
+mod debug_info;
 mod encoding;
 mod expression;
 mod header;
 mod instruction;
+#[cfg(feature = "symbolize")]
+mod line;
+mod lsda;
+#[cfg(test)]
+pub(crate) mod testing;
+
+/// Parsers exposed to the fuzz targets in ../fuzz/, this is not a stable API.
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing {
+    pub use super::address_space::{AddressSpace, SliceAddressSpace};
+    pub use super::cfi::{CfiEntry, Entries};
+    #[cfg(feature = "symbolize")]
+    pub use super::debug_info::{package_sections, DebugSections, UnitIndex};
+    pub use super::header::EhFrameHeader;
+    pub use super::instruction::run;
+    #[cfg(all(feature = "symbolize", target_os = "linux"))]
+    pub use crate::symbolize::xz::decompress as xz_decompress;
+}
 
-#[derive(thiserror::Error, Debug, Copy, Clone)]
+#[derive(thiserror::Error, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum DwarfError {
     #[error("invalid .eh_frame_hdr version: {0}")]
     InvalidHeaderVersion(u8),
@@ -27,6 +58,9 @@ pub enum DwarfError {
     #[error("cie id is not zero")]
     CIEIdIsNotZero,
 
+    #[error("invalid .debug_frame cie id: {0:#x}")]
+    InvalidDebugFrameCieId(u64),
+
     #[error("invalid cie version: {0}")]
     CIEInvalidVersion(u8),
 
@@ -78,37 +112,250 @@ pub enum DwarfError {
     #[error("no remember state")]
     NoRememberState,
 
+    #[error("remember state stack overflow, max depth: {0}")]
+    RememberStackOverflow(usize),
+
     #[error("unreadable address: {0:#x}")]
     UnreadableAddress(u64),
 
-    #[error("unimplemented ra sign state")]
-    UnimplementedRaSignState,
-
     #[error("malformed uleb128 expression at: {0:#x}")]
     MalformedUleb128Expression(u64),
 
     #[error("truncated uleb128 expression at: {0:#x}")]
     TruncatedUleb128Expression(u64),
 
-    #[error("truncated uleb128 expression at: {0:#x}")]
+    #[error("truncated sleb128 expression at: {0:#x}")]
     TruncatedSleb128Expression(u64),
 
+    #[error("malformed sleb128 expression at: {0:#x}")]
+    MalformedSleb128Expression(u64),
+
+    #[error("out of bounds read at: {0:#x}")]
+    OutOfBounds(u64),
+
+    #[error("invalid cie pointer at: {0:#x}")]
+    InvalidCiePointer(u64),
+
     #[error("no way to calculate cfa")]
     NoWayToCalculateCfa,
 
     #[error("dwarf expression not implemented")]
     DwarfExpressionNotImplemented,
+
+    #[error("dwarf expression operation not allowed in CFI: {0:#x}")]
+    ExpressionNotAllowedInCfi(u8),
+
+    #[error("no thread-local storage for: {0:#x}")]
+    NoThreadLocalStorage(u64),
+
+    #[error("dwarf expression stack overflow")]
+    ExpressionStackOverflow,
+
+    #[error("dwarf expression stack underflow")]
+    ExpressionStackUnderflow,
+
+    #[error("dwarf expression division by zero")]
+    ExpressionDivisionByZero,
+
+    #[error("dwarf expression executed too many instructions")]
+    ExpressionTooManyInstructions,
+
+    #[error("invalid dwarf expression piece")]
+    InvalidExpressionPiece,
+
+    #[error("invalid unit version: {0}")]
+    InvalidUnitVersion(u16),
+
+    #[error("invalid address size: {0}")]
+    InvalidAddressSize(u8),
+
+    #[error("invalid abbreviation code: {0}")]
+    InvalidAbbreviationCode(u64),
+
+    #[error("invalid attribute form: {0:#x}")]
+    InvalidForm(u64),
+
+    #[error("invalid debug info reference: {0:#x}")]
+    InvalidReference(u64),
+
+    #[error("invalid range list entry: {0}")]
+    InvalidRangeListEntry(u8),
+
+    #[error("invalid line program version: {0}")]
+    InvalidLineVersion(u16),
+
+    #[error("zero line range")]
+    InvalidLineRange,
+
+    #[error("invalid package index version: {0}")]
+    InvalidPackageVersion(u32),
+
+    #[error("split unit not found")]
+    SplitUnitNotFound,
+
+    #[error("invalid lsda type filter: {0}")]
+    InvalidTypeFilter(i64),
+
+    #[error("too many lsda actions")]
+    TooManyActions,
+}
+
+/// Number of register rules kept when unwinding the current process, only
+/// the registers of its own architecture can have one.
+const NATIVE_REGISTER_COUNT: usize = NativeArch::REGISTER_COUNT;
+
+/// The .eh_frame_hdr and .eh_frame sections of an object, at the addresses
+/// they are mapped at in an [AddressSpace].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct EhFrameSections {
+    /// Address of .eh_frame_hdr.
+    pub eh_frame_hdr: u64,
+    /// Size of .eh_frame_hdr.
+    pub eh_frame_hdr_len: u64,
+    /// Upper bound of the end of .eh_frame, whose size is not recorded
+    /// anywhere. The end of the loaded segments of the object will do.
+    pub eh_frame_end: u64,
 }
 
-pub fn step(pc: u64, section: &SectionInfo, registers: &mut Registers) -> Result<(), DwarfError> {
+impl From<&SectionInfo> for EhFrameSections {
+    fn from(s: &SectionInfo) -> Self {
+        Self {
+            eh_frame_hdr: s.eh_frame_hdr,
+            eh_frame_hdr_len: s.eh_frame_hdr_len,
+            eh_frame_end: s.max_addr,
+        }
+    }
+}
+
+/// Restores the registers of the caller of the frame at `pc`.
+///
+/// Returns whether the frame is a signal frame (its CIE has the 'S'
+/// augmentation). The PC restored from a signal frame is the interrupted
+/// instruction itself rather than a return address, so it must be looked up
+/// as is.
+pub fn step(pc: u64, section: &SectionInfo, registers: &mut Registers) -> Result<bool, DwarfError> {
     // Search FDE & CIE for target PC.
-    let (fde, cie) = search_fde(pc, section)?;
+    let a = &LocalAddressSpace;
+    let (fde, cie) = search_fde(a, pc, &section.into())?;
+    step_with_fde::<_, _, NATIVE_REGISTER_COUNT>(a, pc, &fde, &cie, registers)
+}
+
+/// Restores the registers of the caller of the frame at `pc` from the
+/// .debug_frame sections registered with [register_debug_frame].
+///
+/// Returns whether the frame is a signal frame, like [step].
+#[cfg(feature = "symbolize")]
+pub fn step_debug_frame(pc: u64, registers: &mut Registers) -> Result<bool, DwarfError> {
+    let (fde, cie, base) = search_debug_frame(pc)?;
+    // The instructions of the FDE are relative to the addresses of the
+    // object file.
+    step_with_fde::<_, _, NATIVE_REGISTER_COUNT>(&LocalAddressSpace, pc.wrapping_sub(base), &fde, &cie, registers)
+}
+
+/// Restores the registers of the caller of the frame at `pc`, like the
+/// in-process unwinder does, but for any supported architecture and reading
+/// everything through `a`.
+///
+/// This unwinds stacks captured elsewhere, e.g. core dumps or stack
+/// snapshots: `a` has to map both the sections of the object containing
+/// `pc` and the stack, and its pointer size must be the one of the
+/// architecture of `R`.
+///
+/// `pc` is looked up as is. Return addresses point after the call, so
+/// callers should look up the return address minus one instead, unless the
+/// previous step returned `true`: this returns whether the frame is a
+/// signal frame, whose caller was interrupted at the restored PC.
+pub fn step_eh_frame<A: AddressSpace, R: RegisterFile>(
+    a: &A,
+    sections: &EhFrameSections,
+    pc: u64,
+    registers: &mut R,
+) -> Result<bool, DwarfError> {
+    debug_assert_eq!(a.pointer_size(), R::Arch::POINTER_SIZE);
+    let (fde, cie) = search_fde(a, pc, sections)?;
+    step_with_fde::<_, _, MAX_REGISTER_COUNT>(a, pc, &fde, &cie, registers)
+}
+
+/// Returns the size of the outgoing arguments on the stack at `pc`, as
+/// recorded by DW_CFA_GNU_args_size.
+///
+/// Normal unwinding already accounts for them in the CFA, but they must be
+/// popped when transferring control to a landing pad in the frame.
+pub fn args_size(pc: u64, section: &SectionInfo) -> Result<u64, DwarfError> {
+    let a = &LocalAddressSpace;
+    let (fde, cie) = search_fde(a, pc, &section.into())?;
+    let info = instruction::run::<_, Registers, NATIVE_REGISTER_COUNT>(a, pc, &fde, &cie)?;
+    Ok(info.sp_extra_arg_size as u64)
+}
+
+/// Returns the CFA of the frame at `pc`, the SP of its caller before the
+/// call, as computed by the CFI of the frame from its `registers`.
+pub fn cfa(pc: u64, section: &SectionInfo, registers: &Registers) -> Result<u64, DwarfError> {
+    let a = &LocalAddressSpace;
+    let (fde, cie) = search_fde(a, pc, &section.into())?;
+    let info = instruction::run::<_, Registers, NATIVE_REGISTER_COUNT>(a, pc, &fde, &cie)?;
+    info.cfa(a, registers)
+}
+
+/// The function containing a PC and how exceptions propagate through it,
+/// as described by its FDE and CIE.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct ProcInfo {
+    /// Address of the first instruction of the function.
+    pub start_ip: u64,
+    /// Address right after the last instruction of the function.
+    pub end_ip: u64,
+    /// Language-specific data area, 0 if none.
+    pub lsda: u64,
+    /// Personality routine, 0 if none.
+    pub personality: u64,
+}
 
+/// Returns the function containing `pc` from the FDE covering it.
+pub fn proc_info(pc: u64, section: &SectionInfo) -> Result<ProcInfo, DwarfError> {
+    proc_info_eh_frame(&LocalAddressSpace, &section.into(), pc)
+}
+
+/// Returns the function containing `pc` from the FDE covering it, reading
+/// everything through `a`, see [step_eh_frame].
+///
+/// Its [Lsda] can then be parsed to find the landing pads of the function.
+pub fn proc_info_eh_frame<A: AddressSpace>(a: &A, sections: &EhFrameSections, pc: u64) -> Result<ProcInfo, DwarfError> {
+    let (fde, cie) = search_fde(a, pc, sections)?;
+    Ok(ProcInfo {
+        start_ip: fde.pc_start,
+        end_ip: fde.pc_end,
+        lsda: fde.lsda,
+        personality: cie.personality,
+    })
+}
+
+/// Returns the landing pad of the call site covering `pc` in the LSDA of its
+/// function, if any.
+pub fn landing_pad(pc: u64, section: &SectionInfo) -> Result<Option<LandingPad>, DwarfError> {
+    let a = &LocalAddressSpace;
+    let info = proc_info(pc, section)?;
+    if info.lsda == 0 {
+        return Ok(None);
+    }
+    let lsda = Lsda::decode(a, info.lsda, info.start_ip, section.max_addr)?;
+    lsda.landing_pad(a, pc)
+}
+
+/// Restores the registers of the caller from the FDE covering `pc`, keeping
+/// at most `N` register rules, see [instruction::run].
+fn step_with_fde<A: AddressSpace, R: RegisterFile, const N: usize>(
+    a: &A,
+    pc: u64,
+    fde: &FrameDescriptionEntry,
+    cie: &CommonInformationEntry,
+    registers: &mut R,
+) -> Result<bool, DwarfError> {
     // Run instructions to calculate PrologInfo from FDE.
-    let info = instruction::run(pc, &fde, &cie)?;
+    let info = instruction::run::<A, R, N>(a, pc, fde, cie)?;
 
     // Get pointer to cfa (architecture specific).
-    let cfa = info.cfa(registers)?;
+    let cfa = info.cfa(a, registers)?;
 
     // Restore registers that DWARF says were saved.
     let mut new_registers = *registers;
@@ -122,45 +369,52 @@ pub fn step(pc: u64, section: &SectionInfo, registers: &mut Registers) -> Result
     // by a CFI directive later on.
     new_registers[UNW_REG_SP] = cfa;
 
-    let mut return_address = 0;
-    for n in 0..=Registers::max_register_num() {
-        if info.saved_registers[n].location != RegisterSavedWhere::Unused {
-            if Registers::valid_float_register(n) {
-                new_registers.set_float_register(n, get_saved_float_register(registers, info.saved_registers[n], cfa)?);
-            } else if Registers::valid_vector_register(n) {
-                new_registers
-                    .set_vector_register(n, get_saved_vector_register(registers, info.saved_registers[n], cfa)?);
-            } else if n == cie.return_address_register as usize {
-                return_address = get_saved_register(registers, info.saved_registers[n], cfa)?;
-            } else if Registers::valid_register(n) {
-                new_registers[n] = get_saved_register(registers, info.saved_registers[n], cfa)?;
-            } else {
-                return Err(DwarfError::InvalidRegisterNumber(n));
-            }
-        } else if n == cie.return_address_register as usize {
+    let ra = cie.return_address_register as usize;
+    let mut return_address = None;
+    for (n, loc) in info.saved_registers.iter() {
+        if R::valid_float_register(n) {
+            new_registers.set_float_register(n, get_saved_float_register(a, registers, loc, cfa)?);
+        } else if R::valid_vector_register(n) {
+            new_registers.set_vector_register(n, get_saved_vector_register(a, registers, loc, cfa)?);
+        } else if n == ra {
+            return_address = Some(get_saved_register(a, registers, loc, cfa)?);
+        } else if R::valid_register(n) {
+            new_registers[n] = get_saved_register(a, registers, loc, cfa)?;
+        } else {
+            return Err(DwarfError::InvalidRegisterNumber(n));
+        }
+    }
+    let return_address = match return_address {
+        Some(v) => v,
+        None => {
             // Leaf function keeps the return address in register and there is no
             // explicit instructions how to restore it.
-            if !Registers::valid_register(cie.return_address_register as usize) {
-                return Err(DwarfError::InvalidReturnAddressRegisterNumber(
-                    cie.return_address_register as usize,
-                ));
+            if !R::valid_register(ra) {
+                return Err(DwarfError::InvalidReturnAddressRegisterNumber(ra));
             }
-            return_address = registers[cie.return_address_register as usize];
+            registers[ra]
         }
-    }
-
-    #[cfg(target_arch = "aarch64")]
-    {
-        // If the target is aarch64 then the return address may have been signed
-        // using the v8.3 pointer authentication extensions. The original
-        // return address needs to be authenticated before the return address is
-        // restored. autia1716 is used instead of autia as autia1716 assembles
-        // to a NOP on pre-v8.3a architectures.
-        if info.saved_registers[UNW_ARM64_RA_SIGN_STATE].value != 0 && return_address != 0 {
-            // TODO: implement
-            return Err(DwarfError::UnimplementedRaSignState);
+    };
+
+    // If the target is aarch64 then the return address may have been signed
+    // using the v8.3 pointer authentication extensions, with the A key or, if
+    // the CIE has the 'B' augmentation, with the B key. Authenticating it would
+    // need the key, so we strip the PAC instead, which works for both keys.
+    let return_address = match R::Arch::RA_SIGN_STATE {
+        Some(n) => {
+            let loc = info.saved_registers.get(n);
+            let ra_sign_state = match loc.location {
+                RegisterSavedWhere::Unused => loc.value as u64,
+                _ => get_saved_register(a, registers, loc, cfa)?,
+            };
+            if ra_sign_state != 0 {
+                R::Arch::strip_return_address(return_address)
+            } else {
+                return_address
+            }
         }
-    }
+        None => return_address,
+    };
 
     // Return address is address after call site instruction, so setting IP to
     // that does simulates a return.
@@ -168,15 +422,22 @@ pub fn step(pc: u64, section: &SectionInfo, registers: &mut Registers) -> Result
 
     // Simulate the step by replacing the register set with the new ones.
     *registers = new_registers;
-    Ok(())
+    Ok(cie.is_signal_frame)
 }
 
-fn search_fde(pc: u64, s: &SectionInfo) -> Result<(FrameDescriptionEntry, CommonInformationEntry), DwarfError> {
-    let end = s.eh_frame_hdr + s.eh_frame_hdr_len;
-    let header = EhFrameHeader::decode(s.eh_frame_hdr, end)?;
-    match header.search(pc) {
+fn search_fde<A: AddressSpace>(
+    a: &A,
+    pc: u64,
+    s: &EhFrameSections,
+) -> Result<(FrameDescriptionEntry, CommonInformationEntry), DwarfError> {
+    let end = s.eh_frame_hdr.saturating_add(s.eh_frame_hdr_len);
+    let header = EhFrameHeader::decode(a, s.eh_frame_hdr, end)?;
+    // The length of .eh_frame is not recorded anywhere, but it must be inside
+    // the loaded segments of the object.
+    let eh_frame_end = s.eh_frame_end;
+    match header.search(a, pc, eh_frame_end) {
         Ok(v) => Ok(v),
-        Err(DwarfError::FDENotFound) => cfi::scan(header.eh_frame, u64::MAX, pc),
+        Err(DwarfError::FDENotFound) => cfi::scan(a, header.eh_frame, eh_frame_end, pc),
         Err(err) => Err(err),
     }
 }
@@ -196,3 +457,403 @@ fn load_with_protect<T: Copy>(address: u64) -> Result<T, DwarfError> {
 fn load_with_protect<T: Copy>(address: u64) -> Result<T, DwarfError> {
     Ok(load(address))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::address_space::SliceAddressSpace;
+    use crate::dwarf::cfi::{CfiEntry, Entries};
+    use crate::dwarf::consts::*;
+    use crate::dwarf::testing::EhFrameBuilder;
+    #[cfg(target_arch = "aarch64")]
+    use crate::registers::{UNW_ARM64_LR as RA, UNW_ARM64_SP as SP};
+    #[cfg(target_arch = "arm")]
+    use crate::registers::{UNW_ARM_LR as RA, UNW_ARM_SP as SP};
+    #[cfg(target_arch = "riscv64")]
+    use crate::registers::{UNW_RISCV_RA as RA, UNW_RISCV_SP as SP};
+    #[cfg(target_arch = "x86_64")]
+    use crate::registers::{UNW_X86_64_RIP as RA, UNW_X86_64_RSP as SP};
+    #[cfg(target_arch = "x86")]
+    use crate::registers::{UNW_X86_EIP as RA, UNW_X86_ESP as SP};
+
+    const BASE: u64 = 0x10000;
+    const STACK: u64 = BASE + 0x1000;
+    const CFA: u64 = STACK + 16;
+    const RETURN_ADDRESS: u64 = 0x2000;
+    const SAVED: u64 = 0xaaaa;
+
+    /// Step a frame with CFA = SP+16 and the return address at CFA-8. The
+    /// word at CFA-16 holds `SAVED`, r3 = 0x333 and r6 = 0x666.
+    fn step_cfi(fde_instructions: &[u8]) -> Result<Registers, DwarfError> {
+        step_cfi_with_augmentation("", fde_instructions).map(|(registers, _)| registers)
+    }
+
+    /// Like `step_cfi`, with `augmentation` appended to the CIE augmentation
+    /// string. Also returns whether the frame is a signal frame.
+    fn step_cfi_with_augmentation(
+        augmentation: &str,
+        fde_instructions: &[u8],
+    ) -> Result<(Registers, bool), DwarfError> {
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie(
+            augmentation,
+            1,
+            -8,
+            RA as u64,
+            &[DW_CFA_DEF_CFA, SP as u8, 16, DW_CFA_OFFSET | RA as u8, 1],
+        );
+        b.fde(cie, 0x1000, 0x100, fde_instructions);
+        let mut mem = b.data.clone();
+        mem.resize((STACK - BASE) as usize, 0);
+        mem.extend_from_slice(&SAVED.to_ne_bytes());
+        mem.extend_from_slice(&RETURN_ADDRESS.to_ne_bytes());
+        let space = SliceAddressSpace::new(BASE, &mem);
+        let mut entries = Entries::new(&space, BASE, BASE + b.data.len() as u64);
+        let _ = entries.next().transpose()?;
+        let (fde, cie) = match entries.next().transpose()? {
+            Some(CfiEntry::FdeCie(v)) => v,
+            _ => unreachable!(),
+        };
+        let mut registers = Registers::default();
+        registers[UNW_REG_IP] = 0x1001;
+        registers[UNW_REG_SP] = STACK;
+        registers[3] = 0x333;
+        registers[6] = 0x666;
+        let is_signal_frame = step_with_fde::<_, _, NATIVE_REGISTER_COUNT>(&space, 0x1001, &fde, &cie, &mut registers)?;
+        Ok((registers, is_signal_frame))
+    }
+
+    #[test]
+    fn test_step() {
+        let registers = step_cfi(&[]).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        assert_eq!(registers.sp(), CFA);
+        assert_eq!(registers[3], 0x333);
+        assert_eq!(registers[6], 0x666);
+    }
+
+    #[test]
+    fn test_step_undefined() {
+        let registers = step_cfi(&[DW_CFA_UNDEFINED, 3]).unwrap();
+        assert_eq!(registers[3], 0);
+
+        // An undefined return address terminates the unwinding.
+        let registers = step_cfi(&[DW_CFA_UNDEFINED, RA as u8]).unwrap();
+        assert_eq!(registers.pc(), 0);
+    }
+
+    #[test]
+    fn test_step_same_value() {
+        let registers = step_cfi(&[DW_CFA_OFFSET | 3, 2, DW_CFA_SAME_VALUE, 3]).unwrap();
+        assert_eq!(registers[3], 0x333);
+    }
+
+    #[test]
+    fn test_step_offset() {
+        let registers = step_cfi(&[DW_CFA_OFFSET | 3, 2]).unwrap();
+        assert_eq!(registers[3], SAVED);
+    }
+
+    #[test]
+    fn test_step_val_offset() {
+        let registers = step_cfi(&[DW_CFA_VAL_OFFSET, 3, 2]).unwrap();
+        assert_eq!(registers[3], CFA - 16);
+        let registers = step_cfi(&[DW_CFA_VAL_OFFSET_SF, 3, 0x7e]).unwrap();
+        assert_eq!(registers[3], CFA + 16);
+    }
+
+    #[test]
+    fn test_step_register() {
+        let registers = step_cfi(&[DW_CFA_REGISTER, 3, 6]).unwrap();
+        assert_eq!(registers[3], 0x666);
+        assert_eq!(registers[6], 0x666);
+
+        // Rules are evaluated against the registers of the current frame.
+        let registers = step_cfi(&[DW_CFA_REGISTER, 3, 6, DW_CFA_REGISTER, 6, 3]).unwrap();
+        assert_eq!(registers[3], 0x666);
+        assert_eq!(registers[6], 0x333);
+
+        // The return address can live in another register.
+        let registers = step_cfi(&[DW_CFA_REGISTER, RA as u8, 6]).unwrap();
+        assert_eq!(registers.pc(), 0x666);
+
+        assert_eq!(
+            step_cfi(&[DW_CFA_REGISTER, 3, 0xc8, 0x01]).unwrap_err(),
+            DwarfError::InvalidRegisterNumber(200)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dwarf-expression")]
+    fn test_step_expression() {
+        let registers = step_cfi(&[DW_CFA_EXPRESSION, 3, 2, DW_OP_LIT16, DW_OP_MINUS]).unwrap();
+        assert_eq!(registers[3], SAVED);
+    }
+
+    #[test]
+    #[cfg(feature = "dwarf-expression")]
+    fn test_step_val_expression() {
+        let registers = step_cfi(&[DW_CFA_VAL_EXPRESSION, 3, 2, DW_OP_BREG0 + 6, 1]).unwrap();
+        assert_eq!(registers[3], 0x667);
+    }
+
+    #[test]
+    fn test_step_args_size() {
+        // Outgoing arguments are only popped when resuming in the frame, the
+        // CFA already accounts for them.
+        let registers = step_cfi(&[DW_CFA_GNU_ARGS_SIZE, 16]).unwrap();
+        assert_eq!(registers.sp(), CFA);
+    }
+
+    #[test]
+    fn test_step_signal_frame() {
+        let (registers, is_signal_frame) = step_cfi_with_augmentation("", &[]).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        assert!(!is_signal_frame);
+
+        let (registers, is_signal_frame) = step_cfi_with_augmentation("S", &[]).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        assert_eq!(registers.sp(), CFA);
+        assert!(is_signal_frame);
+    }
+
+    #[test]
+    #[cfg(feature = "symbolize")]
+    fn test_step_debug_frame() {
+        use crate::dwarf::testing::DebugFrameBuilder;
+
+        // The registry is shared, the object is at an address of its own.
+        const OBJECT: u64 = 0x7200_0000;
+        let mut b = DebugFrameBuilder::default();
+        let cie = b.cie(
+            1,
+            -8,
+            RA as u64,
+            &[DW_CFA_DEF_CFA, SP as u8, 16, DW_CFA_OFFSET | RA as u8, 1],
+        );
+        b.fde(cie, 0x1000, 0x100, &[DW_CFA_OFFSET | 3, 2]);
+        assert!(register_debug_frame(DebugFrame::new(
+            OBJECT,
+            Box::leak(b.data.into_boxed_slice())
+        )));
+
+        let stack = [SAVED, RETURN_ADDRESS];
+        let mut registers = Registers::default();
+        registers[UNW_REG_SP] = stack.as_ptr() as u64;
+        assert!(!step_debug_frame(OBJECT + 0x1001, &mut registers).unwrap());
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        assert_eq!(registers.sp(), stack.as_ptr() as u64 + 16);
+        assert_eq!(registers[3], SAVED);
+
+        assert_eq!(
+            step_debug_frame(0x1001, &mut registers).unwrap_err(),
+            DwarfError::FDENotFound
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "aarch64", feature = "dwarf-expression"))]
+    fn test_step_signed_return_address() {
+        // Return address with a PAC in bits 48~54 and 56~63.
+        let mut signed = vec![DW_CFA_VAL_EXPRESSION, RA as u8, 9, DW_OP_CONST8U];
+        signed.extend_from_slice(&(RETURN_ADDRESS | 0x9c35 << 48).to_ne_bytes());
+
+        let registers = step_cfi(&signed).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS | 0x9c35 << 48);
+
+        let mut instructions = signed.clone();
+        instructions.push(DW_CFA_AARCH64_NEGATE_RA_STATE);
+        let registers = step_cfi(&instructions).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+
+        // Signed with the B key.
+        let (registers, _) = step_cfi_with_augmentation("B", &instructions).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+
+        // Toggled back before the return address is used.
+        instructions.push(DW_CFA_AARCH64_NEGATE_RA_STATE);
+        let registers = step_cfi(&instructions).unwrap();
+        assert_eq!(registers.pc(), RETURN_ADDRESS | 0x9c35 << 48);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_step_vector_register() {
+        use crate::registers::{UNW_X86_64_XMM0, UNW_X86_64_XMM1};
+
+        // 16 bytes at CFA-16.
+        let registers = step_cfi(&[DW_CFA_OFFSET | UNW_X86_64_XMM0 as u8, 2]).unwrap();
+        assert_eq!(
+            registers.vector_register(UNW_X86_64_XMM0),
+            SAVED as u128 | (RETURN_ADDRESS as u128) << 64
+        );
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM1), 0);
+
+        let registers = step_cfi(&[DW_CFA_VAL_OFFSET, UNW_X86_64_XMM1 as u8, 2]).unwrap();
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM1), (CFA - 16) as u128);
+    }
+
+    /// Map the .eh_frame_hdr and .eh_frame built by `b` at `BASE`, followed by
+    /// `stack` at `STACK`.
+    fn offline_memory(b: &EhFrameBuilder, stack: &[u8]) -> (Vec<u8>, EhFrameSections) {
+        let mut mem = b.header(BASE);
+        let sections = EhFrameSections {
+            eh_frame_hdr: BASE,
+            eh_frame_hdr_len: mem.len() as u64,
+            eh_frame_end: BASE + (mem.len() + b.data.len()) as u64,
+        };
+        mem.extend_from_slice(&b.data);
+        mem.resize((STACK - BASE) as usize, 0);
+        mem.extend_from_slice(stack);
+        (mem, sections)
+    }
+
+    #[test]
+    fn test_step_eh_frame_aarch64() {
+        use crate::arch::Aarch64;
+        use crate::registers::{GenericRegisters, UNW_ARM64_FP, UNW_ARM64_LR, UNW_ARM64_SP};
+
+        // stp x29, x30, [sp, #-16]! with a signed return address.
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie("", 4, -8, UNW_ARM64_LR as u64, &[DW_CFA_DEF_CFA, UNW_ARM64_SP as u8, 0]);
+        let fde = [
+            DW_CFA_AARCH64_NEGATE_RA_STATE,
+            DW_CFA_ADVANCE_LOC | 1,
+            DW_CFA_DEF_CFA_OFFSET,
+            16,
+            DW_CFA_OFFSET | UNW_ARM64_FP as u8,
+            2,
+            DW_CFA_OFFSET | UNW_ARM64_LR as u8,
+            1,
+        ];
+        b.fde(cie, 0x1000, 0x100, &fde);
+        let mut stack = SAVED.to_le_bytes().to_vec();
+        stack.extend_from_slice(&(RETURN_ADDRESS | 0x9c35 << 48).to_le_bytes());
+        let (mem, sections) = offline_memory(&b, &stack);
+        let space = SliceAddressSpace::new(BASE, &mem).with_pointer_size(8);
+
+        let mut registers = GenericRegisters::<Aarch64>::default();
+        registers[UNW_REG_IP] = 0x1010;
+        registers[UNW_REG_SP] = STACK;
+        registers[UNW_ARM64_LR] = 0x1234;
+        assert_eq!(step_eh_frame(&space, &sections, 0x1010, &mut registers), Ok(false));
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        assert_eq!(registers.sp(), CFA);
+        assert_eq!(registers[UNW_ARM64_FP], SAVED);
+
+        // No FDE covers the return address.
+        assert_eq!(
+            step_eh_frame(&space, &sections, RETURN_ADDRESS - 1, &mut registers),
+            Err(DwarfError::FDENotFound)
+        );
+    }
+
+    #[test]
+    fn test_step_eh_frame_x86() {
+        use crate::arch::X86;
+        use crate::registers::{GenericRegisters, UNW_X86_EBP, UNW_X86_EIP, UNW_X86_ESP};
+
+        // push %ebp, with 4-byte stack slots.
+        let mut b = EhFrameBuilder::default();
+        let cie = b.cie(
+            "",
+            1,
+            -4,
+            UNW_X86_EIP as u64,
+            &[
+                DW_CFA_DEF_CFA,
+                UNW_X86_ESP as u8,
+                4,
+                DW_CFA_OFFSET | UNW_X86_EIP as u8,
+                1,
+            ],
+        );
+        let fde = [
+            DW_CFA_ADVANCE_LOC | 1,
+            DW_CFA_DEF_CFA_OFFSET,
+            8,
+            DW_CFA_OFFSET | UNW_X86_EBP as u8,
+            2,
+        ];
+        b.fde(cie, 0x1000, 0x100, &fde);
+        let mut stack = (SAVED as u32).to_le_bytes().to_vec();
+        stack.extend_from_slice(&(RETURN_ADDRESS as u32).to_le_bytes());
+        let (mem, sections) = offline_memory(&b, &stack);
+        let space = SliceAddressSpace::new(BASE, &mem).with_pointer_size(4);
+
+        let mut registers = GenericRegisters::<X86>::default();
+        registers[UNW_REG_IP] = 0x1001;
+        registers[UNW_REG_SP] = STACK;
+        assert_eq!(step_eh_frame(&space, &sections, 0x1001, &mut registers), Ok(false));
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        assert_eq!(registers.sp(), STACK + 8);
+        assert_eq!(registers[UNW_X86_EBP], SAVED);
+    }
+
+    #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
+    struct Guard;
+
+    #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            std::hint::black_box(self);
+        }
+    }
+
+    /// Has a landing pad to drop `_guard` if `f` unwinds.
+    #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
+    #[inline(never)]
+    fn with_cleanup(f: fn()) {
+        let _guard = Guard;
+        std::hint::black_box(f)();
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
+    fn test_proc_info() {
+        let pc = with_cleanup as fn(fn()) as usize as u64;
+        with_cleanup(|| {});
+        let section = crate::dyld::find_section(pc).unwrap();
+        let info = proc_info(pc, &section).unwrap();
+        assert_eq!(info.start_ip, pc);
+        assert!(info.end_ip > pc);
+        assert_ne!(info.lsda, 0);
+        assert_ne!(info.personality, 0);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
+    fn test_rustc_lsda() {
+        let start = with_cleanup as fn(fn()) as usize as u64;
+        let section = crate::dyld::find_section(start).unwrap();
+        let info = proc_info(start, &section).unwrap();
+        let a = &LocalAddressSpace;
+        let lsda = Lsda::decode(a, info.lsda, info.start_ip, section.max_addr).unwrap();
+
+        // Calls made while the guard is alive drop it if they unwind. Without
+        // optimizations, the drop in that landing pad aborts if it unwinds
+        // too, with an empty exception specification.
+        let mut cleanups = vec![];
+        for call_site in lsda.call_sites(a) {
+            let call_site = call_site.unwrap();
+            assert!(call_site.start >= info.start_ip && call_site.start + call_site.len <= info.end_ip);
+            if call_site.landing_pad == 0 {
+                continue;
+            }
+            let mut actions = lsda.actions(a, &call_site);
+            match actions.next().unwrap().unwrap() {
+                Action::Cleanup => cleanups.push(call_site),
+                Action::Filter(_) => {}
+                action => panic!("unexpected action {:?}", action),
+            }
+            assert!(actions.next().is_none());
+        }
+        assert!(!cleanups.is_empty());
+        for call_site in cleanups {
+            assert!((info.start_ip..info.end_ip).contains(&call_site.landing_pad));
+            let landing_pad = landing_pad(call_site.start, &section).unwrap().unwrap();
+            assert_eq!(landing_pad.address, call_site.landing_pad);
+            assert!(landing_pad.cleanup && !landing_pad.catch);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Helpers to build synthetic CFI for tests.
+
+use crate::dwarf::consts::*;
+
+/// `EhFrameBuilder` assembles an .eh_frame section in memory.
+///
+/// CIEs use the "zR" augmentation with an absolute `udata8` pointer encoding,
+/// so pc ranges in FDEs are plain 8-byte values. All offsets are relative to
+/// the start of the section.
+#[derive(Default)]
+pub struct EhFrameBuilder {
+    pub data: Vec<u8>,
+    pub fdes: Vec<(u64, usize)>, // (pc_start, fde offset)
+}
+
+impl EhFrameBuilder {
+    /// Append a CIE and returns its offset. `augmentation` is appended to "zR"
+    /// and must not need any augmentation data.
+    pub fn cie(&mut self, augmentation: &str, code_align: u64, data_align: i64, ra: u64, instructions: &[u8]) -> usize {
+        let mut body = vec![];
+        body.extend_from_slice(&0u32.to_ne_bytes()); // CIE id
+        body.push(1); // version
+        body.extend_from_slice(b"zR");
+        body.extend_from_slice(augmentation.as_bytes());
+        body.push(0);
+        leb128::write::unsigned(&mut body, code_align).unwrap();
+        leb128::write::signed(&mut body, data_align).unwrap();
+        body.push(ra as u8);
+        body.push(1); // augmentation data length
+        body.push(DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8);
+        body.extend_from_slice(instructions);
+        self.entry(body)
+    }
+
+    /// Append a FDE pointing to the CIE at `cie`, and returns its offset.
+    pub fn fde(&mut self, cie: usize, pc_start: u64, pc_range: u64, instructions: &[u8]) -> usize {
+        let offset = self.data.len();
+        let mut body = vec![];
+        // CIE pointer is relative to the field itself.
+        body.extend_from_slice(&((offset + 4 - cie) as u32).to_ne_bytes());
+        body.extend_from_slice(&pc_start.to_ne_bytes());
+        body.extend_from_slice(&pc_range.to_ne_bytes());
+        body.push(0); // augmentation data length
+        body.extend_from_slice(instructions);
+        self.fdes.push((pc_start, offset));
+        self.entry(body)
+    }
+
+    /// Append the zero terminator.
+    pub fn terminate(&mut self) {
+        self.data.extend_from_slice(&0u32.to_ne_bytes());
+    }
+
+    /// Build an .eh_frame_hdr for the appended FDEs, assuming it's loaded at
+    /// `base` and .eh_frame is placed right after it.
+    pub fn header(&self, base: u64) -> Vec<u8> {
+        let mut fdes = self.fdes.clone();
+        fdes.sort();
+        let header_len = 4 + 4 + 4 + fdes.len() * 8;
+        let mut hdr = vec![
+            1,
+            DW_EH_PE_PCREL | DW_EH_PE_SDATA4,
+            DW_EH_PE_UDATA4,
+            DW_EH_PE_DATAREL | DW_EH_PE_SDATA4,
+        ];
+        // eh_frame_ptr is relative to the field itself, which is at offset 4.
+        hdr.extend_from_slice(&((header_len - 4) as i32).to_ne_bytes());
+        hdr.extend_from_slice(&(fdes.len() as u32).to_ne_bytes());
+        for (pc, offset) in fdes {
+            // Table entries are relative to the start of .eh_frame_hdr.
+            hdr.extend_from_slice(&(pc.wrapping_sub(base) as i32).to_ne_bytes());
+            hdr.extend_from_slice(&((header_len + offset) as i32).to_ne_bytes());
+        }
+        hdr
+    }
+
+    fn entry(&mut self, body: Vec<u8>) -> usize {
+        let offset = self.data.len();
+        self.data.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+        self.data.extend_from_slice(&body);
+        offset
+    }
+}
+
+/// `DebugFrameBuilder` assembles a .debug_frame section in memory.
+///
+/// CIEs are of version 4 without augmentation, so pc ranges in FDEs are
+/// addresses of the native size. CIE pointers are offsets in the section.
+#[derive(Default)]
+pub struct DebugFrameBuilder {
+    pub data: Vec<u8>,
+}
+
+impl DebugFrameBuilder {
+    /// Append a CIE and returns its offset.
+    pub fn cie(&mut self, code_align: u64, data_align: i64, ra: u64, instructions: &[u8]) -> usize {
+        let mut body = vec![];
+        body.extend_from_slice(&u32::MAX.to_ne_bytes()); // CIE id
+        body.push(4); // version
+        body.push(0); // augmentation
+        body.push(std::mem::size_of::<usize>() as u8); // address size
+        body.push(0); // segment selector size
+        leb128::write::unsigned(&mut body, code_align).unwrap();
+        leb128::write::signed(&mut body, data_align).unwrap();
+        leb128::write::unsigned(&mut body, ra).unwrap();
+        body.extend_from_slice(instructions);
+        self.entry(body)
+    }
+
+    /// Append a FDE pointing to the CIE at `cie`, and returns its offset.
+    pub fn fde(&mut self, cie: usize, pc_start: u64, pc_range: u64, instructions: &[u8]) -> usize {
+        let mut body = vec![];
+        body.extend_from_slice(&(cie as u32).to_ne_bytes());
+        body.extend_from_slice(&(pc_start as usize).to_ne_bytes());
+        body.extend_from_slice(&(pc_range as usize).to_ne_bytes());
+        body.extend_from_slice(instructions);
+        self.entry(body)
+    }
+
+    fn entry(&mut self, body: Vec<u8>) -> usize {
+        let offset = self.data.len();
+        self.data.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+        self.data.extend_from_slice(&body);
+        offset
+    }
+}
@@ -4,19 +4,26 @@ use std::slice;
 
 const MAX_OBJECTS_LEN: usize = 128;
 const PF_X: u32 = 1;
+#[cfg(target_arch = "arm")]
+const PT_ARM_EXIDX: u32 = 0x70000001;
 
 lazy_static! {
     static ref SECTIONS: SmallVec<[SectionInfo; MAX_OBJECTS_LEN]> = init_sections();
 }
 
 /// Real loaded addresses of sections in virtual memory space.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Copy, Clone)]
 pub struct SectionInfo {
+    #[cfg_attr(not(feature = "backtrace-compat"), allow(unused))]
     pub base: u64,
     pub text: u64,
     pub text_len: u64,
     pub eh_frame_hdr: u64,
     pub eh_frame_hdr_len: u64,
+    #[cfg(target_arch = "arm")]
+    pub arm_exidx: u64,
+    #[cfg(target_arch = "arm")]
+    pub arm_exidx_len: u64,
     pub max_addr: u64,
 }
 
@@ -42,22 +49,73 @@ fn init_sections() -> SmallVec<[SectionInfo; MAX_OBJECTS_LEN]> {
     data
 }
 
+/// Returns the [SectionInfo] of the loaded object containing `pc`.
+///
+/// Unlike [sections], objects are looked up every time, so objects loaded
+/// after the first unwind (e.g. with `dlopen`) are found, and shared
+/// libraries are always included. `dl_iterate_phdr` takes the lock of the
+/// dynamic loader, so this must not be called from signal handlers.
+pub fn find_section(pc: u64) -> Option<SectionInfo> {
+    let mut data: (u64, Option<SectionInfo>) = (pc, None);
+    unsafe {
+        libc::dl_iterate_phdr(Some(find_callback), &mut data as *mut _ as *mut libc::c_void);
+    }
+    data.1
+}
+
+/// Returns the address at `offset` in the thread-local storage block of the
+/// calling thread for the loaded object containing `address`, or `None` if
+/// it has no such block, or if the block isn't allocated yet in this thread.
+///
+/// Like [find_section], this takes the lock of the dynamic loader.
+#[cfg_attr(not(feature = "dwarf-expression"), allow(unused))]
+pub fn tls_address(address: u64, offset: u64) -> Option<u64> {
+    let mut data: (u64, Option<u64>) = (address, None);
+    unsafe {
+        libc::dl_iterate_phdr(Some(tls_callback), &mut data as *mut _ as *mut libc::c_void);
+    }
+    data.1?.checked_add(offset)
+}
+
+// ELF fields are 32-bit on 32-bit architectures, hence the conversions.
+#[allow(clippy::useless_conversion)]
+#[cfg_attr(not(feature = "dwarf-expression"), allow(unused))]
+extern "C" fn tls_callback(info: *mut libc::dl_phdr_info, _size: libc::size_t, data: *mut libc::c_void) -> libc::c_int {
+    unsafe {
+        let data = data as *mut (u64, Option<u64>);
+        let info = &*info;
+        if info.dlpi_phnum == 0 {
+            return 0;
+        }
+        let base = u64::from(info.dlpi_addr);
+        let hdrs = slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
+        let contains = hdrs.iter().any(|hdr| {
+            let start = base.wrapping_add(u64::from(hdr.p_vaddr));
+            hdr.p_type == libc::PT_LOAD && start <= (*data).0 && (*data).0 - start < u64::from(hdr.p_memsz)
+        });
+        if !contains {
+            return 0;
+        }
+        if !info.dlpi_tls_data.is_null() {
+            (*data).1 = Some(info.dlpi_tls_data as usize as u64);
+        }
+        1 // stop iterating
+    }
+}
+
 extern "C" fn callback(info: *mut libc::dl_phdr_info, _size: libc::size_t, data: *mut libc::c_void) -> libc::c_int {
     unsafe {
         let data = data as *mut SmallVec<[SectionInfo; MAX_OBJECTS_LEN]>;
         if (*data).len() >= MAX_OBJECTS_LEN {
             return 0;
         }
-        if (*info).dlpi_phnum == 0 {
-            return 0;
-        }
         match std::ffi::CStr::from_ptr((*info).dlpi_name).to_str() {
             Ok(name) => {
                 // If `trace-shared-libs` is not enabled, only functions in the current
                 // executable are traced. (The `dlpi_name` of the current executable is
                 // an empty string)
                 #[cfg(not(feature = "trace-shared-libs"))]
-                if name != "" {
+                if !name.is_empty() {
                     return 0;
                 }
                 // After testing, it was found that there is currently no .eh_frame data
@@ -69,36 +127,78 @@ extern "C" fn callback(info: *mut libc::dl_phdr_info, _size: libc::size_t, data:
             }
             Err(_) => return 0,
         }
-        let mut section = SectionInfo::default();
-        section.base = (*info).dlpi_addr;
-        let hdrs = slice::from_raw_parts((*info).dlpi_phdr, (*info).dlpi_phnum as usize);
-        let mut found_text = false;
-        let mut found_unwind = false;
-        for hdr in hdrs {
-            match hdr.p_type {
-                libc::PT_LOAD => {
-                    if hdr.p_flags & PF_X != 0 {
-                        section.text = (*info).dlpi_addr + hdr.p_vaddr;
-                        section.text_len = hdr.p_memsz;
-                        found_text = true;
-                    }
-                    let max_addr = (*info).dlpi_addr + hdr.p_vaddr + hdr.p_filesz;
-                    if section.max_addr < max_addr {
-                        section.max_addr = max_addr;
-                    }
+        if let Some(section) = section_info(&*info) {
+            (*data).push(section);
+        }
+        0
+    }
+}
+
+extern "C" fn find_callback(
+    info: *mut libc::dl_phdr_info,
+    _size: libc::size_t,
+    data: *mut libc::c_void,
+) -> libc::c_int {
+    unsafe {
+        let data = data as *mut (u64, Option<SectionInfo>);
+        match section_info(&*info) {
+            Some(section) if section.contains((*data).0) => {
+                (*data).1 = Some(section);
+                1 // stop iterating
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Returns the sections of a loaded object, if it has both code and unwind
+/// information.
+// ELF fields are 32-bit on 32-bit architectures, hence the conversions.
+#[allow(clippy::useless_conversion)]
+unsafe fn section_info(info: &libc::dl_phdr_info) -> Option<SectionInfo> {
+    if info.dlpi_phnum == 0 {
+        return None;
+    }
+    let base = u64::from(info.dlpi_addr);
+    let mut section = SectionInfo {
+        base,
+        ..Default::default()
+    };
+    let hdrs = slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
+    let mut found_text = false;
+    let mut found_unwind = false;
+    for hdr in hdrs {
+        let vaddr = u64::from(hdr.p_vaddr);
+        match hdr.p_type {
+            libc::PT_LOAD => {
+                if hdr.p_flags & PF_X != 0 {
+                    section.text = base + vaddr;
+                    section.text_len = u64::from(hdr.p_memsz);
+                    found_text = true;
                 }
-                libc::PT_GNU_EH_FRAME => {
-                    section.eh_frame_hdr = (*info).dlpi_addr + hdr.p_vaddr;
-                    section.eh_frame_hdr_len = hdr.p_memsz;
-                    found_unwind = true;
+                let max_addr = base + vaddr + u64::from(hdr.p_filesz);
+                if section.max_addr < max_addr {
+                    section.max_addr = max_addr;
                 }
-                _ => {}
             }
+            libc::PT_GNU_EH_FRAME => {
+                section.eh_frame_hdr = base + vaddr;
+                section.eh_frame_hdr_len = u64::from(hdr.p_memsz);
+                found_unwind = true;
+            }
+            #[cfg(target_arch = "arm")]
+            PT_ARM_EXIDX => {
+                section.arm_exidx = base + vaddr;
+                section.arm_exidx_len = u64::from(hdr.p_memsz);
+                found_unwind = true;
+            }
+            _ => {}
         }
-        if found_text && found_unwind {
-            (*data).push(section);
-        }
-        0
+    }
+    if found_text && found_unwind {
+        Some(section)
+    } else {
+        None
     }
 }
 
@@ -108,7 +208,34 @@ mod tests {
 
     #[test]
     fn test_sections() {
-        assert!(sections().len() > 0);
+        assert!(!sections().is_empty());
         assert!(sections().len() <= MAX_OBJECTS_LEN);
     }
+
+    #[test]
+    fn test_find_section() {
+        let pc = test_find_section as fn() as usize as u64;
+        let section = find_section(pc).unwrap();
+        assert!(section.contains(pc));
+        assert_eq!(section.eh_frame_hdr, sections()[0].eh_frame_hdr);
+        // libc is always found, `trace-shared-libs` or not.
+        assert!(find_section(libc::getpid as *const () as usize as u64).is_some());
+        assert!(find_section(0).is_none());
+    }
+
+    thread_local! {
+        static VALUE: std::cell::Cell<u64> = const { std::cell::Cell::new(42) };
+    }
+
+    #[test]
+    fn test_tls_address() {
+        let pc = test_tls_address as fn() as usize as u64;
+        let block = tls_address(pc, 0).unwrap();
+        let value = VALUE.with(|v| v as *const _ as usize as u64);
+        assert_eq!(tls_address(pc, value - block), Some(value));
+        // Each thread has its own block.
+        let other = std::thread::spawn(move || tls_address(pc, 0)).join().unwrap();
+        assert_ne!(other, Some(block));
+        assert_eq!(tls_address(0, 0), None);
+    }
 }
@@ -0,0 +1,361 @@
+use crate::ehabi::{load_with_protect, EhabiError};
+use crate::registers::{Registers, UNW_ARM_D0, UNW_ARM_LR, UNW_ARM_PC, UNW_ARM_SP, UNW_REG_SP};
+
+/// The unwind opcodes of a function, a byte stream packed into the words of
+/// an exidx or extab entry, most significant byte first.
+///
+/// Words are loaded lazily, so that a truncated table is only read as far
+/// as the opcodes go.
+#[derive(Debug)]
+pub struct Opcodes {
+    address: u64,      // address of the current word
+    index: usize,      // index of the next byte in the current word
+    len: usize,        // number of bytes left
+    word: Option<u32>, // the current word, once loaded
+}
+
+impl Opcodes {
+    /// Creates `Opcodes` of `len` bytes, starting at byte `index` of the word
+    /// at `address`.
+    pub fn new(address: u64, index: usize, len: usize) -> Self {
+        Self {
+            address,
+            index,
+            len,
+            word: None,
+        }
+    }
+
+    /// Returns the next byte, or `None` at the end.
+    pub fn next_byte(&mut self) -> Result<Option<u8>, EhabiError> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        let word = match self.word {
+            Some(word) => word,
+            None => *self.word.insert(load_with_protect(self.address)?),
+        };
+        let byte = (word >> (24 - 8 * self.index)) as u8;
+        self.len -= 1;
+        self.index += 1;
+        if self.index == 4 {
+            self.address += 4;
+            self.index = 0;
+            self.word = None;
+        }
+        Ok(Some(byte))
+    }
+
+    /// Returns the operand byte of an opcode.
+    fn operand(&mut self) -> Result<u8, EhabiError> {
+        self.next_byte()?.ok_or(EhabiError::TruncatedOpcodes)
+    }
+
+    /// Returns the ULEB128 operand of an opcode.
+    fn uleb128(&mut self) -> Result<u32, EhabiError> {
+        let mut value = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = self.operand()?;
+            if shift >= 32 {
+                return Err(EhabiError::InvalidOpcode(byte));
+            }
+            value |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+}
+
+/// Execute the unwind `opcodes` of the frame of `registers`, and restore the
+/// registers of the caller.
+///
+/// The opcodes pop the saved registers off a virtual stack pointer (vsp),
+/// which becomes the SP of the caller. If they don't pop the PC, the caller
+/// resumes at the LR. The end of the opcodes implies a finish.
+pub fn run(opcodes: &mut Opcodes, registers: &mut Registers) -> Result<(), EhabiError> {
+    let mut new_registers = *registers;
+    let mut vsp = registers.sp() as u32;
+    let mut pc_popped = false;
+    while let Some(op) = opcodes.next_byte()? {
+        match op {
+            // 00xxxxxx: vsp = vsp + (xxxxxx << 2) + 4
+            0x00..=0x3f => vsp = vsp.wrapping_add((u32::from(op & 0x3f) << 2) + 4),
+            // 01xxxxxx: vsp = vsp - (xxxxxx << 2) - 4
+            0x40..=0x7f => vsp = vsp.wrapping_sub((u32::from(op & 0x3f) << 2) + 4),
+            // 1000iiii iiiiiiii: pop r4 ~ r15 under mask, refuse to unwind
+            // if the mask is zero.
+            0x80..=0x8f => {
+                let mask = u16::from(op & 0x0f) << 8 | u16::from(opcodes.operand()?);
+                if mask == 0 {
+                    return Err(EhabiError::CantUnwind);
+                }
+                pop_registers(&mut new_registers, &mut vsp, mask << 4)?;
+                pc_popped |= mask << 4 & 1 << UNW_ARM_PC != 0;
+            }
+            // 1001nnnn: vsp = r[nnnn], r13 and r15 are reserved.
+            0x90..=0x9f => {
+                let n = (op & 0x0f) as usize;
+                if n == UNW_ARM_SP || n == UNW_ARM_PC {
+                    return Err(EhabiError::InvalidOpcode(op));
+                }
+                vsp = new_registers[n] as u32;
+            }
+            // 10100nnn: pop r4 ~ r[4+nnn]
+            // 10101nnn: pop r4 ~ r[4+nnn], r14
+            0xa0..=0xaf => {
+                let mut mask = ((1 << ((op & 0x07) + 1)) - 1) << 4;
+                if op & 0x08 != 0 {
+                    mask |= 1 << UNW_ARM_LR;
+                }
+                pop_registers(&mut new_registers, &mut vsp, mask)?;
+            }
+            // 10110000: finish
+            0xb0 => break,
+            // 10110001 0000iiii: pop r0 ~ r3 under mask
+            0xb1 => {
+                let mask = opcodes.operand()?;
+                if mask == 0 || mask & 0xf0 != 0 {
+                    return Err(EhabiError::InvalidOpcode(op));
+                }
+                pop_registers(&mut new_registers, &mut vsp, u16::from(mask))?;
+            }
+            // 10110010 uleb128: vsp = vsp + 0x204 + (uleb128 << 2)
+            0xb2 => {
+                let offset = opcodes.uleb128()?;
+                vsp = vsp.wrapping_add(0x204).wrapping_add(offset << 2);
+            }
+            // 10110011 sssscccc: pop d[ssss] ~ d[ssss+cccc] saved by FSTMFDX,
+            // which stores an extra word after them.
+            0xb3 => {
+                let operand = opcodes.operand()?;
+                pop_vfp(&mut new_registers, &mut vsp, op, operand >> 4, (operand & 0x0f) + 1)?;
+                vsp = vsp.wrapping_add(4);
+            }
+            // 10111nnn: pop d8 ~ d[8+nnn] saved by FSTMFDX
+            0xb8..=0xbf => {
+                pop_vfp(&mut new_registers, &mut vsp, op, 8, (op & 0x07) + 1)?;
+                vsp = vsp.wrapping_add(4);
+            }
+            // 11000nnn (nnn != 6, 7): pop wR10 ~ wR[10+nnn]
+            // iWMMXt registers are not restored, they are only skipped.
+            0xc0..=0xc5 => vsp = vsp.wrapping_add(8 * (u32::from(op & 0x07) + 1)),
+            // 11000110 sssscccc: pop wR[ssss] ~ wR[ssss+cccc]
+            0xc6 => {
+                let operand = opcodes.operand()?;
+                vsp = vsp.wrapping_add(8 * (u32::from(operand & 0x0f) + 1));
+            }
+            // 11000111 0000iiii: pop wCGR0 ~ wCGR3 under mask
+            0xc7 => {
+                let mask = opcodes.operand()?;
+                if mask == 0 || mask & 0xf0 != 0 {
+                    return Err(EhabiError::InvalidOpcode(op));
+                }
+                vsp = vsp.wrapping_add(4 * mask.count_ones());
+            }
+            // 11001000 sssscccc: pop d[16+ssss] ~ d[16+ssss+cccc] saved by VPUSH
+            0xc8 => {
+                let operand = opcodes.operand()?;
+                pop_vfp(
+                    &mut new_registers,
+                    &mut vsp,
+                    op,
+                    16 + (operand >> 4),
+                    (operand & 0x0f) + 1,
+                )?;
+            }
+            // 11001001 sssscccc: pop d[ssss] ~ d[ssss+cccc] saved by VPUSH
+            0xc9 => {
+                let operand = opcodes.operand()?;
+                pop_vfp(&mut new_registers, &mut vsp, op, operand >> 4, (operand & 0x0f) + 1)?;
+            }
+            // 11010nnn: pop d8 ~ d[8+nnn] saved by VPUSH
+            0xd0..=0xd7 => pop_vfp(&mut new_registers, &mut vsp, op, 8, (op & 0x07) + 1)?,
+            // Spare.
+            _ => return Err(EhabiError::InvalidOpcode(op)),
+        }
+    }
+    if !pc_popped {
+        new_registers[UNW_ARM_PC] = new_registers[UNW_ARM_LR];
+    }
+    new_registers[UNW_REG_SP] = u64::from(vsp);
+    *registers = new_registers;
+    Ok(())
+}
+
+/// Pop the core registers in `mask` (bit n is rn) off `vsp`, lowest
+/// numbered first.
+fn pop_registers(registers: &mut Registers, vsp: &mut u32, mask: u16) -> Result<(), EhabiError> {
+    for n in 0..16 {
+        if mask & 1 << n != 0 {
+            registers[n] = u64::from(load_with_protect::<u32>(u64::from(*vsp))?);
+            *vsp = vsp.wrapping_add(4);
+        }
+    }
+    // Popping the SP switches the stack.
+    if mask & 1 << UNW_ARM_SP != 0 {
+        *vsp = registers[UNW_ARM_SP] as u32;
+    }
+    Ok(())
+}
+
+/// Pop `count` VFP registers from d`first` off `vsp`. `op` is the opcode, for
+/// the error of an invalid range.
+fn pop_vfp(registers: &mut Registers, vsp: &mut u32, op: u8, first: u8, count: u8) -> Result<(), EhabiError> {
+    if first + count > 32 {
+        return Err(EhabiError::InvalidOpcode(op));
+    }
+    for n in first..first + count {
+        let v = load_with_protect::<u64>(u64::from(*vsp))?;
+        registers.set_float_register(UNW_ARM_D0 + n as usize, f64::from_bits(v));
+        *vsp = vsp.wrapping_add(8);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registers::{UNW_ARM_D8, UNW_ARM_D9, UNW_ARM_R0, UNW_ARM_R2, UNW_ARM_R4, UNW_ARM_R5, UNW_ARM_R7};
+
+    const RETURN_ADDRESS: u64 = 0x2001;
+
+    /// Pack `ops` into words like an exidx or extab entry does.
+    fn pack(ops: &[u8]) -> Vec<u32> {
+        ops.chunks(4)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |word, (i, &b)| word | u32::from(b) << (24 - 8 * i))
+            })
+            .collect()
+    }
+
+    /// Run `ops` with the SP pointing to `stack`, LR = `RETURN_ADDRESS` and
+    /// r7 pointing to `stack[4]`. Returns the registers and the SP as an
+    /// offset from `stack`.
+    fn run_ops(ops: &[u8], stack: &[u32]) -> Result<(Registers, u64), EhabiError> {
+        let words = pack(ops);
+        let mut opcodes = Opcodes::new(words.as_ptr() as u64, 0, ops.len());
+        let base = stack.as_ptr() as u64;
+        let mut registers = Registers::default();
+        registers[UNW_REG_SP] = base;
+        registers[UNW_ARM_LR] = RETURN_ADDRESS;
+        registers[UNW_ARM_R7] = base + 16;
+        run(&mut opcodes, &mut registers)?;
+        let sp = registers.sp() - base;
+        Ok((registers, sp))
+    }
+
+    #[test]
+    fn test_opcodes() {
+        let words = pack(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+        let mut opcodes = Opcodes::new(words.as_ptr() as u64, 1, 5);
+        let mut bytes = vec![];
+        while let Some(b) = opcodes.next_byte().unwrap() {
+            bytes.push(b);
+        }
+        assert_eq!(bytes, vec![0x02, 0x03, 0x04, 0x05, 0x06]);
+
+        let words = pack(&[0x81, 0x01]);
+        let mut opcodes = Opcodes::new(words.as_ptr() as u64, 0, 2);
+        assert_eq!(opcodes.uleb128(), Ok(0x81));
+        assert_eq!(opcodes.operand(), Err(EhabiError::TruncatedOpcodes));
+    }
+
+    #[test]
+    fn test_vsp() {
+        let stack = [0; 64];
+        // vsp += 12, vsp -= 8, finish.
+        let (registers, sp) = run_ops(&[0x02, 0x41, 0xb0], &stack).unwrap();
+        assert_eq!(sp, 4);
+        assert_eq!(registers.pc(), RETURN_ADDRESS);
+        // The end implies finish.
+        assert_eq!(run_ops(&[0x3f], &stack).unwrap().1, 0x100);
+        // vsp = r7
+        assert_eq!(run_ops(&[0x97], &stack).unwrap().1, 16);
+        // vsp += 0x204 + (0x81 << 2)
+        assert_eq!(run_ops(&[0xb2, 0x81, 0x01], &stack).unwrap().1, 0x204 + 0x204);
+    }
+
+    #[test]
+    fn test_pop_registers() {
+        let stack = [0x40, 0x50, 0x2101, 0xff, 0xff];
+        // pop {r4, r5, lr}
+        let (registers, sp) = run_ops(&[0xa9], &stack).unwrap();
+        assert_eq!(sp, 12);
+        assert_eq!(registers[UNW_ARM_R4], 0x40);
+        assert_eq!(registers[UNW_ARM_R5], 0x50);
+        assert_eq!(registers[UNW_ARM_LR], 0x2101);
+        assert_eq!(registers.pc(), 0x2101);
+
+        // pop {r4, r5, pc}
+        let (registers, sp) = run_ops(&[0x88, 0x03], &stack).unwrap();
+        assert_eq!(sp, 12);
+        assert_eq!(registers[UNW_ARM_R5], 0x50);
+        assert_eq!(registers[UNW_ARM_LR], RETURN_ADDRESS);
+        assert_eq!(registers.pc(), 0x2101);
+
+        // pop {r0, r2}
+        let (registers, sp) = run_ops(&[0xb1, 0x05], &stack).unwrap();
+        assert_eq!(sp, 8);
+        assert_eq!(registers[UNW_ARM_R0], 0x40);
+        assert_eq!(registers[UNW_ARM_R2], 0x50);
+    }
+
+    #[test]
+    fn test_pop_sp() {
+        let mut stack = [0; 8];
+        stack[0] = 0x40;
+        let new_sp = &stack[6] as *const u32 as u32;
+        stack[1] = new_sp;
+        // pop {r4, sp}
+        let (registers, sp) = run_ops(&[0x82, 0x01], &stack).unwrap();
+        assert_eq!(registers[UNW_ARM_R4], 0x40);
+        assert_eq!(sp, 24);
+    }
+
+    #[test]
+    fn test_pop_vfp() {
+        let mut stack = [0; 8];
+        stack[0..2].copy_from_slice(&[1.5f64.to_bits() as u32, (1.5f64.to_bits() >> 32) as u32]);
+        stack[2..4].copy_from_slice(&[2.5f64.to_bits() as u32, (2.5f64.to_bits() >> 32) as u32]);
+        // vpop {d8, d9}
+        for ops in [&[0xd1][..], &[0xc9, 0x81]] {
+            let (registers, sp) = run_ops(ops, &stack).unwrap();
+            assert_eq!(sp, 16);
+            assert_eq!(registers.float_register(UNW_ARM_D8), 1.5);
+            assert_eq!(registers.float_register(UNW_ARM_D9), 2.5);
+        }
+        // fldmfdx {d8, d9}, with the extra word.
+        for ops in [&[0xb9][..], &[0xb3, 0x81]] {
+            let (registers, sp) = run_ops(ops, &stack).unwrap();
+            assert_eq!(sp, 20);
+            assert_eq!(registers.float_register(UNW_ARM_D9), 2.5);
+        }
+        // vpop {d16, d17}
+        assert_eq!(run_ops(&[0xc8, 0x01], &stack).unwrap().1, 16);
+        // vpop {d31, d32}
+        assert_eq!(
+            run_ops(&[0xc8, 0xf1], &stack).unwrap_err(),
+            EhabiError::InvalidOpcode(0xc8)
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let stack = [0; 4];
+        assert_eq!(run_ops(&[0x80, 0x00], &stack).unwrap_err(), EhabiError::CantUnwind);
+        assert_eq!(run_ops(&[0x80], &stack).unwrap_err(), EhabiError::TruncatedOpcodes);
+        assert_eq!(run_ops(&[0x9d], &stack).unwrap_err(), EhabiError::InvalidOpcode(0x9d));
+        assert_eq!(
+            run_ops(&[0xb1, 0x10], &stack).unwrap_err(),
+            EhabiError::InvalidOpcode(0xb1)
+        );
+        assert_eq!(run_ops(&[0xb4], &stack).unwrap_err(), EhabiError::InvalidOpcode(0xb4));
+        assert_eq!(run_ops(&[0xff], &stack).unwrap_err(), EhabiError::InvalidOpcode(0xff));
+    }
+}
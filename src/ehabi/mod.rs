@@ -0,0 +1,60 @@
+//! Unwinding with the exception handling ABI for the ARM architecture
+//! (EHABI), which 32-bit ARM uses instead of .eh_frame.
+//!
+//! The .ARM.exidx section is a table sorted by function address, each entry
+//! either holds up to three unwind opcodes inline or points to an entry in
+//! .ARM.extab with more of them. See "Exception Handling ABI for the Arm
+//! Architecture" (IHI 0038) for the formats.
+
+use crate::dyld::SectionInfo;
+use crate::registers::Registers;
+#[cfg(feature = "mem-protect")]
+use crate::utils::can_access;
+use crate::utils::load;
+
+mod instruction;
+mod table;
+
+#[derive(thiserror::Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EhabiError {
+    #[error("exidx entry not found")]
+    EntryNotFound,
+
+    #[error("function can not be unwound")]
+    CantUnwind,
+
+    #[error("unsupported personality routine index: {0}")]
+    UnsupportedPersonality(u32),
+
+    #[error("invalid unwind opcode: {0:#x}")]
+    InvalidOpcode(u8),
+
+    #[error("truncated unwind opcodes")]
+    TruncatedOpcodes,
+
+    #[error("unreadable address: {0:#x}")]
+    UnreadableAddress(u64),
+}
+
+/// Restores the registers of the caller of the frame at `pc`, which must not
+/// have the Thumb bit set.
+pub fn step(pc: u64, section: &SectionInfo, registers: &mut Registers) -> Result<(), EhabiError> {
+    let mut opcodes = table::search(pc, section.arm_exidx, section.arm_exidx_len)?;
+    instruction::run(&mut opcodes, registers)
+}
+
+#[inline]
+#[cfg(feature = "mem-protect")]
+fn load_with_protect<T: Copy>(address: u64) -> Result<T, EhabiError> {
+    if can_access(address) {
+        Ok(load(address))
+    } else {
+        Err(EhabiError::UnreadableAddress(address))
+    }
+}
+
+#[inline(always)]
+#[cfg(not(feature = "mem-protect"))]
+fn load_with_protect<T: Copy>(address: u64) -> Result<T, EhabiError> {
+    Ok(load(address))
+}
@@ -0,0 +1,155 @@
+use crate::ehabi::instruction::Opcodes;
+use crate::ehabi::{load_with_protect, EhabiError};
+
+/// Size of an .ARM.exidx entry: the function offset and the unwind data.
+const ENTRY_SIZE: u64 = 8;
+
+/// Unwind data of a function that can not be unwound.
+const EXIDX_CANTUNWIND: u32 = 1;
+
+/// Binary search the .ARM.exidx table [exidx, exidx + len) for the entry
+/// that covers `pc`, and returns its unwind opcodes.
+///
+/// Entries are sorted by function address and only record where a function
+/// starts, it ends where the next entry starts.
+pub fn search(pc: u64, exidx: u64, len: u64) -> Result<Opcodes, EhabiError> {
+    let mut low = 0;
+    let mut high = len / ENTRY_SIZE;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let entry = exidx + mid * ENTRY_SIZE;
+        if prel31(entry, load_with_protect(entry)?) <= pc {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    if low == 0 {
+        return Err(EhabiError::EntryNotFound);
+    }
+    decode(exidx + (low - 1) * ENTRY_SIZE)
+}
+
+/// Decode the unwind data of the .ARM.exidx entry at `entry`.
+fn decode(entry: u64) -> Result<Opcodes, EhabiError> {
+    let data = entry + 4;
+    let word: u32 = load_with_protect(data)?;
+    if word == EXIDX_CANTUNWIND {
+        return Err(EhabiError::CantUnwind);
+    }
+    if word & 0x8000_0000 != 0 {
+        // Opcodes inlined in the entry, which only fits personality routine 0.
+        return match (word >> 24) & 0x0f {
+            0 => compact(data, word),
+            index => Err(EhabiError::UnsupportedPersonality(index)),
+        };
+    }
+    let extab = prel31(data, word);
+    let word: u32 = load_with_protect(extab)?;
+    if word & 0x8000_0000 != 0 {
+        return compact(extab, word);
+    }
+    // A generic personality routine, e.g. __gxx_personality_v0. GCC and LLVM
+    // put the opcodes after its address, starting with the number of extra
+    // words like personality routines 1 and 2 do.
+    let data = extab + 4;
+    let word: u32 = load_with_protect(data)?;
+    Ok(Opcodes::new(data, 1, 3 + 4 * (word >> 24) as usize))
+}
+
+/// Returns the opcodes of the compact model entry at `address`, whose first
+/// word is `word`.
+fn compact(address: u64, word: u32) -> Result<Opcodes, EhabiError> {
+    match (word >> 24) & 0x0f {
+        // Su16: 3 opcodes follow the personality routine index.
+        0 => Ok(Opcodes::new(address, 1, 3)),
+        // Lu16 and Lu32: the number of extra words, then 2 opcodes. They only
+        // differ in the format of the LSDA.
+        1 | 2 => Ok(Opcodes::new(address, 2, 2 + 4 * ((word >> 16) & 0xff) as usize)),
+        index => Err(EhabiError::UnsupportedPersonality(index)),
+    }
+}
+
+/// Decode a prel31 value, a signed 31-bit offset from `address` in the low
+/// bits of `word`.
+#[inline]
+fn prel31(address: u64, word: u32) -> u64 {
+    let offset = ((word << 1) as i32) >> 1;
+    u64::from((address as u32).wrapping_add(offset as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_prel31(address: u64, target: u64) -> u32 {
+        (target as u32).wrapping_sub(address as u32) & 0x7fff_ffff
+    }
+
+    fn bytes(mut opcodes: Opcodes) -> Vec<u8> {
+        let mut bytes = vec![];
+        while let Some(b) = opcodes.next_byte().unwrap() {
+            bytes.push(b);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_prel31() {
+        assert_eq!(prel31(0x1000, 0x10), 0x1010);
+        assert_eq!(prel31(0x1000, 0x7fff_fff0), 0xff0);
+        // Bit 31 is not part of the offset.
+        assert_eq!(prel31(0x1000, 0xffff_fff0), 0xff0);
+        assert_eq!(encode_prel31(0x1000, 0xff0), 0x7fff_fff0);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut table = [0u32; 12];
+        let mut extab = [0u32; 8];
+        let exidx = table.as_ptr() as u64;
+        let extab_address = extab.as_ptr() as u64;
+        let function = |n: u64| exidx + 0x1000 + n * 0x100;
+        let entry = |n: usize| exidx + n as u64 * ENTRY_SIZE;
+
+        // Inline: pop {r4, lr}, finish, finish.
+        table[0] = encode_prel31(entry(0), function(0));
+        table[1] = 0x80a8_b0b0;
+        // Can't unwind.
+        table[2] = encode_prel31(entry(1), function(1));
+        table[3] = EXIDX_CANTUNWIND;
+        // Personality routine 0 in .ARM.extab.
+        table[4] = encode_prel31(entry(2), function(2));
+        table[5] = encode_prel31(entry(2) + 4, extab_address);
+        extab[0] = 0x8001_02b0;
+        // Personality routine 1 with an extra word.
+        table[6] = encode_prel31(entry(3), function(3));
+        table[7] = encode_prel31(entry(3) + 4, extab_address + 4);
+        extab[1] = 0x8101_0203;
+        extab[2] = 0x0405_0607;
+        // Generic personality routine with an extra word.
+        table[8] = encode_prel31(entry(4), function(4));
+        table[9] = encode_prel31(entry(4) + 4, extab_address + 12);
+        extab[3] = 0x7fff_0000; // personality routine
+        extab[4] = 0x0101_0203;
+        extab[5] = 0x0405_0607;
+        // Inline with personality routine 1.
+        table[10] = encode_prel31(entry(5), function(5));
+        table[11] = 0x8101_b0b0;
+        assert_eq!(extab.as_ptr() as u64, extab_address);
+
+        let len = table.len() as u64 * 4;
+        let find = |pc| search(pc, exidx, len).map(bytes);
+        assert_eq!(find(function(0) - 1), Err(EhabiError::EntryNotFound));
+        assert_eq!(find(function(0)), Ok(vec![0xa8, 0xb0, 0xb0]));
+        assert_eq!(find(function(1) - 1), Ok(vec![0xa8, 0xb0, 0xb0]));
+        assert_eq!(find(function(1) + 0x10), Err(EhabiError::CantUnwind));
+        assert_eq!(find(function(2) + 0x10), Ok(vec![0x01, 0x02, 0xb0]));
+        assert_eq!(find(function(3) + 0x10), Ok(vec![0x02, 0x03, 0x04, 0x05, 0x06, 0x07]));
+        assert_eq!(
+            find(function(4) + 0x10),
+            Ok(vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07])
+        );
+        assert_eq!(find(function(5) + 0x10), Err(EhabiError::UnsupportedPersonality(1)));
+    }
+}
@@ -0,0 +1,481 @@
+//! The unwinding interface of the Itanium C++ ABI, i.e. the `_Unwind_*`
+//! functions of libgcc_s and LLVM libunwind that C++ exceptions and Rust
+//! panics are built on.
+//!
+//! With the `itanium-abi` feature the functions are exported with their C
+//! names, and replace the system unwinder in programs linking this crate:
+//! the static definitions win over the ones of libgcc_s. A `cdylib` that
+//! depends on this crate with the feature is a library that can be linked
+//! instead of libgcc_s, or preloaded.
+//!
+//! Exceptions are raised in two phases. The search phase walks the stack,
+//! asking the personality routine of each frame whether it handles the
+//! exception, without changing anything. The cleanup phase walks it again,
+//! letting each frame run its cleanups, until the handler frame, whose
+//! landing pad is jumped to with [Registers::resume].
+//!
+//! Objects are looked up with `dl_iterate_phdr` at each step, so libraries
+//! loaded at any time are found, but none of this is async-signal-safe.
+//!
+//! See <https://itanium-cxx-abi.github.io/cxx-abi/abi-eh.html>.
+
+// The names and signatures are the ones of the C ABI, the functions take
+// raw pointers whose requirements are the ones of the ABI.
+#![allow(non_camel_case_types, non_snake_case, clippy::missing_safety_doc)]
+
+use crate::cursor::UnwindCursor;
+use crate::dwarf::{self, ProcInfo};
+use crate::dyld::find_section;
+use crate::registers::{unwind_init_registers, RegisterFile, Registers};
+use libc::{c_int, c_void};
+
+pub type _Unwind_Reason_Code = c_int;
+pub const _URC_NO_REASON: _Unwind_Reason_Code = 0;
+pub const _URC_FOREIGN_EXCEPTION_CAUGHT: _Unwind_Reason_Code = 1;
+pub const _URC_FATAL_PHASE2_ERROR: _Unwind_Reason_Code = 2;
+pub const _URC_FATAL_PHASE1_ERROR: _Unwind_Reason_Code = 3;
+pub const _URC_NORMAL_STOP: _Unwind_Reason_Code = 4;
+pub const _URC_END_OF_STACK: _Unwind_Reason_Code = 5;
+pub const _URC_HANDLER_FOUND: _Unwind_Reason_Code = 6;
+pub const _URC_INSTALL_CONTEXT: _Unwind_Reason_Code = 7;
+pub const _URC_CONTINUE_UNWIND: _Unwind_Reason_Code = 8;
+
+pub type _Unwind_Action = c_int;
+pub const _UA_SEARCH_PHASE: _Unwind_Action = 1;
+pub const _UA_CLEANUP_PHASE: _Unwind_Action = 2;
+pub const _UA_HANDLER_FRAME: _Unwind_Action = 4;
+pub const _UA_FORCE_UNWIND: _Unwind_Action = 8;
+pub const _UA_END_OF_STACK: _Unwind_Action = 16;
+
+pub type _Unwind_Exception_Class = u64;
+
+pub type _Unwind_Exception_Cleanup_Fn = Option<unsafe extern "C" fn(_Unwind_Reason_Code, *mut _Unwind_Exception)>;
+
+pub type _Unwind_Personality_Fn = unsafe extern "C" fn(
+    version: c_int,
+    actions: _Unwind_Action,
+    exception_class: _Unwind_Exception_Class,
+    exception_object: *mut _Unwind_Exception,
+    context: *mut _Unwind_Context,
+) -> _Unwind_Reason_Code;
+
+pub type _Unwind_Stop_Fn = unsafe extern "C" fn(
+    version: c_int,
+    actions: _Unwind_Action,
+    exception_class: _Unwind_Exception_Class,
+    exception_object: *mut _Unwind_Exception,
+    context: *mut _Unwind_Context,
+    stop_parameter: *mut c_void,
+) -> _Unwind_Reason_Code;
+
+pub type _Unwind_Trace_Fn =
+    unsafe extern "C" fn(context: *mut _Unwind_Context, trace_argument: *mut c_void) -> _Unwind_Reason_Code;
+
+/// The header of an exception object, which the language runtime allocates
+/// in front of its own data.
+#[repr(C, align(16))]
+#[derive(Debug)]
+pub struct _Unwind_Exception {
+    pub exception_class: _Unwind_Exception_Class,
+    pub exception_cleanup: _Unwind_Exception_Cleanup_Fn,
+    /// The stop function of a forced unwind, 0 otherwise.
+    pub private_1: usize,
+    /// The SP of the handler frame found by the search phase, or the
+    /// parameter of the stop function of a forced unwind.
+    pub private_2: usize,
+}
+
+/// A frame being unwound, passed to personality routines, stop functions
+/// and trace functions.
+pub struct _Unwind_Context {
+    registers: Registers,
+    cursor: UnwindCursor,
+    info: ProcInfo,
+}
+
+impl _Unwind_Context {
+    /// Creates a context whose first [step](Self::step) goes to the caller
+    /// of the frame `registers` were captured in.
+    fn new(registers: Registers) -> Self {
+        Self {
+            registers,
+            cursor: UnwindCursor::uncached(),
+            info: ProcInfo::default(),
+        }
+    }
+
+    /// Steps to the caller of the current frame. Returns `false` at the end
+    /// of the stack.
+    fn step(&mut self) -> crate::Result<bool> {
+        if !self.cursor.step(&mut self.registers)? {
+            return Ok(false);
+        }
+        self.info = self.cursor.proc_info(&self.registers)?.unwrap_or_default();
+        Ok(true)
+    }
+
+    fn personality(&self) -> Option<_Unwind_Personality_Fn> {
+        if self.info.personality == 0 {
+            return None;
+        }
+        Some(unsafe { std::mem::transmute::<usize, _Unwind_Personality_Fn>(self.info.personality as usize) })
+    }
+
+    /// The SP of the current frame, which identifies it between the phases.
+    fn frame_id(&self) -> usize {
+        self.registers.sp() as usize
+    }
+}
+
+/// Walks the stack from the caller of the frame of `registers`, looking for
+/// a frame that handles `exception_object`.
+unsafe fn search_phase(registers: &Registers, exception_object: *mut _Unwind_Exception) -> _Unwind_Reason_Code {
+    let mut context = _Unwind_Context::new(*registers);
+    let exception_class = (*exception_object).exception_class;
+    loop {
+        match context.step() {
+            Ok(true) => {}
+            Ok(false) => return _URC_END_OF_STACK,
+            Err(_) => return _URC_FATAL_PHASE1_ERROR,
+        }
+        let Some(personality) = context.personality() else {
+            continue;
+        };
+        match personality(1, _UA_SEARCH_PHASE, exception_class, exception_object, &mut context) {
+            _URC_HANDLER_FOUND => {
+                (*exception_object).private_2 = context.frame_id();
+                return _URC_NO_REASON;
+            }
+            _URC_CONTINUE_UNWIND => {}
+            _ => return _URC_FATAL_PHASE1_ERROR,
+        }
+    }
+}
+
+/// Walks the stack from the caller of the frame of `registers`, running the
+/// cleanups of each frame until the handler frame found by the search phase.
+/// Only returns on errors.
+unsafe fn cleanup_phase(registers: &Registers, exception_object: *mut _Unwind_Exception) -> _Unwind_Reason_Code {
+    let mut context = _Unwind_Context::new(*registers);
+    let exception_class = (*exception_object).exception_class;
+    loop {
+        match context.step() {
+            Ok(true) => {}
+            Ok(false) => return _URC_END_OF_STACK,
+            Err(_) => return _URC_FATAL_PHASE2_ERROR,
+        }
+        let Some(personality) = context.personality() else {
+            continue;
+        };
+        let handler_frame = context.frame_id() == (*exception_object).private_2;
+        let actions = if handler_frame {
+            _UA_CLEANUP_PHASE | _UA_HANDLER_FRAME
+        } else {
+            _UA_CLEANUP_PHASE
+        };
+        match personality(1, actions, exception_class, exception_object, &mut context) {
+            _URC_INSTALL_CONTEXT => context.registers.resume(),
+            // The handler frame must not change its mind.
+            _URC_CONTINUE_UNWIND if !handler_frame => {}
+            _ => return _URC_FATAL_PHASE2_ERROR,
+        }
+    }
+}
+
+/// Like [cleanup_phase], but there is no handler frame: `stop` is called for
+/// each frame before its cleanups run and decides when to stop. Only returns
+/// on errors.
+unsafe fn forced_cleanup_phase(
+    registers: &Registers,
+    exception_object: *mut _Unwind_Exception,
+    stop: _Unwind_Stop_Fn,
+    stop_parameter: *mut c_void,
+) -> _Unwind_Reason_Code {
+    let mut context = _Unwind_Context::new(*registers);
+    let exception_class = (*exception_object).exception_class;
+    let actions = _UA_FORCE_UNWIND | _UA_CLEANUP_PHASE;
+    loop {
+        match context.step() {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(_) => return _URC_FATAL_PHASE2_ERROR,
+        }
+        if stop(
+            1,
+            actions,
+            exception_class,
+            exception_object,
+            &mut context,
+            stop_parameter,
+        ) != _URC_NO_REASON
+        {
+            return _URC_FATAL_PHASE2_ERROR;
+        }
+        let Some(personality) = context.personality() else {
+            continue;
+        };
+        match personality(1, actions, exception_class, exception_object, &mut context) {
+            _URC_INSTALL_CONTEXT => context.registers.resume(),
+            _URC_CONTINUE_UNWIND => {}
+            _ => return _URC_FATAL_PHASE2_ERROR,
+        }
+    }
+    // The stop function isn't supposed to return at the end of the stack.
+    stop(
+        1,
+        actions | _UA_END_OF_STACK,
+        exception_class,
+        exception_object,
+        &mut context,
+        stop_parameter,
+    );
+    _URC_FATAL_PHASE2_ERROR
+}
+
+/// Raises an exception. Only returns if no frame handles it, or on errors.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+#[inline(never)]
+pub unsafe extern "C" fn _Unwind_RaiseException(exception_object: *mut _Unwind_Exception) -> _Unwind_Reason_Code {
+    let mut registers = Registers::default();
+    unwind_init_registers(&mut registers);
+    (*exception_object).private_1 = 0;
+    (*exception_object).private_2 = 0;
+    let code = search_phase(&registers, exception_object);
+    if code != _URC_NO_REASON {
+        return code;
+    }
+    cleanup_phase(&registers, exception_object)
+}
+
+/// Continues the cleanup phase after a landing pad that didn't handle the
+/// exception.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+#[inline(never)]
+pub unsafe extern "C" fn _Unwind_Resume(exception_object: *mut _Unwind_Exception) -> ! {
+    let mut registers = Registers::default();
+    unwind_init_registers(&mut registers);
+    if (*exception_object).private_1 != 0 {
+        let stop = std::mem::transmute::<usize, _Unwind_Stop_Fn>((*exception_object).private_1);
+        forced_cleanup_phase(
+            &registers,
+            exception_object,
+            stop,
+            (*exception_object).private_2 as *mut c_void,
+        );
+    } else {
+        cleanup_phase(&registers, exception_object);
+    }
+    // The search phase found a handler, so only a corrupted stack can get
+    // here.
+    std::process::abort()
+}
+
+/// Rethrows an exception caught by a handler, or continues a forced unwind.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_Resume_or_Rethrow(exception_object: *mut _Unwind_Exception) -> _Unwind_Reason_Code {
+    if (*exception_object).private_1 == 0 {
+        return _Unwind_RaiseException(exception_object);
+    }
+    _Unwind_Resume(exception_object)
+}
+
+/// Unwinds the stack without a search phase, running the cleanups of each
+/// frame, until `stop` says otherwise. Used e.g. for thread cancellation.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+#[inline(never)]
+pub unsafe extern "C" fn _Unwind_ForcedUnwind(
+    exception_object: *mut _Unwind_Exception,
+    stop: _Unwind_Stop_Fn,
+    stop_parameter: *mut c_void,
+) -> _Unwind_Reason_Code {
+    let mut registers = Registers::default();
+    unwind_init_registers(&mut registers);
+    (*exception_object).private_1 = stop as usize;
+    (*exception_object).private_2 = stop_parameter as usize;
+    forced_cleanup_phase(&registers, exception_object, stop, stop_parameter)
+}
+
+/// Destroys an exception object with its cleanup function, if any.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_DeleteException(exception_object: *mut _Unwind_Exception) {
+    if let Some(cleanup) = (*exception_object).exception_cleanup {
+        cleanup(_URC_FOREIGN_EXCEPTION_CAUGHT, exception_object);
+    }
+}
+
+/// Calls `trace` for each frame of the stack, starting from the caller.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+#[inline(never)]
+pub unsafe extern "C" fn _Unwind_Backtrace(
+    trace: _Unwind_Trace_Fn,
+    trace_argument: *mut c_void,
+) -> _Unwind_Reason_Code {
+    let mut registers = Registers::default();
+    unwind_init_registers(&mut registers);
+    let mut context = _Unwind_Context::new(registers);
+    loop {
+        match context.step() {
+            Ok(true) => {}
+            Ok(false) => return _URC_END_OF_STACK,
+            Err(_) => return _URC_FATAL_PHASE1_ERROR,
+        }
+        if trace(&mut context, trace_argument) != _URC_NO_REASON {
+            return _URC_FATAL_PHASE1_ERROR;
+        }
+    }
+}
+
+/// Returns general register `index`, by DWARF register number, or 0 if
+/// there is no such register.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetGR(context: *mut _Unwind_Context, index: c_int) -> usize {
+    match usize::try_from(index) {
+        Ok(n) => (*context).registers.get(n).unwrap_or(0) as usize,
+        Err(_) => 0,
+    }
+}
+
+/// Sets general register `index`, by DWARF register number, e.g. to pass
+/// the exception object to a landing pad. Unknown registers are ignored.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_SetGR(context: *mut _Unwind_Context, index: c_int, value: usize) {
+    if let Ok(n) = usize::try_from(index) {
+        let _ = (*context).registers.set(n, value as u64);
+    }
+}
+
+/// Returns the PC of the frame, which is a return address unless the frame
+/// was interrupted by a signal.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetIP(context: *mut _Unwind_Context) -> usize {
+    (*context).registers.pc() as usize
+}
+
+/// Like [_Unwind_GetIP], also telling whether the PC is the instruction
+/// that was executing rather than a return address.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetIPInfo(context: *mut _Unwind_Context, ip_before_insn: *mut c_int) -> usize {
+    *ip_before_insn = (*context).cursor.pc_is_exact() as c_int;
+    _Unwind_GetIP(context)
+}
+
+/// Sets the PC the frame resumes at, e.g. a landing pad.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_SetIP(context: *mut _Unwind_Context, value: usize) {
+    let context = &mut *context;
+    let _ = context.cursor.set_ip(&mut context.registers, value as u64);
+}
+
+/// Returns the CFA of the frame, the SP of its caller before the call, as
+/// computed by the CFI of the frame. Frames without CFI return their SP.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetCFA(context: *mut _Unwind_Context) -> usize {
+    let context = &*context;
+    match context.cursor.cfa(&context.registers) {
+        Ok(Some(cfa)) => cfa as usize,
+        _ => context.frame_id(),
+    }
+}
+
+/// Returns the LSDA of the function of the frame, or null.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetLanguageSpecificData(context: *mut _Unwind_Context) -> *mut c_void {
+    (*context).info.lsda as *mut c_void
+}
+
+/// Returns the address of the function of the frame.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetRegionStart(context: *mut _Unwind_Context) -> usize {
+    (*context).info.start_ip as usize
+}
+
+/// Returns the base of DW_EH_PE_datarel pointers in the LSDA, which is only
+/// used on i386.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetDataRelBase(_context: *mut _Unwind_Context) -> usize {
+    0
+}
+
+/// Returns the base of DW_EH_PE_textrel pointers in the LSDA, which no
+/// supported architecture uses.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_GetTextRelBase(_context: *mut _Unwind_Context) -> usize {
+    0
+}
+
+/// Returns the address of the function containing `pc`, or null if it has
+/// no unwind information.
+#[cfg_attr(feature = "itanium-abi", no_mangle)]
+pub unsafe extern "C" fn _Unwind_FindEnclosingFunction(pc: *mut c_void) -> *mut c_void {
+    let pc = pc as u64;
+    match find_section(pc).map(|s| dwarf::proc_info(pc, &s)) {
+        Some(Ok(info)) => info.start_ip as *mut c_void,
+        _ => std::ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn collect(context: *mut _Unwind_Context, pcs: *mut c_void) -> _Unwind_Reason_Code {
+        let pcs = &mut *(pcs as *mut Vec<(usize, usize)>);
+        pcs.push((_Unwind_GetIP(context), _Unwind_GetRegionStart(context)));
+        _URC_NO_REASON
+    }
+
+    #[inline(never)]
+    fn backtrace() -> Vec<(usize, usize)> {
+        let mut pcs = vec![];
+        let code = unsafe { _Unwind_Backtrace(collect, &mut pcs as *mut _ as *mut c_void) };
+        assert_eq!(code, _URC_END_OF_STACK);
+        pcs
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let pcs = backtrace();
+        assert!(pcs.len() > 2);
+        // The first frame is the caller of `_Unwind_Backtrace`.
+        let (pc, start) = pcs[0];
+        assert_eq!(start, backtrace as fn() -> Vec<(usize, usize)> as usize);
+        assert_eq!(
+            unsafe { _Unwind_FindEnclosingFunction(pc as *mut c_void) },
+            start as *mut c_void
+        );
+        assert!(pcs[1].1 <= pcs[1].0);
+    }
+
+    /// The SP and the CFA of the frames.
+    unsafe extern "C" fn collect_cfa(context: *mut _Unwind_Context, frames: *mut c_void) -> _Unwind_Reason_Code {
+        #[cfg(target_arch = "aarch64")]
+        const SP: c_int = crate::registers::UNW_ARM64_SP as c_int;
+        #[cfg(target_arch = "x86_64")]
+        const SP: c_int = crate::registers::UNW_X86_64_RSP as c_int;
+        let frames = &mut *(frames as *mut Vec<(usize, usize)>);
+        frames.push((_Unwind_GetGR(context, SP), _Unwind_GetCFA(context)));
+        _URC_NO_REASON
+    }
+
+    #[test]
+    fn test_cfa() {
+        let mut frames: Vec<(usize, usize)> = vec![];
+        let code = unsafe { _Unwind_Backtrace(collect_cfa, &mut frames as *mut _ as *mut c_void) };
+        assert_eq!(code, _URC_END_OF_STACK);
+        assert!(frames.len() > 2);
+        // The CFA is the SP of the caller, above the SP of the frame.
+        for pair in frames[..3].windows(2) {
+            let ((sp, cfa), (caller_sp, _)) = (pair[0], pair[1]);
+            assert!(cfa > sp);
+            assert_eq!(cfa, caller_sp);
+        }
+    }
+
+    #[test]
+    fn test_find_enclosing_function() {
+        let f = test_find_enclosing_function as fn() as usize;
+        let pc = (f + 1) as *mut c_void;
+        assert_eq!(unsafe { _Unwind_FindEnclosingFunction(pc) }, f as *mut c_void);
+        assert!(unsafe { _Unwind_FindEnclosingFunction(std::ptr::null_mut()) }.is_null());
+    }
+}
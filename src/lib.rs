@@ -4,21 +4,19 @@
 //!
 //! Simple usage:
 //! ```
-//! fn main() {
-//!     // Do stack backtrace.
-//!     let mut pcs = vec![];
-//!     unwind::trace(|registers| {
-//!         pcs.push(registers.pc());
-//!         true
-//!     }).unwrap();
+//! // Do stack backtrace.
+//! let mut pcs = vec![];
+//! unwind::trace(|registers| {
+//!     pcs.push(registers.pc());
+//!     true
+//! }).unwrap();
 //!
-//!     // Resolve addresses into symbols and display.
-//!     for pc in pcs {
-//!         println!("{:#x}:", pc);
-//!         backtrace::resolve(pc as _, |s| {
-//!             println!("    {:?}", s.name());
-//!         });
-//!     }
+//! // Resolve addresses into symbols and display.
+//! for pc in pcs {
+//!     println!("{:#x}:", pc);
+//!     backtrace::resolve(pc as _, |s| {
+//!         println!("    {:?}", s.name());
+//!     });
 //! }
 //! ```
 //!
@@ -52,6 +50,9 @@
 //!
 //! For more examples, please refer to ../examples/.
 
+pub mod arch;
+#[cfg(all(feature = "backtrace-compat", target_os = "linux"))]
+pub mod backtrace;
 #[cfg(all(target_arch = "x86_64", target_os = "macos"))]
 mod compact;
 mod cursor;
@@ -59,13 +60,27 @@ mod cursor;
 mod dwarf;
 #[cfg(not(all(target_arch = "aarch64", target_os = "macos")))]
 mod dyld;
+#[cfg(all(target_arch = "arm", target_os = "linux"))]
+mod ehabi;
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub mod itanium;
 mod registers;
+#[cfg(all(feature = "symbolize", target_os = "linux"))]
+pub mod symbolize;
 mod utils;
 
 pub use cursor::UnwindCursor;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub use dwarf::fuzzing;
 #[cfg(not(all(target_arch = "aarch64", target_os = "macos")))]
-pub use dwarf::DwarfError;
-pub use registers::{unwind_init_registers, Registers};
+pub use dwarf::{
+    proc_info_eh_frame, step_eh_frame, Action, Actions, AddressSpace, CallSite, CallSites, DwarfError, EhFrameSections,
+    LandingPad, Lsda, Primitive, ProcInfo, SliceAddressSpace,
+};
+#[cfg(all(target_arch = "arm", target_os = "linux"))]
+pub use ehabi::EhabiError;
+pub use registers::{unwind_init_registers, GenericRegisters, RegisterFile, RegisterIter, Registers};
 
 /// A result type that wraps [Error].
 pub type Result<T> = std::result::Result<T, Error>;
@@ -77,12 +92,19 @@ pub enum Error {
     #[error("dwarf: {0}")]
     Dwarf(#[from] dwarf::DwarfError),
 
+    #[cfg(all(target_arch = "arm", target_os = "linux"))]
+    #[error("ehabi: {0}")]
+    Ehabi(#[from] ehabi::EhabiError),
+
     #[cfg(target_os = "linux")]
     #[error("read maps: {0}")]
     ReadMaps(std::io::Error),
 
     #[error("invalid ucontext")]
     InvalidUcontext,
+
+    #[error("invalid register {0}")]
+    InvalidRegister(usize),
 }
 
 /// Inspects the current call-stack, passing all active frames into the closure
@@ -116,6 +138,16 @@ where
 /// The closure's return value is an indication of whether the backtrace should
 /// continue. A return value of `false` will terminate the backtrace and return
 /// immediately.
+///
+/// # Stack usage
+///
+/// Signal handlers often run on a small alternate stack (see `sigaltstack(2)`).
+/// In release builds this function needs at most 6 KiB of stack on x86_64.
+/// That excludes the signal frame pushed by the kernel, which takes another
+/// 1-4 KiB depending on the CPU's extended state. Unoptimized builds need
+/// about three times more. Other architectures are unmeasured.
+/// `tests/sigaltstack.rs` traces on a 16 KiB alternate stack and checks that
+/// bound, both tripled in debug builds.
 pub fn trace_from_ucontext<F>(ucontext: *mut libc::c_void, mut f: F) -> Result<bool>
 where
     F: FnMut(&Registers) -> bool,
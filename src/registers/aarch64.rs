@@ -43,9 +43,25 @@ pub struct Registers {
     sp: u64,
     pc: u64,
     ra_sign_state: u64,
-    d: [f64; 32], // d0 ~ d31
+    v: [u128; 32], // v0 ~ v31, the low 64 bits are d0 ~ d31
 }
 
+// `unwind_init_registers` stores v0 ~ v31 at this offset.
+const _: () = assert!(std::mem::offset_of!(Registers, v) == 0x110);
+
+/// Magic number of the fpsimd_context record in `mcontext_t.__reserved`,
+/// see arch/arm64/include/uapi/asm/sigcontext.h in the Linux kernel.
+#[cfg(target_os = "linux")]
+const FPSIMD_MAGIC: u32 = 0x46508001;
+
+/// Size of the fpsimd_context record: header, fpsr, fpcr and v0 ~ v31.
+#[cfg(target_os = "linux")]
+const FPSIMD_CONTEXT_SIZE: usize = 8 + 8 + 32 * 16;
+
+/// Size of `mcontext_t.__reserved`.
+#[cfg(target_os = "linux")]
+const RESERVED_SIZE: usize = 4096;
+
 impl Index<usize> for Registers {
     type Output = u64;
 
@@ -96,9 +112,42 @@ impl Registers {
         registers.sp = mcontext.sp;
         registers.pc = mcontext.pc;
         registers.ra_sign_state = mcontext.pstate;
+        let reserved = unsafe { std::slice::from_raw_parts(reserved(ucontext), RESERVED_SIZE) };
+        if let Some(v) = fpsimd_vregs(reserved) {
+            registers.v = v;
+        }
         Some(registers)
     }
 
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point. `pstate` is left alone, and so are v0 ~ v31
+    /// if `ucontext` has no fpsimd record.
+    #[cfg(target_os = "linux")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        {
+            let mcontext = unsafe { &mut (*ucontext).uc_mcontext };
+            mcontext.regs[..29].copy_from_slice(&self.x);
+            mcontext.regs[29] = self.fp;
+            mcontext.regs[30] = self.lr;
+            mcontext.sp = self.sp;
+            mcontext.pc = self.pc;
+        }
+        let reserved = unsafe { std::slice::from_raw_parts_mut(reserved(ucontext), RESERVED_SIZE) };
+        if let Some(offset) = fpsimd_vregs_offset(reserved) {
+            for (chunk, v) in reserved[offset..offset + 32 * 16].chunks_exact_mut(16).zip(self.v) {
+                chunk.copy_from_slice(&v.to_ne_bytes());
+            }
+        }
+        Ok(())
+    }
+
     /// Initialize `Registers` with value from `ucontext`.
     #[cfg(target_os = "macos")]
     pub fn from_ucontext(ucontext: *mut libc::c_void) -> Option<Self> {
@@ -118,11 +167,37 @@ impl Registers {
                 sp: (*mcontext).__ss.__sp,
                 pc: (*mcontext).__ss.__pc,
                 ra_sign_state: 0,
-                d: [0f64; 32],
+                v: (*mcontext).__ns.__v,
             })
         }
     }
 
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point.
+    #[cfg(target_os = "macos")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        unsafe {
+            let mcontext = (*ucontext).uc_mcontext;
+            if mcontext.is_null() {
+                return Err(crate::Error::InvalidUcontext);
+            }
+            (*mcontext).__ss.__x = self.x;
+            (*mcontext).__ss.__fp = self.fp;
+            (*mcontext).__ss.__lr = self.lr;
+            (*mcontext).__ss.__sp = self.sp;
+            (*mcontext).__ss.__pc = self.pc;
+            (*mcontext).__ns.__v = self.v;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn max_register_num() -> usize {
         UNW_ARM64_MAX_REG_NUM
@@ -130,55 +205,46 @@ impl Registers {
 
     #[inline]
     pub fn valid_register(n: usize) -> bool {
-        if n == UNW_REG_IP || n == UNW_REG_SP {
-            return true;
-        }
-        if n > UNW_ARM64_MAX_REG_NUM {
-            return false;
-        }
-        if n == UNW_ARM64_RA_SIGN_STATE {
-            return true;
-        }
-        if n > 32 && n < 64 {
-            return false;
-        }
-        true
+        // d0 ~ d31 are only float and vector registers.
+        n == UNW_REG_IP || n == UNW_REG_SP || n <= UNW_ARM64_PC || n == UNW_ARM64_RA_SIGN_STATE
     }
 
     #[inline]
     pub fn valid_float_register(n: usize) -> bool {
-        if n >= UNW_ARM64_D0 && n <= UNW_ARM64_D31 {
-            true
-        } else {
-            false
-        }
+        (UNW_ARM64_D0..=UNW_ARM64_D31).contains(&n)
     }
 
+    /// q0 ~ q31 share the DWARF numbers of d0 ~ d31. Only the low 64 bits
+    /// are callee-saved, so CFI describes them as float registers.
     #[inline]
-    pub fn valid_vector_register(_n: usize) -> bool {
-        false
+    pub fn valid_vector_register(n: usize) -> bool {
+        Self::valid_float_register(n)
     }
 
     #[inline]
     pub fn float_register(&self, n: usize) -> f64 {
         assert!(Self::valid_float_register(n));
-        self.d[n - UNW_ARM64_D0]
+        f64::from_bits(self.v[n - UNW_ARM64_D0] as u64)
     }
 
+    /// Like writing a d register, this clears the high 64 bits of the
+    /// q register.
     #[inline]
     pub fn set_float_register(&mut self, n: usize, v: f64) {
         assert!(Self::valid_float_register(n));
-        self.d[n - UNW_ARM64_D0] = v;
+        self.v[n - UNW_ARM64_D0] = v.to_bits() as u128;
     }
 
     #[inline]
-    pub fn vector_register(&self, _n: usize) -> bool {
-        unreachable!();
+    pub fn vector_register(&self, n: usize) -> u128 {
+        assert!(Self::valid_vector_register(n));
+        self.v[n - UNW_ARM64_D0]
     }
 
     #[inline]
-    pub fn set_vector_register(&mut self, _n: usize, _v: u128) {
-        unreachable!();
+    pub fn set_vector_register(&mut self, n: usize, v: u128) {
+        assert!(Self::valid_vector_register(n));
+        self.v[n - UNW_ARM64_D0] = v;
     }
 
     /// Get the value of the PC (Program Counter) register.
@@ -193,3 +259,124 @@ impl Registers {
         self[UNW_REG_SP]
     }
 }
+
+/// Returns the `__reserved` area of the `mcontext_t` in `ucontext`, which is
+/// private in libc. It follows `pstate` and is aligned to 16 bytes.
+#[cfg(target_os = "linux")]
+unsafe fn reserved(ucontext: *mut libc::ucontext_t) -> *mut u8 {
+    let offset = (std::mem::offset_of!(libc::mcontext_t, pstate) + 8 + 15) & !15;
+    (std::ptr::addr_of_mut!((*ucontext).uc_mcontext) as *mut u8).add(offset)
+}
+
+/// Find the fpsimd_context record in `reserved`, the `__reserved` area of
+/// `mcontext_t`, and returns v0 ~ v31.
+///
+/// The area holds a list of records, each starting with a magic number and
+/// its size, and terminated by a zero record. The kernel always puts
+/// fpsimd_context first, other records (ESR, SVE, ...) are skipped.
+#[cfg(target_os = "linux")]
+fn fpsimd_vregs(reserved: &[u8]) -> Option<[u128; 32]> {
+    let offset = fpsimd_vregs_offset(reserved)?;
+    let mut v = [0; 32];
+    for (n, chunk) in reserved[offset..offset + 32 * 16].chunks_exact(16).enumerate() {
+        v[n] = u128::from_ne_bytes(chunk.try_into().unwrap());
+    }
+    Some(v)
+}
+
+/// Finds the fpsimd record in `__reserved` and returns the offset of v0 in it.
+#[cfg(target_os = "linux")]
+fn fpsimd_vregs_offset(reserved: &[u8]) -> Option<usize> {
+    let mut head = 0;
+    while head + 8 <= reserved.len() {
+        let magic = u32::from_ne_bytes(reserved[head..head + 4].try_into().unwrap());
+        let size = u32::from_ne_bytes(reserved[head + 4..head + 8].try_into().unwrap()) as usize;
+        if magic == 0 || size < 8 || size > reserved.len() - head {
+            return None;
+        }
+        if magic == FPSIMD_MAGIC && size >= FPSIMD_CONTEXT_SIZE {
+            return Some(head + 16);
+        }
+        head += size;
+    }
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    const ESR_MAGIC: u32 = 0x45535201;
+
+    fn record(reserved: &mut Vec<u8>, magic: u32, body: &[u8]) {
+        reserved.extend_from_slice(&magic.to_ne_bytes());
+        reserved.extend_from_slice(&(8 + body.len() as u32).to_ne_bytes());
+        reserved.extend_from_slice(body);
+    }
+
+    fn fpsimd_body() -> Vec<u8> {
+        let mut body = vec![0; 8]; // fpsr, fpcr
+        for n in 0..32u128 {
+            body.extend_from_slice(&(n << 64 | n).to_ne_bytes());
+        }
+        body
+    }
+
+    #[test]
+    fn test_fpsimd_vregs() {
+        let mut reserved = vec![];
+        record(&mut reserved, FPSIMD_MAGIC, &fpsimd_body());
+        record(&mut reserved, ESR_MAGIC, &[0; 8]);
+        reserved.resize(RESERVED_SIZE, 0);
+        let v = fpsimd_vregs(&reserved).unwrap();
+        assert_eq!(v[0], 0);
+        assert_eq!(v[31], 31 << 64 | 31);
+
+        // Unknown records before it are skipped.
+        let mut reserved = vec![];
+        record(&mut reserved, ESR_MAGIC, &[0; 8]);
+        record(&mut reserved, FPSIMD_MAGIC, &fpsimd_body());
+        reserved.resize(RESERVED_SIZE, 0);
+        assert_eq!(fpsimd_vregs(&reserved).unwrap()[8], 8 << 64 | 8);
+
+        // Missing, truncated or malformed.
+        assert_eq!(fpsimd_vregs(&[0; RESERVED_SIZE]), None);
+        let mut reserved = vec![];
+        record(&mut reserved, FPSIMD_MAGIC, &fpsimd_body()[..100]);
+        reserved.resize(RESERVED_SIZE, 0);
+        assert_eq!(fpsimd_vregs(&reserved), None);
+        let mut reserved = vec![];
+        record(&mut reserved, FPSIMD_MAGIC, &fpsimd_body());
+        assert_eq!(fpsimd_vregs(&reserved[..100]), None);
+    }
+
+    #[test]
+    fn test_write_to_ucontext() {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        let mut reserved = vec![];
+        record(&mut reserved, FPSIMD_MAGIC, &fpsimd_body());
+        unsafe { std::ptr::copy_nonoverlapping(reserved.as_ptr(), super::reserved(&mut ucontext), reserved.len()) };
+        let mut registers = Registers::default();
+        for n in 0..=UNW_ARM64_PC {
+            registers[n] = 0x100 + n as u64;
+        }
+        registers.set_vector_register(UNW_ARM64_D8, 1 << 64 | 1.5f64.to_bits() as u128);
+        registers.write_to_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(ucontext.uc_mcontext.regs[UNW_ARM64_FP], 0x11d);
+        assert_eq!(ucontext.uc_mcontext.sp, 0x11f);
+        assert_eq!(ucontext.uc_mcontext.pc, 0x120);
+
+        let restored = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(restored.to_string(), registers.to_string());
+        assert!(registers.write_to_ucontext(std::ptr::null_mut()).is_err());
+    }
+
+    #[test]
+    fn test_float_and_vector_registers() {
+        let mut registers = Registers::default();
+        registers.set_vector_register(UNW_ARM64_D8, 1 << 64 | 1.5f64.to_bits() as u128);
+        assert_eq!(registers.float_register(UNW_ARM64_D8), 1.5);
+        registers.set_float_register(UNW_ARM64_D8, 2.5);
+        assert_eq!(registers.vector_register(UNW_ARM64_D8), 2.5f64.to_bits() as u128);
+    }
+}
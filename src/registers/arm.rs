@@ -0,0 +1,354 @@
+use crate::registers::consts::*;
+use std::ops::{Index, IndexMut};
+
+/// `Registers` holds the register context for a specific platform (OS+ISA).
+///
+/// We can use [unwind_init_registers] to initialize `Registers` based on
+/// the current execution context:
+/// ```
+/// use unwind::{unwind_init_registers, Registers};
+///
+/// fn main() {
+///     let mut registers = Registers::default();
+///     unsafe { unwind_init_registers(&mut registers as _) };
+///     assert_ne!(registers.pc(), 0);
+/// }
+/// ```
+///
+/// But more suitable for this crate usage scenario is to use an existing
+/// `ucontext`. Usually the kernel provides an `ucontext` for the signal
+/// handler:
+/// ```ignore
+/// extern "C" fn signal_handler(_: libc::c_int, _: *mut libc::siginfo_t, ucontext: *mut libc::c_void) {
+///     let registers = Registers::from_ucontext(ucontext);
+///     assert_ne!(registers.pc(), 0);
+/// }
+/// ```
+///
+/// We can restore `Registers` through [UnwindCursor] to get the execution
+/// context of the **parent** function:
+/// ```ignore
+/// let mut cursor = UnwindCursor::new();
+/// cursor.step(&mut registers).unwrap();
+/// ```
+///
+/// [UnwindCursor]: crate::cursor::UnwindCursor
+/// [unwind_init_registers]: crate::registers::unwind_init_registers
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Registers {
+    // Registers are 32-bit, each is kept zero-extended in a 64-bit slot.
+    r: [u64; 16], // r0 ~ r15, r13 is sp, r14 is lr and r15 is pc
+    d: [f64; 32], // d0 ~ d31
+}
+
+// `unwind_init_registers` stores d0 ~ d15 at this offset.
+const _: () = assert!(std::mem::offset_of!(Registers, d) == 128);
+
+/// Magic number of the vfp_sigframe record in `ucontext_t.uc_regspace`,
+/// see arch/arm/include/asm/ucontext.h in the Linux kernel.
+#[cfg(target_os = "linux")]
+const VFP_MAGIC: u32 = 0x56465001;
+
+/// Size of the vfp_sigframe record: header, d0 ~ d31, fpscr and the
+/// exception registers, padded to 8 bytes.
+#[cfg(target_os = "linux")]
+const VFP_SIGFRAME_SIZE: usize = 0x120;
+
+impl Index<usize> for Registers {
+    type Output = u64;
+
+    fn index(&self, index: usize) -> &u64 {
+        assert!(Self::valid_register(index));
+        match index {
+            UNW_REG_IP => &self.r[UNW_ARM_PC],
+            UNW_REG_SP => &self.r[UNW_ARM_SP],
+            0..=15 => &self.r[index],
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl IndexMut<usize> for Registers {
+    fn index_mut(&mut self, index: usize) -> &mut u64 {
+        assert!(Self::valid_register(index));
+        match index {
+            UNW_REG_IP => &mut self.r[UNW_ARM_PC],
+            UNW_REG_SP => &mut self.r[UNW_ARM_SP],
+            0..=15 => &mut self.r[index],
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Registers {
+    /// Initialize `Registers` with value from `ucontext`.
+    #[cfg(target_os = "linux")]
+    pub fn from_ucontext(ucontext: *mut libc::c_void) -> Option<Self> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return None;
+        }
+        let mcontext = unsafe { &(*ucontext).uc_mcontext };
+        let r = [
+            mcontext.arm_r0,
+            mcontext.arm_r1,
+            mcontext.arm_r2,
+            mcontext.arm_r3,
+            mcontext.arm_r4,
+            mcontext.arm_r5,
+            mcontext.arm_r6,
+            mcontext.arm_r7,
+            mcontext.arm_r8,
+            mcontext.arm_r9,
+            mcontext.arm_r10,
+            mcontext.arm_fp,
+            mcontext.arm_ip,
+            mcontext.arm_sp,
+            mcontext.arm_lr,
+            mcontext.arm_pc,
+        ];
+        let regspace = unsafe {
+            let regspace = std::ptr::addr_of!((*ucontext).uc_regspace);
+            std::slice::from_raw_parts(regspace as *const u8, std::mem::size_of_val(&*regspace))
+        };
+        Some(Self {
+            r: r.map(u64::from),
+            d: vfp_dregs(regspace).unwrap_or_default(),
+        })
+    }
+
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point. d0 ~ d31 are left alone if `ucontext` has no
+    /// VFP record.
+    #[cfg(target_os = "linux")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        let mcontext = unsafe { &mut (*ucontext).uc_mcontext };
+        let r = [
+            &mut mcontext.arm_r0,
+            &mut mcontext.arm_r1,
+            &mut mcontext.arm_r2,
+            &mut mcontext.arm_r3,
+            &mut mcontext.arm_r4,
+            &mut mcontext.arm_r5,
+            &mut mcontext.arm_r6,
+            &mut mcontext.arm_r7,
+            &mut mcontext.arm_r8,
+            &mut mcontext.arm_r9,
+            &mut mcontext.arm_r10,
+            &mut mcontext.arm_fp,
+            &mut mcontext.arm_ip,
+            &mut mcontext.arm_sp,
+            &mut mcontext.arm_lr,
+            &mut mcontext.arm_pc,
+        ];
+        for (reg, v) in r.into_iter().zip(self.r) {
+            *reg = v as _;
+        }
+        let regspace = unsafe {
+            let regspace = std::ptr::addr_of_mut!((*ucontext).uc_regspace);
+            std::slice::from_raw_parts_mut(regspace as *mut u8, std::mem::size_of_val(&*regspace))
+        };
+        if let Some(offset) = vfp_dregs_offset(regspace) {
+            for (chunk, d) in regspace[offset..offset + 32 * 8].chunks_exact_mut(8).zip(self.d) {
+                chunk.copy_from_slice(&d.to_ne_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn max_register_num() -> usize {
+        UNW_ARM_MAX_REG_NUM
+    }
+
+    #[inline]
+    pub fn valid_register(n: usize) -> bool {
+        if n == UNW_REG_IP || n == UNW_REG_SP {
+            return true;
+        }
+        n <= UNW_ARM_R15
+    }
+
+    #[inline]
+    pub fn valid_float_register(n: usize) -> bool {
+        (UNW_ARM_D0..=UNW_ARM_D31).contains(&n)
+    }
+
+    #[inline]
+    pub fn valid_vector_register(_n: usize) -> bool {
+        false
+    }
+
+    #[inline]
+    pub fn float_register(&self, n: usize) -> f64 {
+        assert!(Self::valid_float_register(n));
+        self.d[n - UNW_ARM_D0]
+    }
+
+    #[inline]
+    pub fn set_float_register(&mut self, n: usize, v: f64) {
+        assert!(Self::valid_float_register(n));
+        self.d[n - UNW_ARM_D0] = v;
+    }
+
+    #[inline]
+    pub fn vector_register(&self, _n: usize) -> u128 {
+        unreachable!();
+    }
+
+    #[inline]
+    pub fn set_vector_register(&mut self, _n: usize, _v: u128) {
+        unreachable!();
+    }
+
+    /// Get the value of the PC (Program Counter) register.
+    ///
+    /// Return addresses in Thumb code have the lowest bit set.
+    #[inline]
+    pub fn pc(&self) -> u64 {
+        self[UNW_REG_IP]
+    }
+
+    /// Get the value of the SP (Stack Pointer) register.
+    #[inline]
+    pub fn sp(&self) -> u64 {
+        self[UNW_REG_SP]
+    }
+}
+
+/// Find the vfp_sigframe record in `regspace`, the `uc_regspace` area of
+/// `ucontext_t`, and returns d0 ~ d31.
+///
+/// The area holds a list of records, each starting with a magic number and
+/// its size, and terminated by a zero record. An iWMMXt record may come
+/// before the VFP one and is skipped.
+#[cfg(target_os = "linux")]
+fn vfp_dregs(regspace: &[u8]) -> Option<[f64; 32]> {
+    let offset = vfp_dregs_offset(regspace)?;
+    let mut d = [0.0; 32];
+    for (n, chunk) in regspace[offset..offset + 32 * 8].chunks_exact(8).enumerate() {
+        d[n] = f64::from_ne_bytes(chunk.try_into().unwrap());
+    }
+    Some(d)
+}
+
+/// Finds the vfp_sigframe record in `regspace` and returns the offset of d0
+/// in it.
+#[cfg(target_os = "linux")]
+fn vfp_dregs_offset(regspace: &[u8]) -> Option<usize> {
+    let mut head = 0;
+    while head + 8 <= regspace.len() {
+        let magic = u32::from_ne_bytes(regspace[head..head + 4].try_into().unwrap());
+        let size = u32::from_ne_bytes(regspace[head + 4..head + 8].try_into().unwrap()) as usize;
+        if magic == 0 || size < 8 || size > regspace.len() - head {
+            return None;
+        }
+        if magic == VFP_MAGIC && size >= VFP_SIGFRAME_SIZE {
+            return Some(head + 8);
+        }
+        head += size;
+    }
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    const IWMMXT_MAGIC: u32 = 0x12ef842a;
+
+    fn record(regspace: &mut Vec<u8>, magic: u32, body: &[u8]) {
+        regspace.extend_from_slice(&magic.to_ne_bytes());
+        regspace.extend_from_slice(&(8 + body.len() as u32).to_ne_bytes());
+        regspace.extend_from_slice(body);
+    }
+
+    fn vfp_body() -> Vec<u8> {
+        let mut body = vec![];
+        for n in 0..32 {
+            body.extend_from_slice(&(n as f64).to_ne_bytes());
+        }
+        body.resize(VFP_SIGFRAME_SIZE - 8, 0); // fpscr, fpexc, ...
+        body
+    }
+
+    #[test]
+    fn test_vfp_dregs() {
+        let mut regspace = vec![];
+        record(&mut regspace, VFP_MAGIC, &vfp_body());
+        regspace.resize(512, 0);
+        let d = vfp_dregs(&regspace).unwrap();
+        assert_eq!(d[0], 0.0);
+        assert_eq!(d[31], 31.0);
+
+        // Unknown records before it are skipped.
+        let mut regspace = vec![];
+        record(&mut regspace, IWMMXT_MAGIC, &[0; 0x98 - 8]);
+        record(&mut regspace, VFP_MAGIC, &vfp_body());
+        regspace.resize(512, 0);
+        assert_eq!(vfp_dregs(&regspace).unwrap()[8], 8.0);
+
+        // Missing, truncated or malformed.
+        assert_eq!(vfp_dregs(&[0; 512]), None);
+        let mut regspace = vec![];
+        record(&mut regspace, VFP_MAGIC, &vfp_body()[..100]);
+        regspace.resize(512, 0);
+        assert_eq!(vfp_dregs(&regspace), None);
+        let mut regspace = vec![];
+        record(&mut regspace, VFP_MAGIC, &vfp_body());
+        assert_eq!(vfp_dregs(&regspace[..100]), None);
+    }
+
+    #[test]
+    fn test_from_ucontext() {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        ucontext.uc_mcontext.arm_r4 = 0x44;
+        ucontext.uc_mcontext.arm_sp = 0x1000;
+        ucontext.uc_mcontext.arm_lr = 0x2001;
+        ucontext.uc_mcontext.arm_pc = 0x3000;
+        let registers = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(registers[UNW_ARM_R4], 0x44);
+        assert_eq!(registers.sp(), 0x1000);
+        assert_eq!(registers[UNW_ARM_LR], 0x2001);
+        assert_eq!(registers.pc(), 0x3000);
+        assert_eq!(registers.float_register(UNW_ARM_D8), 0.0);
+
+        let mut regspace = vec![];
+        record(&mut regspace, VFP_MAGIC, &vfp_body());
+        let bytes =
+            unsafe { std::slice::from_raw_parts_mut(ucontext.uc_regspace.as_mut_ptr() as *mut u8, regspace.len()) };
+        bytes.copy_from_slice(&regspace);
+        let registers = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(registers.float_register(UNW_ARM_D8), 8.0);
+    }
+
+    #[test]
+    fn test_write_to_ucontext() {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        let mut regspace = vec![];
+        record(&mut regspace, VFP_MAGIC, &vfp_body());
+        let bytes =
+            unsafe { std::slice::from_raw_parts_mut(ucontext.uc_regspace.as_mut_ptr() as *mut u8, regspace.len()) };
+        bytes.copy_from_slice(&regspace);
+        let mut registers = Registers::default();
+        for n in 0..=UNW_ARM_R15 {
+            registers[n] = 0xffff_ff00 + n as u64;
+        }
+        registers.set_float_register(UNW_ARM_D8, -8.5);
+        registers.write_to_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(ucontext.uc_mcontext.arm_fp, 0xffff_ff0b);
+        assert_eq!(ucontext.uc_mcontext.arm_pc, 0xffff_ff0f);
+
+        let restored = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(restored.to_string(), registers.to_string());
+        assert!(registers.write_to_ucontext(std::ptr::null_mut()).is_err());
+    }
+}
@@ -4,6 +4,19 @@
 pub const UNW_REG_IP: usize = usize::MAX; // instruction pointer
 pub const UNW_REG_SP: usize = usize::MAX - 1; // stack pointer
 
+// X86 register numbers, as used by .eh_frame on Linux. (Darwin swaps ESP
+// and EBP.)
+pub const UNW_X86_EAX: usize = 0;
+pub const UNW_X86_ECX: usize = 1;
+pub const UNW_X86_EDX: usize = 2;
+pub const UNW_X86_EBX: usize = 3;
+pub const UNW_X86_ESP: usize = 4;
+pub const UNW_X86_EBP: usize = 5;
+pub const UNW_X86_ESI: usize = 6;
+pub const UNW_X86_EDI: usize = 7;
+pub const UNW_X86_EIP: usize = 8;
+pub const UNW_X86_MAX_REG_NUM: usize = 8;
+
 // X86_64 register numbers.
 pub const UNW_X86_64_RAX: usize = 0;
 pub const UNW_X86_64_RDX: usize = 1;
@@ -113,3 +126,128 @@ pub const UNW_ARM64_D29: usize = 93;
 pub const UNW_ARM64_D30: usize = 94;
 pub const UNW_ARM64_D31: usize = 95;
 pub const UNW_ARM64_MAX_REG_NUM: usize = 95;
+
+// ARM register numbers.
+pub const UNW_ARM_R0: usize = 0;
+pub const UNW_ARM_R1: usize = 1;
+pub const UNW_ARM_R2: usize = 2;
+pub const UNW_ARM_R3: usize = 3;
+pub const UNW_ARM_R4: usize = 4;
+pub const UNW_ARM_R5: usize = 5;
+pub const UNW_ARM_R6: usize = 6;
+pub const UNW_ARM_R7: usize = 7;
+pub const UNW_ARM_R8: usize = 8;
+pub const UNW_ARM_R9: usize = 9;
+pub const UNW_ARM_R10: usize = 10;
+pub const UNW_ARM_R11: usize = 11;
+pub const UNW_ARM_R12: usize = 12;
+pub const UNW_ARM_R13: usize = 13;
+pub const UNW_ARM_SP: usize = 13;
+pub const UNW_ARM_R14: usize = 14;
+pub const UNW_ARM_LR: usize = 14;
+pub const UNW_ARM_R15: usize = 15;
+pub const UNW_ARM_PC: usize = 15;
+// reserved block
+pub const UNW_ARM_D0: usize = 256;
+pub const UNW_ARM_D1: usize = 257;
+pub const UNW_ARM_D2: usize = 258;
+pub const UNW_ARM_D3: usize = 259;
+pub const UNW_ARM_D4: usize = 260;
+pub const UNW_ARM_D5: usize = 261;
+pub const UNW_ARM_D6: usize = 262;
+pub const UNW_ARM_D7: usize = 263;
+pub const UNW_ARM_D8: usize = 264;
+pub const UNW_ARM_D9: usize = 265;
+pub const UNW_ARM_D10: usize = 266;
+pub const UNW_ARM_D11: usize = 267;
+pub const UNW_ARM_D12: usize = 268;
+pub const UNW_ARM_D13: usize = 269;
+pub const UNW_ARM_D14: usize = 270;
+pub const UNW_ARM_D15: usize = 271;
+pub const UNW_ARM_D16: usize = 272;
+pub const UNW_ARM_D17: usize = 273;
+pub const UNW_ARM_D18: usize = 274;
+pub const UNW_ARM_D19: usize = 275;
+pub const UNW_ARM_D20: usize = 276;
+pub const UNW_ARM_D21: usize = 277;
+pub const UNW_ARM_D22: usize = 278;
+pub const UNW_ARM_D23: usize = 279;
+pub const UNW_ARM_D24: usize = 280;
+pub const UNW_ARM_D25: usize = 281;
+pub const UNW_ARM_D26: usize = 282;
+pub const UNW_ARM_D27: usize = 283;
+pub const UNW_ARM_D28: usize = 284;
+pub const UNW_ARM_D29: usize = 285;
+pub const UNW_ARM_D30: usize = 286;
+pub const UNW_ARM_D31: usize = 287;
+pub const UNW_ARM_MAX_REG_NUM: usize = 287;
+
+// RISC-V register numbers.
+pub const UNW_RISCV_X0: usize = 0;
+pub const UNW_RISCV_X1: usize = 1;
+pub const UNW_RISCV_RA: usize = 1;
+pub const UNW_RISCV_X2: usize = 2;
+pub const UNW_RISCV_SP: usize = 2;
+pub const UNW_RISCV_X3: usize = 3;
+pub const UNW_RISCV_X4: usize = 4;
+pub const UNW_RISCV_X5: usize = 5;
+pub const UNW_RISCV_X6: usize = 6;
+pub const UNW_RISCV_X7: usize = 7;
+pub const UNW_RISCV_X8: usize = 8;
+pub const UNW_RISCV_FP: usize = 8;
+pub const UNW_RISCV_X9: usize = 9;
+pub const UNW_RISCV_X10: usize = 10;
+pub const UNW_RISCV_X11: usize = 11;
+pub const UNW_RISCV_X12: usize = 12;
+pub const UNW_RISCV_X13: usize = 13;
+pub const UNW_RISCV_X14: usize = 14;
+pub const UNW_RISCV_X15: usize = 15;
+pub const UNW_RISCV_X16: usize = 16;
+pub const UNW_RISCV_X17: usize = 17;
+pub const UNW_RISCV_X18: usize = 18;
+pub const UNW_RISCV_X19: usize = 19;
+pub const UNW_RISCV_X20: usize = 20;
+pub const UNW_RISCV_X21: usize = 21;
+pub const UNW_RISCV_X22: usize = 22;
+pub const UNW_RISCV_X23: usize = 23;
+pub const UNW_RISCV_X24: usize = 24;
+pub const UNW_RISCV_X25: usize = 25;
+pub const UNW_RISCV_X26: usize = 26;
+pub const UNW_RISCV_X27: usize = 27;
+pub const UNW_RISCV_X28: usize = 28;
+pub const UNW_RISCV_X29: usize = 29;
+pub const UNW_RISCV_X30: usize = 30;
+pub const UNW_RISCV_X31: usize = 31;
+pub const UNW_RISCV_F0: usize = 32;
+pub const UNW_RISCV_F1: usize = 33;
+pub const UNW_RISCV_F2: usize = 34;
+pub const UNW_RISCV_F3: usize = 35;
+pub const UNW_RISCV_F4: usize = 36;
+pub const UNW_RISCV_F5: usize = 37;
+pub const UNW_RISCV_F6: usize = 38;
+pub const UNW_RISCV_F7: usize = 39;
+pub const UNW_RISCV_F8: usize = 40;
+pub const UNW_RISCV_F9: usize = 41;
+pub const UNW_RISCV_F10: usize = 42;
+pub const UNW_RISCV_F11: usize = 43;
+pub const UNW_RISCV_F12: usize = 44;
+pub const UNW_RISCV_F13: usize = 45;
+pub const UNW_RISCV_F14: usize = 46;
+pub const UNW_RISCV_F15: usize = 47;
+pub const UNW_RISCV_F16: usize = 48;
+pub const UNW_RISCV_F17: usize = 49;
+pub const UNW_RISCV_F18: usize = 50;
+pub const UNW_RISCV_F19: usize = 51;
+pub const UNW_RISCV_F20: usize = 52;
+pub const UNW_RISCV_F21: usize = 53;
+pub const UNW_RISCV_F22: usize = 54;
+pub const UNW_RISCV_F23: usize = 55;
+pub const UNW_RISCV_F24: usize = 56;
+pub const UNW_RISCV_F25: usize = 57;
+pub const UNW_RISCV_F26: usize = 58;
+pub const UNW_RISCV_F27: usize = 59;
+pub const UNW_RISCV_F28: usize = 60;
+pub const UNW_RISCV_F29: usize = 61;
+pub const UNW_RISCV_F30: usize = 62;
+pub const UNW_RISCV_F31: usize = 63;
+pub const UNW_RISCV_MAX_REG_NUM: usize = 63;
@@ -0,0 +1,308 @@
+use crate::arch::{Arch, NativeArch};
+use crate::registers::consts::*;
+use crate::registers::{RegisterFile, Registers};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// One more than the highest general register number of all architectures,
+/// the aarch64 RA_SIGN_STATE pseudo register.
+const GENERAL_REGISTERS: usize = UNW_ARM64_RA_SIGN_STATE + 1;
+
+/// Where the program counter is kept on architectures that don't give it a
+/// DWARF register number.
+const PC_SLOT: usize = GENERAL_REGISTERS;
+
+/// Number of floating-point or vector registers of all architectures.
+const EXTENDED_REGISTERS: usize = 32;
+
+/// `GenericRegisters` holds the registers of any supported architecture `A`,
+/// which doesn't have to be the one of the current process.
+///
+/// It is what the DWARF unwinder restores when unwinding stacks that were
+/// captured elsewhere, e.g. a core dump or a stack snapshot taken on an
+/// aarch64 device and analyzed on an x86_64 host:
+/// ```
+/// use unwind::arch::{Aarch64, UNW_ARM64_LR, UNW_REG_IP, UNW_REG_SP};
+/// use unwind::GenericRegisters;
+///
+/// let mut registers = GenericRegisters::<Aarch64>::default();
+/// registers[UNW_REG_IP] = 0x1000;
+/// registers[UNW_REG_SP] = 0x7fff_0000;
+/// registers[UNW_ARM64_LR] = 0x2000;
+/// assert_eq!(registers.pc(), 0x1000);
+/// ```
+///
+/// Values narrower than 64 bits, like the registers of 32-bit architectures,
+/// are kept zero-extended. Floating-point registers are the low 64 bits of
+/// the vector registers they alias, if any.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GenericRegisters<A: Arch> {
+    general: [u64; GENERAL_REGISTERS + 1],
+    extended: [u128; EXTENDED_REGISTERS],
+    arch: PhantomData<A>,
+}
+
+impl<A: Arch> Default for GenericRegisters<A> {
+    fn default() -> Self {
+        Self {
+            general: [0; GENERAL_REGISTERS + 1],
+            extended: [0; EXTENDED_REGISTERS],
+            arch: PhantomData,
+        }
+    }
+}
+
+impl<A: Arch> Index<usize> for GenericRegisters<A> {
+    type Output = u64;
+
+    fn index(&self, index: usize) -> &u64 {
+        &self.general[Self::slot(index)]
+    }
+}
+
+impl<A: Arch> IndexMut<usize> for GenericRegisters<A> {
+    fn index_mut(&mut self, index: usize) -> &mut u64 {
+        &mut self.general[Self::slot(index)]
+    }
+}
+
+impl<A: Arch> GenericRegisters<A> {
+    #[inline]
+    fn slot(index: usize) -> usize {
+        match index {
+            UNW_REG_IP => A::PC.unwrap_or(PC_SLOT),
+            UNW_REG_SP => A::SP,
+            _ => {
+                assert!(A::valid_register(index));
+                index
+            }
+        }
+    }
+
+    #[inline]
+    pub fn max_register_num() -> usize {
+        A::MAX_REGISTER_NUM
+    }
+
+    #[inline]
+    pub fn valid_register(n: usize) -> bool {
+        n == UNW_REG_IP || n == UNW_REG_SP || A::valid_register(n)
+    }
+
+    #[inline]
+    pub fn valid_float_register(n: usize) -> bool {
+        A::valid_float_register(n)
+    }
+
+    #[inline]
+    pub fn valid_vector_register(n: usize) -> bool {
+        A::valid_vector_register(n)
+    }
+
+    #[inline]
+    pub fn float_register(&self, n: usize) -> f64 {
+        assert!(Self::valid_float_register(n));
+        f64::from_bits(self.extended[n - A::FLOAT_REGISTERS.start] as u64)
+    }
+
+    /// Like writing a d register on aarch64, this clears the high 64 bits of
+    /// the vector register it aliases.
+    #[inline]
+    pub fn set_float_register(&mut self, n: usize, v: f64) {
+        assert!(Self::valid_float_register(n));
+        self.extended[n - A::FLOAT_REGISTERS.start] = v.to_bits() as u128;
+    }
+
+    #[inline]
+    pub fn vector_register(&self, n: usize) -> u128 {
+        assert!(Self::valid_vector_register(n));
+        self.extended[n - A::VECTOR_REGISTERS.start]
+    }
+
+    #[inline]
+    pub fn set_vector_register(&mut self, n: usize, v: u128) {
+        assert!(Self::valid_vector_register(n));
+        self.extended[n - A::VECTOR_REGISTERS.start] = v;
+    }
+
+    /// Get the value of the PC (Program Counter) register.
+    #[inline]
+    pub fn pc(&self) -> u64 {
+        self[UNW_REG_IP]
+    }
+
+    /// Get the value of the SP (Stack Pointer) register.
+    #[inline]
+    pub fn sp(&self) -> u64 {
+        self[UNW_REG_SP]
+    }
+}
+
+impl<A: Arch> RegisterFile for GenericRegisters<A> {
+    type Arch = A;
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        Self::valid_register(n)
+    }
+
+    #[inline]
+    fn valid_float_register(n: usize) -> bool {
+        Self::valid_float_register(n)
+    }
+
+    #[inline]
+    fn valid_vector_register(n: usize) -> bool {
+        Self::valid_vector_register(n)
+    }
+
+    #[inline]
+    fn float_register(&self, n: usize) -> f64 {
+        self.float_register(n)
+    }
+
+    #[inline]
+    fn set_float_register(&mut self, n: usize, v: f64) {
+        self.set_float_register(n, v)
+    }
+
+    #[inline]
+    fn vector_register(&self, n: usize) -> u128 {
+        self.vector_register(n)
+    }
+
+    #[inline]
+    fn set_vector_register(&mut self, n: usize, v: u128) {
+        self.set_vector_register(n, v)
+    }
+}
+
+impl<A: Arch> fmt::Display for GenericRegisters<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        super::fmt_registers(self, f)
+    }
+}
+
+impl From<Registers> for GenericRegisters<NativeArch> {
+    fn from(registers: Registers) -> Self {
+        let mut generic = Self::default();
+        for n in 0..=NativeArch::MAX_REGISTER_NUM {
+            if NativeArch::valid_float_register(n) {
+                generic.set_float_register(n, registers.float_register(n));
+            }
+            if NativeArch::valid_vector_register(n) {
+                generic.set_vector_register(n, registers.vector_register(n));
+            }
+            if NativeArch::valid_register(n) {
+                generic[n] = registers[n];
+            }
+        }
+        generic[UNW_REG_IP] = registers.pc();
+        generic[UNW_REG_SP] = registers.sp();
+        generic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::{Aarch64, Arm, Riscv64, X86, X86_64};
+
+    fn check<A: Arch>() {
+        for n in 0..=A::MAX_REGISTER_NUM {
+            if A::valid_register(n) {
+                assert!(n < GENERAL_REGISTERS, "{:?}: {}", A::default(), n);
+            }
+            if A::valid_float_register(n) {
+                assert!(n - A::FLOAT_REGISTERS.start < EXTENDED_REGISTERS);
+            }
+            if A::valid_vector_register(n) {
+                assert!(n - A::VECTOR_REGISTERS.start < EXTENDED_REGISTERS);
+            }
+        }
+        let mut registers = GenericRegisters::<A>::default();
+        registers[UNW_REG_IP] = 0x1000;
+        registers[UNW_REG_SP] = 0x2000;
+        assert_eq!(registers.pc(), 0x1000);
+        assert_eq!(registers.sp(), 0x2000);
+        assert_eq!(registers[A::SP], 0x2000);
+        if let Some(pc) = A::PC {
+            assert_eq!(registers[pc], 0x1000);
+        }
+    }
+
+    #[test]
+    fn test_generic_registers() {
+        check::<X86>();
+        check::<X86_64>();
+        check::<Aarch64>();
+        check::<Arm>();
+        check::<Riscv64>();
+
+        // The program counter has no number on riscv64, and doesn't alias x0.
+        let mut registers = GenericRegisters::<Riscv64>::default();
+        registers[UNW_REG_IP] = 0x1000;
+        assert_eq!(registers[UNW_RISCV_X0], 0);
+
+        // d registers are the low half of v registers on aarch64.
+        let mut registers = GenericRegisters::<Aarch64>::default();
+        registers.set_vector_register(UNW_ARM64_D8, u128::MAX);
+        assert_eq!(registers.float_register(UNW_ARM64_D8).to_bits(), u64::MAX);
+        registers.set_float_register(UNW_ARM64_D8, 1.5);
+        assert_eq!(registers.vector_register(UNW_ARM64_D8), 1.5f64.to_bits() as u128);
+        assert!(!GenericRegisters::<Aarch64>::valid_register(UNW_ARM64_D8));
+    }
+
+    #[test]
+    fn test_get_set_and_iter() {
+        let mut registers = GenericRegisters::<Aarch64>::default();
+        registers.set(UNW_ARM64_FP, 0x10).unwrap();
+        assert_eq!(registers.get(UNW_ARM64_FP), Some(0x10));
+        assert_eq!(registers.get(40), None);
+        assert!(matches!(registers.set(40, 0), Err(crate::Error::InvalidRegister(40))));
+        assert!(registers.set(UNW_ARM64_D8, 0).is_err());
+        let numbers: Vec<usize> = registers.iter().map(|(n, _)| n).collect();
+        assert_eq!(numbers.len(), 34);
+        assert_eq!(numbers[33], UNW_ARM64_RA_SIGN_STATE);
+        assert_eq!(registers.iter().nth(UNW_ARM64_FP), Some((UNW_ARM64_FP, 0x10)));
+
+        // The program counter comes last where it has no number.
+        let mut registers = GenericRegisters::<Riscv64>::default();
+        registers[UNW_REG_IP] = 0x1000;
+        assert_eq!(registers.iter().count(), 33);
+        assert_eq!(registers.iter().last(), Some((UNW_REG_IP, 0x1000)));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut registers = GenericRegisters::<X86>::default();
+        registers[UNW_X86_EBP] = 0xbeef;
+        let s = registers.to_string();
+        assert_eq!(s.lines().count(), 9);
+        assert_eq!(s.lines().nth(UNW_X86_EBP), Some("ebp   0x0000beef"));
+        assert!(!s.ends_with('\n'));
+
+        let mut registers = GenericRegisters::<Aarch64>::default();
+        registers.set_float_register(UNW_ARM64_D8, 1.5);
+        let s = registers.to_string();
+        assert_eq!(s.lines().count(), 34 + 32);
+        assert!(s.contains("\nv8    0x00000000000000003ff8000000000000\n"));
+
+        let mut registers = GenericRegisters::<Riscv64>::default();
+        registers.set_float_register(UNW_RISCV_F8, 1.5);
+        let s = registers.to_string();
+        assert!(s.contains("\npc    0x0000000000000000\n"));
+        assert!(s.contains("\nf8    0x3ff8000000000000 (1.5)\n"));
+    }
+
+    #[test]
+    fn test_from_native() {
+        let mut registers = Registers::default();
+        unsafe { crate::registers::unwind_init_registers(&mut registers as _) };
+        let generic = GenericRegisters::from(registers);
+        assert_eq!(generic.pc(), registers.pc());
+        assert_eq!(generic.sp(), registers.sp());
+        assert_eq!(generic[NativeArch::RA], registers[NativeArch::RA]);
+    }
+}
@@ -1,6 +1,18 @@
-mod consts;
+use crate::arch::{Arch, NativeArch};
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+pub(crate) mod consts;
 pub use consts::*;
 
+mod generic;
+pub use generic::GenericRegisters;
+
+#[cfg(target_arch = "x86")]
+mod x86;
+#[cfg(target_arch = "x86")]
+pub use x86::*;
+
 #[cfg(target_arch = "x86_64")]
 mod x64;
 #[cfg(target_arch = "x86_64")]
@@ -11,6 +23,16 @@ mod aarch64;
 #[cfg(target_arch = "aarch64")]
 pub use aarch64::*;
 
+#[cfg(target_arch = "arm")]
+mod arm;
+#[cfg(target_arch = "arm")]
+pub use arm::*;
+
+#[cfg(target_arch = "riscv64")]
+mod riscv64;
+#[cfg(target_arch = "riscv64")]
+pub use riscv64::*;
+
 extern "C" {
     /// Get the register context of the current thread stack and save it in `Registers`.
     ///
@@ -19,6 +41,178 @@ extern "C" {
     ///
     /// The definition of `Registers` also varies with the OS and ISA.
     pub fn unwind_init_registers(registers: *mut Registers);
+
+    /// Restores the registers in `Registers` and jumps to its pc, see
+    /// [Registers::resume].
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn unwind_resume_registers(registers: *const Registers) -> !;
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+impl Registers {
+    /// Resume execution with these registers, like `unw_resume` of libunwind.
+    ///
+    /// All general and vector registers, the stack pointer last, are restored
+    /// and execution continues at the pc. It is meant for registers unwound
+    /// with [UnwindCursor](crate::UnwindCursor) from the current thread's
+    /// stack, which makes the frame unwound to return again from the call it
+    /// was in, with whatever the return value registers hold.
+    ///
+    /// Scratch registers used for the jump are clobbered: x16 and x30 on
+    /// aarch64, where x30 ends up holding the pc, and the flags on x86_64.
+    ///
+    /// # Safety
+    ///
+    /// The stack pointer must point into a frame of the current thread that
+    /// is still live, i.e. an ancestor of the caller. The frames skipped over
+    /// are discarded without running destructors, and the resumed code must
+    /// be fine with the register values it gets.
+    pub unsafe fn resume(&self) -> ! {
+        unwind_resume_registers(self)
+    }
+}
+
+/// A register set the DWARF unwinder can restore, either the native
+/// [Registers] or [GenericRegisters] of any supported architecture.
+///
+/// Registers are indexed by DWARF register number, [UNW_REG_IP] and
+/// [UNW_REG_SP] select the program counter and the stack pointer whatever
+/// their number is.
+pub trait RegisterFile: Copy + Index<usize, Output = u64> + IndexMut<usize> {
+    /// The architecture the registers belong to.
+    type Arch: Arch;
+
+    fn valid_register(n: usize) -> bool;
+    fn valid_float_register(n: usize) -> bool;
+    fn valid_vector_register(n: usize) -> bool;
+    fn float_register(&self, n: usize) -> f64;
+    fn set_float_register(&mut self, n: usize, v: f64);
+    fn vector_register(&self, n: usize) -> u128;
+    fn set_vector_register(&mut self, n: usize, v: u128);
+
+    /// Returns general register `n`, or `None` if the architecture doesn't
+    /// have it.
+    #[inline]
+    fn get(&self, n: usize) -> Option<u64> {
+        if Self::valid_register(n) {
+            Some(self[n])
+        } else {
+            None
+        }
+    }
+
+    /// Sets general register `n` to `v`, unlike indexing this fails instead
+    /// of panicking if the architecture doesn't have it.
+    #[inline]
+    fn set(&mut self, n: usize, v: u64) -> crate::Result<()> {
+        if !Self::valid_register(n) {
+            return Err(crate::Error::InvalidRegister(n));
+        }
+        self[n] = v;
+        Ok(())
+    }
+
+    /// Iterates over the general registers and their values in DWARF
+    /// register number order. The program counter comes last as
+    /// [UNW_REG_IP] on architectures where it has no number.
+    #[inline]
+    fn iter(&self) -> RegisterIter<'_, Self> {
+        RegisterIter { registers: self, n: 0 }
+    }
+}
+
+/// An iterator over the general registers of a [RegisterFile], see
+/// [RegisterFile::iter].
+#[derive(Debug, Clone)]
+pub struct RegisterIter<'a, R> {
+    registers: &'a R,
+    n: usize,
+}
+
+impl<R: RegisterFile> Iterator for RegisterIter<'_, R> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<(usize, u64)> {
+        let max = R::Arch::MAX_REGISTER_NUM;
+        while self.n <= max {
+            let n = self.n;
+            self.n += 1;
+            if R::Arch::valid_register(n) {
+                return Some((n, self.registers[n]));
+            }
+        }
+        if self.n == max + 1 && R::Arch::PC.is_none() {
+            self.n += 1;
+            return Some((UNW_REG_IP, self.registers[UNW_REG_IP]));
+        }
+        None
+    }
+}
+
+/// Writes one register per line: the general registers, then floating-point
+/// and vector registers, which are shown as vectors if they are both.
+fn fmt_registers<R: RegisterFile>(registers: &R, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = |n| R::Arch::register_name(n).unwrap_or("?");
+    let width = 2 + 2 * R::Arch::POINTER_SIZE as usize;
+    let mut separator = "";
+    for (n, v) in registers.iter() {
+        write!(f, "{}{:<5} {:#0width$x}", separator, name(n), v, width = width)?;
+        separator = "\n";
+    }
+    for n in 0..=R::Arch::MAX_REGISTER_NUM {
+        if R::valid_vector_register(n) {
+            write!(f, "\n{:<5} {:#034x}", name(n), registers.vector_register(n))?;
+        } else if R::valid_float_register(n) {
+            let v = registers.float_register(n);
+            write!(f, "\n{:<5} {:#018x} ({:?})", name(n), v.to_bits(), v)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_registers(self, f)
+    }
+}
+
+impl RegisterFile for Registers {
+    type Arch = NativeArch;
+
+    #[inline]
+    fn valid_register(n: usize) -> bool {
+        Registers::valid_register(n)
+    }
+
+    #[inline]
+    fn valid_float_register(n: usize) -> bool {
+        Registers::valid_float_register(n)
+    }
+
+    #[inline]
+    fn valid_vector_register(n: usize) -> bool {
+        Registers::valid_vector_register(n)
+    }
+
+    #[inline]
+    fn float_register(&self, n: usize) -> f64 {
+        Registers::float_register(self, n)
+    }
+
+    #[inline]
+    fn set_float_register(&mut self, n: usize, v: f64) {
+        Registers::set_float_register(self, n, v)
+    }
+
+    #[inline]
+    fn vector_register(&self, n: usize) -> u128 {
+        Registers::vector_register(self, n)
+    }
+
+    #[inline]
+    fn set_vector_register(&mut self, n: usize, v: u128) {
+        Registers::set_vector_register(self, n, v)
+    }
 }
 
 #[cfg(test)]
@@ -34,4 +228,146 @@ mod tests {
         assert!(registers.pc() > 0);
         assert!(registers.sp() > 0);
     }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_unwind_init_registers_xmm() {
+        let mut registers = Registers::default();
+        let v = 0x0f0e_0d0c_0b0a_0908_0706_0504_0302_0100u128;
+        unsafe {
+            std::arch::asm!(
+                "movups ({v}), %xmm15",
+                "call {f}",
+                v = in(reg) &v,
+                f = sym unwind_init_registers,
+                in("rdi") &mut registers,
+                clobber_abi("C"),
+                options(att_syntax),
+            );
+        }
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM15), v);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_vector_registers() {
+        assert!(!Registers::valid_vector_register(UNW_X86_64_RIP));
+        assert!(Registers::valid_vector_register(UNW_X86_64_XMM0));
+        assert!(Registers::valid_vector_register(UNW_X86_64_XMM15));
+        assert!(!Registers::valid_vector_register(UNW_X86_64_XMM15 + 1));
+
+        let mut registers = Registers::default();
+        registers.set_vector_register(UNW_X86_64_XMM3, u128::MAX - 3);
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM3), u128::MAX - 3);
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM4), 0);
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn test_from_ucontext_xmm() {
+        let mut fpstate: libc::_libc_fpstate = unsafe { std::mem::zeroed() };
+        fpstate._xmm[0].element = [1, 2, 3, 4];
+        fpstate._xmm[15].element = [u32::MAX, 0, 0, 0x8000_0000];
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        ucontext.uc_mcontext.gregs[libc::REG_RIP as usize] = 0x1234;
+        let registers = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(registers.pc(), 0x1234);
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM0), 0);
+
+        ucontext.uc_mcontext.fpregs = &mut fpstate;
+        let registers = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(
+            registers.vector_register(UNW_X86_64_XMM0),
+            0x4_0000_0003_0000_0002_0000_0001
+        );
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM1), 0);
+        assert_eq!(registers.vector_register(UNW_X86_64_XMM15), 1 << 127 | 0xffff_ffff);
+    }
+
+    #[test]
+    fn test_register_file() {
+        let mut registers = Registers::default();
+        unsafe { unwind_init_registers(&mut registers as _) };
+        assert_eq!(registers.get(UNW_REG_IP), Some(registers.pc()));
+        assert_eq!(registers.get(NativeArch::MAX_REGISTER_NUM + 1), None);
+        assert!(registers.set(NativeArch::MAX_REGISTER_NUM + 1, 0).is_err());
+        registers.set(NativeArch::RA, 0x1234).unwrap();
+        assert!(registers.iter().any(|(n, v)| n == NativeArch::RA && v == 0x1234));
+        let s = registers.to_string();
+        assert!(s.lines().count() >= registers.iter().count());
+        assert!(s.contains(NativeArch::register_name(NativeArch::RA).unwrap()));
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn test_write_to_ucontext_x86_64() {
+        let mut fpstate: libc::_libc_fpstate = unsafe { std::mem::zeroed() };
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        ucontext.uc_mcontext.fpregs = &mut fpstate;
+        let mut registers = Registers::default();
+        for n in 0..=UNW_X86_64_RIP {
+            registers[n] = 0x100 + n as u64;
+        }
+        registers.set_vector_register(UNW_X86_64_XMM15, 1 << 127 | 0xffff_ffff);
+        registers.write_to_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(ucontext.uc_mcontext.gregs[libc::REG_RBP as usize], 0x106);
+        assert_eq!(ucontext.uc_mcontext.gregs[libc::REG_RIP as usize], 0x110);
+        assert_eq!(fpstate._xmm[15].element, [u32::MAX, 0, 0, 0x8000_0000]);
+
+        let restored = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(restored.to_string(), registers.to_string());
+
+        // Without the FPU state only the general registers are written.
+        ucontext.uc_mcontext.fpregs = std::ptr::null_mut();
+        registers.write_to_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert!(registers.write_to_ucontext(std::ptr::null_mut()).is_err());
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86", target_os = "linux"))]
+    fn test_write_to_ucontext_x86() {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        let mut registers = Registers::default();
+        for n in 0..=UNW_X86_EIP {
+            registers[n] = 0xffff_ff00 + n as u64;
+        }
+        registers.write_to_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(ucontext.uc_mcontext.gregs[libc::REG_EBP as usize] as u32, 0xffff_ff05);
+        let restored = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(restored.to_string(), registers.to_string());
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+    fn test_write_to_ucontext_riscv64() {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        let mut registers = Registers::default();
+        for n in 1..=UNW_RISCV_X31 {
+            registers[n] = 0x100 + n as u64;
+        }
+        registers[UNW_REG_IP] = 0x1000;
+        registers.set_float_register(UNW_RISCV_F8, 1.5);
+        registers.write_to_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(ucontext.uc_mcontext.__gregs[0], 0x1000);
+        assert_eq!(ucontext.uc_mcontext.__gregs[UNW_RISCV_RA], 0x101);
+        let restored = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(restored.to_string(), registers.to_string());
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+    fn test_from_ucontext_riscv64() {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        for n in 0..32 {
+            ucontext.uc_mcontext.__gregs[n] = 0x100 + n as u64;
+        }
+        unsafe { ucontext.uc_mcontext.__fpregs.__d.__f[8] = 1.5f64.to_bits() };
+        let registers = Registers::from_ucontext(&mut ucontext as *mut _ as _).unwrap();
+        assert_eq!(registers.pc(), 0x100);
+        assert_eq!(registers[UNW_RISCV_X0], 0);
+        assert_eq!(registers[UNW_RISCV_RA], 0x101);
+        assert_eq!(registers.sp(), 0x102);
+        assert_eq!(registers[UNW_RISCV_X31], 0x11f);
+        assert_eq!(registers.float_register(UNW_RISCV_F8), 1.5);
+    }
 }
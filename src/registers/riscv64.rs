@@ -0,0 +1,166 @@
+use crate::registers::consts::*;
+use std::ops::{Index, IndexMut};
+
+/// `Registers` holds the register context for a specific platform (OS+ISA).
+///
+/// We can use [unwind_init_registers] to initialize `Registers` based on
+/// the current execution context:
+/// ```
+/// use unwind::{unwind_init_registers, Registers};
+///
+/// fn main() {
+///     let mut registers = Registers::default();
+///     unsafe { unwind_init_registers(&mut registers as _) };
+///     assert_ne!(registers.pc(), 0);
+/// }
+/// ```
+///
+/// But more suitable for this crate usage scenario is to use an existing
+/// `ucontext`. Usually the kernel provides an `ucontext` for the signal
+/// handler:
+/// ```ignore
+/// extern "C" fn signal_handler(_: libc::c_int, _: *mut libc::siginfo_t, ucontext: *mut libc::c_void) {
+///     let registers = Registers::from_ucontext(ucontext);
+///     assert_ne!(registers.pc(), 0);
+/// }
+/// ```
+///
+/// We can restore `Registers` through [UnwindCursor] to get the execution
+/// context of the **parent** function:
+/// ```ignore
+/// let mut cursor = UnwindCursor::new();
+/// cursor.step(&mut registers).unwrap();
+/// ```
+///
+/// [UnwindCursor]: crate::cursor::UnwindCursor
+/// [unwind_init_registers]: crate::registers::unwind_init_registers
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Registers {
+    x: [u64; 32], // x0 ~ x31, x0 is always zero
+    pc: u64,
+    f: [f64; 32], // f0 ~ f31
+}
+
+impl Index<usize> for Registers {
+    type Output = u64;
+
+    fn index(&self, index: usize) -> &u64 {
+        assert!(Self::valid_register(index));
+        match index {
+            UNW_REG_IP => &self.pc,
+            UNW_REG_SP => &self.x[UNW_RISCV_SP],
+            0..=31 => &self.x[index],
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl IndexMut<usize> for Registers {
+    fn index_mut(&mut self, index: usize) -> &mut u64 {
+        assert!(Self::valid_register(index));
+        match index {
+            UNW_REG_IP => &mut self.pc,
+            UNW_REG_SP => &mut self.x[UNW_RISCV_SP],
+            0..=31 => &mut self.x[index],
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Registers {
+    /// Initialize `Registers` with value from `ucontext`.
+    #[cfg(target_os = "linux")]
+    pub fn from_ucontext(ucontext: *mut libc::c_void) -> Option<Self> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return None;
+        }
+        let mcontext = unsafe { &(*ucontext).uc_mcontext };
+        // __gregs[0] holds the pc, since x0 is always zero.
+        let mut x = mcontext.__gregs;
+        x[0] = 0;
+        // The kernel saves the D extension state, rv64gc has it.
+        let f = unsafe { mcontext.__fpregs.__d.__f };
+        Some(Self {
+            x,
+            pc: mcontext.__gregs[0],
+            f: f.map(f64::from_bits),
+        })
+    }
+
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point.
+    #[cfg(target_os = "linux")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        let mcontext = unsafe { &mut (*ucontext).uc_mcontext };
+        mcontext.__gregs[0] = self.pc;
+        mcontext.__gregs[1..].copy_from_slice(&self.x[1..]);
+        mcontext.__fpregs.__d.__f = self.f.map(f64::to_bits);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn max_register_num() -> usize {
+        UNW_RISCV_MAX_REG_NUM
+    }
+
+    #[inline]
+    pub fn valid_register(n: usize) -> bool {
+        if n == UNW_REG_IP || n == UNW_REG_SP {
+            return true;
+        }
+        n <= UNW_RISCV_X31
+    }
+
+    #[inline]
+    pub fn valid_float_register(n: usize) -> bool {
+        (UNW_RISCV_F0..=UNW_RISCV_F31).contains(&n)
+    }
+
+    #[inline]
+    pub fn valid_vector_register(_n: usize) -> bool {
+        false
+    }
+
+    #[inline]
+    pub fn float_register(&self, n: usize) -> f64 {
+        assert!(Self::valid_float_register(n));
+        self.f[n - UNW_RISCV_F0]
+    }
+
+    #[inline]
+    pub fn set_float_register(&mut self, n: usize, v: f64) {
+        assert!(Self::valid_float_register(n));
+        self.f[n - UNW_RISCV_F0] = v;
+    }
+
+    #[inline]
+    pub fn vector_register(&self, _n: usize) -> u128 {
+        unreachable!();
+    }
+
+    #[inline]
+    pub fn set_vector_register(&mut self, _n: usize, _v: u128) {
+        unreachable!();
+    }
+
+    /// Get the value of the PC (Program Counter) register.
+    #[inline]
+    pub fn pc(&self) -> u64 {
+        self[UNW_REG_IP]
+    }
+
+    /// Get the value of the SP (Stack Pointer) register.
+    #[inline]
+    pub fn sp(&self) -> u64 {
+        self[UNW_REG_SP]
+    }
+}
@@ -54,8 +54,35 @@ pub struct Registers {
     r14: u64,
     r15: u64,
     rip: u64,
+    xmm: [u128; 16], // xmm0 ~ xmm15
 }
 
+// `unwind_init_registers` stores xmm0 ~ xmm15 at this offset.
+const _: () = assert!(std::mem::offset_of!(Registers, xmm) == 144);
+
+/// DWARF register numbers of the general registers and their indexes in
+/// `mcontext_t.gregs`.
+#[cfg(target_os = "linux")]
+const GREGS: [(usize, libc::c_int); 17] = [
+    (UNW_X86_64_RAX, libc::REG_RAX),
+    (UNW_X86_64_RBX, libc::REG_RBX),
+    (UNW_X86_64_RCX, libc::REG_RCX),
+    (UNW_X86_64_RDX, libc::REG_RDX),
+    (UNW_X86_64_RDI, libc::REG_RDI),
+    (UNW_X86_64_RSI, libc::REG_RSI),
+    (UNW_X86_64_RBP, libc::REG_RBP),
+    (UNW_X86_64_RSP, libc::REG_RSP),
+    (UNW_X86_64_R8, libc::REG_R8),
+    (UNW_X86_64_R9, libc::REG_R9),
+    (UNW_X86_64_R10, libc::REG_R10),
+    (UNW_X86_64_R11, libc::REG_R11),
+    (UNW_X86_64_R12, libc::REG_R12),
+    (UNW_X86_64_R13, libc::REG_R13),
+    (UNW_X86_64_R14, libc::REG_R14),
+    (UNW_X86_64_R15, libc::REG_R15),
+    (UNW_X86_64_RIP, libc::REG_RIP),
+];
+
 impl Index<usize> for Registers {
     type Output = u64;
 
@@ -120,26 +147,46 @@ impl Registers {
         }
         let mcontext = unsafe { (*ucontext).uc_mcontext };
         let mut registers = Self::default();
-        registers[UNW_X86_64_RAX] = mcontext.gregs[libc::REG_RAX as usize] as _;
-        registers[UNW_X86_64_RBX] = mcontext.gregs[libc::REG_RBX as usize] as _;
-        registers[UNW_X86_64_RCX] = mcontext.gregs[libc::REG_RCX as usize] as _;
-        registers[UNW_X86_64_RDX] = mcontext.gregs[libc::REG_RDX as usize] as _;
-        registers[UNW_X86_64_RDI] = mcontext.gregs[libc::REG_RDI as usize] as _;
-        registers[UNW_X86_64_RSI] = mcontext.gregs[libc::REG_RSI as usize] as _;
-        registers[UNW_X86_64_RBP] = mcontext.gregs[libc::REG_RBP as usize] as _;
-        registers[UNW_X86_64_RSP] = mcontext.gregs[libc::REG_RSP as usize] as _;
-        registers[UNW_X86_64_R8] = mcontext.gregs[libc::REG_R8 as usize] as _;
-        registers[UNW_X86_64_R9] = mcontext.gregs[libc::REG_R9 as usize] as _;
-        registers[UNW_X86_64_R10] = mcontext.gregs[libc::REG_R10 as usize] as _;
-        registers[UNW_X86_64_R11] = mcontext.gregs[libc::REG_R11 as usize] as _;
-        registers[UNW_X86_64_R12] = mcontext.gregs[libc::REG_R12 as usize] as _;
-        registers[UNW_X86_64_R13] = mcontext.gregs[libc::REG_R13 as usize] as _;
-        registers[UNW_X86_64_R14] = mcontext.gregs[libc::REG_R14 as usize] as _;
-        registers[UNW_X86_64_R15] = mcontext.gregs[libc::REG_R15 as usize] as _;
-        registers[UNW_X86_64_RIP] = mcontext.gregs[libc::REG_RIP as usize] as _;
+        for (n, reg) in GREGS {
+            registers[n] = mcontext.gregs[reg as usize] as _;
+        }
+        // The kernel always saves the FPU state, but `fpregs` is null in
+        // contexts from other sources that don't have it.
+        if !mcontext.fpregs.is_null() {
+            let fpregs = unsafe { &*mcontext.fpregs };
+            for (n, xmm) in fpregs._xmm.iter().enumerate() {
+                let e = xmm.element;
+                registers.xmm[n] = e[0] as u128 | (e[1] as u128) << 32 | (e[2] as u128) << 64 | (e[3] as u128) << 96;
+            }
+        }
         Some(registers)
     }
 
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point. The xmm registers are left alone if
+    /// `ucontext` has no FPU state.
+    #[cfg(target_os = "linux")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        let mcontext = unsafe { &mut (*ucontext).uc_mcontext };
+        for (n, reg) in GREGS {
+            mcontext.gregs[reg as usize] = self[n] as _;
+        }
+        if !mcontext.fpregs.is_null() {
+            let fpregs = unsafe { &mut *mcontext.fpregs };
+            for (xmm, v) in fpregs._xmm.iter_mut().zip(self.xmm) {
+                xmm.element = [v as u32, (v >> 32) as u32, (v >> 64) as u32, (v >> 96) as u32];
+            }
+        }
+        Ok(())
+    }
+
     /// Initialize `Registers` with value from `ucontext`.
     #[cfg(target_os = "macos")]
     pub fn from_ucontext(ucontext: *mut libc::c_void) -> Option<Self> {
@@ -170,10 +217,90 @@ impl Registers {
             registers[UNW_X86_64_R14] = (*mcontext).__ss.__r14;
             registers[UNW_X86_64_R15] = (*mcontext).__ss.__r15;
             registers[UNW_X86_64_RIP] = (*mcontext).__ss.__rip;
+            let fs = &(*mcontext).__fs;
+            registers.xmm = [
+                &fs.__fpu_xmm0,
+                &fs.__fpu_xmm1,
+                &fs.__fpu_xmm2,
+                &fs.__fpu_xmm3,
+                &fs.__fpu_xmm4,
+                &fs.__fpu_xmm5,
+                &fs.__fpu_xmm6,
+                &fs.__fpu_xmm7,
+                &fs.__fpu_xmm8,
+                &fs.__fpu_xmm9,
+                &fs.__fpu_xmm10,
+                &fs.__fpu_xmm11,
+                &fs.__fpu_xmm12,
+                &fs.__fpu_xmm13,
+                &fs.__fpu_xmm14,
+                &fs.__fpu_xmm15,
+            ]
+            .map(|xmm| u128::from_le_bytes(xmm.__xmm_reg.map(|b| b as u8)));
             Some(registers)
         }
     }
 
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point.
+    #[cfg(target_os = "macos")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        unsafe {
+            let mcontext = (*ucontext).uc_mcontext;
+            if mcontext.is_null() {
+                return Err(crate::Error::InvalidUcontext);
+            }
+            let ss = &mut (*mcontext).__ss;
+            ss.__rax = self.rax;
+            ss.__rbx = self.rbx;
+            ss.__rcx = self.rcx;
+            ss.__rdx = self.rdx;
+            ss.__rdi = self.rdi;
+            ss.__rsi = self.rsi;
+            ss.__rbp = self.rbp;
+            ss.__rsp = self.rsp;
+            ss.__r8 = self.r8;
+            ss.__r9 = self.r9;
+            ss.__r10 = self.r10;
+            ss.__r11 = self.r11;
+            ss.__r12 = self.r12;
+            ss.__r13 = self.r13;
+            ss.__r14 = self.r14;
+            ss.__r15 = self.r15;
+            ss.__rip = self.rip;
+            let fs = &mut (*mcontext).__fs;
+            let xmm = [
+                &mut fs.__fpu_xmm0,
+                &mut fs.__fpu_xmm1,
+                &mut fs.__fpu_xmm2,
+                &mut fs.__fpu_xmm3,
+                &mut fs.__fpu_xmm4,
+                &mut fs.__fpu_xmm5,
+                &mut fs.__fpu_xmm6,
+                &mut fs.__fpu_xmm7,
+                &mut fs.__fpu_xmm8,
+                &mut fs.__fpu_xmm9,
+                &mut fs.__fpu_xmm10,
+                &mut fs.__fpu_xmm11,
+                &mut fs.__fpu_xmm12,
+                &mut fs.__fpu_xmm13,
+                &mut fs.__fpu_xmm14,
+                &mut fs.__fpu_xmm15,
+            ];
+            for (xmm, v) in xmm.into_iter().zip(self.xmm) {
+                xmm.__xmm_reg = v.to_le_bytes().map(|b| b as _);
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn max_register_num() -> usize {
         UNW_X86_64_MAX_REG_NUM
@@ -197,8 +324,8 @@ impl Registers {
     }
 
     #[inline]
-    pub fn valid_vector_register(_n: usize) -> bool {
-        false
+    pub fn valid_vector_register(n: usize) -> bool {
+        (UNW_X86_64_XMM0..=UNW_X86_64_XMM15).contains(&n)
     }
 
     #[inline]
@@ -212,13 +339,15 @@ impl Registers {
     }
 
     #[inline]
-    pub fn vector_register(&self, _n: usize) -> bool {
-        unreachable!();
+    pub fn vector_register(&self, n: usize) -> u128 {
+        assert!(Self::valid_vector_register(n));
+        self.xmm[n - UNW_X86_64_XMM0]
     }
 
     #[inline]
-    pub fn set_vector_register(&mut self, _n: usize, _v: u128) {
-        unreachable!();
+    pub fn set_vector_register(&mut self, n: usize, v: u128) {
+        assert!(Self::valid_vector_register(n));
+        self.xmm[n - UNW_X86_64_XMM0] = v;
     }
 
     /// Get the value of the PC (Program Counter) register.
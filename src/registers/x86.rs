@@ -0,0 +1,192 @@
+use crate::registers::consts::*;
+use std::ops::{Index, IndexMut};
+
+/// `Registers` holds the register context for a specific platform (OS+ISA).
+///
+/// We can use [unwind_init_registers] to initialize `Registers` based on
+/// the current execution context:
+/// ```
+/// use unwind::{unwind_init_registers, Registers};
+///
+/// fn main() {
+///     let mut registers = Registers::default();
+///     unsafe { unwind_init_registers(&mut registers as _) };
+///     assert_ne!(registers.pc(), 0);
+/// }
+/// ```
+///
+/// But more suitable for this crate usage scenario is to use an existing
+/// `ucontext`. Usually the kernel provides an `ucontext` for the signal
+/// handler:
+/// ```ignore
+/// extern "C" fn signal_handler(_: libc::c_int, _: *mut libc::siginfo_t, ucontext: *mut libc::c_void) {
+///     let registers = Registers::from_ucontext(ucontext);
+///     assert_ne!(registers.pc(), 0);
+/// }
+/// ```
+///
+/// We can restore `Registers` through [UnwindCursor] to get the execution
+/// context of the **parent** function:
+/// ```ignore
+/// let mut cursor = UnwindCursor::new();
+/// cursor.step(&mut registers).unwrap();
+/// ```
+///
+/// [UnwindCursor]: crate::cursor::UnwindCursor
+/// [unwind_init_registers]: crate::registers::unwind_init_registers
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Registers {
+    // Registers are 32-bit, each is kept zero-extended in a 64-bit slot.
+    eax: u64,
+    ecx: u64,
+    edx: u64,
+    ebx: u64,
+    esp: u64,
+    ebp: u64,
+    esi: u64,
+    edi: u64,
+    eip: u64,
+}
+
+impl Index<usize> for Registers {
+    type Output = u64;
+
+    fn index(&self, index: usize) -> &u64 {
+        assert!(Self::valid_register(index));
+        match index {
+            UNW_REG_IP | UNW_X86_EIP => &self.eip,
+            UNW_REG_SP | UNW_X86_ESP => &self.esp,
+            UNW_X86_EAX => &self.eax,
+            UNW_X86_ECX => &self.ecx,
+            UNW_X86_EDX => &self.edx,
+            UNW_X86_EBX => &self.ebx,
+            UNW_X86_EBP => &self.ebp,
+            UNW_X86_ESI => &self.esi,
+            UNW_X86_EDI => &self.edi,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl IndexMut<usize> for Registers {
+    fn index_mut(&mut self, index: usize) -> &mut u64 {
+        assert!(Self::valid_register(index));
+        match index {
+            UNW_REG_IP | UNW_X86_EIP => &mut self.eip,
+            UNW_REG_SP | UNW_X86_ESP => &mut self.esp,
+            UNW_X86_EAX => &mut self.eax,
+            UNW_X86_ECX => &mut self.ecx,
+            UNW_X86_EDX => &mut self.edx,
+            UNW_X86_EBX => &mut self.ebx,
+            UNW_X86_EBP => &mut self.ebp,
+            UNW_X86_ESI => &mut self.esi,
+            UNW_X86_EDI => &mut self.edi,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Registers {
+    /// Initialize `Registers` with value from `ucontext`.
+    #[cfg(target_os = "linux")]
+    pub fn from_ucontext(ucontext: *mut libc::c_void) -> Option<Self> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return None;
+        }
+        let gregs = unsafe { (*ucontext).uc_mcontext.gregs };
+        let reg = |n: libc::c_int| gregs[n as usize] as u32 as u64;
+        Some(Self {
+            eax: reg(libc::REG_EAX),
+            ecx: reg(libc::REG_ECX),
+            edx: reg(libc::REG_EDX),
+            ebx: reg(libc::REG_EBX),
+            esp: reg(libc::REG_ESP),
+            ebp: reg(libc::REG_EBP),
+            esi: reg(libc::REG_ESI),
+            edi: reg(libc::REG_EDI),
+            eip: reg(libc::REG_EIP),
+        })
+    }
+
+    /// Write `Registers` back to `ucontext`, the inverse of [Registers::from_ucontext].
+    ///
+    /// The thread interrupted by a signal resumes with the registers in its
+    /// `ucontext` when the signal handler returns, so this can redirect it,
+    /// e.g. to a recovery point.
+    #[cfg(target_os = "linux")]
+    pub fn write_to_ucontext(&self, ucontext: *mut libc::c_void) -> crate::Result<()> {
+        let ucontext = ucontext as *mut libc::ucontext_t;
+        if ucontext.is_null() {
+            return Err(crate::Error::InvalidUcontext);
+        }
+        let gregs = unsafe { &mut (*ucontext).uc_mcontext.gregs };
+        let mut set = |n: libc::c_int, v: u64| gregs[n as usize] = v as u32 as _;
+        set(libc::REG_EAX, self.eax);
+        set(libc::REG_ECX, self.ecx);
+        set(libc::REG_EDX, self.edx);
+        set(libc::REG_EBX, self.ebx);
+        set(libc::REG_ESP, self.esp);
+        set(libc::REG_EBP, self.ebp);
+        set(libc::REG_ESI, self.esi);
+        set(libc::REG_EDI, self.edi);
+        set(libc::REG_EIP, self.eip);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn max_register_num() -> usize {
+        UNW_X86_MAX_REG_NUM
+    }
+
+    #[inline]
+    pub fn valid_register(n: usize) -> bool {
+        if n == UNW_REG_IP || n == UNW_REG_SP {
+            return true;
+        }
+        n <= UNW_X86_EIP
+    }
+
+    #[inline]
+    pub fn valid_float_register(_n: usize) -> bool {
+        false
+    }
+
+    #[inline]
+    pub fn valid_vector_register(_n: usize) -> bool {
+        false
+    }
+
+    #[inline]
+    pub fn float_register(&self, _n: usize) -> f64 {
+        unreachable!()
+    }
+
+    #[inline]
+    pub fn set_float_register(&mut self, _n: usize, _v: f64) {
+        unreachable!()
+    }
+
+    #[inline]
+    pub fn vector_register(&self, _n: usize) -> u128 {
+        unreachable!();
+    }
+
+    #[inline]
+    pub fn set_vector_register(&mut self, _n: usize, _v: u128) {
+        unreachable!();
+    }
+
+    /// Get the value of the PC (Program Counter) register.
+    #[inline]
+    pub fn pc(&self) -> u64 {
+        self[UNW_REG_IP]
+    }
+
+    /// Get the value of the SP (Stack Pointer) register.
+    #[inline]
+    pub fn sp(&self) -> u64 {
+        self[UNW_REG_SP]
+    }
+}
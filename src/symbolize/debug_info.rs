@@ -0,0 +1,233 @@
+use super::elf::{self, Data, Mmap};
+use super::{demangle, SymbolizeError};
+use crate::dwarf::{package_sections, DebugSections, SourceFrame, Unit, UnitIndex};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A function at an address, from debug information, with the source
+/// location of the address in it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Frame {
+    name: Option<Vec<u8>>,
+    file: Option<PathBuf>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+impl Frame {
+    fn new(frame: SourceFrame<'_>) -> Self {
+        Self {
+            name: frame.name.map(|name| name.to_vec()),
+            file: frame.file.map(|file| PathBuf::from(OsStr::from_bytes(&file))),
+            line: Some(frame.line).filter(|&line| line != 0),
+            column: Some(frame.column).filter(|&column| column != 0),
+        }
+    }
+
+    /// Returns the name of the function, or `None` if unknown or not UTF-8.
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        std::str::from_utf8(self.name.as_deref()?).ok()
+    }
+
+    /// Returns the raw bytes of the name of the function, mangled if it has
+    /// a linkage name.
+    #[inline]
+    pub fn name_bytes(&self) -> Option<&[u8]> {
+        self.name.as_deref()
+    }
+
+    /// Returns the name of the function demangled, or as is if it isn't
+    /// mangled, with invalid UTF-8 replaced. See [demangle] for
+    /// `strip_hash`.
+    pub fn demangled_name(&self, strip_hash: bool) -> Option<String> {
+        let name = self.name.as_deref()?;
+        Some(demangle(name, strip_hash).unwrap_or_else(|| String::from_utf8_lossy(name).into_owned()))
+    }
+
+    /// Returns the path of the source file.
+    #[inline]
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the line number in the source file.
+    #[inline]
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// Returns the column in the line.
+    #[inline]
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+
+    #[inline]
+    pub(super) fn set_name(&mut self, name: &[u8]) {
+        self.name = Some(name.to_vec());
+    }
+}
+
+/// The DWARF debug information of an ELF file, indexed by compilation unit.
+///
+/// The .dwo files of split DWARF are looked up by the paths recorded in the
+/// skeleton units, or next to the ELF file, and the .dwp package by the path
+/// of the ELF file with a .dwp suffix. They are read on first use.
+pub struct DebugInfo {
+    path: PathBuf,
+    data: Data,
+    units: UnitIndex,
+    dwos: Mutex<HashMap<PathBuf, Option<Arc<Mmap>>>>,
+    dwp: OnceLock<Option<Mmap>>,
+}
+
+impl DebugInfo {
+    /// Reads the debug information of the ELF file at `path`, which must be
+    /// of the native class and byte order.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SymbolizeError> {
+        let path = path.as_ref();
+        let data = Data::Mmap(Mmap::open(path)?);
+        let units = UnitIndex::new(&debug_sections(data.bytes(), false)?)?;
+        Ok(Self {
+            // The executable is opened through /proc/self/exe, but its
+            // package is next to the real file.
+            path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            data,
+            units,
+            dwos: Mutex::new(HashMap::new()),
+            dwp: OnceLock::new(),
+        })
+    }
+
+    /// Returns the path of the ELF file.
+    #[inline]
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether there is no debug information about code.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// Returns the functions at `address`, an address of the ELF file, the
+    /// innermost inlined one first, with their source locations. Empty if
+    /// there is no debug information about the address.
+    pub fn frames(&self, address: u64) -> Result<Vec<Frame>, SymbolizeError> {
+        let Some(unit) = self.units.find(address) else {
+            return Ok(vec![]);
+        };
+        let sections = debug_sections(self.data.bytes(), false)?;
+        let mut split = None;
+        let dwo;
+        if unit.is_skeleton() {
+            if let (Some(dwp), Some(dwo_id)) = (self.dwp(), unit.dwo_id()) {
+                split = package_sections(&debug_sections(dwp.bytes(), true)?, dwo_id)?;
+            }
+            if split.is_none() {
+                dwo = self.dwo(unit);
+                if let Some(dwo) = &dwo {
+                    split = Some(debug_sections(dwo.bytes(), true)?);
+                }
+            }
+        }
+        let frames = self.units.frames(&sections, unit, split.as_ref(), address)?;
+        Ok(frames.into_iter().map(Frame::new).collect())
+    }
+
+    fn dwp(&self) -> Option<&Mmap> {
+        self.dwp
+            .get_or_init(|| {
+                let mut path = self.path.clone().into_os_string();
+                path.push(".dwp");
+                Mmap::open(Path::new(&path)).ok()
+            })
+            .as_ref()
+    }
+
+    fn dwo(&self, unit: &Unit) -> Option<Arc<Mmap>> {
+        let name = Path::new(OsStr::from_bytes(unit.dwo_name()?));
+        let path = match unit.comp_dir() {
+            Some(comp_dir) => Path::new(OsStr::from_bytes(comp_dir)).join(name),
+            None => name.to_path_buf(),
+        };
+        let mut dwos = self.dwos.lock().unwrap_or_else(|e| e.into_inner());
+        dwos.entry(path)
+            .or_insert_with_key(|path| {
+                // The build directory may be gone, with the .dwo file moved
+                // next to the ELF file.
+                let moved = self.path.with_file_name(name.file_name()?);
+                Mmap::open(path).or_else(|_| Mmap::open(&moved)).ok().map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+/// Returns the DWARF sections of an ELF file, or of a .dwo or .dwp file.
+fn debug_sections(data: &[u8], dwo: bool) -> Result<DebugSections<'_>, SymbolizeError> {
+    let mut sections = DebugSections::default();
+    for (name, contents) in elf::sections(data)? {
+        let name = match (dwo, name.strip_suffix(b".dwo")) {
+            (true, Some(stripped)) => stripped,
+            (false, None) => name,
+            _ if name == b".debug_cu_index" => name,
+            _ => continue,
+        };
+        let section = match name {
+            b".debug_info" => &mut sections.info,
+            b".debug_abbrev" => &mut sections.abbrev,
+            b".debug_str" => &mut sections.str,
+            b".debug_line" => &mut sections.line,
+            b".debug_line_str" => &mut sections.line_str,
+            b".debug_ranges" => &mut sections.ranges,
+            b".debug_rnglists" => &mut sections.rnglists,
+            b".debug_addr" => &mut sections.addr,
+            b".debug_str_offsets" => &mut sections.str_offsets,
+            b".debug_cu_index" => &mut sections.cu_index,
+            _ => continue,
+        };
+        *section = contents;
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNCTION_LINE: u32 = line!() + 2;
+    #[inline(never)]
+    fn function() -> u64 {
+        std::hint::black_box(42)
+    }
+
+    #[test]
+    fn test_frames() {
+        // Test binaries of the release profile have no debug information.
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let debug_info = DebugInfo::open("/proc/self/exe").unwrap();
+        assert!(!debug_info.is_empty());
+        let address = function as fn() -> u64 as usize as u64;
+        let module = super::super::modules()
+            .into_iter()
+            .find(|m| m.contains(address))
+            .unwrap();
+        let frames = debug_info.frames(address - module.base()).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].name().unwrap().contains("5tests8function"));
+        assert_eq!(
+            frames[0].demangled_name(true).unwrap(),
+            "unwind::symbolize::debug_info::tests::function"
+        );
+        assert!(frames[0].file().unwrap().ends_with("src/symbolize/debug_info.rs"));
+        assert_eq!(frames[0].line(), Some(FUNCTION_LINE));
+        assert!(debug_info.frames(0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,357 @@
+//! A client of debuginfod servers, which serve the debug information and
+//! executables of ELF files by build-id, for objects whose debug packages
+//! aren't installed.
+//!
+//! Servers are queried over plain HTTP, as by
+//! <https://sourceware.org/elfutils/Debuginfod.html>, and the files kept in
+//! the same cache layout as elfutils. There is no TLS client in the crate, so
+//! `https://` servers are skipped: a local debuginfod federating them can
+//! stand in front.
+
+use super::SymbolizeError;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{env, process};
+
+/// How long a file the servers don't have is remembered, elfutils'
+/// default `cache_miss_s`.
+const CACHE_MISS: Duration = Duration::from_secs(600);
+
+/// The default of `DEBUGINFOD_TIMEOUT`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The maximum size of the status line and headers of a response.
+const MAX_HEAD_LEN: u64 = 64 << 10;
+
+/// Tells apart the temporary files of concurrent downloads.
+static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+fn invalid(reason: &'static str) -> SymbolizeError {
+    SymbolizeError::Debuginfod(reason)
+}
+
+/// A client of debuginfod servers, which downloads files into a cache
+/// directory, `<cache>/<build-id>/debuginfo` and
+/// `<cache>/<build-id>/executable`, and finds them there afterwards.
+///
+/// Files no server has are recorded as empty files for 10 minutes, in
+/// which they aren't asked for again.
+#[derive(Debug, Clone)]
+pub struct Client {
+    urls: Vec<String>,
+    cache: PathBuf,
+    timeout: Option<Duration>,
+}
+
+impl Client {
+    /// Creates a client of the servers at `urls`, e.g.
+    /// `http://localhost:8002`, asked in order, which keeps the files it
+    /// downloads in the directory `cache`.
+    pub fn new<I, S>(urls: I, cache: impl Into<PathBuf>) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            urls: urls.into_iter().map(Into::into).collect(),
+            cache: cache.into(),
+            timeout: Some(DEFAULT_TIMEOUT),
+        }
+    }
+
+    /// Creates a client configured by the environment like the one of
+    /// elfutils, or returns `None` if `DEBUGINFOD_URLS` lists no servers.
+    ///
+    /// - `DEBUGINFOD_URLS`: the servers, separated by spaces.
+    /// - `DEBUGINFOD_CACHE_PATH`: the cache directory, by default
+    ///   `$XDG_CACHE_HOME/debuginfod_client` or
+    ///   `$HOME/.cache/debuginfod_client`.
+    /// - `DEBUGINFOD_TIMEOUT`: the timeout of connections and reads in
+    ///   seconds, 90 by default and none if 0.
+    pub fn from_env() -> Option<Self> {
+        let urls = env::var("DEBUGINFOD_URLS").ok()?;
+        let urls: Vec<_> = urls.split_ascii_whitespace().collect();
+        if urls.is_empty() {
+            return None;
+        }
+        let cache = match env::var_os("DEBUGINFOD_CACHE_PATH") {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => match env::var_os("XDG_CACHE_HOME") {
+                Some(path) if !path.is_empty() => PathBuf::from(path),
+                _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+            }
+            .join("debuginfod_client"),
+        };
+        let timeout = match env::var("DEBUGINFOD_TIMEOUT").ok().and_then(|t| t.trim().parse().ok()) {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(DEFAULT_TIMEOUT),
+        };
+        Some(Self::new(urls, cache).with_timeout(timeout))
+    }
+
+    /// Sets the timeout of connections and of each read and write, or
+    /// waits forever if `None`.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the path of the debug information of the ELF file with
+    /// `build_id`, e.g. the file `objcopy --only-keep-debug` makes, from the
+    /// cache or downloaded.
+    pub fn debuginfo(&self, build_id: &[u8]) -> Result<PathBuf, SymbolizeError> {
+        self.fetch(build_id, "debuginfo")
+    }
+
+    /// Returns the path of the ELF file with `build_id`, from the cache or
+    /// downloaded.
+    pub fn executable(&self, build_id: &[u8]) -> Result<PathBuf, SymbolizeError> {
+        self.fetch(build_id, "executable")
+    }
+
+    fn fetch(&self, build_id: &[u8], kind: &str) -> Result<PathBuf, SymbolizeError> {
+        if build_id.is_empty() {
+            return Err(invalid("empty build-id"));
+        }
+        let build_id: String = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+        let dir = self.cache.join(&build_id);
+        let path = dir.join(kind);
+        if let Ok(metadata) = fs::metadata(&path) {
+            if metadata.len() != 0 {
+                return Ok(path);
+            }
+            let age = metadata.modified().ok().and_then(|t| t.elapsed().ok());
+            if age.is_some_and(|age| age < CACHE_MISS) {
+                return Err(invalid("not found"));
+            }
+        }
+
+        fs::create_dir_all(&dir)?;
+        let id = DOWNLOADS.fetch_add(1, Ordering::Relaxed);
+        let tmp = dir.join(format!(".{}.{}.{}", kind, process::id(), id));
+        let mut error = None;
+        for url in &self.urls {
+            match self.download(url, &format!("/buildid/{}/{}", build_id, kind), &tmp) {
+                Ok(true) => {
+                    fs::rename(&tmp, &path)?;
+                    return Ok(path);
+                }
+                Ok(false) => {}
+                Err(err) => error = Some(err),
+            }
+        }
+        let _ = fs::remove_file(&tmp);
+        match error {
+            // The file may be there once the server is reachable.
+            Some(err) => Err(err),
+            None => {
+                let _ = File::create(&path);
+                Err(invalid("not found"))
+            }
+        }
+    }
+
+    /// Downloads `path` from the server at `url` into the file `dest`.
+    /// Returns `false` if the server doesn't have it.
+    fn download(&self, url: &str, path: &str, dest: &Path) -> Result<bool, SymbolizeError> {
+        let rest = url.strip_prefix("http://").ok_or(invalid("unsupported url"))?;
+        let (authority, prefix) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let address = if has_port(authority) {
+            authority.to_string()
+        } else {
+            format!("{}:80", authority)
+        };
+        let mut stream = Err(io::Error::new(io::ErrorKind::NotFound, "no address"));
+        for address in address.to_socket_addrs()? {
+            stream = match self.timeout {
+                Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+                None => TcpStream::connect(address),
+            };
+            if stream.is_ok() {
+                break;
+            }
+        }
+        let mut stream = stream?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        write!(
+            stream,
+            "GET {}{} HTTP/1.1\r\nHost: {}\r\nUser-Agent: unwind\r\nConnection: close\r\n\r\n",
+            prefix.trim_end_matches('/'),
+            path,
+            authority
+        )?;
+        let mut reader = BufReader::new(stream);
+        let Some(body) = read_head(&mut reader)? else {
+            return Ok(false);
+        };
+        let mut file = File::create(dest)?;
+        read_body(&mut reader, body, &mut file)?;
+        file.sync_all()?;
+        Ok(true)
+    }
+}
+
+/// Returns whether the host and port of a URL has a port.
+fn has_port(authority: &str) -> bool {
+    match authority.rfind(':') {
+        // The colons of IPv6 addresses are in brackets.
+        Some(i) => !authority[i..].contains(']') && authority[i + 1..].bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// How the body of a response is delimited.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Body {
+    Length(u64),
+    Chunked,
+    /// Up to the end of the connection.
+    Close,
+}
+
+/// Reads the status line and headers of a response, returning how its body
+/// is delimited, or `None` if the file isn't found.
+fn read_head<R: BufRead>(reader: &mut R) -> Result<Option<Body>, SymbolizeError> {
+    let mut reader = reader.take(MAX_HEAD_LEN);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .strip_prefix("HTTP/1.")
+        .and_then(|rest| rest.get(2..5))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(invalid("bad status line"))?;
+    let mut body = Body::Close;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated headers"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(invalid("bad header"));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") && body != Body::Chunked {
+            body = Body::Length(value.parse().map_err(|_| invalid("bad content length"))?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") && value.eq_ignore_ascii_case("chunked") {
+            body = Body::Chunked;
+        }
+    }
+    match status {
+        200 => Ok(Some(body)),
+        404 => Ok(None),
+        status => Err(SymbolizeError::HttpStatus(status)),
+    }
+}
+
+/// Copies the body of a response into `out`.
+fn read_body<R: BufRead, W: Write>(reader: &mut R, body: Body, out: &mut W) -> Result<(), SymbolizeError> {
+    match body {
+        Body::Length(len) => copy_exact(reader, len, out),
+        Body::Close => {
+            io::copy(reader, out)?;
+            Ok(())
+        }
+        Body::Chunked => {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                reader.take(MAX_HEAD_LEN).read_line(&mut line)?;
+                let size = line.trim_end_matches(['\r', '\n']);
+                // Chunk extensions are ignored.
+                let size = size.split(';').next().unwrap_or_default().trim();
+                let size = u64::from_str_radix(size, 16).map_err(|_| invalid("bad chunk size"))?;
+                if size == 0 {
+                    break;
+                }
+                copy_exact(reader, size, out)?;
+                line.clear();
+                reader.take(2).read_line(&mut line)?;
+                if line != "\r\n" {
+                    return Err(invalid("bad chunk"));
+                }
+            }
+            // Trailers, up to an empty line.
+            loop {
+                line.clear();
+                if reader.take(MAX_HEAD_LEN).read_line(&mut line)? == 0 {
+                    return Err(invalid("truncated chunked body"));
+                }
+                if line.trim_end_matches(['\r', '\n']).is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn copy_exact<R: Read, W: Write>(reader: &mut R, len: u64, out: &mut W) -> Result<(), SymbolizeError> {
+    if io::copy(&mut reader.take(len), out)? != len {
+        return Err(invalid("truncated body"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(data: &[u8]) -> Result<Option<Vec<u8>>, SymbolizeError> {
+        let mut reader = data;
+        let Some(body) = read_head(&mut reader)? else {
+            return Ok(None);
+        };
+        let mut out = vec![];
+        read_body(&mut reader, body, &mut out)?;
+        Ok(Some(out))
+    }
+
+    #[test]
+    fn test_response() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Debuginfod-Size: 5\r\n\r\nhello";
+        assert_eq!(response(data).unwrap().unwrap(), b"hello");
+        let data = b"HTTP/1.0 200 OK\r\n\r\nhello, world";
+        assert_eq!(response(data).unwrap().unwrap(), b"hello, world");
+        let data =
+            b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5;x=y\r\nhello\r\n7\r\n, world\r\n0\r\nA: b\r\n\r\n";
+        assert_eq!(response(data).unwrap().unwrap(), b"hello, world");
+        let data = b"HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\n\r\nnot found";
+        assert!(response(data).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_response() {
+        assert!(matches!(
+            response(b"HTTP/1.1 503 Service Unavailable\r\n\r\n"),
+            Err(SymbolizeError::HttpStatus(503))
+        ));
+        for data in [
+            &b"SSH-2.0-OpenSSH\r\n"[..],
+            b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nhello",
+            b"HTTP/1.1 200 OK\r\nContent-Length",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello!\r\n0\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nz\r\n",
+        ] {
+            assert!(response(data).is_err(), "{:?}", String::from_utf8_lossy(data));
+        }
+    }
+
+    #[test]
+    fn test_has_port() {
+        assert!(has_port("localhost:8002"));
+        assert!(has_port("[::1]:8002"));
+        assert!(!has_port("localhost"));
+        assert!(!has_port("[::1]"));
+    }
+}
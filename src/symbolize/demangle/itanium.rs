@@ -0,0 +1,2208 @@
+//! Demangler of the Itanium C++ ABI mangling, e.g. `_ZNK3foo3barEi`.
+//!
+//! Names are parsed and printed as by libiberty's `cp-demangle.c`, so that
+//! they read like the output of `c++filt`. The names are parsed into a tree
+//! of nodes, whose template parameters are resolved when it is printed.
+
+use super::Output;
+
+/// The maximum depth of nested names, types and expressions, when parsing
+/// and printing.
+const MAX_DEPTH: u32 = 256;
+
+/// The maximum number of nodes visited when printing, bounding the work on
+/// names whose substitutions expand exponentially.
+const MAX_PRINT_STEPS: u32 = 1 << 20;
+
+/// The index of a node of a parsed name.
+type NodeId = usize;
+
+/// An operator of expressions and of operator function names.
+struct Operator {
+    code: &'static str,
+    name: &'static str,
+    /// The number of operands in expressions.
+    arity: u8,
+}
+
+const fn op(code: &'static str, name: &'static str, arity: u8) -> Operator {
+    Operator { code, name, arity }
+}
+
+static OPERATORS: &[Operator] = &[
+    op("aN", "&=", 2),
+    op("aS", "=", 2),
+    op("aa", "&&", 2),
+    op("ad", "&", 1),
+    op("an", "&", 2),
+    op("at", "alignof ", 1),
+    op("aw", "co_await ", 1),
+    op("az", "alignof ", 1),
+    op("cc", "const_cast", 2),
+    op("cl", "()", 2),
+    op("cm", ",", 2),
+    op("co", "~", 1),
+    op("dV", "/=", 2),
+    op("da", "delete[] ", 1),
+    op("dc", "dynamic_cast", 2),
+    op("de", "*", 1),
+    op("dl", "delete ", 1),
+    op("ds", ".*", 2),
+    op("dt", ".", 2),
+    op("dv", "/", 2),
+    op("eO", "^=", 2),
+    op("eo", "^", 2),
+    op("eq", "==", 2),
+    op("ge", ">=", 2),
+    op("gs", "::", 1),
+    op("gt", ">", 2),
+    op("ix", "[]", 2),
+    op("lS", "<<=", 2),
+    op("le", "<=", 2),
+    op("li", "operator\"\" ", 1),
+    op("ls", "<<", 2),
+    op("lt", "<", 2),
+    op("mI", "-=", 2),
+    op("mL", "*=", 2),
+    op("mi", "-", 2),
+    op("ml", "*", 2),
+    op("mm", "--", 1),
+    op("na", "new[]", 3),
+    op("ne", "!=", 2),
+    op("ng", "-", 1),
+    op("nt", "!", 1),
+    op("nw", "new", 3),
+    op("nx", "noexcept", 1),
+    op("oR", "|=", 2),
+    op("oo", "||", 2),
+    op("or", "|", 2),
+    op("pL", "+=", 2),
+    op("pl", "+", 2),
+    op("pm", "->*", 2),
+    op("pp", "++", 1),
+    op("ps", "+", 1),
+    op("pt", "->", 2),
+    op("qu", "?", 3),
+    op("rM", "%=", 2),
+    op("rS", ">>=", 2),
+    op("rc", "reinterpret_cast", 2),
+    op("rm", "%", 2),
+    op("rs", ">>", 2),
+    op("sP", "sizeof...", 1),
+    op("sZ", "sizeof...", 1),
+    op("sc", "static_cast", 2),
+    op("ss", "<=>", 2),
+    op("st", "sizeof ", 1),
+    op("sz", "sizeof ", 1),
+    op("tr", "throw", 0),
+    op("tw", "throw ", 1),
+];
+
+/// Returns the builtin type of the one letter `code`.
+fn builtin_type(code: u8) -> Option<&'static str> {
+    Some(match code {
+        b'a' => "signed char",
+        b'b' => "bool",
+        b'c' => "char",
+        b'd' => "double",
+        b'e' => "long double",
+        b'f' => "float",
+        b'g' => "__float128",
+        b'h' => "unsigned char",
+        b'i' => "int",
+        b'j' => "unsigned int",
+        b'l' => "long",
+        b'm' => "unsigned long",
+        b'n' => "__int128",
+        b'o' => "unsigned __int128",
+        b's' => "short",
+        b't' => "unsigned short",
+        b'v' => "void",
+        b'w' => "wchar_t",
+        b'x' => "long long",
+        b'y' => "unsigned long long",
+        b'z' => "...",
+        _ => return None,
+    })
+}
+
+const CONST: u8 = 1;
+const VOLATILE: u8 = 2;
+const RESTRICT: u8 = 4;
+
+/// The qualifiers of a function type, or of the `this` of a member
+/// function.
+#[derive(Clone, Default)]
+struct Quals {
+    /// `CONST`, `VOLATILE` and `RESTRICT` bits.
+    cv: u8,
+    ref_qual: Option<&'static str>,
+    /// Exception specifications and `transaction_safe`, as `Spec` nodes.
+    specs: Vec<NodeId>,
+}
+
+impl Quals {
+    fn is_empty(&self) -> bool {
+        self.cv == 0 && self.ref_qual.is_none() && self.specs.is_empty()
+    }
+}
+
+enum Node<'a> {
+    /// A source name, or another name printed as is.
+    Name(&'a str),
+    /// A builtin type, or a standard substitution such as `std::allocator`.
+    Builtin(&'static str),
+    Number(i64),
+    /// `_Float<bits>`, with an optional `x` suffix.
+    FloatN(&'a str, &'static str),
+    /// `prefix::name`.
+    Nested(NodeId, NodeId),
+    /// A name local to a function, `function::entity`.
+    Local(NodeId, NodeId),
+    /// `{default arg#N}::entity`, of a 0-based `N`.
+    DefaultArg(usize, NodeId),
+    /// A template and its `Args`.
+    Template(NodeId, NodeId),
+    /// Template arguments, or the arguments of a parameter pack.
+    Args(Vec<NodeId>),
+    /// Expressions, e.g. the arguments of a call.
+    List(Vec<NodeId>),
+    AbiTag(NodeId, NodeId),
+    /// A constructor, of the class named by the node.
+    Ctor(NodeId),
+    Dtor(NodeId),
+    Operator(&'static Operator),
+    LiteralOperator(NodeId),
+    /// A conversion operator, to the type.
+    Conversion(NodeId),
+    /// A cast in an expression, to the type.
+    Cast(NodeId),
+    Lambda {
+        params: Vec<NodeId>,
+        number: usize,
+    },
+    UnnamedType(usize),
+    /// A special name, e.g. `vtable for A`.
+    Special(&'static str, NodeId),
+    /// A construction vtable, of a base class in a derived class.
+    ConstructionVtable(NodeId, NodeId),
+    ReferenceTemporary(NodeId, i64),
+    /// A function cloned by the compiler, e.g. `f() [clone .cold]`.
+    Clone(NodeId, &'a str),
+    /// A function, with its return type only if it is a template.
+    Encoding {
+        name: NodeId,
+        ret: Option<NodeId>,
+        params: Vec<NodeId>,
+        quals: Quals,
+    },
+    /// A 0-based template parameter.
+    TemplateParam(usize),
+    /// A function parameter in an expression, 1-based, or 0 for `this`.
+    FunctionParam(usize),
+    Qualified(NodeId, u8),
+    /// A type with a vendor qualifier, the name.
+    VendorQualified(NodeId, NodeId),
+    /// A type followed by a keyword, e.g. `double _Complex`.
+    Postfix(NodeId, &'static str),
+    /// A vector type, `int __vector(4)`.
+    Vector(NodeId, NodeId),
+    Pointer(NodeId),
+    Reference(NodeId, bool),
+    /// A pointer to a member of the class, of the member type.
+    MemberPointer(NodeId, NodeId),
+    Function {
+        ret: NodeId,
+        params: Vec<NodeId>,
+        quals: Quals,
+    },
+    Array(NodeId, Option<NodeId>),
+    Decltype(NodeId),
+    PackExpansion(NodeId),
+    /// An exception specification or `transaction_safe` of a function type.
+    Spec(&'static str, Option<NodeId>),
+    Literal {
+        ty: NodeId,
+        value: &'a str,
+        negative: bool,
+    },
+    Nullary(NodeId),
+    /// A unary operator or cast, possibly postfix, and its operand.
+    Unary {
+        op: NodeId,
+        operand: NodeId,
+        postfix: bool,
+    },
+    Binary {
+        op: NodeId,
+        left: NodeId,
+        right: NodeId,
+    },
+    Call(NodeId, NodeId),
+    Conditional(NodeId, NodeId, NodeId),
+    New {
+        placement: NodeId,
+        ty: NodeId,
+        init: Option<NodeId>,
+    },
+    InitList(Option<NodeId>, NodeId),
+}
+
+/// Demangles `name`, starting with `_Z`.
+pub(super) fn demangle(name: &str) -> Option<String> {
+    let b = name.as_bytes();
+    if b.len() > 11
+        && name.starts_with("_GLOBAL_")
+        && matches!(b[8], b'.' | b'_' | b'$')
+        && matches!(b[9], b'D' | b'I')
+        && b[10] == b'_'
+    {
+        let keyed = if b[9] == b'I' {
+            "global constructors keyed to "
+        } else {
+            "global destructors keyed to "
+        };
+        let rest = &name[11..];
+        let rest = match rest.starts_with("_Z") {
+            true => demangle(rest)?,
+            false => rest.to_owned(),
+        };
+        return Some(format!("{keyed}{rest}"));
+    }
+
+    let mut p = Parser::new(name.strip_prefix("_Z")?);
+    let mut id = p.encoding(true)?;
+    while p.peek() == Some(b'.')
+        && p.peek_at(1)
+            .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_')
+    {
+        id = p.clone_suffix(id);
+    }
+    if p.next != p.sym.len() {
+        return None;
+    }
+
+    let mut printer = Printer {
+        nodes: &p.nodes,
+        out: Output::new(),
+        templates: vec![],
+        current_template: None,
+        pack_index: 0,
+        lambda_params: 0,
+        depth: 0,
+        steps: 0,
+    };
+    printer.print(id)?;
+    Some(printer.out.into_string())
+}
+
+struct Parser<'a> {
+    sym: &'a str,
+    next: usize,
+    depth: u32,
+    nodes: Vec<Node<'a>>,
+    /// The substitution candidates, referenced by `S_` and `S<seq-id>_`.
+    subs: Vec<NodeId>,
+    /// The last source name, naming constructors and destructors.
+    last_name: Option<NodeId>,
+    /// Whether an expression is parsed, where `cv` is a cast rather than
+    /// a conversion operator.
+    in_expression: bool,
+    /// Whether the type of a conversion operator is parsed, where template
+    /// arguments after a template parameter are those of the operator.
+    in_conversion: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(sym: &'a str) -> Self {
+        Parser {
+            sym,
+            next: 0,
+            depth: 0,
+            nodes: vec![],
+            subs: vec![],
+            last_name: None,
+            in_expression: false,
+            in_conversion: false,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.sym.as_bytes().get(self.next + n).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.next += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        self.eat(c).then_some(())
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.next += 1;
+        Some(c)
+    }
+
+    fn add(&mut self, node: Node<'a>) -> NodeId {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn enter(&mut self) -> Option<()> {
+        self.depth += 1;
+        (self.depth <= MAX_DEPTH).then_some(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Parses a decimal number, which may be negative with an `n` prefix,
+    /// and is 0 if there are no digits.
+    fn number(&mut self) -> Option<i64> {
+        let negative = self.eat(b'n');
+        let mut n: i64 = 0;
+        while let Some(c @ b'0'..=b'9') = self.peek() {
+            n = n * 10 + i64::from(c - b'0');
+            if n > i64::from(i32::MAX) {
+                return None;
+            }
+            self.next += 1;
+        }
+        Some(if negative { -n } else { n })
+    }
+
+    /// Parses an optional number followed by `_`, `_` being 0 and `<n>_`
+    /// being `n + 1`.
+    fn compact_number(&mut self) -> Option<usize> {
+        let n = match self.peek()? {
+            b'_' => 0,
+            b'n' => return None,
+            _ => usize::try_from(self.number()?).ok()? + 1,
+        };
+        self.expect(b'_')?;
+        Some(n)
+    }
+
+    /// Skips the discriminator of a local name.
+    fn discriminator(&mut self) -> Option<()> {
+        if !self.eat(b'_') {
+            return Some(());
+        }
+        let underscores = self.eat(b'_');
+        let n = self.number()?;
+        if n < 0 {
+            return None;
+        }
+        if underscores && n >= 10 {
+            self.expect(b'_')?;
+        }
+        Some(())
+    }
+
+    fn clone_suffix(&mut self, encoding: NodeId) -> NodeId {
+        let start = self.next;
+        let b = self.sym.as_bytes();
+        let is_word = |c: u8| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_';
+        let mut end = start + 2;
+        while b.get(end).is_some_and(|&c| is_word(c)) {
+            end += 1;
+        }
+        while b.get(end) == Some(&b'.') && b.get(end + 1).is_some_and(u8::is_ascii_digit) {
+            end += 2;
+            while b.get(end).is_some_and(u8::is_ascii_digit) {
+                end += 1;
+            }
+        }
+        self.next = end;
+        self.add(Node::Clone(encoding, &self.sym[start..end]))
+    }
+
+    fn encoding(&mut self, top_level: bool) -> Option<NodeId> {
+        self.enter()?;
+        let encoding = self.encoding_inner(top_level);
+        self.leave();
+        encoding
+    }
+
+    fn encoding_inner(&mut self, top_level: bool) -> Option<NodeId> {
+        if matches!(self.peek()?, b'G' | b'T') {
+            return self.special_name();
+        }
+        let (name, quals) = self.name()?;
+        if matches!(self.peek(), None | Some(b'E')) {
+            return Some(name);
+        }
+        let has_ret = self.has_return_type(name);
+        let (mut ret, params) = self.bare_function_type(has_ret)?;
+        // The return type of a function nested in another is elided.
+        if !top_level && matches!(self.nodes[name], Node::Local(..)) {
+            ret = None;
+        }
+        Some(self.add(Node::Encoding {
+            name,
+            ret,
+            params,
+            quals,
+        }))
+    }
+
+    /// Returns whether the type of the function `name` starts with its
+    /// return type, which is the case of templates but not of their
+    /// constructors, destructors and conversion operators.
+    fn has_return_type(&self, name: NodeId) -> bool {
+        match self.nodes[name] {
+            Node::Local(_, entity) => self.has_return_type(entity),
+            Node::Template(name, _) => !self.is_ctor_dtor_or_conversion(name),
+            _ => false,
+        }
+    }
+
+    fn is_ctor_dtor_or_conversion(&self, name: NodeId) -> bool {
+        match self.nodes[name] {
+            Node::Nested(_, name) | Node::Local(_, name) => self.is_ctor_dtor_or_conversion(name),
+            Node::Ctor(_) | Node::Dtor(_) | Node::Conversion(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Parses the optional return type and the parameters of a function.
+    fn bare_function_type(&mut self, has_ret: bool) -> Option<(Option<NodeId>, Vec<NodeId>)> {
+        let has_ret = self.eat(b'J') || has_ret;
+        let ret = match has_ret {
+            true => Some(self.ty()?),
+            false => None,
+        };
+        Some((ret, self.params()?))
+    }
+
+    /// Parses the parameter types of a function, at least one, with a
+    /// single `void` meaning none.
+    fn params(&mut self) -> Option<Vec<NodeId>> {
+        let mut params = vec![];
+        loop {
+            match self.peek() {
+                None | Some(b'E' | b'.' | b'Q') => break,
+                Some(b'R' | b'O') if self.peek_at(1) == Some(b'E') => break,
+                _ => params.push(self.ty()?),
+            }
+        }
+        match params[..] {
+            [] => None,
+            [param] if matches!(self.nodes[param], Node::Builtin("void")) => Some(vec![]),
+            _ => Some(params),
+        }
+    }
+
+    fn call_offset(&mut self, kind: Option<u8>) -> Option<()> {
+        let kind = match kind {
+            Some(kind) => kind,
+            None => self.next_byte()?,
+        };
+        match kind {
+            b'h' => {
+                self.number();
+            }
+            b'v' => {
+                self.number();
+                self.expect(b'_')?;
+                self.number();
+            }
+            _ => return None,
+        }
+        self.expect(b'_')
+    }
+
+    fn special_name(&mut self) -> Option<NodeId> {
+        let (prefix, child) = if self.eat(b'T') {
+            match self.next_byte()? {
+                b'V' => ("vtable for ", self.ty()?),
+                b'T' => ("VTT for ", self.ty()?),
+                b'I' => ("typeinfo for ", self.ty()?),
+                b'S' => ("typeinfo name for ", self.ty()?),
+                b'F' => ("typeinfo fn for ", self.ty()?),
+                b'h' => {
+                    self.call_offset(Some(b'h'))?;
+                    ("non-virtual thunk to ", self.encoding(false)?)
+                }
+                b'v' => {
+                    self.call_offset(Some(b'v'))?;
+                    ("virtual thunk to ", self.encoding(false)?)
+                }
+                b'c' => {
+                    self.call_offset(None)?;
+                    self.call_offset(None)?;
+                    ("covariant return thunk to ", self.encoding(false)?)
+                }
+                b'C' => {
+                    let derived = self.ty()?;
+                    if self.number()? < 0 {
+                        return None;
+                    }
+                    self.expect(b'_')?;
+                    let base = self.ty()?;
+                    return Some(self.add(Node::ConstructionVtable(base, derived)));
+                }
+                b'H' => ("TLS init function for ", self.name()?.0),
+                b'W' => ("TLS wrapper function for ", self.name()?.0),
+                b'A' => ("template parameter object for ", self.template_arg()?),
+                _ => return None,
+            }
+        } else {
+            self.expect(b'G')?;
+            match self.next_byte()? {
+                b'V' => ("guard variable for ", self.name()?.0),
+                b'R' => {
+                    let name = self.name()?.0;
+                    let number = self.number()?;
+                    return Some(self.add(Node::ReferenceTemporary(name, number)));
+                }
+                b'A' => ("hidden alias for ", self.encoding(false)?),
+                b'T' => match self.next_byte()? {
+                    b'n' => ("non-transaction clone for ", self.encoding(false)?),
+                    _ => ("transaction clone for ", self.encoding(false)?),
+                },
+                _ => return None,
+            }
+        };
+        Some(self.add(Node::Special(prefix, child)))
+    }
+
+    /// Parses a name, and the qualifiers of the `this` of member
+    /// functions.
+    fn name(&mut self) -> Option<(NodeId, Quals)> {
+        self.enter()?;
+        let name = self.name_inner();
+        self.leave();
+        name
+    }
+
+    fn name_inner(&mut self) -> Option<(NodeId, Quals)> {
+        match self.peek()? {
+            b'N' => return self.nested_name(),
+            b'Z' => return self.local_name(),
+            _ => {}
+        }
+        let mut scope = None;
+        let mut name = None;
+        if self.peek() == Some(b'S') {
+            if self.peek_at(1) == Some(b't') {
+                self.next += 2;
+                scope = Some(self.add(Node::Name("std")));
+            }
+            if self.peek() == Some(b'S') {
+                if scope.is_some() {
+                    return None;
+                }
+                name = Some(self.substitution()?);
+            }
+        }
+        let from_substitution = name.is_some();
+        let mut name = match name {
+            Some(name) => name,
+            None => self.unqualified_name(scope)?,
+        };
+        if self.peek() == Some(b'I') {
+            // An unscoped template name.
+            if !from_substitution {
+                self.subs.push(name);
+            }
+            let args = self.template_args()?;
+            name = self.add(Node::Template(name, args));
+        }
+        Some((name, Quals::default()))
+    }
+
+    fn nested_name(&mut self) -> Option<(NodeId, Quals)> {
+        self.expect(b'N')?;
+        let quals = Quals {
+            cv: self.cv_qualifiers(),
+            ref_qual: self.ref_qualifier(),
+            specs: vec![],
+        };
+        let mut prefix = None;
+        loop {
+            match (self.peek()?, self.peek_at(1)) {
+                (b'D', Some(b'T' | b't')) => {
+                    if prefix.is_some() {
+                        return None;
+                    }
+                    prefix = Some(self.ty()?);
+                }
+                (b'I', _) => {
+                    let name = prefix?;
+                    let args = self.template_args()?;
+                    prefix = Some(self.add(Node::Template(name, args)));
+                }
+                (b'T', _) => {
+                    if prefix.is_some() {
+                        return None;
+                    }
+                    prefix = Some(self.template_param()?);
+                }
+                (b'M', _) => {
+                    // The initializer scope of a lambda.
+                    self.next += 1;
+                    continue;
+                }
+                (b'S', _) => {
+                    if prefix.is_some() {
+                        return None;
+                    }
+                    prefix = Some(self.substitution()?);
+                    continue;
+                }
+                _ => prefix = Some(self.unqualified_name(prefix)?),
+            }
+            if self.peek() == Some(b'E') {
+                break;
+            }
+            self.subs.extend(prefix);
+        }
+        self.next += 1;
+        Some((prefix?, quals))
+    }
+
+    fn local_name(&mut self) -> Option<(NodeId, Quals)> {
+        self.expect(b'Z')?;
+        let function = self.encoding(false)?;
+        self.expect(b'E')?;
+        let (entity, quals) = if self.eat(b's') {
+            self.discriminator()?;
+            (self.add(Node::Name("string literal")), Quals::default())
+        } else {
+            let default_arg = match self.eat(b'd') {
+                true => Some(self.compact_number()?),
+                false => None,
+            };
+            let (mut entity, quals) = self.name()?;
+            if !matches!(self.nodes[entity], Node::Lambda { .. } | Node::UnnamedType(_)) {
+                self.discriminator()?;
+            }
+            if let Some(n) = default_arg {
+                entity = self.add(Node::DefaultArg(n, entity));
+            }
+            (entity, quals)
+        };
+        // The return type of the function would read as the one of the
+        // entity.
+        if let Node::Encoding { ret, .. } = &mut self.nodes[function] {
+            *ret = None;
+        }
+        Some((self.add(Node::Local(function, entity)), quals))
+    }
+
+    fn unqualified_name(&mut self, scope: Option<NodeId>) -> Option<NodeId> {
+        let c = self.peek()?;
+        let mut name = match (c, self.peek_at(1)) {
+            (b'0'..=b'9', _) => self.source_name()?,
+            (b'a'..=b'z', _) => {
+                let in_expression = self.in_expression;
+                if (c, self.peek_at(1)) == (b'o', Some(b'n')) {
+                    self.next += 2;
+                    self.in_expression = false;
+                }
+                let op = self.operator_name();
+                self.in_expression = in_expression;
+                let op = op?;
+                match self.nodes[op] {
+                    Node::Operator(Operator { code: "li", .. }) => {
+                        let name = self.source_name()?;
+                        self.add(Node::LiteralOperator(name))
+                    }
+                    _ => op,
+                }
+            }
+            // Structured bindings.
+            (b'D', Some(b'C')) => return None,
+            (b'C' | b'D', _) => self.ctor_dtor_name()?,
+            (b'L', _) => {
+                self.next += 1;
+                let name = self.source_name()?;
+                self.discriminator()?;
+                name
+            }
+            (b'U', Some(b'l')) => self.lambda()?,
+            (b'U', Some(b't')) => {
+                self.next += 2;
+                let n = self.compact_number()?;
+                let id = self.add(Node::UnnamedType(n));
+                self.subs.push(id);
+                id
+            }
+            _ => return None,
+        };
+        if self.peek() == Some(b'B') {
+            let last_name = self.last_name;
+            while self.eat(b'B') {
+                let tag = self.source_name()?;
+                name = self.add(Node::AbiTag(name, tag));
+            }
+            self.last_name = last_name;
+        }
+        if let Some(scope) = scope {
+            name = self.add(Node::Nested(scope, name));
+        }
+        Some(name)
+    }
+
+    fn source_name(&mut self) -> Option<NodeId> {
+        let len = usize::try_from(self.number()?).ok().filter(|&len| len > 0)?;
+        let name = self.sym.get(self.next..self.next.checked_add(len)?)?;
+        self.next += len;
+        let b = name.as_bytes();
+        let name =
+            if b.len() >= 10 && name.starts_with("_GLOBAL_") && matches!(b[8], b'.' | b'_' | b'$') && b[9] == b'N' {
+                "(anonymous namespace)"
+            } else {
+                name
+            };
+        let id = self.add(Node::Name(name));
+        self.last_name = Some(id);
+        Some(id)
+    }
+
+    fn operator_name(&mut self) -> Option<NodeId> {
+        let code = self.sym.get(self.next..self.next + 2)?;
+        self.next += 2;
+        if code == "cv" {
+            let in_expression = self.in_expression;
+            let in_conversion = std::mem::replace(&mut self.in_conversion, !in_expression);
+            let ty = self.ty();
+            self.in_conversion = in_conversion;
+            let ty = ty?;
+            return Some(self.add(match in_expression {
+                true => Node::Cast(ty),
+                false => Node::Conversion(ty),
+            }));
+        }
+        let op = OPERATORS.iter().find(|op| op.code == code)?;
+        Some(self.add(Node::Operator(op)))
+    }
+
+    fn ctor_dtor_name(&mut self) -> Option<NodeId> {
+        let name = self.last_name?;
+        if self.eat(b'C') {
+            let inheriting = self.eat(b'I');
+            if !matches!(self.next_byte()?, b'1'..=b'5') {
+                return None;
+            }
+            if inheriting {
+                self.ty()?;
+            }
+            Some(self.add(Node::Ctor(name)))
+        } else {
+            self.expect(b'D')?;
+            if !matches!(self.next_byte()?, b'0' | b'1' | b'2' | b'4' | b'5') {
+                return None;
+            }
+            Some(self.add(Node::Dtor(name)))
+        }
+    }
+
+    fn lambda(&mut self) -> Option<NodeId> {
+        self.next += 2;
+        let params = self.params()?;
+        self.expect(b'E')?;
+        let number = self.compact_number()?;
+        Some(self.add(Node::Lambda { params, number }))
+    }
+
+    fn substitution(&mut self) -> Option<NodeId> {
+        self.expect(b'S')?;
+        let mut c = self.next_byte()?;
+        if c == b'_' || c.is_ascii_digit() || c.is_ascii_uppercase() {
+            let mut index: usize = 0;
+            if c != b'_' {
+                while c != b'_' {
+                    let digit = match c {
+                        b'0'..=b'9' => c - b'0',
+                        b'A'..=b'Z' => c - b'A' + 10,
+                        _ => return None,
+                    };
+                    index = index.checked_mul(36)?.checked_add(usize::from(digit))?;
+                    c = self.next_byte()?;
+                }
+                index += 1;
+            }
+            return self.subs.get(index).copied();
+        }
+        let (name, last_name) = match c {
+            b't' => ("std", None),
+            b'a' => ("std::allocator", Some("allocator")),
+            b'b' => ("std::basic_string", Some("basic_string")),
+            b's' => (
+                "std::basic_string<char, std::char_traits<char>, std::allocator<char> >",
+                Some("basic_string"),
+            ),
+            b'i' => (
+                "std::basic_istream<char, std::char_traits<char> >",
+                Some("basic_istream"),
+            ),
+            b'o' => (
+                "std::basic_ostream<char, std::char_traits<char> >",
+                Some("basic_ostream"),
+            ),
+            b'd' => (
+                "std::basic_iostream<char, std::char_traits<char> >",
+                Some("basic_iostream"),
+            ),
+            _ => return None,
+        };
+        if let Some(last_name) = last_name {
+            self.last_name = Some(self.add(Node::Name(last_name)));
+        }
+        Some(self.add(Node::Builtin(name)))
+    }
+
+    fn template_args(&mut self) -> Option<NodeId> {
+        let last_name = self.last_name;
+        if !self.eat(b'I') && !self.eat(b'J') {
+            return None;
+        }
+        let mut args = vec![];
+        while !self.eat(b'E') {
+            args.push(self.template_arg()?);
+        }
+        self.last_name = last_name;
+        Some(self.add(Node::Args(args)))
+    }
+
+    fn template_arg(&mut self) -> Option<NodeId> {
+        match self.peek()? {
+            b'X' => {
+                self.next += 1;
+                let expr = self.expression()?;
+                self.expect(b'E')?;
+                Some(expr)
+            }
+            b'L' => self.expr_primary(),
+            b'I' | b'J' => self.template_args(),
+            _ => self.ty(),
+        }
+    }
+
+    fn template_param(&mut self) -> Option<NodeId> {
+        self.expect(b'T')?;
+        let index = self.compact_number()?;
+        Some(self.add(Node::TemplateParam(index)))
+    }
+
+    fn next_is_type_qualifier(&self) -> bool {
+        matches!(
+            (self.peek(), self.peek_at(1)),
+            (Some(b'r' | b'V' | b'K'), _) | (Some(b'D'), Some(b'x' | b'o' | b'O' | b'w'))
+        )
+    }
+
+    fn cv_qualifiers(&mut self) -> u8 {
+        let mut cv = 0;
+        for (c, bit) in [(b'r', RESTRICT), (b'V', VOLATILE), (b'K', CONST)] {
+            if self.eat(c) {
+                cv |= bit;
+            }
+        }
+        cv
+    }
+
+    fn ref_qualifier(&mut self) -> Option<&'static str> {
+        if self.eat(b'R') {
+            Some(" &")
+        } else if self.eat(b'O') {
+            Some(" &&")
+        } else {
+            None
+        }
+    }
+
+    fn ty(&mut self) -> Option<NodeId> {
+        self.enter()?;
+        let ty = self.ty_inner();
+        self.leave();
+        ty
+    }
+
+    fn ty_inner(&mut self) -> Option<NodeId> {
+        if self.next_is_type_qualifier() {
+            let mut quals = Quals::default();
+            while self.next_is_type_qualifier() {
+                quals.cv |= self.cv_qualifiers();
+                if self.next_is_type_qualifier() {
+                    self.next += 1;
+                    let spec = match self.next_byte()? {
+                        b'x' => Node::Spec("transaction_safe", None),
+                        b'o' => Node::Spec("noexcept", None),
+                        b'O' => {
+                            let expr = self.expression()?;
+                            self.expect(b'E')?;
+                            Node::Spec("noexcept", Some(expr))
+                        }
+                        _ => {
+                            let types = self.params()?;
+                            self.expect(b'E')?;
+                            let types = self.add(Node::List(types));
+                            Node::Spec("throw", Some(types))
+                        }
+                    };
+                    quals.specs.push(self.add(spec));
+                }
+            }
+            let id = if self.peek() == Some(b'F') {
+                // The qualifiers of a member function.
+                let function = self.function_type()?;
+                if let Node::Function { quals: q, .. } = &mut self.nodes[function] {
+                    q.cv = quals.cv;
+                    q.specs = quals.specs;
+                }
+                function
+            } else {
+                if !quals.specs.is_empty() {
+                    return None;
+                }
+                let ty = self.ty()?;
+                self.add(Node::Qualified(ty, quals.cv))
+            };
+            self.subs.push(id);
+            return Some(id);
+        }
+
+        let c = self.peek()?;
+        if let Some(name) = builtin_type(c) {
+            self.next += 1;
+            return Some(self.add(Node::Builtin(name)));
+        }
+        let id = match c {
+            b'u' => {
+                self.next += 1;
+                self.source_name()?
+            }
+            b'F' => self.function_type()?,
+            b'0'..=b'9' | b'N' | b'Z' => self.name()?.0,
+            b'A' => {
+                self.next += 1;
+                let dim = match self.peek()? {
+                    b'_' => None,
+                    b'0'..=b'9' => {
+                        let start = self.next;
+                        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                            self.next += 1;
+                        }
+                        Some(self.add(Node::Name(&self.sym[start..self.next])))
+                    }
+                    _ => Some(self.expression()?),
+                };
+                self.expect(b'_')?;
+                let element = self.ty()?;
+                self.add(Node::Array(element, dim))
+            }
+            b'M' => {
+                self.next += 1;
+                let class = self.ty()?;
+                let member = self.ty()?;
+                self.add(Node::MemberPointer(class, member))
+            }
+            b'T' => {
+                let param = self.template_param()?;
+                if self.peek() != Some(b'I') {
+                    param
+                } else if !self.in_conversion {
+                    // A template template parameter.
+                    self.subs.push(param);
+                    let args = self.template_args()?;
+                    self.add(Node::Template(param, args))
+                } else {
+                    // The template arguments are the ones of the
+                    // conversion operator, unless others follow them.
+                    let (next, nodes, subs) = (self.next, self.nodes.len(), self.subs.len());
+                    let args = self.template_args()?;
+                    if self.peek() == Some(b'I') {
+                        self.subs.push(param);
+                        self.add(Node::Template(param, args))
+                    } else {
+                        self.next = next;
+                        self.nodes.truncate(nodes);
+                        self.subs.truncate(subs);
+                        param
+                    }
+                }
+            }
+            b'S' => {
+                if matches!(self.peek_at(1), Some(b'0'..=b'9' | b'_' | b'A'..=b'Z')) {
+                    let sub = self.substitution()?;
+                    if self.peek() != Some(b'I') {
+                        return Some(sub);
+                    }
+                    let args = self.template_args()?;
+                    self.add(Node::Template(sub, args))
+                } else {
+                    let name = self.name()?.0;
+                    // A standard substitution is not a new candidate.
+                    if matches!(self.nodes[name], Node::Builtin(_)) {
+                        return Some(name);
+                    }
+                    name
+                }
+            }
+            b'P' | b'R' | b'O' | b'C' | b'G' => {
+                self.next += 1;
+                let ty = self.ty()?;
+                self.add(match c {
+                    b'P' => Node::Pointer(ty),
+                    b'R' => Node::Reference(ty, false),
+                    b'O' => Node::Reference(ty, true),
+                    b'C' => Node::Postfix(ty, " _Complex"),
+                    _ => Node::Postfix(ty, " _Imaginary"),
+                })
+            }
+            b'U' => {
+                self.next += 1;
+                let mut qualifier = self.source_name()?;
+                if self.peek() == Some(b'I') {
+                    let args = self.template_args()?;
+                    qualifier = self.add(Node::Template(qualifier, args));
+                }
+                let ty = self.ty()?;
+                self.add(Node::VendorQualified(ty, qualifier))
+            }
+            b'D' => {
+                self.next += 1;
+                let name = match self.next_byte()? {
+                    b'T' | b't' => {
+                        let expr = self.expression()?;
+                        self.expect(b'E')?;
+                        let id = self.add(Node::Decltype(expr));
+                        self.subs.push(id);
+                        return Some(id);
+                    }
+                    b'p' => {
+                        let ty = self.ty()?;
+                        let id = self.add(Node::PackExpansion(ty));
+                        self.subs.push(id);
+                        return Some(id);
+                    }
+                    b'v' => {
+                        let dim = match self.eat(b'_') {
+                            true => self.expression()?,
+                            false => {
+                                let n = self.number()?;
+                                self.add(Node::Number(n))
+                            }
+                        };
+                        self.expect(b'_')?;
+                        let ty = self.ty()?;
+                        let id = self.add(Node::Vector(ty, dim));
+                        self.subs.push(id);
+                        return Some(id);
+                    }
+                    b'F' => {
+                        let start = self.next;
+                        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                            self.next += 1;
+                        }
+                        let bits = &self.sym[start..self.next];
+                        let id = match self.next_byte()? {
+                            b'b' if bits == "16" => Node::Builtin("std::bfloat16_t"),
+                            b'x' => Node::FloatN(bits, "x"),
+                            b'_' => Node::FloatN(bits, ""),
+                            _ => return None,
+                        };
+                        return Some(self.add(id));
+                    }
+                    b'a' => return Some(self.add(Node::Name("auto"))),
+                    b'c' => return Some(self.add(Node::Name("decltype(auto)"))),
+                    b'f' => "decimal32",
+                    b'd' => "decimal64",
+                    b'e' => "decimal128",
+                    b'h' => "half",
+                    b'u' => "char8_t",
+                    b's' => "char16_t",
+                    b'i' => "char32_t",
+                    b'n' => "decltype(nullptr)",
+                    _ => return None,
+                };
+                return Some(self.add(Node::Builtin(name)));
+            }
+            _ => return None,
+        };
+        self.subs.push(id);
+        Some(id)
+    }
+
+    fn function_type(&mut self) -> Option<NodeId> {
+        self.expect(b'F')?;
+        self.eat(b'Y');
+        let (ret, params) = self.bare_function_type(true)?;
+        let ref_qual = self.ref_qualifier();
+        self.expect(b'E')?;
+        let quals = Quals {
+            ref_qual,
+            ..Quals::default()
+        };
+        Some(self.add(Node::Function {
+            ret: ret?,
+            params,
+            quals,
+        }))
+    }
+
+    fn expression(&mut self) -> Option<NodeId> {
+        self.enter()?;
+        let in_expression = std::mem::replace(&mut self.in_expression, true);
+        let expr = self.expression_inner();
+        self.in_expression = in_expression;
+        self.leave();
+        expr
+    }
+
+    fn expression_inner(&mut self) -> Option<NodeId> {
+        let c = self.peek()?;
+        match (c, self.peek_at(1)) {
+            (b'L', _) => return self.expr_primary(),
+            (b'T', _) => return self.template_param(),
+            (b's', Some(b'r')) => {
+                // An unresolved name, with the qualifiers of `srN` and of
+                // `sr<digit>` ending with `E`.
+                self.next += 2;
+                let mut qualified = self.eat(b'N');
+                let mut name = match self.peek()? {
+                    b'0'..=b'9' if !qualified => {
+                        qualified = true;
+                        self.simple_id()?
+                    }
+                    _ => {
+                        let mut ty = self.ty()?;
+                        if self.peek() == Some(b'I') {
+                            let args = self.template_args()?;
+                            ty = self.add(Node::Template(ty, args));
+                        }
+                        ty
+                    }
+                };
+                if qualified {
+                    while !self.eat(b'E') {
+                        let qualifier = self.simple_id()?;
+                        name = self.add(Node::Nested(name, qualifier));
+                    }
+                }
+                // The template arguments apply to the whole name.
+                let base = self.unqualified_name(None)?;
+                let name = self.add(Node::Nested(name, base));
+                if self.peek() != Some(b'I') {
+                    return Some(name);
+                }
+                let args = self.template_args()?;
+                return Some(self.add(Node::Template(name, args)));
+            }
+            (b's', Some(b'p')) => {
+                self.next += 2;
+                let expr = self.expression()?;
+                return Some(self.add(Node::PackExpansion(expr)));
+            }
+            (b'f', Some(b'p')) => {
+                self.next += 2;
+                let index = match self.eat(b'T') {
+                    true => 0,
+                    false => self.compact_number()? + 1,
+                };
+                return Some(self.add(Node::FunctionParam(index)));
+            }
+            (b'0'..=b'9', _) | (b'o', Some(b'n')) => {
+                if c == b'o' {
+                    self.next += 2;
+                }
+                let name = self.unqualified_name(None)?;
+                if self.peek() != Some(b'I') {
+                    return Some(name);
+                }
+                let args = self.template_args()?;
+                return Some(self.add(Node::Template(name, args)));
+            }
+            (b'i' | b't', Some(b'l')) => {
+                self.next += 2;
+                let ty = match c {
+                    b't' => Some(self.ty()?),
+                    _ => None,
+                };
+                let list = self.expr_list(b'E')?;
+                return Some(self.add(Node::InitList(ty, list)));
+            }
+            _ => {}
+        }
+
+        let op = self.operator_name()?;
+        let (code, arity) = match self.nodes[op] {
+            Node::Operator(&Operator { code, arity, .. }) => (code, arity),
+            Node::Cast(_) => ("cv", 1),
+            _ => return None,
+        };
+        if code == "st" {
+            let ty = self.ty()?;
+            return Some(self.add(Node::Unary {
+                op,
+                operand: ty,
+                postfix: false,
+            }));
+        }
+        let node = match arity {
+            0 => Node::Nullary(op),
+            1 => {
+                // `pp_` and `mm_` are the prefix forms.
+                let postfix = matches!(code, "pp" | "mm") && !self.eat(b'_');
+                let operand = if code == "cv" && self.eat(b'_') {
+                    self.expr_list(b'E')?
+                } else if code == "sP" {
+                    return None;
+                } else {
+                    self.expression()?
+                };
+                Node::Unary { op, operand, postfix }
+            }
+            2 => {
+                if code == "cl" {
+                    let function = self.expression()?;
+                    let args = self.expr_list(b'E')?;
+                    return Some(self.add(Node::Call(function, args)));
+                }
+                let left = match code {
+                    "dc" | "sc" | "rc" | "cc" => self.ty()?,
+                    _ => self.expression()?,
+                };
+                let right = match code {
+                    "dt" | "pt" => {
+                        let mut name = self.unqualified_name(None)?;
+                        if self.peek() == Some(b'I') {
+                            let args = self.template_args()?;
+                            name = self.add(Node::Template(name, args));
+                        }
+                        name
+                    }
+                    _ => self.expression()?,
+                };
+                Node::Binary { op, left, right }
+            }
+            _ => match code {
+                "qu" => {
+                    let first = self.expression()?;
+                    let second = self.expression()?;
+                    let third = self.expression()?;
+                    Node::Conditional(first, second, third)
+                }
+                "nw" | "na" => {
+                    let placement = self.expr_list(b'_')?;
+                    let ty = self.ty()?;
+                    let init = if self.eat(b'E') {
+                        None
+                    } else if self.peek() == Some(b'p') && self.peek_at(1) == Some(b'i') {
+                        self.next += 2;
+                        Some(self.expr_list(b'E')?)
+                    } else if self.peek() == Some(b'i') && self.peek_at(1) == Some(b'l') {
+                        Some(self.expression()?)
+                    } else {
+                        return None;
+                    };
+                    Node::New { placement, ty, init }
+                }
+                _ => return None,
+            },
+        };
+        Some(self.add(node))
+    }
+
+    /// Parses a name and its optional template arguments.
+    fn simple_id(&mut self) -> Option<NodeId> {
+        let name = self.unqualified_name(None)?;
+        if self.peek() != Some(b'I') {
+            return Some(name);
+        }
+        let args = self.template_args()?;
+        Some(self.add(Node::Template(name, args)))
+    }
+
+    /// Parses expressions up to `end`.
+    fn expr_list(&mut self, end: u8) -> Option<NodeId> {
+        let mut exprs = vec![];
+        while !self.eat(end) {
+            exprs.push(self.expression()?);
+        }
+        Some(self.add(Node::List(exprs)))
+    }
+
+    fn expr_primary(&mut self) -> Option<NodeId> {
+        self.expect(b'L')?;
+        let id = if matches!(self.peek()?, b'_' | b'Z') {
+            self.eat(b'_');
+            self.expect(b'Z')?;
+            self.encoding(false)?
+        } else {
+            let ty = self.ty()?;
+            if matches!(self.nodes[ty], Node::Builtin("decltype(nullptr)")) && self.eat(b'E') {
+                return Some(ty);
+            }
+            let negative = self.eat(b'n');
+            let start = self.next;
+            while self.peek()? != b'E' {
+                self.next += 1;
+            }
+            let value = &self.sym[start..self.next];
+            self.add(Node::Literal { ty, value, negative })
+        };
+        self.expect(b'E')?;
+        Some(id)
+    }
+}
+
+/// Prints nodes, types in two parts around what they apply to, as in
+/// `void (*name)(int)`.
+struct Printer<'p, 'a> {
+    nodes: &'p [Node<'a>],
+    out: Output,
+    /// The `Args` of the templates in scope, the innermost last.
+    templates: Vec<NodeId>,
+    /// The `Args` of the innermost template printed, in scope of the type
+    /// of a conversion operator.
+    current_template: Option<NodeId>,
+    /// The index of the element of packs printed in a pack expansion.
+    pack_index: usize,
+    /// Whether the parameters of a lambda are printed, where template
+    /// parameters are `auto`.
+    lambda_params: u32,
+    depth: u32,
+    steps: u32,
+}
+
+impl Printer<'_, '_> {
+    fn push(&mut self, s: &str) -> Option<()> {
+        self.out.push(s)
+    }
+
+    fn last_char(&self) -> Option<char> {
+        self.out.last_char()
+    }
+
+    fn enter(&mut self) -> Option<()> {
+        self.depth += 1;
+        self.steps += 1;
+        (self.depth <= MAX_DEPTH && self.steps <= MAX_PRINT_STEPS).then_some(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Returns the argument of the template parameter `index`, in the
+    /// templates in scope up to `level`.
+    fn template_arg(&self, index: usize, level: usize) -> Option<NodeId> {
+        let args = *self.templates.get(level.checked_sub(1)?)?;
+        let Node::Args(args) = &self.nodes[args] else {
+            return None;
+        };
+        let arg = *args.get(index)?;
+        match &self.nodes[arg] {
+            Node::Args(pack) => pack.get(self.pack_index).copied(),
+            _ => Some(arg),
+        }
+    }
+
+    /// Returns the node which `id` stands for, looking through template
+    /// parameters, and the level of the templates in scope of it.
+    fn resolve(&self, mut id: NodeId, mut level: usize) -> (NodeId, usize) {
+        while let Node::TemplateParam(index) = self.nodes[id] {
+            match self.template_arg(index, level) {
+                Some(arg) if self.lambda_params == 0 => {
+                    id = arg;
+                    level -= 1;
+                }
+                _ => break,
+            }
+        }
+        (id, level)
+    }
+
+    /// Returns whether the type `id` is printed around what it applies to,
+    /// as are functions and arrays and pointers to them.
+    fn has_right(&self, id: NodeId, level: usize) -> bool {
+        let (id, level) = self.resolve(id, level);
+        match self.nodes[id] {
+            Node::Function { .. } | Node::Array(..) => true,
+            Node::Pointer(ty)
+            | Node::Reference(ty, _)
+            | Node::Qualified(ty, _)
+            | Node::VendorQualified(ty, _)
+            | Node::Postfix(ty, _)
+            | Node::Vector(ty, _)
+            | Node::MemberPointer(_, ty) => self.has_right(ty, level),
+            _ => false,
+        }
+    }
+
+    /// Prints a template parameter with `f`, out of the scope of its
+    /// template.
+    fn print_template_param(&mut self, index: usize, f: fn(&mut Self, NodeId) -> Option<()>) -> Option<()> {
+        let arg = self.template_arg(index, self.templates.len())?;
+        let template = self.templates.pop();
+        f(self, arg)?;
+        self.templates.extend(template);
+        Some(())
+    }
+
+    fn print(&mut self, id: NodeId) -> Option<()> {
+        self.print_left(id)?;
+        self.print_right(id)
+    }
+
+    /// Prints nodes separated by commas, dropping the separators before
+    /// empty packs ending the list.
+    fn print_list(&mut self, ids: &[NodeId]) -> Option<()> {
+        let mut separators = vec![];
+        for (i, &id) in ids.iter().enumerate() {
+            if i != 0 {
+                separators.push(self.out.len());
+                self.push(", ")?;
+            }
+            self.print(id)?;
+        }
+        for &at in separators.iter().rev() {
+            if self.out.len() == at + 2 {
+                self.out.truncate(at);
+            }
+        }
+        Some(())
+    }
+
+    fn print_template_args(&mut self, args: NodeId) -> Option<()> {
+        if self.last_char() == Some('<') {
+            self.push(" ")?;
+        }
+        self.push("<")?;
+        self.print(args)?;
+        if self.last_char() == Some('>') {
+            self.push(" ")?;
+        }
+        self.push(">")
+    }
+
+    fn print_cv(&mut self, cv: u8) -> Option<()> {
+        for (bit, s) in [(CONST, " const"), (VOLATILE, " volatile"), (RESTRICT, " restrict")] {
+            if cv & bit != 0 {
+                self.push(s)?;
+            }
+        }
+        Some(())
+    }
+
+    fn print_quals(&mut self, quals: &Quals) -> Option<()> {
+        self.print_cv(quals.cv)?;
+        if let Some(ref_qual) = quals.ref_qual {
+            self.push(ref_qual)?;
+        }
+        for &spec in &quals.specs {
+            self.print(spec)?;
+        }
+        Some(())
+    }
+
+    fn print_left(&mut self, id: NodeId) -> Option<()> {
+        self.enter()?;
+        let nodes = self.nodes;
+        match &nodes[id] {
+            Node::Name(s) => self.push(s)?,
+            Node::Builtin(s) => self.push(s)?,
+            Node::Number(n) => self.push(&n.to_string())?,
+            Node::FloatN(bits, suffix) => {
+                self.push("_Float")?;
+                self.push(bits)?;
+                self.push(suffix)?;
+            }
+            &Node::Nested(prefix, name) | &Node::Local(prefix, name) => {
+                self.print(prefix)?;
+                self.push("::")?;
+                self.print(name)?;
+            }
+            &Node::DefaultArg(n, entity) => {
+                self.push(&format!("{{default arg#{}}}::", n + 1))?;
+                self.print(entity)?;
+            }
+            &Node::Template(name, args) => {
+                let current_template = self.current_template.replace(args);
+                self.print(name)?;
+                self.print_template_args(args)?;
+                self.current_template = current_template;
+            }
+            Node::Args(ids) | Node::List(ids) => self.print_list(ids)?,
+            &Node::AbiTag(name, tag) => {
+                self.print(name)?;
+                self.push("[abi:")?;
+                self.print(tag)?;
+                self.push("]")?;
+            }
+            &Node::Ctor(name) => self.print(name)?,
+            &Node::Dtor(name) => {
+                self.push("~")?;
+                self.print(name)?;
+            }
+            Node::Operator(op) => {
+                self.push("operator")?;
+                if op.name.starts_with(|c: char| c.is_ascii_lowercase()) {
+                    self.push(" ")?;
+                }
+                self.push(op.name.trim_end_matches(' '))?;
+            }
+            &Node::LiteralOperator(name) => {
+                self.push("operator\"\" ")?;
+                self.print(name)?;
+            }
+            &Node::Conversion(ty) => {
+                self.push("operator ")?;
+                // The type is in scope of the template of the operator.
+                let level = self.templates.len();
+                self.templates.extend(self.current_template);
+                match nodes[ty] {
+                    Node::Template(name, args) => {
+                        self.print(name)?;
+                        self.templates.truncate(level);
+                        self.print_template_args(args)?;
+                    }
+                    _ => {
+                        self.print(ty)?;
+                        self.templates.truncate(level);
+                    }
+                }
+            }
+            &Node::Cast(ty) => self.print(ty)?,
+            Node::Lambda { params, number } => {
+                self.push("{lambda(")?;
+                self.lambda_params += 1;
+                self.print_list(params)?;
+                self.lambda_params -= 1;
+                self.push(&format!(")#{}}}", number + 1))?;
+            }
+            Node::UnnamedType(n) => self.push(&format!("{{unnamed type#{}}}", n + 1))?,
+            &Node::Special(prefix, child) => {
+                self.push(prefix)?;
+                self.print(child)?;
+            }
+            &Node::ConstructionVtable(base, derived) => {
+                self.push("construction vtable for ")?;
+                self.print(base)?;
+                self.push("-in-")?;
+                self.print(derived)?;
+            }
+            &Node::ReferenceTemporary(name, n) => {
+                self.push(&format!("reference temporary #{n} for "))?;
+                self.print(name)?;
+            }
+            &Node::Clone(encoding, suffix) => {
+                self.print(encoding)?;
+                self.push(" [clone ")?;
+                self.push(suffix)?;
+                self.push("]")?;
+            }
+            Node::Encoding {
+                name,
+                ret,
+                params,
+                quals,
+            } => self.print_encoding(*name, *ret, params, quals)?,
+            &Node::TemplateParam(index) => {
+                if self.lambda_params > 0 {
+                    self.push(&format!("auto:{}", index + 1))?;
+                } else {
+                    self.print_template_param(index, Self::print_left)?;
+                }
+            }
+            &Node::FunctionParam(0) => self.push("this")?,
+            Node::FunctionParam(n) => self.push(&format!("{{parm#{n}}}"))?,
+            &Node::Qualified(ty, cv) => {
+                self.print_left(ty)?;
+                // Qualifiers the template argument already has, or which
+                // would apply to a reference, are dropped.
+                let (arg, _) = self.resolve(ty, self.templates.len());
+                let redundant = match self.nodes[arg] {
+                    _ if arg == ty => false,
+                    Node::Reference(_, rvalue) => !rvalue,
+                    Node::Qualified(_, arg_cv) => arg_cv == cv,
+                    _ => false,
+                };
+                if !redundant {
+                    self.print_cv(cv)?;
+                }
+            }
+            &Node::VendorQualified(ty, qualifier) => {
+                self.print_left(ty)?;
+                self.push(" ")?;
+                self.print(qualifier)?;
+            }
+            &Node::Postfix(ty, s) => {
+                self.print_left(ty)?;
+                self.push(s)?;
+            }
+            &Node::Vector(ty, dim) => {
+                self.print_left(ty)?;
+                self.push(" __vector(")?;
+                self.print(dim)?;
+                self.push(")")?;
+            }
+            &Node::Pointer(ty) => self.print_indirection_left(ty, "*")?,
+            &Node::Reference(ty, rvalue) => {
+                let (ty, rvalue) = self.collapse_reference(ty, rvalue);
+                self.print_indirection_left(ty, if rvalue { "&&" } else { "&" })?;
+            }
+            &Node::MemberPointer(class, member) => {
+                let (resolved, _) = self.resolve(member, self.templates.len());
+                self.print_left(member)?;
+                match nodes[resolved] {
+                    Node::Function { .. } => {
+                        if self.last_char() != Some(' ') {
+                            self.push(" ")?;
+                        }
+                        self.push("(")?;
+                    }
+                    Node::Array(..) => self.push(" (")?,
+                    _ => {
+                        if self.last_char() != Some('(') {
+                            self.push(" ")?;
+                        }
+                    }
+                }
+                self.print(class)?;
+                self.push("::*")?;
+            }
+            &Node::Function { ret, .. } => {
+                self.print_left(ret)?;
+                if !self.has_right(ret, self.templates.len()) {
+                    self.push(" ")?;
+                }
+            }
+            &Node::Array(element, _) => self.print_left(element)?,
+            &Node::Decltype(expr) => {
+                self.push("decltype (")?;
+                self.print(expr)?;
+                self.push(")")?;
+            }
+            &Node::PackExpansion(pattern) => self.print_pack_expansion(pattern)?,
+            &Node::Spec(name, arg) => {
+                self.push(" ")?;
+                self.push(name)?;
+                if let Some(arg) = arg {
+                    self.push("(")?;
+                    self.print(arg)?;
+                    self.push(")")?;
+                }
+            }
+            &Node::Literal { ty, value, negative } => self.print_literal(ty, value, negative)?,
+            &Node::Nullary(op) => self.print_operator(op)?,
+            &Node::Unary { op, operand, postfix } => self.print_unary(op, operand, postfix)?,
+            &Node::Binary { op, left, right } => self.print_binary(op, left, right)?,
+            &Node::Call(function, args) => {
+                // The parameter types of a called function are not printed.
+                let function = match nodes[function] {
+                    Node::Encoding { name, .. } => name,
+                    _ => function,
+                };
+                self.print_subexpr(function)?;
+                self.print_subexpr(args)?;
+            }
+            &Node::Conditional(first, second, third) => {
+                self.print_subexpr(first)?;
+                self.push("?")?;
+                self.print_subexpr(second)?;
+                self.push(" : ")?;
+                self.print_subexpr(third)?;
+            }
+            &Node::New { placement, ty, init } => {
+                self.push("new ")?;
+                if matches!(&nodes[placement], Node::List(exprs) if !exprs.is_empty()) {
+                    self.print_subexpr(placement)?;
+                    self.push(" ")?;
+                }
+                self.print(ty)?;
+                if let Some(init) = init {
+                    self.print_subexpr(init)?;
+                }
+            }
+            &Node::InitList(ty, list) => {
+                if let Some(ty) = ty {
+                    self.print(ty)?;
+                }
+                self.push("{")?;
+                self.print(list)?;
+                self.push("}")?;
+            }
+        }
+        self.leave();
+        Some(())
+    }
+
+    fn print_right(&mut self, id: NodeId) -> Option<()> {
+        self.enter()?;
+        let nodes = self.nodes;
+        match &nodes[id] {
+            &Node::TemplateParam(index) if self.lambda_params == 0 => {
+                self.print_template_param(index, Self::print_right)?
+            }
+            &Node::Qualified(ty, _) | &Node::VendorQualified(ty, _) | &Node::Postfix(ty, _) | &Node::Vector(ty, _) => {
+                self.print_right(ty)?
+            }
+            &Node::Pointer(ty) | &Node::MemberPointer(_, ty) => self.print_indirection_right(ty)?,
+            &Node::Reference(ty, rvalue) => {
+                let (ty, _) = self.collapse_reference(ty, rvalue);
+                self.print_indirection_right(ty)?;
+            }
+            Node::Function { ret, params, quals } => {
+                self.push("(")?;
+                self.print_list(params)?;
+                self.push(")")?;
+                self.print_quals(quals)?;
+                self.print_right(*ret)?;
+            }
+            &Node::Array(..) => self.print_array_right(id, true)?,
+            _ => {}
+        }
+        self.leave();
+        Some(())
+    }
+
+    /// Prints the part of a pointer or reference to `ty` before what it
+    /// applies to.
+    fn print_indirection_left(&mut self, ty: NodeId, op: &str) -> Option<()> {
+        let resolved = self.indirection_target(ty);
+        self.print_left(ty)?;
+        match self.nodes[resolved] {
+            Node::Function { .. } => {
+                if !matches!(self.last_char(), Some('(' | '*' | ' ')) {
+                    self.push(" ")?;
+                }
+                self.push("(")?;
+            }
+            Node::Array(..) => self.push(" (")?,
+            _ => {}
+        }
+        self.push(op)
+    }
+
+    fn print_indirection_right(&mut self, ty: NodeId) -> Option<()> {
+        let resolved = self.indirection_target(ty);
+        if matches!(self.nodes[resolved], Node::Function { .. } | Node::Array(..)) {
+            self.push(")")?;
+        }
+        self.print_right(ty)
+    }
+
+    /// Returns the type a pointer or reference to `ty` applies to, looking
+    /// through template parameters and the qualifiers of arrays.
+    fn indirection_target(&self, ty: NodeId) -> NodeId {
+        let (mut resolved, mut level) = self.resolve(ty, self.templates.len());
+        while let Node::Qualified(inner, _) | Node::VendorQualified(inner, _) = self.nodes[resolved] {
+            let (inner, inner_level) = self.resolve(inner, level);
+            if !matches!(self.nodes[inner], Node::Array(..)) {
+                break;
+            }
+            (resolved, level) = (inner, inner_level);
+        }
+        resolved
+    }
+
+    /// Returns the type referenced by a reference to `ty`, and whether the
+    /// reference is an rvalue one, collapsing references to references.
+    fn collapse_reference(&self, mut ty: NodeId, mut rvalue: bool) -> (NodeId, bool) {
+        // Template arguments may refer to themselves.
+        for _ in 0..MAX_DEPTH {
+            // Qualifiers of references are dropped.
+            let param = match self.nodes[ty] {
+                Node::Qualified(inner, _) | Node::VendorQualified(inner, _) => inner,
+                _ => ty,
+            };
+            let referenced = match self.nodes[param] {
+                Node::TemplateParam(_) if self.lambda_params > 0 => None,
+                Node::TemplateParam(index) => self.template_arg(index, self.templates.len()),
+                _ => Some(ty),
+            };
+            match referenced.map(|id| &self.nodes[id]) {
+                Some(&Node::Reference(inner, inner_rvalue)) => {
+                    ty = inner;
+                    rvalue &= inner_rvalue;
+                }
+                _ => break,
+            }
+        }
+        (ty, rvalue)
+    }
+
+    /// Prints the dimensions of an array, separated from the element type
+    /// unless they follow those of an outer array.
+    fn print_array_right(&mut self, id: NodeId, outer: bool) -> Option<()> {
+        let Node::Array(element, dim) = self.nodes[id] else {
+            return self.print_right(id);
+        };
+        self.push(if outer && self.last_char() != Some(']') {
+            " ["
+        } else {
+            "["
+        })?;
+        if let Some(dim) = dim {
+            self.print(dim)?;
+        }
+        self.push("]")?;
+        self.print_array_right(element, false)
+    }
+
+    fn print_encoding(&mut self, name: NodeId, ret: Option<NodeId>, params: &[NodeId], quals: &Quals) -> Option<()> {
+        // The template arguments of the function are in scope of its type,
+        // but not of its name.
+        let mut entity = name;
+        while let Node::Local(_, inner) | Node::DefaultArg(_, inner) = self.nodes[entity] {
+            entity = inner;
+        }
+        let template = match self.nodes[entity] {
+            Node::Template(_, args) => Some(args),
+            _ => None,
+        };
+        let level = self.templates.len();
+        if let Some(ret) = ret {
+            self.templates.extend(template);
+            self.print_left(ret)?;
+            if !self.has_right(ret, self.templates.len()) {
+                self.push(" ")?;
+            }
+            self.templates.truncate(level);
+        }
+        self.print(name)?;
+        self.templates.extend(template);
+        self.push("(")?;
+        self.print_list(params)?;
+        self.push(")")?;
+        self.print_quals(quals)?;
+        if let Some(ret) = ret {
+            self.print_right(ret)?;
+        }
+        self.templates.truncate(level);
+        Some(())
+    }
+
+    /// Returns the length of the first pack in `id`, or `None` if it has
+    /// none.
+    fn find_pack(&mut self, id: NodeId) -> Option<Option<usize>> {
+        self.enter()?;
+        let nodes = self.nodes;
+        let mut children = vec![];
+        match &nodes[id] {
+            &Node::TemplateParam(index) => {
+                let args = self.templates.last().map(|&args| &nodes[args]);
+                let pack = match args {
+                    Some(Node::Args(args)) => args.get(index).and_then(|&arg| match &nodes[arg] {
+                        Node::Args(pack) => Some(pack.len()),
+                        _ => None,
+                    }),
+                    _ => None,
+                };
+                self.leave();
+                return Some(pack);
+            }
+            Node::Args(ids) | Node::List(ids) => children.extend(ids),
+            &Node::Nested(a, b)
+            | &Node::Local(a, b)
+            | &Node::Template(a, b)
+            | &Node::AbiTag(a, b)
+            | &Node::ConstructionVtable(a, b)
+            | &Node::Vector(a, b)
+            | &Node::VendorQualified(a, b)
+            | &Node::MemberPointer(a, b)
+            | &Node::Call(a, b)
+            | &Node::Binary {
+                op: _,
+                left: a,
+                right: b,
+            } => children.extend([a, b]),
+            &Node::Ctor(a)
+            | &Node::Dtor(a)
+            | &Node::LiteralOperator(a)
+            | &Node::Conversion(a)
+            | &Node::Cast(a)
+            | &Node::Special(_, a)
+            | &Node::ReferenceTemporary(a, _)
+            | &Node::Clone(a, _)
+            | &Node::Qualified(a, _)
+            | &Node::Postfix(a, _)
+            | &Node::Pointer(a)
+            | &Node::Reference(a, _)
+            | &Node::Decltype(a)
+            | &Node::Literal { ty: a, .. }
+            | &Node::Unary { operand: a, .. } => children.push(a),
+            Node::Encoding { name, ret, params, .. } => {
+                children.push(*name);
+                children.extend(*ret);
+                children.extend(params);
+            }
+            Node::Function { ret, params, .. } => {
+                children.push(*ret);
+                children.extend(params);
+            }
+            &Node::Array(element, dim) => {
+                children.extend(dim);
+                children.push(element);
+            }
+            &Node::Conditional(a, b, c) => children.extend([a, b, c]),
+            &Node::New { placement, ty, init } => {
+                children.extend([placement, ty]);
+                children.extend(init);
+            }
+            &Node::InitList(ty, list) => {
+                children.extend(ty);
+                children.push(list);
+            }
+            Node::Name(_)
+            | Node::Builtin(_)
+            | Node::Number(_)
+            | Node::FloatN(..)
+            | Node::DefaultArg(..)
+            | Node::Operator(_)
+            | Node::Lambda { .. }
+            | Node::UnnamedType(_)
+            | Node::FunctionParam(_)
+            | Node::PackExpansion(_)
+            | Node::Spec(..)
+            | Node::Nullary(_) => {}
+        }
+        for child in children {
+            if let Some(len) = self.find_pack(child)? {
+                self.leave();
+                return Some(Some(len));
+            }
+        }
+        self.leave();
+        Some(None)
+    }
+
+    fn print_pack_expansion(&mut self, pattern: NodeId) -> Option<()> {
+        let Some(len) = self.find_pack(pattern)? else {
+            // The packs are function parameter packs.
+            self.print_subexpr(pattern)?;
+            return self.push("...");
+        };
+        for i in 0..len {
+            self.pack_index = i;
+            self.print(pattern)?;
+            if i + 1 < len {
+                self.push(", ")?;
+            }
+        }
+        Some(())
+    }
+
+    /// Prints an operand, in parentheses unless it is a name or another
+    /// simple expression.
+    fn print_subexpr(&mut self, id: NodeId) -> Option<()> {
+        let simple = matches!(
+            self.nodes[id],
+            Node::Name(_) | Node::Nested(..) | Node::InitList(..) | Node::FunctionParam(_)
+        );
+        if !simple {
+            self.push("(")?;
+        }
+        self.print(id)?;
+        if !simple {
+            self.push(")")?;
+        }
+        Some(())
+    }
+
+    fn print_operator(&mut self, op: NodeId) -> Option<()> {
+        match self.nodes[op] {
+            Node::Operator(op) => self.push(op.name),
+            _ => self.print(op),
+        }
+    }
+
+    fn print_unary(&mut self, op: NodeId, mut operand: NodeId, postfix: bool) -> Option<()> {
+        let code = match self.nodes[op] {
+            Node::Operator(op) => op.code,
+            _ => "",
+        };
+        if code == "ad" {
+            // The address of a function is printed without its parameters.
+            if let Node::Encoding { name, ref quals, .. } = self.nodes[operand] {
+                if quals.is_empty() && matches!(self.nodes[name], Node::Nested(..)) {
+                    operand = name;
+                }
+            }
+        }
+        if postfix {
+            self.print_subexpr(operand)?;
+            return self.print_operator(op);
+        }
+        if code == "sZ" {
+            let len = self.find_pack(operand)?.unwrap_or(0);
+            return self.push(&len.to_string());
+        }
+        match self.nodes[op] {
+            Node::Cast(ty) => {
+                self.push("(")?;
+                self.print(ty)?;
+                self.push(")")?;
+            }
+            _ => self.print_operator(op)?,
+        }
+        match code {
+            "gs" => self.print(operand),
+            "st" => {
+                self.push("(")?;
+                self.print(operand)?;
+                self.push(")")
+            }
+            _ => self.print_subexpr(operand),
+        }
+    }
+
+    fn print_binary(&mut self, op: NodeId, left: NodeId, right: NodeId) -> Option<()> {
+        let Node::Operator(operator) = self.nodes[op] else {
+            return None;
+        };
+        if matches!(operator.code, "dc" | "sc" | "rc" | "cc") {
+            self.push(operator.name)?;
+            self.push("<")?;
+            self.print(left)?;
+            self.push(">(")?;
+            self.print(right)?;
+            return self.push(")");
+        }
+        // Parentheses tell `>` apart from the end of template arguments.
+        let greater = operator.name == ">";
+        if greater {
+            self.push("(")?;
+        }
+        self.print_subexpr(left)?;
+        if operator.code == "ix" {
+            self.push("[")?;
+            self.print(right)?;
+            self.push("]")?;
+        } else {
+            self.push(operator.name)?;
+            self.print_subexpr(right)?;
+        }
+        if greater {
+            self.push(")")?;
+        }
+        Some(())
+    }
+
+    fn print_literal(&mut self, ty: NodeId, value: &str, negative: bool) -> Option<()> {
+        let builtin = match self.nodes[ty] {
+            Node::Builtin(name) => name,
+            Node::FloatN(..) => "float",
+            _ => "",
+        };
+        let suffix = match builtin {
+            "int" => Some(""),
+            "unsigned int" => Some("u"),
+            "long" => Some("l"),
+            "unsigned long" => Some("ul"),
+            "long long" => Some("ll"),
+            "unsigned long long" => Some("ull"),
+            _ => None,
+        };
+        if let Some(suffix) = suffix {
+            if negative {
+                self.push("-")?;
+            }
+            self.push(value)?;
+            return self.push(suffix);
+        }
+        if builtin == "bool" && !negative {
+            match value {
+                "0" => return self.push("false"),
+                "1" => return self.push("true"),
+                _ => {}
+            }
+        }
+        let float = matches!(
+            builtin,
+            "float" | "double" | "long double" | "__float128" | "half" | "std::bfloat16_t"
+        );
+        self.push("(")?;
+        self.print(ty)?;
+        self.push(")")?;
+        if negative {
+            self.push("-")?;
+        }
+        if float {
+            self.push("[")?;
+        }
+        self.push(value)?;
+        if float {
+            self.push("]")?;
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle() {
+        for (mangled, demangled) in [
+            ("_ZNK1A1fIiEEPFvvEv", "void (*A::f<int>() const)()"),
+            ("_Z1fIiEPA3_iv", "int (*f<int>()) [3]"),
+            ("_Z1fM1AFviE", "f(void (A::*)(int))"),
+            ("_Z1fDoFvvE", "f(void () noexcept)"),
+            ("_Z1fPKDd", "f(decimal64 const*)"),
+            // Template parameters and packs.
+            ("_Z1fIJidEEvT_", "void f<int, double>(int)"),
+            ("_Z1fIJEiEvv", "void f<, int>()"),
+            ("_ZN1AIJEEC1Ev", "A<>::A()"),
+            ("_Z1fIKiEvRKT_", "void f<int const>(int const&)"),
+            ("_Z1fIOiEvRT_", "void f<int&&>(int&)"),
+            ("_Z1fIRA3_KiEvRKT_", "void f<int const (&) [3]>(int const (&) [3])"),
+            ("_Z1fILi3EEvv", "void f<3>()"),
+            // Local names and lambdas, which aren't substitutions.
+            ("_ZZ1fvE1x", "f()::x"),
+            ("_ZZ1fvENKUlvE_clEv", "f()::{lambda()#1}::operator()() const"),
+            (
+                "_Z1fIN1AIZ1gvEUlvE_EEEvT_S1_S2_",
+                "void f<A<g()::{lambda()#1}> >(A<g()::{lambda()#1}>, g()::{lambda()#1}, A<g()::{lambda()#1}>)",
+            ),
+            // Expressions.
+            ("_Z1fIiEDTcl1gIT_EEEv", "decltype ((g<int>)()) f<int>()"),
+            ("_Z1gIiEDTclsr1AE1fIT_EEEv", "decltype ((A::f<int>)()) g<int>()"),
+            // Special names.
+            ("_ZTV1A", "vtable for A"),
+            ("_ZThn8_N1A1fEv", "non-virtual thunk to A::f()"),
+            ("_ZGR1x0", "reference temporary #0 for x"),
+            ("_ZN1A1fEv.cold", "A::f() [clone .cold]"),
+        ] {
+            assert_eq!(demangle(mangled).as_deref(), Some(demangled), "{}", mangled);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        for name in ["_Z", "_Z1", "_Z1fS_", "_Z1fIiEvT0_", "_ZN1A1fEv_", "_Z1fv_"] {
+            assert_eq!(demangle(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let deep = format!("_Z1f{}i", "P".repeat(100_000));
+        assert_eq!(demangle(&deep), None);
+        // Each argument refers twice to the previous one, doubling the
+        // length of the name.
+        let mut exponential = String::from("_Z1f1AI1BS0_E");
+        for i in 1..30u8 {
+            let sub = (if i < 10 { b'0' + i } else { b'A' + i - 10 }) as char;
+            exponential += &format!("S_IS{}_S{}_E", sub, sub);
+        }
+        assert_eq!(demangle(&exponential), None);
+    }
+}
@@ -0,0 +1,132 @@
+//! Demangler of the legacy Rust mangling, Itanium-like names whose last
+//! component is a hash, e.g. `_ZN4core3fmt5write17h0123456789abcdefE`.
+//!
+//! Names are printed as by the `rustc-demangle` crate.
+
+use super::Output;
+
+/// Demangles `name`, starting with `_ZN`, returning it, the rest of `name`
+/// after the mangled name, and whether the path ends with a hash.
+pub(super) fn demangle(name: &str, strip_hash: bool) -> Option<(String, &str, bool)> {
+    let mut rest = name.strip_prefix("_ZN")?;
+    if !rest.is_ascii() {
+        return None;
+    }
+    let mut elements = vec![];
+    while !rest.starts_with('E') {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let len: usize = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        elements.push(rest.get(..len)?);
+        rest = &rest[len..];
+    }
+    if elements.is_empty() {
+        return None;
+    }
+    let rest = &rest[1..];
+
+    let hashed = elements.last().is_some_and(|element| is_rust_hash(element));
+    let mut out = Output::new();
+    for (i, &element) in elements.iter().enumerate() {
+        if strip_hash && hashed && i + 1 == elements.len() {
+            break;
+        }
+        if i != 0 {
+            out.push("::")?;
+        }
+        unescape(&mut out, element)?;
+    }
+    Some((out.into_string(), rest, hashed))
+}
+
+/// Returns whether `element` is the hash ending the path of a function.
+fn is_rust_hash(element: &str) -> bool {
+    element
+        .strip_prefix('h')
+        .is_some_and(|hash| hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Writes a path element, with the `$` escapes of the characters not
+/// allowed in symbols replaced.
+fn unescape(out: &mut Output, element: &str) -> Option<()> {
+    let mut rest = element.strip_prefix("_$").map_or(element, |_| &element[1..]);
+    loop {
+        if let Some(after) = rest.strip_prefix('.') {
+            if let Some(after) = after.strip_prefix('.') {
+                out.push("::")?;
+                rest = after;
+            } else {
+                out.push(".")?;
+                rest = after;
+            }
+        } else if rest.starts_with('$') {
+            let Some(end) = rest[1..].find('$') else {
+                break;
+            };
+            let (escape, after) = (&rest[1..=end], &rest[end + 2..]);
+            let unescaped = match escape {
+                "SP" => "@",
+                "BP" => "*",
+                "RF" => "&",
+                "LT" => "<",
+                "GT" => ">",
+                "LP" => "(",
+                "RP" => ")",
+                "C" => ",",
+                _ => {
+                    // A code point, e.g. `$u7e$` for `~`.
+                    let c = escape
+                        .strip_prefix('u')
+                        .filter(|digits| digits.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')))
+                        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                        .and_then(char::from_u32)
+                        .filter(|c| !c.is_control());
+                    match c {
+                        Some(c) => {
+                            out.push(c.encode_utf8(&mut [0; 4]))?;
+                            rest = after;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+            };
+            out.push(unescaped)?;
+            rest = after;
+        } else if let Some(i) = rest.find(['$', '.']) {
+            out.push(&rest[..i])?;
+            rest = &rest[i..];
+        } else {
+            break;
+        }
+    }
+    out.push(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle() {
+        let name = "_ZN4core3ptr85drop_in_place$LT$std..rt..lang_start$LT$$LP$$RP$$GT$..$u7b$$u7b$closure$u7d$$u7d$$GT$17h1234567890abcdefE";
+        let demangled = "core::ptr::drop_in_place<std::rt::lang_start<()>::{{closure}}>";
+        assert_eq!(
+            demangle(name, false),
+            Some((format!("{}::h1234567890abcdef", demangled), "", true))
+        );
+        assert_eq!(demangle(name, true), Some((demangled.to_string(), "", true)));
+        assert_eq!(
+            demangle("_ZN3foo3barE.cold", true),
+            Some(("foo::bar".to_string(), ".cold", false))
+        );
+        assert_eq!(demangle("_ZN3foo3barE", true).unwrap().0, "foo::bar");
+    }
+
+    #[test]
+    fn test_invalid() {
+        for name in ["_ZN", "_ZNE", "_ZN3foo", "_ZN9fooE", "_ZN3f\u{e9}oE"] {
+            assert_eq!(demangle(name, false), None, "{}", name);
+        }
+    }
+}
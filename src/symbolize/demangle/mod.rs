@@ -0,0 +1,110 @@
+//! Demangling of Rust (legacy and v0) and Itanium C++ symbol names.
+//!
+//! Rust names are printed as by the `rustc-demangle` crate, with its
+//! alternate format if the hashes are stripped, and C++ names as by
+//! `c++filt`.
+
+mod itanium;
+mod legacy;
+mod v0;
+
+/// The maximum length of a demangled name, bounding the output of names
+/// whose back references expand exponentially.
+const MAX_LEN: usize = 1_000_000;
+
+/// A demangled name being written.
+struct Output {
+    s: String,
+    /// The last character pushed, kept when truncating as by `c++filt`,
+    /// which e.g. prints `A<B<>>` from `A<B<>, >`.
+    last_char: Option<char>,
+}
+
+impl Output {
+    fn new() -> Self {
+        Output {
+            s: String::new(),
+            last_char: None,
+        }
+    }
+
+    /// Appends `s`, returning `None` if the name gets too long.
+    fn push(&mut self, s: &str) -> Option<()> {
+        if self.s.len() + s.len() > MAX_LEN {
+            return None;
+        }
+        self.s.push_str(s);
+        self.last_char = s.chars().next_back().or(self.last_char);
+        Some(())
+    }
+
+    fn last_char(&self) -> Option<char> {
+        self.last_char
+    }
+
+    fn len(&self) -> usize {
+        self.s.len()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.s.truncate(len);
+    }
+
+    fn into_string(self) -> String {
+        self.s
+    }
+}
+
+/// Demangles a symbol name, returning `None` if it isn't a Rust or C++
+/// mangled name.
+///
+/// If `strip_hash` is true, the hashes distinguishing Rust symbols are
+/// left out: the `::h<hash>` suffix of legacy names, and the crate
+/// disambiguators and the types of integer constants of v0 names.
+///
+/// # Examples
+///
+/// ```
+/// use unwind::symbolize::demangle;
+///
+/// let name = b"_ZN4core3fmt5write17h0123456789abcdefE";
+/// assert_eq!(demangle(name, false).unwrap(), "core::fmt::write::h0123456789abcdef");
+/// assert_eq!(demangle(name, true).unwrap(), "core::fmt::write");
+/// assert_eq!(demangle(b"_ZNK3foo3barEi", false).unwrap(), "foo::bar(int) const");
+/// ```
+pub fn demangle(name: &[u8], strip_hash: bool) -> Option<String> {
+    let mut name = std::str::from_utf8(name).ok()?;
+    // The suffix LLVM adds to the names of internal symbols it renames.
+    if let Some(i) = name.find(".llvm.") {
+        if name[i + 6..].chars().all(|c| matches!(c, 'A'..='F' | '0'..='9' | '@')) {
+            name = &name[..i];
+        }
+    }
+
+    // Legacy names without a hash are C++ names too.
+    let mut legacy = legacy::demangle(name, strip_hash);
+    let rust = match legacy {
+        Some((_, _, true)) => legacy.take().map(|(demangled, suffix, _)| (demangled, suffix)),
+        Some(_) => None,
+        None => v0::demangle(name, strip_hash),
+    };
+    if let Some((demangled, suffix)) = rust {
+        if suffix.is_empty() {
+            return Some(demangled);
+        }
+        // Suffixes added by LLVM, e.g. `.cold`, are kept.
+        if suffix.starts_with('.')
+            && suffix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c.is_ascii_punctuation())
+        {
+            return Some(demangled + suffix);
+        }
+        return None;
+    }
+
+    itanium::demangle(name).or_else(|| {
+        let (demangled, suffix, _) = legacy?;
+        suffix.is_empty().then_some(demangled)
+    })
+}
@@ -0,0 +1,779 @@
+//! Demangler of the Rust v0 mangling, of `-C symbol-mangling-version=v0`.
+//!
+//! Names are printed as by the `rustc-demangle` crate.
+
+use super::Output;
+use std::mem;
+
+/// The maximum depth of nested paths, types, constants and back references.
+const MAX_DEPTH: u32 = 500;
+
+/// The maximum number of characters of a decoded Punycode identifier.
+const MAX_PUNYCODE_LEN: usize = 128;
+
+/// Demangles `name`, starting with `_R`, returning it and the rest of
+/// `name` after the mangled name.
+pub(super) fn demangle(name: &str, strip_hash: bool) -> Option<(String, &str)> {
+    let inner = name.strip_prefix("_R")?;
+    if !inner.starts_with(|c: char| c.is_ascii_uppercase()) || !inner.is_ascii() {
+        return None;
+    }
+    let mut d = Demangler {
+        sym: inner.as_bytes(),
+        next: 0,
+        depth: 0,
+        out: Output::new(),
+        printing: true,
+        strip_hash,
+        bound_lifetime_depth: 0,
+    };
+    d.print_path(true)?;
+    // The instantiating crate.
+    if d.peek().is_some_and(|c| c.is_ascii_uppercase()) {
+        d.printing = false;
+        d.print_path(false)?;
+    }
+    Some((d.out.into_string(), &inner[d.next..]))
+}
+
+/// An identifier, whose non-ASCII characters may be Punycode encoded.
+struct Ident<'a> {
+    ascii: &'a str,
+    punycode: &'a str,
+}
+
+impl Ident<'_> {
+    fn is_empty(&self) -> bool {
+        self.ascii.is_empty() && self.punycode.is_empty()
+    }
+
+    /// Decodes the Punycode part, returning `None` if it is invalid or too
+    /// long.
+    fn decode(&self) -> Option<Vec<char>> {
+        let mut chars: Vec<char> = self.ascii.chars().collect();
+        let mut digits = self.punycode.bytes().peekable();
+        digits.peek()?;
+        let (base, t_min, t_max, skew) = (36, 1, 26, 38);
+        let mut damp = 700;
+        let mut bias = 72;
+        let mut i: usize = 0;
+        let mut n: usize = 0x80;
+        loop {
+            let mut delta: usize = 0;
+            let mut w = 1;
+            let mut k: usize = 0;
+            loop {
+                k += base;
+                let t = k.saturating_sub(bias).clamp(t_min, t_max);
+                let d = match digits.next()? {
+                    d @ b'a'..=b'z' => d - b'a',
+                    d @ b'0'..=b'9' => 26 + (d - b'0'),
+                    _ => return None,
+                } as usize;
+                delta = delta.checked_add(d.checked_mul(w)?)?;
+                if d < t {
+                    break;
+                }
+                w = w.checked_mul(base - t)?;
+            }
+            let len = chars.len() + 1;
+            if len > MAX_PUNYCODE_LEN {
+                return None;
+            }
+            i = i.checked_add(delta)?;
+            n = n.checked_add(i / len)?;
+            i %= len;
+            chars.insert(i, char::from_u32(u32::try_from(n).ok()?)?);
+            i += 1;
+            if digits.peek().is_none() {
+                return Some(chars);
+            }
+            delta /= damp;
+            damp = 2;
+            delta += delta / len;
+            let mut k = 0;
+            while delta > ((base - t_min) * t_max) / 2 {
+                delta /= base - t_min;
+                k += base;
+            }
+            bias = k + ((base - t_min + 1) * delta) / (delta + skew);
+        }
+    }
+}
+
+struct Demangler<'a> {
+    sym: &'a [u8],
+    next: usize,
+    depth: u32,
+    out: Output,
+    /// Whether the output is written, not while skipping the paths of
+    /// impls.
+    printing: bool,
+    strip_hash: bool,
+    bound_lifetime_depth: u64,
+}
+
+impl<'a> Demangler<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.sym.get(self.next).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.next += 1;
+        }
+        eaten
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.next += 1;
+        Some(c)
+    }
+
+    fn push_depth(&mut self) -> Option<()> {
+        self.depth += 1;
+        (self.depth <= MAX_DEPTH).then_some(())
+    }
+
+    fn pop_depth(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn print(&mut self, s: &str) -> Option<()> {
+        if self.printing {
+            self.out.push(s)?;
+        }
+        Some(())
+    }
+
+    fn print_display(&mut self, value: impl std::fmt::Display) -> Option<()> {
+        if self.printing {
+            self.out.push(&value.to_string())?;
+        }
+        Some(())
+    }
+
+    /// Parses hexadecimal digits terminated by `_`.
+    fn hex_nibbles(&mut self) -> Option<&'a str> {
+        let start = self.next;
+        loop {
+            match self.next()? {
+                b'0'..=b'9' | b'a'..=b'f' => {}
+                b'_' => break,
+                _ => return None,
+            }
+        }
+        std::str::from_utf8(&self.sym[start..self.next - 1]).ok()
+    }
+
+    fn integer_62(&mut self) -> Option<u64> {
+        if self.eat(b'_') {
+            return Some(0);
+        }
+        let mut x: u64 = 0;
+        while !self.eat(b'_') {
+            let d = match self.next()? {
+                d @ b'0'..=b'9' => d - b'0',
+                d @ b'a'..=b'z' => 10 + (d - b'a'),
+                d @ b'A'..=b'Z' => 36 + (d - b'A'),
+                _ => return None,
+            };
+            x = x.checked_mul(62)?.checked_add(u64::from(d))?;
+        }
+        x.checked_add(1)
+    }
+
+    fn opt_integer_62(&mut self, tag: u8) -> Option<u64> {
+        if !self.eat(tag) {
+            return Some(0);
+        }
+        self.integer_62()?.checked_add(1)
+    }
+
+    fn disambiguator(&mut self) -> Option<u64> {
+        self.opt_integer_62(b's')
+    }
+
+    fn ident(&mut self) -> Option<Ident<'a>> {
+        let is_punycode = self.eat(b'u');
+        let mut len = usize::from(self.next()?.checked_sub(b'0').filter(|&d| d <= 9)?);
+        if len != 0 {
+            while let Some(d) = self.peek().filter(u8::is_ascii_digit) {
+                self.next += 1;
+                len = len.checked_mul(10)?.checked_add(usize::from(d - b'0'))?;
+            }
+        }
+        self.eat(b'_');
+        let start = self.next;
+        self.next = start.checked_add(len).filter(|&end| end <= self.sym.len())?;
+        let ident = std::str::from_utf8(&self.sym[start..self.next]).ok()?;
+        if !is_punycode {
+            return Some(Ident {
+                ascii: ident,
+                punycode: "",
+            });
+        }
+        let ident = match ident.rfind('_') {
+            Some(i) => Ident {
+                ascii: &ident[..i],
+                punycode: &ident[i + 1..],
+            },
+            None => Ident {
+                ascii: "",
+                punycode: ident,
+            },
+        };
+        (!ident.punycode.is_empty()).then_some(ident)
+    }
+
+    fn print_ident(&mut self, ident: &Ident<'_>) -> Option<()> {
+        if !self.printing {
+            return Some(());
+        }
+        if ident.punycode.is_empty() {
+            return self.print(ident.ascii);
+        }
+        match ident.decode() {
+            Some(chars) => self.print(&chars.into_iter().collect::<String>()),
+            None => {
+                self.print("punycode{")?;
+                if !ident.ascii.is_empty() {
+                    self.print(ident.ascii)?;
+                    self.print("-")?;
+                }
+                self.print(ident.punycode)?;
+                self.print("}")
+            }
+        }
+    }
+
+    /// Runs `f` at the position a back reference refers to, if printing.
+    fn print_backref(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        let start = self.next - 1;
+        let i = self.integer_62()?;
+        if i >= start as u64 {
+            return None;
+        }
+        if !self.printing {
+            return Some(());
+        }
+        let next = mem::replace(&mut self.next, i as usize);
+        self.push_depth()?;
+        f(self)?;
+        self.pop_depth();
+        self.next = next;
+        Some(())
+    }
+
+    fn print_lifetime_from_index(&mut self, lt: u64) -> Option<()> {
+        if !self.printing {
+            return Some(());
+        }
+        if lt == 0 {
+            return self.print("'_");
+        }
+        let depth = self.bound_lifetime_depth.checked_sub(lt)?;
+        if depth < 26 {
+            self.print_display(format_args!("'{}", (b'a' + depth as u8) as char))
+        } else {
+            self.print_display(format_args!("'_{}", depth))
+        }
+    }
+
+    fn in_binder(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        let bound_lifetimes = self.opt_integer_62(b'G')?;
+        if bound_lifetimes > 0 {
+            self.print("for<")?;
+            for i in 0..bound_lifetimes {
+                if i > 0 {
+                    self.print(", ")?;
+                }
+                self.bound_lifetime_depth += 1;
+                self.print_lifetime_from_index(1)?;
+            }
+            self.print("> ")?;
+        }
+        f(self)?;
+        self.bound_lifetime_depth -= bound_lifetimes;
+        Some(())
+    }
+
+    /// Prints the items up to `E` separated by `sep`, returning their
+    /// number.
+    fn print_sep_list(&mut self, f: impl Fn(&mut Self) -> Option<()>, sep: &str) -> Option<usize> {
+        let mut i = 0;
+        while !self.eat(b'E') {
+            if i > 0 {
+                self.print(sep)?;
+            }
+            f(self)?;
+            i += 1;
+        }
+        Some(i)
+    }
+
+    fn print_path(&mut self, in_value: bool) -> Option<()> {
+        self.push_depth()?;
+        match self.next()? {
+            b'C' => {
+                let dis = self.disambiguator()?;
+                let name = self.ident()?;
+                self.print_ident(&name)?;
+                if !self.strip_hash && dis != 0 {
+                    self.print_display(format_args!("[{:x}]", dis))?;
+                }
+            }
+            b'N' => {
+                let ns = self.next()?;
+                if !ns.is_ascii_alphabetic() {
+                    return None;
+                }
+                self.print_path(in_value)?;
+                let dis = self.disambiguator()?;
+                let name = self.ident()?;
+                if ns.is_ascii_uppercase() {
+                    self.print("::{")?;
+                    match ns {
+                        b'C' => self.print("closure")?,
+                        b'S' => self.print("shim")?,
+                        _ => self.print_display(ns as char)?,
+                    }
+                    if !name.is_empty() {
+                        self.print(":")?;
+                        self.print_ident(&name)?;
+                    }
+                    self.print_display(format_args!("#{}}}", dis))?;
+                } else if !name.is_empty() {
+                    self.print("::")?;
+                    self.print_ident(&name)?;
+                }
+            }
+            tag @ (b'M' | b'X' | b'Y') => {
+                if tag != b'Y' {
+                    self.disambiguator()?;
+                    let printing = mem::replace(&mut self.printing, false);
+                    self.print_path(false)?;
+                    self.printing = printing;
+                }
+                self.print("<")?;
+                self.print_type()?;
+                if tag != b'M' {
+                    self.print(" as ")?;
+                    self.print_path(false)?;
+                }
+                self.print(">")?;
+            }
+            b'I' => {
+                self.print_path(in_value)?;
+                if in_value {
+                    self.print("::")?;
+                }
+                self.print("<")?;
+                self.print_sep_list(Self::print_generic_arg, ", ")?;
+                self.print(">")?;
+            }
+            b'B' => self.print_backref(|d| d.print_path(in_value))?,
+            _ => return None,
+        }
+        self.pop_depth();
+        Some(())
+    }
+
+    fn print_generic_arg(&mut self) -> Option<()> {
+        if self.eat(b'L') {
+            let lt = self.integer_62()?;
+            self.print_lifetime_from_index(lt)
+        } else if self.eat(b'K') {
+            self.print_const(false)
+        } else {
+            self.print_type()
+        }
+    }
+
+    fn print_type(&mut self) -> Option<()> {
+        if self.eat(b'w') {
+            self.print("#[splat] ")?;
+        }
+        let tag = self.next()?;
+        if let Some(ty) = basic_type(tag) {
+            return self.print(ty);
+        }
+        self.push_depth()?;
+        match tag {
+            b'R' | b'Q' => {
+                self.print("&")?;
+                if self.eat(b'L') {
+                    let lt = self.integer_62()?;
+                    if lt != 0 {
+                        self.print_lifetime_from_index(lt)?;
+                        self.print(" ")?;
+                    }
+                }
+                if tag == b'Q' {
+                    self.print("mut ")?;
+                }
+                self.print_type()?;
+            }
+            b'P' | b'O' => {
+                self.print(if tag == b'P' { "*const " } else { "*mut " })?;
+                self.print_type()?;
+            }
+            b'A' | b'S' => {
+                self.print("[")?;
+                self.print_type()?;
+                if tag == b'A' {
+                    self.print("; ")?;
+                    self.print_const(true)?;
+                }
+                self.print("]")?;
+            }
+            b'T' => {
+                self.print("(")?;
+                if self.print_sep_list(Self::print_type, ", ")? == 1 {
+                    self.print(",")?;
+                }
+                self.print(")")?;
+            }
+            b'F' => self.in_binder(|d| {
+                let is_unsafe = d.eat(b'U');
+                let abi = if !d.eat(b'K') {
+                    None
+                } else if d.eat(b'C') {
+                    Some("C")
+                } else {
+                    let abi = d.ident()?;
+                    if abi.ascii.is_empty() || !abi.punycode.is_empty() {
+                        return None;
+                    }
+                    Some(abi.ascii)
+                };
+                if is_unsafe {
+                    d.print("unsafe ")?;
+                }
+                if let Some(abi) = abi {
+                    d.print_display(format_args!("extern \"{}\" ", abi.replace('_', "-")))?;
+                }
+                d.print("fn(")?;
+                d.print_sep_list(Self::print_type, ", ")?;
+                d.print(")")?;
+                if !d.eat(b'u') {
+                    d.print(" -> ")?;
+                    d.print_type()?;
+                }
+                Some(())
+            })?,
+            b'D' => {
+                self.print("dyn ")?;
+                self.in_binder(|d| d.print_sep_list(Self::print_dyn_trait, " + ").map(drop))?;
+                if !self.eat(b'L') {
+                    return None;
+                }
+                let lt = self.integer_62()?;
+                if lt != 0 {
+                    self.print(" + ")?;
+                    self.print_lifetime_from_index(lt)?;
+                }
+            }
+            b'B' => self.print_backref(Self::print_type)?,
+            b'W' => {
+                self.print_type()?;
+                self.print(" is ")?;
+                self.print_pat()?;
+            }
+            _ => {
+                self.next -= 1;
+                self.print_path(false)?;
+            }
+        }
+        self.pop_depth();
+        Some(())
+    }
+
+    /// Prints a path, leaving its generic arguments open for associated type
+    /// bindings. Returns whether they were left open.
+    fn print_path_maybe_open_generics(&mut self) -> Option<bool> {
+        if self.eat(b'B') {
+            let mut open = false;
+            self.print_backref(|d| {
+                open = d.print_path_maybe_open_generics()?;
+                Some(())
+            })?;
+            Some(open)
+        } else if self.eat(b'I') {
+            self.print_path(false)?;
+            self.print("<")?;
+            self.print_sep_list(Self::print_generic_arg, ", ")?;
+            Some(true)
+        } else {
+            self.print_path(false)?;
+            Some(false)
+        }
+    }
+
+    fn print_dyn_trait(&mut self) -> Option<()> {
+        let mut open = self.print_path_maybe_open_generics()?;
+        while self.eat(b'p') {
+            self.print(if open { ", " } else { "<" })?;
+            open = true;
+            let name = self.ident()?;
+            self.print_ident(&name)?;
+            self.print(" = ")?;
+            if self.eat(b'K') {
+                self.print_const(false)?;
+            } else {
+                self.print_type()?;
+            }
+        }
+        if open {
+            self.print(">")?;
+        }
+        Some(())
+    }
+
+    /// Prints the pattern of a pattern type.
+    fn print_pat(&mut self) -> Option<()> {
+        match self.next()? {
+            b'R' => {
+                self.print_const(false)?;
+                self.print("..=")?;
+                self.print_const(false)?;
+            }
+            b'O' => {
+                self.push_depth()?;
+                self.print_pat()?;
+                while !self.eat(b'E') {
+                    self.print(" | ")?;
+                    self.print_pat()?;
+                }
+                self.pop_depth();
+            }
+            b'N' => self.print("!null")?,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn print_const(&mut self, in_value: bool) -> Option<()> {
+        let tag = self.next()?;
+        self.push_depth()?;
+        // Compound constants are braced outside of expressions.
+        let braced = !in_value && matches!(tag, b'e' | b'Q' | b'A' | b'T' | b'V')
+            || tag == b'R' && !in_value && self.peek() != Some(b'e');
+        if braced {
+            self.print("{")?;
+        }
+        match tag {
+            b'p' => self.print("_")?,
+            b'h' | b't' | b'm' | b'y' | b'o' | b'j' => self.print_const_uint(tag)?,
+            b'a' | b's' | b'l' | b'x' | b'n' | b'i' => {
+                if self.eat(b'n') {
+                    self.print("-")?;
+                }
+                self.print_const_uint(tag)?;
+            }
+            b'b' => match parse_uint(self.hex_nibbles()?) {
+                Some(0) => self.print("false")?,
+                Some(1) => self.print("true")?,
+                _ => return None,
+            },
+            b'c' => {
+                let c = parse_uint(self.hex_nibbles()?)
+                    .and_then(|v| u32::try_from(v).ok())
+                    .and_then(char::from_u32)?;
+                self.print_quoted('\'', [c].into_iter())?;
+            }
+            b'e' => {
+                self.print("*")?;
+                self.print_const_str()?;
+            }
+            b'R' | b'Q' => {
+                if tag == b'R' && self.eat(b'e') {
+                    self.print_const_str()?;
+                } else {
+                    self.print(if tag == b'R' { "&" } else { "&mut " })?;
+                    self.print_const(true)?;
+                }
+            }
+            b'A' => {
+                self.print("[")?;
+                self.print_sep_list(|d| d.print_const(true), ", ")?;
+                self.print("]")?;
+            }
+            b'T' => {
+                self.print("(")?;
+                if self.print_sep_list(|d| d.print_const(true), ", ")? == 1 {
+                    self.print(",")?;
+                }
+                self.print(")")?;
+            }
+            b'V' => {
+                self.print_path(true)?;
+                match self.next()? {
+                    b'U' => {}
+                    b'T' => {
+                        self.print("(")?;
+                        self.print_sep_list(|d| d.print_const(true), ", ")?;
+                        self.print(")")?;
+                    }
+                    b'S' => {
+                        self.print(" { ")?;
+                        self.print_sep_list(
+                            |d| {
+                                d.disambiguator()?;
+                                let name = d.ident()?;
+                                d.print_ident(&name)?;
+                                d.print(": ")?;
+                                d.print_const(true)
+                            },
+                            ", ",
+                        )?;
+                        self.print(" }")?;
+                    }
+                    _ => return None,
+                }
+            }
+            b'B' => self.print_backref(|d| d.print_const(in_value))?,
+            _ => return None,
+        }
+        if braced {
+            self.print("}")?;
+        }
+        self.pop_depth();
+        Some(())
+    }
+
+    fn print_const_uint(&mut self, tag: u8) -> Option<()> {
+        let hex = self.hex_nibbles()?;
+        match parse_uint(hex) {
+            Some(v) => self.print_display(v)?,
+            None => self.print_display(format_args!("0x{}", hex))?,
+        }
+        if !self.strip_hash {
+            self.print(basic_type(tag)?)?;
+        }
+        Some(())
+    }
+
+    fn print_const_str(&mut self) -> Option<()> {
+        let hex = self.hex_nibbles()?;
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let s = std::str::from_utf8(&bytes).ok()?;
+        self.print_quoted('"', s.chars())
+    }
+
+    fn print_quoted(&mut self, quote: char, chars: impl Iterator<Item = char>) -> Option<()> {
+        let mut quoted = String::new();
+        quoted.push(quote);
+        for c in chars {
+            // The other quote isn't escaped.
+            if matches!((quote, c), ('\'', '"') | ('"', '\'')) {
+                quoted.push(c);
+            } else {
+                quoted.extend(c.escape_debug());
+            }
+        }
+        quoted.push(quote);
+        self.print(&quoted)
+    }
+}
+
+/// Parses hexadecimal digits into an integer, or returns `None` if it
+/// doesn't fit in a `u64`.
+fn parse_uint(hex: &str) -> Option<u64> {
+    let hex = hex.trim_start_matches('0');
+    if hex.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(hex, 16).ok()
+}
+
+fn basic_type(tag: u8) -> Option<&'static str> {
+    Some(match tag {
+        b'b' => "bool",
+        b'c' => "char",
+        b'e' => "str",
+        b'u' => "()",
+        b'a' => "i8",
+        b's' => "i16",
+        b'l' => "i32",
+        b'x' => "i64",
+        b'n' => "i128",
+        b'i' => "isize",
+        b'h' => "u8",
+        b't' => "u16",
+        b'm' => "u32",
+        b'y' => "u64",
+        b'o' => "u128",
+        b'j' => "usize",
+        b'f' => "f32",
+        b'd' => "f64",
+        b'z' => "!",
+        b'p' => "_",
+        b'v' => "...",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle() {
+        for (mangled, demangled, stripped) in [
+            ("_RNvC6_123foo3bar", "123foo::bar", "123foo::bar"),
+            ("_RNvCs1234_7mycrate3foo", "mycrate[3c1c0]::foo", "mycrate::foo"),
+            (
+                "_RNvXs_Cs1234_7mycrateNtB4_3FooNtNtCs5678_4core3fmt7Display3fmt",
+                "<mycrate[3c1c0]::Foo as core[128aac]::fmt::Display>::fmt",
+                "<mycrate::Foo as core::fmt::Display>::fmt",
+            ),
+            (
+                "_RNvNvMCs4fqI2P2rA04_13const_genericINtB4_3FooKpE3foo3FOO",
+                "<const_generic[317d481089b8c8fe]::Foo<_>>::foo::FOO",
+                "<const_generic::Foo<_>>::foo::FOO",
+            ),
+            (
+                "_RINvCsaNSqNPt8l73_4core3fooKjf_E",
+                "core[7dd8b3e7b6d14f7b]::foo::<15usize>",
+                "core::foo::<15>",
+            ),
+            (
+                "_RNCNvC4test4main0B3_",
+                "test::main::{closure#0}",
+                "test::main::{closure#0}",
+            ),
+            ("_RINvC1a1fAhj3_E", "a::f::<[u8; 3usize]>", "a::f::<[u8; 3]>"),
+        ] {
+            assert_eq!(
+                demangle(mangled, false),
+                Some((demangled.to_string(), "")),
+                "{}",
+                mangled
+            );
+            assert_eq!(demangle(mangled, true), Some((stripped.to_string(), "")), "{}", mangled);
+        }
+        assert_eq!(
+            demangle("_RNvC4test4main.llvm.123", false),
+            Some(("test::main".to_string(), ".llvm.123"))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        for name in ["_R", "_RNvC", "_RNvCs1234_7mycrate7_0123ab", "_RINvC1a1fjj"] {
+            assert_eq!(demangle(name, false), None, "{}", name);
+        }
+        // Back references to themselves.
+        assert_eq!(demangle("_RNvB0_4main", false), None);
+    }
+}
@@ -0,0 +1,375 @@
+use super::SymbolizeError;
+use std::fs::File;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::{ptr, slice};
+
+#[cfg(target_pointer_width = "32")]
+use libc::{Elf32_Ehdr as Ehdr, Elf32_Shdr as Shdr, Elf32_Sym as Sym};
+#[cfg(target_pointer_width = "64")]
+use libc::{Elf64_Ehdr as Ehdr, Elf64_Shdr as Shdr, Elf64_Sym as Sym};
+
+#[cfg(target_pointer_width = "32")]
+const ELFCLASS: u8 = 1; // ELFCLASS32
+#[cfg(target_pointer_width = "64")]
+const ELFCLASS: u8 = 2; // ELFCLASS64
+#[cfg(target_endian = "little")]
+const ELFDATA: u8 = 1; // ELFDATA2LSB
+#[cfg(target_endian = "big")]
+const ELFDATA: u8 = 2; // ELFDATA2MSB
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+const SHF_COMPRESSED: u64 = 0x800;
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// The bytes of an ELF file, mapped into memory or in place.
+pub enum Data {
+    Mmap(Mmap),
+    /// The vDSO, mapped by the kernel for the lifetime of the process.
+    Static(&'static [u8]),
+}
+
+impl Data {
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            Data::Mmap(mmap) => mmap.bytes(),
+            Data::Static(bytes) => bytes,
+        }
+    }
+}
+
+/// A read-only private mapping of a whole file.
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read-only.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    pub fn open(path: &Path) -> Result<Self, SymbolizeError> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(SymbolizeError::InvalidElf("empty file"));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// The name and contents of a section.
+pub type Section<'a> = (&'a [u8], &'a [u8]);
+
+/// A function symbol as found in a symbol table.
+#[derive(Debug, Copy, Clone)]
+pub struct ElfSymbol {
+    pub address: u64,
+    /// The size, which `address` can be added to without overflow.
+    pub size: u64,
+    /// The end of the section it is in, which bounds zero-sized symbols.
+    pub section_end: u64,
+    /// Offset of the NUL-terminated name in the file.
+    pub name: usize,
+    /// Which of aliases at the same address to prefer, lowest first.
+    pub rank: u8,
+}
+
+/// Returns the section headers of an ELF file of the native class and byte
+/// order.
+fn section_headers(data: &[u8]) -> Result<(Ehdr, Vec<Shdr>), SymbolizeError> {
+    let ehdr: Ehdr = read(data, 0).ok_or(SymbolizeError::InvalidElf("truncated header"))?;
+    if ehdr.e_ident[..4] != *b"\x7fELF" {
+        return Err(SymbolizeError::InvalidElf("bad magic"));
+    }
+    if ehdr.e_ident[4] != ELFCLASS || ehdr.e_ident[5] != ELFDATA {
+        return Err(SymbolizeError::InvalidElf("foreign class or byte order"));
+    }
+    if ehdr.e_shnum == 0 {
+        return Ok((ehdr, vec![]));
+    }
+    if ehdr.e_shentsize as usize != size_of::<Shdr>() {
+        return Err(SymbolizeError::InvalidElf("bad section header size"));
+    }
+    let sections = (0..ehdr.e_shnum as usize)
+        .map(|i| read::<Shdr>(data, ehdr.e_shoff as usize + i * size_of::<Shdr>()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(SymbolizeError::InvalidElf("truncated section headers"))?;
+    Ok((ehdr, sections))
+}
+
+/// Returns the names and contents of the sections of an ELF file of the
+/// native class and byte order. Sections without data in the file, or with
+/// compressed data, are empty.
+// ELF fields are 32-bit on 32-bit architectures, hence the conversions.
+#[allow(clippy::useless_conversion)]
+pub fn sections(data: &[u8]) -> Result<Vec<Section<'_>>, SymbolizeError> {
+    let (ehdr, headers) = section_headers(data)?;
+    let names = headers
+        .get(ehdr.e_shstrndx as usize)
+        .ok_or(SymbolizeError::InvalidElf("bad section name table index"))?;
+    let names = data
+        .get(names.sh_offset as usize..)
+        .ok_or(SymbolizeError::InvalidElf("truncated section name table"))?;
+    let mut sections = vec![];
+    for header in &headers {
+        let name = names
+            .get(header.sh_name as usize..)
+            .ok_or(SymbolizeError::InvalidElf("bad section name"))?;
+        let contents = if header.sh_type == SHT_NOBITS || u64::from(header.sh_flags) & SHF_COMPRESSED != 0 {
+            &[][..]
+        } else {
+            let start = header.sh_offset as usize;
+            start
+                .checked_add(header.sh_size as usize)
+                .and_then(|end| data.get(start..end))
+                .ok_or(SymbolizeError::InvalidElf("truncated section"))?
+        };
+        sections.push((c_str(name, 0), contents));
+    }
+    Ok(sections)
+}
+
+/// Returns the function symbols of both .symtab and .dynsym of an ELF file
+/// of the native class and byte order.
+// ELF fields are 32-bit on 32-bit architectures, hence the conversions.
+#[allow(clippy::useless_conversion)]
+pub fn function_symbols(data: &[u8]) -> Result<Vec<ElfSymbol>, SymbolizeError> {
+    let (_, sections) = section_headers(data)?;
+    let mut symbols = vec![];
+    for section in &sections {
+        let rank = match section.sh_type {
+            SHT_SYMTAB => 0,
+            SHT_DYNSYM => 1,
+            _ => continue,
+        };
+        let strtab = sections
+            .get(section.sh_link as usize)
+            .ok_or(SymbolizeError::InvalidElf("bad string table index"))?;
+        let strtab_start = strtab.sh_offset as usize;
+        let strtab_end = strtab_start.saturating_add(strtab.sh_size as usize).min(data.len());
+        let count = section.sh_size as usize / size_of::<Sym>();
+        for i in 0..count {
+            let sym: Sym = read(data, section.sh_offset as usize + i * size_of::<Sym>())
+                .ok_or(SymbolizeError::InvalidElf("truncated symbol table"))?;
+            let kind = sym.st_info & 0xf;
+            if (kind != STT_FUNC && kind != STT_GNU_IFUNC) || sym.st_shndx == SHN_UNDEF || sym.st_value == 0 {
+                continue;
+            }
+            let name = strtab_start.saturating_add(sym.st_name as usize);
+            if name >= strtab_end {
+                return Err(SymbolizeError::InvalidElf("bad symbol name"));
+            }
+            let address = u64::from(sym.st_value);
+            let size = u64::from(sym.st_size);
+            let section_end = match sections.get(sym.st_shndx as usize) {
+                Some(s) if sym.st_shndx < SHN_LORESERVE => u64::from(s.sh_addr).checked_add(u64::from(s.sh_size)),
+                _ => address.checked_add(size),
+            };
+            // Symbols or sections ending past the address space are corrupt.
+            let (Some(section_end), Some(_)) = (section_end, address.checked_add(size)) else {
+                continue;
+            };
+            // Global before weak before local, .symtab before .dynsym.
+            let binding = match sym.st_info >> 4 {
+                STB_GLOBAL => 0,
+                STB_LOCAL => 2,
+                _ => 1,
+            };
+            symbols.push(ElfSymbol {
+                address,
+                size,
+                section_end,
+                name,
+                rank: binding * 2 + rank,
+            });
+        }
+    }
+    Ok(symbols)
+}
+
+/// Returns the build-id in `notes`, the contents of a PT_NOTE segment or of
+/// a SHT_NOTE section whose alignment is `align`.
+pub fn build_id(notes: &[u8], align: usize) -> Option<&[u8]> {
+    // Notes are aligned to 4 bytes, or to 8 in segments of that alignment.
+    let align = if align == 8 { 8 } else { 4 };
+    let mut rest = notes;
+    while rest.len() >= 12 {
+        let namesz = u32::from_ne_bytes(rest[..4].try_into().unwrap()) as usize;
+        let descsz = u32::from_ne_bytes(rest[4..8].try_into().unwrap()) as usize;
+        let kind = u32::from_ne_bytes(rest[8..12].try_into().unwrap());
+        let name_end = 12usize.checked_add(namesz)?;
+        let desc_start = name_end.checked_next_multiple_of(align)?;
+        let desc_end = desc_start.checked_add(descsz)?;
+        let name = rest.get(12..name_end)?;
+        let desc = rest.get(desc_start..desc_end)?;
+        if kind == NT_GNU_BUILD_ID && name == b"GNU\0" && !desc.is_empty() {
+            return Some(desc);
+        }
+        rest = rest.get(desc_end.checked_next_multiple_of(align)?.min(rest.len())..)?;
+    }
+    None
+}
+
+/// Returns the NUL-terminated string at `offset`, without the NUL.
+#[inline]
+pub fn c_str(data: &[u8], offset: usize) -> &[u8] {
+    let s = &data[offset..];
+    match s.iter().position(|&b| b == 0) {
+        Some(len) => &s[..len],
+        None => s,
+    }
+}
+
+/// Returns the length of the ELF image at `ehdr`, up to the end of its
+/// section headers, which come last.
+///
+/// # Safety
+///
+/// `ehdr` must point to an ELF header.
+pub unsafe fn image_len(ehdr: *const u8) -> usize {
+    let ehdr = ptr::read_unaligned(ehdr as *const Ehdr);
+    ehdr.e_shoff as usize + ehdr.e_shnum as usize * ehdr.e_shentsize as usize
+}
+
+#[inline]
+fn read<T: Copy>(data: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(size_of::<T>())?;
+    if end > data.len() {
+        return None;
+    }
+    Some(unsafe { ptr::read_unaligned(data.as_ptr().add(offset) as *const T) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_symbols() {
+        let mmap = Mmap::open(&std::env::current_exe().unwrap()).unwrap();
+        let symbols = function_symbols(mmap.bytes()).unwrap();
+        let names: Vec<_> = symbols.iter().map(|s| c_str(mmap.bytes(), s.name)).collect();
+        assert!(names.iter().any(|name| name == b"main"));
+        assert!(symbols.iter().all(|s| s.address + s.size <= s.section_end));
+
+        assert!(function_symbols(b"\x7fELF").is_err());
+        assert!(function_symbols(&mmap.bytes()[..100]).is_err());
+        let mut data = mmap.bytes()[..64].to_vec();
+        data[4] ^= 3;
+        assert!(function_symbols(&data).is_err());
+    }
+
+    // 32-bit fields converted to 64 bits can't overflow.
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_function_symbols_overflow() {
+        let mmap = Mmap::open(&std::env::current_exe().unwrap()).unwrap();
+        let data = mmap.bytes();
+        let (_, headers) = section_headers(data).unwrap();
+        let symtab = headers.iter().find(|h| h.sh_type == SHT_SYMTAB).unwrap();
+        let (sym_offset, main) = (0..symtab.sh_size as usize / size_of::<Sym>())
+            .map(|i| {
+                let offset = symtab.sh_offset as usize + i * size_of::<Sym>();
+                (offset, read::<Sym>(data, offset).unwrap())
+            })
+            .find(|(_, sym)| {
+                let name = symtab_name(data, &headers, symtab, sym);
+                name == b"main" && sym.st_shndx != SHN_UNDEF
+            })
+            .unwrap();
+        let has_main = |data: &[u8]| {
+            let symbols = function_symbols(data).unwrap();
+            symbols.iter().any(|s| s.address == main.st_value)
+        };
+        assert!(has_main(data));
+
+        // A symbol ending past the address space.
+        let mut corrupt = data.to_vec();
+        let offset = sym_offset + std::mem::offset_of!(Sym, st_size);
+        corrupt[offset..offset + size_of_val(&main.st_size)].fill(0xff);
+        assert!(!has_main(&corrupt));
+
+        // A section ending past the address space.
+        let mut corrupt = data.to_vec();
+        let ehdr: Ehdr = read(data, 0).unwrap();
+        let header = ehdr.e_shoff as usize + main.st_shndx as usize * size_of::<Shdr>();
+        let offset = header + std::mem::offset_of!(Shdr, sh_addr);
+        corrupt[offset..offset + size_of_val(&symtab.sh_addr)].fill(0xff);
+        assert!(!has_main(&corrupt));
+    }
+
+    /// Returns the name of `sym`, a symbol of `symtab`.
+    #[cfg(target_pointer_width = "64")]
+    fn symtab_name<'a>(data: &'a [u8], headers: &[Shdr], symtab: &Shdr, sym: &Sym) -> &'a [u8] {
+        let strtab = &headers[symtab.sh_link as usize];
+        c_str(data, strtab.sh_offset as usize + sym.st_name as usize)
+    }
+
+    #[test]
+    fn test_sections() {
+        let mmap = Mmap::open(&std::env::current_exe().unwrap()).unwrap();
+        let all = sections(mmap.bytes()).unwrap();
+        let (_, text) = all.iter().find(|(name, _)| name == b".text").unwrap();
+        assert!(!text.is_empty());
+        // .bss has no data in the file.
+        let (_, bss) = all.iter().find(|(name, _)| name == b".bss").unwrap();
+        assert!(bss.is_empty());
+        assert!(sections(b"\x7fELF").is_err());
+    }
+
+    #[test]
+    fn test_build_id() {
+        let mut notes = vec![];
+        // A note of another type first, whose name is padded.
+        for (kind, name, desc) in [(1u32, &b"GNU\0\0"[..], &[1u8, 2, 3, 4][..]), (3, b"GNU\0", &[0xab; 20])] {
+            notes.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+            notes.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+            notes.extend_from_slice(&kind.to_ne_bytes());
+            notes.extend_from_slice(name);
+            notes.resize(notes.len().next_multiple_of(4), 0);
+            notes.extend_from_slice(desc);
+        }
+        assert_eq!(build_id(&notes, 4), Some(&[0xab; 20][..]));
+        assert_eq!(build_id(&notes[..notes.len() - 1], 4), None);
+        assert_eq!(build_id(&notes[..20], 4), None);
+        assert_eq!(build_id(&[], 4), None);
+    }
+}
@@ -0,0 +1,612 @@
+//! Resolves addresses into function symbols from the ELF symbol tables
+//! (.symtab and .dynsym, and the .symtab of MiniDebugInfo) of the loaded
+//! objects, and into inlined functions and source locations from their DWARF
+//! debug information.
+//!
+//! With the `debuginfod` feature, the debug information of objects without
+//! any is fetched by build-id from [debuginfod] servers.
+//!
+//! [Symbolizer] indexes every object loaded when it is created, for repeated
+//! lookups, and [resolve] only indexes the object containing an address.
+//! Both allocate and read files, so they shouldn't be used in signal
+//! handlers: save PCs there, e.g. with [trace_from_ucontext], and resolve
+//! them later.
+//!
+//! To resolve them in another process or on another host, a [ModuleMap]
+//! makes them relative to the ELF files of their objects, and is encoded
+//! along with them.
+//!
+//! [trace_from_ucontext]: crate::trace_from_ucontext
+
+mod debug_info;
+#[cfg(feature = "debuginfod")]
+pub mod debuginfod;
+mod demangle;
+mod elf;
+mod module_map;
+pub(crate) mod xz;
+
+use crate::dwarf::{deregister_debug_frame, register_debug_frame, DebugFrame, DwarfError};
+pub use debug_info::{DebugInfo, Frame};
+pub use demangle::demangle;
+use elf::{Data, ElfSymbol, Mmap};
+pub use module_map::{ModuleMap, NormalizedAddress};
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SymbolizeError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid elf: {0}")]
+    InvalidElf(&'static str),
+
+    #[error("invalid xz: {0}")]
+    InvalidXz(&'static str),
+
+    #[error("invalid module map: {0}")]
+    InvalidModuleMap(&'static str),
+
+    #[error("dwarf: {0}")]
+    Dwarf(#[from] DwarfError),
+
+    #[cfg(feature = "debuginfod")]
+    #[error("debuginfod: {0}")]
+    Debuginfod(&'static str),
+
+    #[cfg(feature = "debuginfod")]
+    #[error("debuginfod: http status {0}")]
+    HttpStatus(u16),
+}
+
+/// A function symbol of an ELF file, with its address in the file.
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    address: u64,
+    end: u64,
+    name: usize,
+}
+
+/// The function symbols of an ELF file, sorted by address, including those
+/// of its MiniDebugInfo.
+///
+/// Zero-sized symbols, e.g. of assembly functions, extend to the next
+/// symbol in their section. Of aliases, global symbols are preferred to weak
+/// ones and weak ones to local ones.
+pub struct SymbolTable {
+    data: Data,
+    /// The ELF file compressed in .gnu_debugdata, whose names are at the
+    /// offsets past the end of `data`.
+    mini_debug_info: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+impl SymbolTable {
+    /// Reads the symbol table of the ELF file at `path`, which must be of
+    /// the native class and byte order.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SymbolizeError> {
+        Self::new(Data::Mmap(Mmap::open(path.as_ref())?))
+    }
+
+    fn new(data: Data) -> Result<Self, SymbolizeError> {
+        let mut symbols = elf::function_symbols(data.bytes())?;
+        // Stripped objects may keep the symbols of .symtab not in .dynsym in
+        // MiniDebugInfo. If it is corrupt, the other symbols are still used.
+        let mini_debug_info = mini_debug_info(data.bytes()).ok().flatten().unwrap_or_default();
+        if let Ok(mini_symbols) = elf::function_symbols(&mini_debug_info) {
+            let offset = data.bytes().len();
+            symbols.extend(mini_symbols.into_iter().map(|s| ElfSymbol {
+                name: offset + s.name,
+                ..s
+            }));
+        }
+        symbols.sort_unstable_by_key(|s| (s.address, s.rank));
+        symbols.dedup_by_key(|s| s.address);
+        let mut entries: Vec<_> = symbols
+            .iter()
+            .map(|s| Entry {
+                address: s.address,
+                end: if s.size == 0 { s.section_end } else { s.address + s.size },
+                name: s.name,
+            })
+            .collect();
+        // Bound zero-sized symbols by the next one.
+        for i in 1..entries.len() {
+            let next = entries[i].address;
+            let prev = &mut entries[i - 1];
+            if prev.end > next && symbols[i - 1].size == 0 {
+                prev.end = next;
+            }
+        }
+        Ok(Self {
+            data,
+            mini_debug_info,
+            entries,
+        })
+    }
+
+    /// Returns the number of function symbols.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no function symbols, e.g. in a stripped
+    /// executable.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the name, address and size of the function containing
+    /// `address`, an address of the ELF file (not of the loaded object).
+    pub fn lookup(&self, address: u64) -> Option<(&[u8], u64, u64)> {
+        let i = self.entries.partition_point(|e| e.address <= address).checked_sub(1)?;
+        let entry = &self.entries[i];
+        if address >= entry.end {
+            return None;
+        }
+        let data = self.data.bytes();
+        let name = match entry.name.checked_sub(data.len()) {
+            Some(offset) => elf::c_str(&self.mini_debug_info, offset),
+            None => elf::c_str(data, entry.name),
+        };
+        Some((name, entry.address, entry.end - entry.address))
+    }
+}
+
+/// Returns the decompressed ELF file of the MiniDebugInfo of an ELF file, in
+/// its .gnu_debugdata section, if any.
+fn mini_debug_info(data: &[u8]) -> Result<Option<Vec<u8>>, SymbolizeError> {
+    let sections = elf::sections(data)?;
+    match sections.iter().find(|(name, _)| *name == b".gnu_debugdata") {
+        Some((_, compressed)) => xz::decompress(compressed).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Returns the .debug_frame section of the ELF file at `path`, if any,
+/// mapped for the rest of the process.
+fn debug_frame(path: &Path) -> Option<&'static [u8]> {
+    let mmap = Mmap::open(path).ok()?;
+    let sections = elf::sections(mmap.bytes()).ok()?;
+    let (_, section) = sections.iter().find(|(name, _)| *name == b".debug_frame")?;
+    if section.is_empty() {
+        return None;
+    }
+    let start = section.as_ptr() as usize - mmap.bytes().as_ptr() as usize;
+    let range = start..start + section.len();
+    // The unwinder may read it at any time, even in signal handlers.
+    let mmap: &'static Mmap = Box::leak(Box::new(mmap));
+    Some(&mmap.bytes()[range])
+}
+
+/// A loaded segment of an object.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Segment {
+    /// The addresses it is loaded at.
+    range: Range<u64>,
+    /// Its offset in the ELF file.
+    offset: u64,
+}
+
+/// A loaded object, the executable or a shared library.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Module {
+    path: PathBuf,
+    base: u64,
+    segments: Vec<Segment>,
+    build_id: Vec<u8>,
+    vdso: bool,
+}
+
+impl Module {
+    /// Returns the path of the object. The executable is `/proc/self/exe`,
+    /// the vDSO has the name given by the kernel, e.g. `linux-vdso.so.1`.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the address the object is loaded at, which the addresses in
+    /// its ELF file are relative to.
+    #[inline]
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Returns the build-id of the object, the note identifying its ELF file
+    /// and the files of its debug information, if it has one.
+    #[inline]
+    pub fn build_id(&self) -> Option<&[u8]> {
+        Some(&self.build_id[..]).filter(|id| !id.is_empty())
+    }
+
+    /// Returns whether the object has a segment loaded at `address`.
+    #[inline]
+    pub fn contains(&self, address: u64) -> bool {
+        self.segments.iter().any(|s| s.range.contains(&address))
+    }
+
+    /// Returns the offset in the ELF file of the byte loaded at `address`,
+    /// if the object has a segment loaded there.
+    pub fn file_offset(&self, address: u64) -> Option<u64> {
+        let segment = self.segments.iter().find(|s| s.range.contains(&address))?;
+        (address - segment.range.start).checked_add(segment.offset)
+    }
+
+    fn symbol_table(&self) -> Result<SymbolTable, SymbolizeError> {
+        if self.vdso {
+            let ehdr = unsafe { libc::getauxval(libc::AT_SYSINFO_EHDR) } as *const u8;
+            let data = unsafe { std::slice::from_raw_parts(ehdr, elf::image_len(ehdr)) };
+            return SymbolTable::new(Data::Static(data));
+        }
+        SymbolTable::open(&self.path)
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        if self.vdso {
+            return None;
+        }
+        DebugInfo::open(&self.path)
+            .ok()
+            .filter(|debug_info| !debug_info.is_empty())
+    }
+}
+
+/// The function symbol an address resolved to.
+#[derive(Debug, Copy, Clone)]
+pub struct Symbol<'a> {
+    name: &'a [u8],
+    address: u64,
+    size: u64,
+    offset: u64,
+    module: &'a Module,
+}
+
+impl<'a> Symbol<'a> {
+    /// Returns the name of the symbol, or `None` if it isn't UTF-8.
+    #[inline]
+    pub fn name(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.name).ok()
+    }
+
+    /// Returns the raw bytes of the name of the symbol, mangled.
+    #[inline]
+    pub fn name_bytes(&self) -> &'a [u8] {
+        self.name
+    }
+
+    /// Returns the name of the symbol demangled, or as is if it isn't
+    /// mangled, with invalid UTF-8 replaced. See [demangle] for
+    /// `strip_hash`.
+    pub fn demangled_name(&self, strip_hash: bool) -> String {
+        demangle(self.name, strip_hash).unwrap_or_else(|| String::from_utf8_lossy(self.name).into_owned())
+    }
+
+    /// Returns the address of the function in memory.
+    #[inline]
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Returns the size of the function.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the offset of the resolved address in the function.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the object containing the function.
+    #[inline]
+    pub fn module(&self) -> &'a Module {
+        self.module
+    }
+}
+
+fn lookup<'a>(module: &'a Module, table: &'a SymbolTable, address: u64) -> Option<Symbol<'a>> {
+    let (name, start, size) = table.lookup(address.wrapping_sub(module.base))?;
+    let start = start.wrapping_add(module.base);
+    Some(Symbol {
+        name,
+        address: start,
+        size,
+        offset: address - start,
+        module,
+    })
+}
+
+/// An index of the function symbols and debug information of all the
+/// objects loaded when it was created.
+///
+/// Objects whose file can't be read or parsed are skipped, unless fetched
+/// from debuginfod servers.
+pub struct Symbolizer {
+    modules: Vec<(Module, Option<SymbolTable>, Option<DebugInfo>)>,
+}
+
+impl Default for Symbolizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Symbolizer {
+    /// Reads the symbol tables and indexes the debug information of all
+    /// loaded objects, from local files only.
+    pub fn new() -> Self {
+        let modules = modules()
+            .into_iter()
+            .map(|module| {
+                let table = module.symbol_table().ok();
+                let debug_info = module.debug_info();
+                (module, table, debug_info)
+            })
+            .collect();
+        Self { modules }
+    }
+
+    /// Like [new](Self::new), but fetches the debug information of the
+    /// objects without any from the servers of `client`, whose symbol table
+    /// also replaces theirs, and their ELF file if it can't be read.
+    ///
+    /// This blocks on the servers, up to the timeout of `client` for each
+    /// object and server. The servers configured by the environment are
+    /// those of [Client::from_env](debuginfod::Client::from_env).
+    #[cfg(feature = "debuginfod")]
+    pub fn with_debuginfod(client: &debuginfod::Client) -> Self {
+        let modules = modules()
+            .into_iter()
+            .map(|module| {
+                let mut table = module.symbol_table().ok();
+                let mut debug_info = module.debug_info();
+                if let (false, Some(build_id)) = (module.vdso, module.build_id()) {
+                    if debug_info.is_none() {
+                        if let Ok(path) = client.debuginfo(build_id) {
+                            debug_info = DebugInfo::open(&path).ok().filter(|d| !d.is_empty());
+                            // It keeps the local symbols stripped from the
+                            // object.
+                            if let Ok(debug_table) = SymbolTable::open(&path) {
+                                if !debug_table.is_empty() {
+                                    table = Some(debug_table);
+                                }
+                            }
+                        }
+                    }
+                    if table.is_none() {
+                        table = client.executable(build_id).and_then(SymbolTable::open).ok();
+                    }
+                }
+                (module, table, debug_info)
+            })
+            .collect();
+        Self { modules }
+    }
+
+    /// Returns the loaded objects.
+    pub fn modules(&self) -> impl Iterator<Item = &Module> {
+        self.modules.iter().map(|(module, _, _)| module)
+    }
+
+    /// Makes [UnwindCursor] step through the code of the objects described
+    /// by the .debug_frame of their debug information but not by their
+    /// unwind tables, e.g. C built with `-fno-asynchronous-unwind-tables`.
+    /// Returns the number of objects with a .debug_frame.
+    ///
+    /// The sections stay mapped for the rest of the process, and replace
+    /// those registered before for the same objects. They are used until
+    /// [deregister_debug_frames](Self::deregister_debug_frames) is called,
+    /// which must happen before any of the objects is unloaded with
+    /// `dlclose`: another object may be loaded at its addresses. This must
+    /// not be called in signal handlers, but the unwinder uses the sections
+    /// there too.
+    ///
+    /// [UnwindCursor]: crate::UnwindCursor
+    pub fn register_debug_frames(&self) -> usize {
+        let mut registered = 0;
+        for (module, _, debug_info) in &self.modules {
+            if module.vdso {
+                continue;
+            }
+            // The debug information may be in another file than the object.
+            let section = debug_info
+                .as_ref()
+                .and_then(|debug_info| debug_frame(debug_info.path()))
+                .or_else(|| debug_frame(&module.path));
+            let Some(section) = section else {
+                continue;
+            };
+            let debug_frame = DebugFrame::new(module.base, section);
+            if !debug_frame.is_empty() && register_debug_frame(debug_frame) {
+                registered += 1;
+            }
+        }
+        registered
+    }
+
+    /// Stops the unwinder from using the .debug_frame sections registered
+    /// for the objects by [register_debug_frames](Self::register_debug_frames).
+    /// Returns the number of objects which had one.
+    pub fn deregister_debug_frames(&self) -> usize {
+        self.modules
+            .iter()
+            .filter(|(module, _, _)| deregister_debug_frame(module.base))
+            .count()
+    }
+
+    /// Returns the function containing `address`, if any.
+    pub fn resolve(&self, address: u64) -> Option<Symbol<'_>> {
+        let (module, table, _) = self.modules.iter().find(|(module, _, _)| module.contains(address))?;
+        lookup(module, table.as_ref()?, address)
+    }
+
+    /// Returns the functions at `address` from debug information, the
+    /// innermost inlined one first, with their source locations. Empty if
+    /// the object containing `address` has no debug information about it.
+    ///
+    /// The outermost function is named after the symbol tables if the debug
+    /// information doesn't name it, e.g. when .dwo files are missing.
+    pub fn frames(&self, address: u64) -> Vec<Frame> {
+        let Some((module, table, Some(debug_info))) =
+            self.modules.iter().find(|(module, _, _)| module.contains(address))
+        else {
+            return vec![];
+        };
+        let mut frames = debug_info.frames(address.wrapping_sub(module.base)).unwrap_or_default();
+        if let (Some(outermost), Some(table)) = (frames.last_mut(), table) {
+            if outermost.name_bytes().is_none() {
+                if let Some(symbol) = lookup(module, table, address) {
+                    outermost.set_name(symbol.name);
+                }
+            }
+        }
+        frames
+    }
+}
+
+/// Resolves `address` into the function containing it, if any, passing it
+/// to `f`. Returns whether it was found.
+///
+/// Only the symbol table of the object containing `address` is read, use a
+/// [Symbolizer] to resolve many addresses.
+pub fn resolve<F: FnOnce(&Symbol<'_>)>(address: u64, f: F) -> bool {
+    let Some(module) = modules().into_iter().find(|module| module.contains(address)) else {
+        return false;
+    };
+    let Ok(table) = module.symbol_table() else {
+        return false;
+    };
+    match lookup(&module, &table, address) {
+        Some(symbol) => {
+            f(&symbol);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the objects loaded now.
+fn modules() -> Vec<Module> {
+    let mut modules: Vec<Module> = vec![];
+    unsafe {
+        libc::dl_iterate_phdr(Some(callback), &mut modules as *mut _ as *mut libc::c_void);
+    }
+    modules
+}
+
+// ELF fields are 32-bit on 32-bit architectures, hence the conversions.
+#[allow(clippy::useless_conversion)]
+extern "C" fn callback(info: *mut libc::dl_phdr_info, _size: libc::size_t, data: *mut libc::c_void) -> libc::c_int {
+    unsafe {
+        let modules = &mut *(data as *mut Vec<Module>);
+        let info = &*info;
+        if info.dlpi_phnum == 0 {
+            return 0;
+        }
+        let base = u64::from(info.dlpi_addr);
+        let hdrs = std::slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
+        let mut segments = vec![];
+        let mut ehdr = 0;
+        let mut build_id = vec![];
+        for hdr in hdrs {
+            let start = base + u64::from(hdr.p_vaddr);
+            match hdr.p_type {
+                libc::PT_LOAD => {
+                    segments.push(Segment {
+                        range: start..start + u64::from(hdr.p_memsz),
+                        offset: u64::from(hdr.p_offset),
+                    });
+                    if hdr.p_offset == 0 {
+                        ehdr = start;
+                    }
+                }
+                libc::PT_NOTE if build_id.is_empty() => {
+                    let notes = std::slice::from_raw_parts(start as *const u8, hdr.p_memsz as usize);
+                    if let Some(id) = elf::build_id(notes, hdr.p_align as usize) {
+                        build_id = id.to_vec();
+                    }
+                }
+                _ => {}
+            }
+        }
+        let name = std::ffi::CStr::from_ptr(info.dlpi_name);
+        let vdso = ehdr != 0 && ehdr == libc::getauxval(libc::AT_SYSINFO_EHDR) as u64;
+        // The `dlpi_name` of the executable is an empty string.
+        let path = match name.to_bytes() {
+            b"" => PathBuf::from("/proc/self/exe"),
+            bytes => PathBuf::from(OsStr::from_bytes(bytes)),
+        };
+        modules.push(Module {
+            path,
+            base,
+            segments,
+            build_id,
+            vdso,
+        });
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn function() -> u64 {
+        std::hint::black_box(42)
+    }
+
+    #[test]
+    fn test_symbolizer() {
+        let symbolizer = Symbolizer::new();
+        let address = function as fn() -> u64 as usize as u64;
+        let symbol = symbolizer.resolve(address + 1).unwrap();
+        assert!(symbol.name().unwrap().contains("5tests8function"));
+        assert_eq!(symbol.demangled_name(true), "unwind::symbolize::tests::function");
+        assert_eq!(symbol.address(), address);
+        assert_eq!(symbol.offset(), 1);
+        assert!(symbol.size() > 1);
+        assert_eq!(symbol.module().path(), Path::new("/proc/self/exe"));
+
+        // Through the PLT, the address of the function in libc.
+        let getpid = libc::getpid as *const () as usize as u64;
+        let symbol = symbolizer.resolve(getpid).unwrap();
+        assert!(symbol.name().unwrap().contains("getpid"));
+        assert_eq!(symbol.demangled_name(true), symbol.name().unwrap());
+        assert_eq!(symbol.offset(), 0);
+
+        assert!(symbolizer.resolve(0).is_none());
+        let vdso = symbolizer.modules().find(|m| m.vdso).unwrap();
+        assert!(symbolizer.resolve(vdso.segments[0].range.start).is_none());
+    }
+
+    #[test]
+    fn test_vdso() {
+        let symbolizer = Symbolizer::new();
+        let (vdso, table, debug_info) = symbolizer.modules.iter().find(|(m, _, _)| m.vdso).unwrap();
+        assert!(debug_info.is_none());
+        let table = table.as_ref().unwrap();
+        assert!(!table.is_empty());
+        let symbol = symbolizer.resolve(vdso.base() + table.entries[0].address).unwrap();
+        assert!(!symbol.name_bytes().is_empty());
+        assert_eq!(symbol.offset(), 0);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let address = function as fn() -> u64 as usize as u64;
+        assert!(resolve(address, |symbol| {
+            assert!(symbol.name().unwrap().contains("function"));
+            assert_eq!(symbol.offset(), 0);
+        }));
+        assert!(!resolve(0, |_| unreachable!()));
+    }
+}
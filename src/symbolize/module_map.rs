@@ -0,0 +1,324 @@
+//! Addresses made independent of where objects are loaded, and a compact
+//! encoding of the objects of a process, so that traces can be symbolized
+//! elsewhere.
+//!
+//! ASLR loads objects at other addresses in every process. An address minus
+//! the load bias of its object, the `dlpi_addr` of the dynamic loader, is an
+//! address of the ELF file, as in its symbol table and debug information,
+//! whichever process it was taken in.
+
+use super::{modules, Module, Segment, SymbolizeError};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// The start of an encoded [ModuleMap].
+const MAGIC: &[u8] = b"UNWMAP";
+
+/// The version of the encoding.
+const VERSION: u8 = 1;
+
+/// The parameters of the 64-bit FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn invalid(reason: &'static str) -> SymbolizeError {
+    SymbolizeError::InvalidModuleMap(reason)
+}
+
+/// An address of a loaded object, relative to its ELF file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct NormalizedAddress {
+    /// The index of the object in [ModuleMap::modules].
+    pub module: usize,
+    /// The address in the ELF file, the one its symbol table and debug
+    /// information have.
+    pub address: u64,
+    /// The offset of the address in the ELF file.
+    pub file_offset: u64,
+}
+
+/// The objects loaded in a process, to make its addresses relative to their
+/// ELF files, and to find these files again from another process.
+///
+/// The executable has its real path rather than `/proc/self/exe`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModuleMap {
+    modules: Vec<Module>,
+}
+
+impl Default for ModuleMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleMap {
+    /// Returns the objects loaded now.
+    pub fn new() -> Self {
+        let mut modules = modules();
+        for module in &mut modules {
+            if module.path == Path::new("/proc/self/exe") {
+                if let Ok(path) = std::fs::read_link(&module.path) {
+                    module.path = path;
+                }
+            }
+        }
+        Self { modules }
+    }
+
+    /// Returns the loaded objects.
+    #[inline]
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// Returns the object of `address`, if it is in the map.
+    #[inline]
+    pub fn module(&self, address: &NormalizedAddress) -> Option<&Module> {
+        self.modules.get(address.module)
+    }
+
+    /// Makes `address`, an address of the process the map was taken in,
+    /// relative to the ELF file of its object. Returns `None` if no object
+    /// has a segment loaded there.
+    pub fn normalize(&self, address: u64) -> Option<NormalizedAddress> {
+        let (index, module) = self.modules.iter().enumerate().find(|(_, m)| m.contains(address))?;
+        Some(NormalizedAddress {
+            module: index,
+            address: address.wrapping_sub(module.base),
+            file_offset: module.file_offset(address)?,
+        })
+    }
+
+    /// Returns a hash of the stack whose frames are at `addresses`, which is
+    /// the same for the same code in other processes and on other hosts.
+    ///
+    /// Objects are told apart by build-id, or by path if they have none.
+    /// The hash is FNV-1a, stable across versions of the crate.
+    pub fn fingerprint(&self, addresses: &[NormalizedAddress]) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+        for address in addresses {
+            let id = match self.module(address) {
+                Some(module) => module.build_id().unwrap_or_else(|| module.path.as_os_str().as_bytes()),
+                None => &[],
+            };
+            hash = fnv1a(hash, &(id.len() as u64).to_le_bytes());
+            hash = fnv1a(hash, id);
+            hash = fnv1a(hash, &address.address.to_le_bytes());
+        }
+        hash
+    }
+
+    /// Encodes the map, to be decoded by [decode](Self::decode) in another
+    /// process or on another host.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        write_varint(&mut out, self.modules.len() as u64);
+        for module in &self.modules {
+            write_varint(&mut out, module.base);
+            out.push(module.vdso as u8);
+            write_bytes(&mut out, &module.build_id);
+            write_bytes(&mut out, module.path.as_os_str().as_bytes());
+            write_varint(&mut out, module.segments.len() as u64);
+            for segment in &module.segments {
+                write_varint(&mut out, segment.range.start.wrapping_sub(module.base));
+                write_varint(&mut out, segment.range.end - segment.range.start);
+                write_varint(&mut out, segment.offset);
+            }
+        }
+        out
+    }
+
+    /// Decodes a map encoded by [encode](Self::encode).
+    pub fn decode(data: &[u8]) -> Result<Self, SymbolizeError> {
+        let mut input = Input { data, pos: 0 };
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        if input.take(1)?[0] != VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        let mut modules = vec![];
+        for _ in 0..input.varint()? {
+            let base = input.varint()?;
+            let vdso = match input.take(1)?[0] {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid("bad flags")),
+            };
+            let build_id = input.bytes()?.to_vec();
+            let path = PathBuf::from(OsStr::from_bytes(input.bytes()?));
+            let mut segments = vec![];
+            for _ in 0..input.varint()? {
+                let start = input.varint()?.wrapping_add(base);
+                let end = start.checked_add(input.varint()?).ok_or(invalid("bad segment"))?;
+                segments.push(Segment {
+                    range: start..end,
+                    offset: input.varint()?,
+                });
+            }
+            modules.push(Module {
+                path,
+                base,
+                segments,
+                build_id,
+                vdso,
+            });
+        }
+        if input.pos != data.len() {
+            return Err(invalid("trailing data"));
+        }
+        Ok(Self { modules })
+    }
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
+}
+
+/// Appends `value` as an unsigned LEB128.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Appends `bytes` after their length.
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Bytes being read.
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SymbolizeError> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or(invalid("truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Reads an unsigned LEB128.
+    fn varint(&mut self) -> Result<u64, SymbolizeError> {
+        let mut value = 0;
+        for i in 0..10 {
+            let byte = self.take(1)?[0];
+            if i == 9 && byte > 1 {
+                break;
+            }
+            value |= u64::from(byte & 0x7f) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("bad integer"))
+    }
+
+    /// Reads bytes after their length.
+    fn bytes(&mut self) -> Result<&'a [u8], SymbolizeError> {
+        let len = usize::try_from(self.varint()?).map_err(|_| invalid("truncated"))?;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn function() -> u64 {
+        std::hint::black_box(42)
+    }
+
+    #[test]
+    fn test_normalize() {
+        let map = ModuleMap::new();
+        let pc = function as fn() -> u64 as usize as u64;
+        let address = map.normalize(pc).unwrap();
+        let module = map.module(&address).unwrap();
+        assert_eq!(module.path(), std::env::current_exe().unwrap());
+        assert_eq!(address.address, pc - module.base());
+        // The code loaded there is the one at the offset of the file.
+        let file = std::fs::read(module.path()).unwrap();
+        let code = unsafe { std::slice::from_raw_parts(pc as *const u8, 16) };
+        let offset = address.file_offset as usize;
+        assert_eq!(&file[offset..offset + 16], code);
+        assert!(map.normalize(0).is_none());
+    }
+
+    #[test]
+    fn test_encode() {
+        let map = ModuleMap::new();
+        let data = map.encode();
+        let decoded = ModuleMap::decode(&data).unwrap();
+        assert_eq!(decoded, map);
+        assert!(decoded.modules().iter().any(|m| m.vdso));
+        let pc = function as fn() -> u64 as usize as u64;
+        assert_eq!(decoded.normalize(pc), map.normalize(pc));
+
+        for len in 0..data.len() {
+            assert_eq!(
+                ModuleMap::decode(&data[..len]).unwrap_err().to_string(),
+                "invalid module map: truncated"
+            );
+        }
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert!(ModuleMap::decode(&trailing).is_err());
+        let mut version = data.clone();
+        version[MAGIC.len()] = VERSION + 1;
+        assert!(ModuleMap::decode(&version).is_err());
+        let overflow = [MAGIC, &[VERSION], &[0xff; 9], &[0x02]].concat();
+        assert_eq!(
+            ModuleMap::decode(&overflow).unwrap_err().to_string(),
+            "invalid module map: bad integer"
+        );
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u64::MAX - 1, u64::MAX] {
+            let mut out = vec![];
+            write_varint(&mut out, value);
+            let mut input = Input { data: &out, pos: 0 };
+            assert_eq!(input.varint().unwrap(), value);
+            assert_eq!(input.pos, out.len());
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let map = ModuleMap::new();
+        let pc = function as fn() -> u64 as usize as u64;
+        let stack = [map.normalize(pc).unwrap(), map.normalize(pc + 1).unwrap()];
+        let fingerprint = map.fingerprint(&stack);
+        assert_eq!(
+            ModuleMap::decode(&map.encode()).unwrap().fingerprint(&stack),
+            fingerprint
+        );
+        assert_ne!(map.fingerprint(&stack[..1]), fingerprint);
+        assert_ne!(map.fingerprint(&[stack[1], stack[0]]), fingerprint);
+        assert_eq!(map.fingerprint(&[]), FNV_OFFSET_BASIS);
+
+        // The same addresses of an object loaded elsewhere.
+        let mut moved = map.clone();
+        for module in &mut moved.modules {
+            module.base += 0x10000;
+        }
+        assert_eq!(moved.fingerprint(&stack), fingerprint);
+    }
+}
@@ -0,0 +1,606 @@
+//! Decompression of the xz streams of MiniDebugInfo (.gnu_debugdata), with
+//! LZMA2 as the only filter, as `xz` compresses by default.
+//!
+//! See the formats at <https://tukaani.org/xz/xz-file-format.txt> and
+//! <https://tukaani.org/xz/lzma.txt>.
+
+use super::SymbolizeError;
+
+/// The maximum size of decompressed data, far more than any symbol table.
+const MAX_OUTPUT: usize = 1 << 28;
+
+const STREAM_MAGIC: &[u8] = b"\xfd7zXZ\0";
+const FILTER_LZMA2: u64 = 0x21;
+const CHECK_NONE: u8 = 0;
+const CHECK_CRC32: u8 = 1;
+const CHECK_CRC64: u8 = 4;
+
+fn invalid(reason: &'static str) -> SymbolizeError {
+    SymbolizeError::InvalidXz(reason)
+}
+
+/// Decompresses the blocks of the first stream of `data`. Checks of other
+/// types than CRC32 and CRC64 are skipped.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, SymbolizeError> {
+    let mut input = Input { data, pos: 0 };
+    if input.take(6)? != STREAM_MAGIC {
+        return Err(invalid("bad magic"));
+    }
+    let flags = input.take(2)?;
+    if input.u32_le()? != crc32(flags) {
+        return Err(invalid("stream header checksum mismatch"));
+    }
+    if flags[0] != 0 || flags[1] > 0xf {
+        return Err(invalid("unsupported stream flags"));
+    }
+    let check = flags[1];
+
+    let mut output = vec![];
+    // A zero block header size is the index, which ends the blocks.
+    while input.peek()? != 0 {
+        let start = output.len();
+        block(&mut input, &mut output)?;
+        let len = input.pos.next_multiple_of(4) - input.pos;
+        if input.take(len)?.iter().any(|&b| b != 0) {
+            return Err(invalid("bad block padding"));
+        }
+        let block = &output[start..];
+        match check {
+            CHECK_NONE => {}
+            CHECK_CRC32 if input.u32_le()? != crc32(block) => return Err(invalid("block checksum mismatch")),
+            CHECK_CRC64 if input.u64_le()? != crc64(block) => return Err(invalid("block checksum mismatch")),
+            CHECK_CRC32 | CHECK_CRC64 => {}
+            _ => {
+                input.take([0, 4, 8, 16, 32, 64][(check as usize).div_ceil(3)])?;
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Decompresses a block, appending its data to `output`.
+fn block(input: &mut Input<'_>, output: &mut Vec<u8>) -> Result<(), SymbolizeError> {
+    let header_len = (input.peek()? as usize + 1) * 4;
+    let header = input.take(header_len)?;
+    let (header, crc) = header.split_at(header_len - 4);
+    if u32::from_le_bytes(crc.try_into().unwrap()) != crc32(header) {
+        return Err(invalid("block header checksum mismatch"));
+    }
+    let mut fields = Input { data: header, pos: 2 };
+    let flags = header[1];
+    if flags & 0x3c != 0 {
+        return Err(invalid("unsupported block flags"));
+    }
+    let compressed_len = if flags & 0x40 != 0 {
+        Some(fields.varint()?)
+    } else {
+        None
+    };
+    let uncompressed_len = if flags & 0x80 != 0 {
+        Some(fields.varint()?)
+    } else {
+        None
+    };
+    if (flags & 3) != 0 || fields.varint()? != FILTER_LZMA2 {
+        return Err(invalid("unsupported filters"));
+    }
+    if fields.varint()? != 1 {
+        return Err(invalid("bad lzma2 properties"));
+    }
+    let dict_size = fields.take(1)?[0];
+    if dict_size > 40 || fields.data[fields.pos..].iter().any(|&b| b != 0) {
+        return Err(invalid("bad block header"));
+    }
+
+    let output_start = output.len();
+    let data_start = input.pos;
+    lzma2(input, output)?;
+    if compressed_len.is_some_and(|len| len != (input.pos - data_start) as u64)
+        || uncompressed_len.is_some_and(|len| len != (output.len() - output_start) as u64)
+    {
+        return Err(invalid("block size mismatch"));
+    }
+    Ok(())
+}
+
+/// Decodes LZMA2 chunks up to the end marker, appending their data to
+/// `output`.
+fn lzma2(input: &mut Input<'_>, output: &mut Vec<u8>) -> Result<(), SymbolizeError> {
+    let mut lzma: Option<Lzma> = None;
+    // Where the dictionary starts, matches can't refer to data before it.
+    let mut dict_start = None;
+    loop {
+        let control = input.take(1)?[0];
+        if control == 0 {
+            return Ok(());
+        }
+        if control & 0x80 == 0 {
+            // Uncompressed, with a dictionary reset if 1.
+            if control > 2 {
+                return Err(invalid("bad lzma2 chunk"));
+            }
+            if control == 1 {
+                dict_start = Some(output.len());
+            }
+            dict_start.ok_or(invalid("missing lzma2 dictionary reset"))?;
+            let len = input.u16_be()? as usize + 1;
+            if output.len() + len > MAX_OUTPUT {
+                return Err(invalid("too large"));
+            }
+            output.extend_from_slice(input.take(len)?);
+            continue;
+        }
+
+        let uncompressed_len = ((control as usize & 0x1f) << 16) + input.u16_be()? as usize + 1;
+        let compressed_len = input.u16_be()? as usize + 1;
+        let reset = (control >> 5) & 3;
+        if reset == 3 {
+            dict_start = Some(output.len());
+        }
+        let dict_start = dict_start.ok_or(invalid("missing lzma2 dictionary reset"))?;
+        if reset >= 2 {
+            lzma = Some(Lzma::new(input.take(1)?[0])?);
+        }
+        let lzma = lzma.as_mut().ok_or(invalid("missing lzma2 properties"))?;
+        if reset == 1 {
+            lzma.reset();
+        }
+        let end = output.len() + uncompressed_len;
+        if end > MAX_OUTPUT {
+            return Err(invalid("too large"));
+        }
+        let mut rc = RangeDecoder::new(input.take(compressed_len)?)?;
+        lzma.decode(&mut rc, output, dict_start, end)?;
+        if !rc.is_finished()? {
+            return Err(invalid("lzma2 chunk size mismatch"));
+        }
+    }
+}
+
+/// Bytes being read.
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SymbolizeError> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or(invalid("truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, SymbolizeError> {
+        self.data.get(self.pos).copied().ok_or(invalid("truncated"))
+    }
+
+    fn u16_be(&mut self) -> Result<u16, SymbolizeError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32_le(&mut self) -> Result<u32, SymbolizeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64_le(&mut self) -> Result<u64, SymbolizeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a multibyte integer of up to 9 bytes.
+    fn varint(&mut self) -> Result<u64, SymbolizeError> {
+        let mut value = 0;
+        for i in 0..9 {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("bad integer"))
+    }
+}
+
+/// A probability of a bit being 0, out of 2^11.
+type Prob = u16;
+
+const PROB_INIT: Prob = 1 << 10;
+
+/// The range decoder of a chunk of LZMA data.
+struct RangeDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    range: u32,
+    code: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, SymbolizeError> {
+        if data.len() < 5 || data[0] != 0 {
+            return Err(invalid("bad range coder data"));
+        }
+        Ok(Self {
+            data,
+            pos: 5,
+            range: u32::MAX,
+            code: u32::from_be_bytes(data[1..5].try_into().unwrap()),
+        })
+    }
+
+    /// Returns whether all the data was decoded, as the encoder flushes
+    /// its state.
+    fn is_finished(&mut self) -> Result<bool, SymbolizeError> {
+        self.normalize()?;
+        Ok(self.pos == self.data.len() && self.code == 0)
+    }
+
+    fn normalize(&mut self) -> Result<(), SymbolizeError> {
+        if self.range < 1 << 24 {
+            let byte = *self.data.get(self.pos).ok_or(invalid("truncated lzma data"))?;
+            self.pos += 1;
+            self.range <<= 8;
+            self.code = (self.code << 8) | u32::from(byte);
+        }
+        Ok(())
+    }
+
+    fn bit(&mut self, prob: &mut Prob) -> Result<u32, SymbolizeError> {
+        self.normalize()?;
+        let bound = (self.range >> 11) * u32::from(*prob);
+        let bit = if self.code < bound {
+            self.range = bound;
+            *prob += ((1 << 11) - *prob) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *prob -= *prob >> 5;
+            1
+        };
+        Ok(bit)
+    }
+
+    /// Decodes `bits` bits, most significant first, with `probs` indexed
+    /// by the bits decoded so far.
+    fn bit_tree(&mut self, probs: &mut [Prob], bits: u32) -> Result<u32, SymbolizeError> {
+        let mut symbol = 1;
+        for _ in 0..bits {
+            symbol = (symbol << 1) | self.bit(&mut probs[symbol as usize])?;
+        }
+        Ok(symbol - (1 << bits))
+    }
+
+    /// Decodes `bits` bits, least significant first.
+    fn reverse_bit_tree(&mut self, probs: &mut [Prob], bits: u32) -> Result<u32, SymbolizeError> {
+        let mut symbol = 1;
+        let mut value = 0;
+        for i in 0..bits {
+            let bit = self.bit(&mut probs[symbol])?;
+            symbol = (symbol << 1) | bit as usize;
+            value |= bit << i;
+        }
+        Ok(value)
+    }
+
+    /// Decodes `bits` bits of equal probabilities.
+    fn direct_bits(&mut self, bits: u32) -> Result<u32, SymbolizeError> {
+        let mut value = 0;
+        for _ in 0..bits {
+            self.normalize()?;
+            self.range >>= 1;
+            let bit = self.code >= self.range;
+            if bit {
+                self.code -= self.range;
+            }
+            value = (value << 1) | bit as u32;
+        }
+        Ok(value)
+    }
+}
+
+const STATES: usize = 12;
+const POS_STATES: usize = 1 << 4;
+const MATCH_LEN_MIN: usize = 2;
+/// The number of distance slots whose low bits are coded with
+/// probabilities, the end of the dist_special probabilities.
+const DIST_MODEL_END: u32 = 14;
+
+/// The decoder of match lengths.
+struct LenDecoder {
+    choice: Prob,
+    choice2: Prob,
+    low: [[Prob; 8]; POS_STATES],
+    mid: [[Prob; 8]; POS_STATES],
+    high: [Prob; 256],
+}
+
+impl LenDecoder {
+    fn new() -> Self {
+        Self {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [[PROB_INIT; 8]; POS_STATES],
+            mid: [[PROB_INIT; 8]; POS_STATES],
+            high: [PROB_INIT; 256],
+        }
+    }
+
+    fn decode(&mut self, rc: &mut RangeDecoder<'_>, pos_state: usize) -> Result<usize, SymbolizeError> {
+        let len = if rc.bit(&mut self.choice)? == 0 {
+            rc.bit_tree(&mut self.low[pos_state], 3)?
+        } else if rc.bit(&mut self.choice2)? == 0 {
+            8 + rc.bit_tree(&mut self.mid[pos_state], 3)?
+        } else {
+            16 + rc.bit_tree(&mut self.high, 8)?
+        };
+        Ok(len as usize + MATCH_LEN_MIN)
+    }
+}
+
+/// The state of an LZMA decoder, kept across the chunks of LZMA2 data.
+struct Lzma {
+    lc: u32,
+    lp: u32,
+    pb: u32,
+    state: usize,
+    reps: [usize; 4],
+    is_match: [[Prob; POS_STATES]; STATES],
+    is_rep: [Prob; STATES],
+    is_rep0: [Prob; STATES],
+    is_rep1: [Prob; STATES],
+    is_rep2: [Prob; STATES],
+    is_rep0_long: [[Prob; POS_STATES]; STATES],
+    literal: Vec<Prob>,
+    dist_slot: [[Prob; 64]; 4],
+    /// The trees of the low bits of distances, indexed from 1 as the
+    /// others, so the first probability is unused.
+    dist_special: [Prob; 115],
+    align: [Prob; 16],
+    len: LenDecoder,
+    rep_len: LenDecoder,
+}
+
+impl Lzma {
+    fn new(props: u8) -> Result<Self, SymbolizeError> {
+        let props = u32::from(props);
+        let (lc, lp, pb) = (props % 9, props / 9 % 5, props / 45);
+        if pb > 4 || lc + lp > 4 {
+            return Err(invalid("bad lzma properties"));
+        }
+        Ok(Self {
+            lc,
+            lp,
+            pb,
+            state: 0,
+            reps: [0; 4],
+            is_match: [[PROB_INIT; POS_STATES]; STATES],
+            is_rep: [PROB_INIT; STATES],
+            is_rep0: [PROB_INIT; STATES],
+            is_rep1: [PROB_INIT; STATES],
+            is_rep2: [PROB_INIT; STATES],
+            is_rep0_long: [[PROB_INIT; POS_STATES]; STATES],
+            literal: vec![PROB_INIT; 0x300 << (lc + lp)],
+            dist_slot: [[PROB_INIT; 64]; 4],
+            dist_special: [PROB_INIT; 115],
+            align: [PROB_INIT; 16],
+            len: LenDecoder::new(),
+            rep_len: LenDecoder::new(),
+        })
+    }
+
+    /// Resets the state and the probabilities, keeping the properties.
+    fn reset(&mut self) {
+        let props = (self.pb * 5 + self.lp) * 9 + self.lc;
+        *self = Self::new(props as u8).unwrap();
+    }
+
+    /// Decodes data up to `end` bytes of `output`, which matches can refer
+    /// to after `dict_start`.
+    fn decode(
+        &mut self,
+        rc: &mut RangeDecoder<'_>,
+        output: &mut Vec<u8>,
+        dict_start: usize,
+        end: usize,
+    ) -> Result<(), SymbolizeError> {
+        let pos_mask = (1 << self.pb) - 1;
+        while output.len() < end {
+            let pos_state = output.len() & pos_mask;
+            if rc.bit(&mut self.is_match[self.state][pos_state])? == 0 {
+                let byte = self.literal(rc, output, dict_start)?;
+                output.push(byte);
+                self.state = match self.state {
+                    0..=3 => 0,
+                    4..=9 => self.state - 3,
+                    _ => self.state - 6,
+                };
+                continue;
+            }
+
+            let len = if rc.bit(&mut self.is_rep[self.state])? == 0 {
+                let len = self.len.decode(rc, pos_state)?;
+                self.state = if self.state < 7 { 7 } else { 10 };
+                let distance = self.distance(rc, len)?;
+                self.reps = [distance, self.reps[0], self.reps[1], self.reps[2]];
+                len
+            } else {
+                if rc.bit(&mut self.is_rep0[self.state])? == 0 {
+                    if rc.bit(&mut self.is_rep0_long[self.state][pos_state])? == 0 {
+                        // A single byte at the last distance.
+                        self.state = if self.state < 7 { 9 } else { 11 };
+                        copy_match(output, dict_start, self.reps[0], 1)?;
+                        continue;
+                    }
+                } else {
+                    let i = if rc.bit(&mut self.is_rep1[self.state])? == 0 {
+                        1
+                    } else if rc.bit(&mut self.is_rep2[self.state])? == 0 {
+                        2
+                    } else {
+                        3
+                    };
+                    self.reps[..=i].rotate_right(1);
+                }
+                self.state = if self.state < 7 { 8 } else { 11 };
+                self.rep_len.decode(rc, pos_state)?
+            };
+            if len > end - output.len() {
+                return Err(invalid("lzma match past the chunk"));
+            }
+            copy_match(output, dict_start, self.reps[0], len)?;
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, rc: &mut RangeDecoder<'_>, output: &[u8], dict_start: usize) -> Result<u8, SymbolizeError> {
+        let pos = output.len();
+        let prev = if pos > dict_start { output[pos - 1] } else { 0 };
+        let lit_state = ((pos & ((1 << self.lp) - 1)) << self.lc) + (prev as usize >> (8 - self.lc));
+        let probs = &mut self.literal[0x300 * lit_state..][..0x300];
+        let mut symbol = 1;
+        if self.state >= 7 {
+            // After a match, the byte at the last distance predicts this one.
+            let distance = self.reps[0] + 1;
+            if distance > pos - dict_start {
+                return Err(invalid("lzma distance past the dictionary"));
+            }
+            let mut match_byte = u32::from(output[pos - distance]);
+            while symbol < 0x100 {
+                let match_bit = (match_byte >> 7) & 1;
+                match_byte <<= 1;
+                let bit = rc.bit(&mut probs[(((1 + match_bit) << 8) + symbol) as usize])?;
+                symbol = (symbol << 1) | bit;
+                if match_bit != bit {
+                    break;
+                }
+            }
+        }
+        while symbol < 0x100 {
+            symbol = (symbol << 1) | rc.bit(&mut probs[symbol as usize])?;
+        }
+        Ok(symbol as u8)
+    }
+
+    fn distance(&mut self, rc: &mut RangeDecoder<'_>, len: usize) -> Result<usize, SymbolizeError> {
+        let len_state = (len - MATCH_LEN_MIN).min(3);
+        let slot = rc.bit_tree(&mut self.dist_slot[len_state], 6)?;
+        if slot < 4 {
+            return Ok(slot as usize);
+        }
+        let bits = (slot >> 1) - 1;
+        let mut distance = (2 | (slot & 1)) << bits;
+        if slot < DIST_MODEL_END {
+            let probs = &mut self.dist_special[(distance - slot) as usize..];
+            distance += rc.reverse_bit_tree(probs, bits)?;
+        } else {
+            distance += rc.direct_bits(bits - 4)? << 4;
+            distance += rc.reverse_bit_tree(&mut self.align, 4)?;
+        }
+        Ok(distance as usize)
+    }
+}
+
+/// Appends `len` bytes copied from `distance + 1` bytes back.
+fn copy_match(output: &mut Vec<u8>, dict_start: usize, distance: usize, len: usize) -> Result<(), SymbolizeError> {
+    let distance = distance + 1;
+    if distance > output.len() - dict_start {
+        return Err(invalid("lzma distance past the dictionary"));
+    }
+    let start = output.len() - distance;
+    // The copy overlaps itself for distances shorter than the length.
+    for i in 0..len {
+        output.push(output[start + i]);
+    }
+    Ok(())
+}
+
+/// Returns the table of a reflected CRC of polynomial `poly`.
+const fn crc_table(poly: u64) -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ poly } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u64; 256] = crc_table(0xedb8_8320);
+const CRC64_TABLE: [u64; 256] = crc_table(0xc96c_5795_d787_0f42);
+
+fn crc(table: &[u64; 256], init: u64, data: &[u8]) -> u64 {
+    let crc = data.iter().fold(init, |crc, &b| {
+        table[((crc ^ u64::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    });
+    crc ^ init
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    crc(&CRC32_TABLE, u64::from(u32::MAX), data) as u32
+}
+
+fn crc64(data: &[u8]) -> u64 {
+    crc(&CRC64_TABLE, u64::MAX, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `printf 'hello hello hello hello, xz!\n' | xz -c`
+    const COMPRESSED: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x04, 0xe6, 0xd6, 0xb4, 0x46, 0x04, 0xc0, 0x1a, 0x1d, 0x21, 0x01,
+        0x16, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xbf, 0x15, 0xf2, 0x93, 0xe0, 0x00, 0x1c, 0x00,
+        0x12, 0x5d, 0x00, 0x34, 0x19, 0x49, 0xee, 0x8d, 0xe9, 0x56, 0x0a, 0xe7, 0x79, 0x9d, 0x78, 0xab, 0x8e, 0x60,
+        0xc6, 0x40, 0x00, 0x00, 0x00, 0x00, 0xce, 0xc0, 0x93, 0xa2, 0xdd, 0xca, 0x41, 0xcc, 0x00, 0x01, 0x36, 0x1d,
+        0x87, 0x48, 0x9c, 0xca, 0x1f, 0xb6, 0xf3, 0x7d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x04, 0x59, 0x5a,
+    ];
+
+    /// The bytes 0 to 63 through `xz -c --check=crc32`, stored in an
+    /// uncompressed chunk.
+    const UNCOMPRESSED: &[u8] = &[
+        0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00, 0x00, 0x01, 0x69, 0x22, 0xde, 0x36, 0x04, 0xc0, 0x44, 0x40, 0x21, 0x01,
+        0x16, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xad, 0xdc, 0xc4, 0x31, 0x01, 0x00, 0x3f, 0x00,
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12,
+        0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23, 0x24,
+        0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36,
+        0x37, 0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x00, 0x8c, 0xce, 0x0e, 0x10, 0x00, 0x01, 0x5c, 0x40,
+        0xe3, 0xe6, 0x54, 0x40, 0x90, 0x42, 0x99, 0x0d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x59, 0x5a,
+    ];
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(decompress(COMPRESSED).unwrap(), b"hello hello hello hello, xz!\n");
+        assert_eq!(decompress(UNCOMPRESSED).unwrap(), (0..64).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_invalid() {
+        for data in [COMPRESSED, UNCOMPRESSED] {
+            assert!(decompress(&data[..data.len() - 30]).is_err());
+            assert!(decompress(&data[1..]).is_err());
+            // A flipped bit of the data or of its check.
+            for i in [40, data.len() - 30] {
+                let mut data = data.to_vec();
+                data[i] ^= 0x10;
+                assert!(decompress(&data).is_err(), "{}", i);
+            }
+        }
+        assert!(decompress(&[]).is_err());
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc64(b"123456789"), 0x995d_c9bb_df19_39fa);
+    }
+}
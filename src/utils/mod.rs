@@ -2,9 +2,14 @@
 mod access_check;
 #[cfg(feature = "mem-protect")]
 pub use access_check::*;
+#[cfg_attr(not(all(target_arch = "aarch64", target_os = "linux")), allow(unused))]
+mod pac;
+#[cfg_attr(not(all(target_arch = "aarch64", target_os = "linux")), allow(unused))]
+pub use pac::*;
 
 /// [start, end)
 #[derive(Debug, Copy, Clone)]
+#[allow(unused)]
 pub struct AddressRange {
     pub start: u64,
     pub end: u64,
@@ -13,10 +18,7 @@ pub struct AddressRange {
 impl AddressRange {
     /// Determine whether the target address is in the current range.
     #[inline]
-    #[cfg_attr(
-        any(all(target_os = "macos", target_arch = "aarch64"), not(feature = "mem-protect")),
-        allow(unused)
-    )]
+    #[allow(unused)]
     pub fn contains(&self, target: u64) -> bool {
         self.start <= target && target < self.end
     }
@@ -27,9 +29,11 @@ impl AddressRange {
 /// Note that although `load` is not unsafe, it is implemented by unsafe
 /// internally and simply attempts to read the specified address. So the
 /// correctness of the address needs to be guaranteed by the caller.
+///
+/// The address does not need to be aligned, since most DWARF data is packed.
 #[inline]
 pub fn load<T: Copy>(address: u64) -> T {
-    unsafe { *(address as *const T) }
+    unsafe { std::ptr::read_unaligned(address as *const T) }
 }
 
 #[cfg(test)]
@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Used when the size of the virtual address space can't be determined.
+pub const DEFAULT_VA_BITS: u32 = 48;
+
+// The size of the virtual address space, or 0 if not yet computed.
+static VA_BITS: AtomicU32 = AtomicU32::new(0);
+
+/// Strip the pointer authentication code (PAC) from a signed code address.
+///
+/// AArch64 v8.3 stores the PAC in the bits above the virtual address space,
+/// whichever key (A or B) was used to sign the address, so stripping only
+/// needs the size of the address space and no PAC instructions. The
+/// signature is not verified.
+///
+/// This is async-signal-safe.
+#[inline]
+pub fn strip_pac(address: u64) -> u64 {
+    let mut bits = VA_BITS.load(Ordering::Relaxed);
+    if bits == 0 {
+        bits = va_bits();
+        VA_BITS.store(bits, Ordering::Relaxed);
+    }
+    strip_pac_with_va_bits(address, bits)
+}
+
+/// Strip the PAC from `address`, assuming a `va_bits` virtual address space.
+#[inline]
+pub fn strip_pac_with_va_bits(address: u64, va_bits: u32) -> u64 {
+    address & ((1 << va_bits) - 1)
+}
+
+/// Returns the size in bits of the user virtual address space.
+///
+/// The initial process stack is placed at the top of the user address
+/// space, so the highest bit of an address on it (we use the one of the
+/// AT_RANDOM bytes from the auxiliary vector) gives the number of address
+/// bits, e.g. 39, 42 or 48. Kernels configured for 52-bit addresses only
+/// hand them out on request, which leaves bits 48~51 zero in code addresses.
+#[cfg(target_os = "linux")]
+fn va_bits() -> u32 {
+    let address = unsafe { libc::getauxval(libc::AT_RANDOM) };
+    if address == 0 {
+        return DEFAULT_VA_BITS;
+    }
+    (64 - address.leading_zeros()).clamp(32, 52)
+}
+
+/// Returns the size in bits of the user virtual address space.
+#[cfg(not(target_os = "linux"))]
+fn va_bits() -> u32 {
+    DEFAULT_VA_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_pac_with_va_bits() {
+        let address = 0x0000_aaaa_bbbb_cccc;
+        // PAC in bits 48~54 (top byte ignore enabled) or 48~63.
+        assert_eq!(strip_pac_with_va_bits(0x0035_aaaa_bbbb_cccc, 48), address);
+        assert_eq!(strip_pac_with_va_bits(0xc935_aaaa_bbbb_cccc, 48), address);
+        assert_eq!(strip_pac_with_va_bits(address, 48), address);
+        // A 39-bit address space leaves more room for the PAC.
+        assert_eq!(strip_pac_with_va_bits(0x3a5c_e97f_bbbb_cccc, 39), 0x007f_bbbb_cccc);
+    }
+
+    #[test]
+    fn test_strip_pac() {
+        let bits = va_bits();
+        assert!((32..=52).contains(&bits), "va bits: {}", bits);
+
+        // Unsigned addresses are left untouched.
+        let v = 1;
+        let address = &v as *const i32 as u64;
+        assert_eq!(strip_pac(address), address);
+        let address = test_strip_pac as fn() as usize as u64;
+        assert_eq!(strip_pac(address), address);
+        assert_eq!(strip_pac(address | (0x55u64 << bits)), address);
+    }
+}
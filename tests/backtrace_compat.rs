@@ -0,0 +1,74 @@
+#![cfg(all(feature = "backtrace-compat", target_os = "linux"))]
+
+use libc::c_void;
+use std::hint::black_box;
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+    ip: *mut c_void,
+    sp: *mut c_void,
+    symbol_address: *mut c_void,
+}
+
+#[inline(never)]
+fn outer() -> (Vec<Frame>, Vec<Frame>) {
+    let frames = inner();
+    black_box(frames)
+}
+
+#[inline(never)]
+fn inner() -> (Vec<Frame>, Vec<Frame>) {
+    let mut ours = vec![];
+    unwind::backtrace::trace(|frame| {
+        ours.push(Frame {
+            ip: frame.ip(),
+            sp: frame.sp(),
+            symbol_address: frame.symbol_address(),
+        });
+        true
+    });
+    let mut theirs = vec![];
+    backtrace::trace(|frame| {
+        theirs.push(Frame {
+            ip: frame.ip(),
+            sp: frame.sp(),
+            symbol_address: frame.symbol_address(),
+        });
+        true
+    });
+    black_box((ours, theirs))
+}
+
+#[test]
+fn test_equivalence() {
+    let (ours, theirs) = outer();
+    // The frames of the backtrace crate itself come first.
+    let inner = inner as fn() -> (Vec<Frame>, Vec<Frame>) as *mut c_void;
+    let start = theirs.iter().position(|f| f.symbol_address == inner).unwrap();
+    let theirs = &theirs[start..];
+    assert_eq!(ours[0].symbol_address, inner);
+    // Same function, different call sites.
+    assert_eq!(ours[0].sp, theirs[0].sp);
+    // Up to the outermost frames, which are left to the unwinders.
+    let n = theirs.len().min(ours.len()) - 1;
+    assert!(n > 3);
+    assert_eq!(ours[1..n], theirs[1..n]);
+}
+
+#[test]
+fn test_module_base_address() {
+    unwind::backtrace::trace(|frame| {
+        // The outermost frame returns to nowhere.
+        if frame.ip().is_null() {
+            return true;
+        }
+        let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+        assert_ne!(unsafe { libc::dladdr(frame.ip(), &mut info) }, 0);
+        let base = frame.module_base_address();
+        // Shared libraries aren't found without `trace-shared-libs`.
+        if base.is_some() || cfg!(feature = "trace-shared-libs") {
+            assert_eq!(base, Some(info.dli_fbase));
+        }
+        true
+    });
+}
@@ -0,0 +1,9 @@
+/* Built by tests/debuginfod.rs without unwind tables, so that only the
+   .debug_frame of its debug information, served by a stand-in debuginfod,
+   describes its functions. */
+
+__attribute__((noinline)) static int twice(int (*f)(void)) { return f() * 2; }
+
+int call(int (*f)(void)) { return twice(f) + 1; }
+
+void *twice_address(void) { return (void *)twice; }
@@ -0,0 +1,219 @@
+#![cfg(all(feature = "debuginfod", target_os = "linux"))]
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::hint::black_box;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use unwind::symbolize::debuginfod::Client;
+use unwind::symbolize::Symbolizer;
+
+/// A stand-in debuginfod serving files by the path of their URL, which
+/// records the paths asked for.
+struct Server {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    fn start(files: HashMap<String, PathBuf>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split(' ').nth(1).unwrap().to_string();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                recorded.lock().unwrap().push(path.clone());
+                match files.get(&path) {
+                    Some(file) => {
+                        let data = std::fs::read(file).unwrap();
+                        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len()).unwrap();
+                        stream.write_all(&data).unwrap();
+                    }
+                    None => stream
+                        .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                        .unwrap(),
+                }
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Builds tests/debuginfod.c into `dir`, returning the stripped object, the
+/// object before stripping and its debug information. The test needs cc,
+/// objcopy and strip, it fails without them.
+fn build_fixture(dir: &Path) -> (PathBuf, PathBuf, PathBuf) {
+    let source_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/debuginfod.c");
+    let lib = dir.join("libdebuginfod.so");
+    let status = Command::new("cc")
+        .args([
+            "-O1",
+            "-g",
+            "-shared",
+            "-fPIC",
+            "-fno-asynchronous-unwind-tables",
+            "-fno-unwind-tables",
+        ])
+        .args(["-Wl,--build-id", "-o"])
+        .arg(&lib)
+        .arg(&source_path)
+        .status()
+        .expect("can't run cc");
+    assert!(status.success());
+    let status = Command::new("sh")
+        .current_dir(dir)
+        .args([
+            "-c",
+            "cp \"$1\" unstripped && objcopy --only-keep-debug \"$1\" debug && strip --strip-all \"$1\"",
+        ])
+        .arg("sh")
+        .arg(&lib)
+        .status()
+        .unwrap();
+    assert!(status.success(), "missing objcopy or strip?");
+    (lib, dir.join("unstripped"), dir.join("debug"))
+}
+
+/// Returns the build-id of the ELF file at `path` in hex, as printed by
+/// `readelf`.
+fn build_id(path: &Path) -> Option<String> {
+    let output = Command::new("readelf").arg("-n").arg(path).output().ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    let line = output.lines().find_map(|line| line.trim().strip_prefix("Build ID: "))?;
+    Some(line.to_string())
+}
+
+type Call = extern "C" fn(extern "C" fn() -> libc::c_int) -> libc::c_int;
+
+static PCS: Mutex<Option<unwind::Result<Vec<u64>>>> = Mutex::new(None);
+
+extern "C" fn callback() -> libc::c_int {
+    let mut pcs = vec![];
+    let result = unwind::trace(|registers| {
+        pcs.push(registers.pc());
+        true
+    });
+    *PCS.lock().unwrap() = Some(result.map(|_| pcs));
+    21
+}
+
+/// Traces the stack from `callback`, called by `call` through `twice`.
+#[inline(never)]
+fn caller(call: Call) -> Vec<u64> {
+    assert_eq!(black_box(call)(callback), 43);
+    PCS.lock().unwrap().take().unwrap().unwrap()
+}
+
+#[test]
+fn test_debuginfod() {
+    let exe = std::env::current_exe().unwrap();
+    let dir = exe.parent().unwrap().join(format!("debuginfod-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (lib, unstripped, debug) = build_fixture(&dir);
+    let build_id = build_id(&unstripped).expect("can't read the build-id with readelf");
+    let server = Server::start(HashMap::from([
+        (format!("/buildid/{}/debuginfo", build_id), debug),
+        (format!("/buildid/{}/executable", build_id), unstripped.clone()),
+    ]));
+    let cache = dir.join("cache");
+    let client = Client::new([server.url.clone()], &cache);
+
+    let path = CString::new(lib.as_os_str().as_bytes()).unwrap();
+    let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW) };
+    assert!(!handle.is_null());
+    let call = unsafe { libc::dlsym(handle, c"call".as_ptr()) };
+    let twice_address = unsafe { libc::dlsym(handle, c"twice_address".as_ptr()) };
+    assert!(!call.is_null() && !twice_address.is_null());
+    let call: Call = unsafe { std::mem::transmute(call) };
+    let twice_address: extern "C" fn() -> usize = unsafe { std::mem::transmute(twice_address) };
+    let twice = twice_address() as u64;
+
+    // The static function is only in the symbol table of the debug
+    // information. The other objects without any ask for theirs too.
+    let symbolizer = Symbolizer::with_debuginfod(&client);
+    let module = symbolizer.modules().find(|m| m.contains(twice)).unwrap();
+    let module_build_id: String = module
+        .build_id()
+        .unwrap()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(module_build_id, build_id);
+    let symbol = symbolizer.resolve(twice + 1).unwrap();
+    assert_eq!(symbol.name(), Some("twice"));
+    assert_eq!(symbol.address(), twice);
+    let frames = symbolizer.frames(twice + 1);
+    assert_eq!(frames.last().unwrap().name(), Some("twice"));
+    assert!(frames[0].file().unwrap().ends_with("tests/debuginfod.c"));
+    let requests = server.requests();
+    assert!(requests.contains(&format!("/buildid/{}/debuginfo", build_id)));
+    assert!(!requests.iter().any(|path| path.ends_with("/executable")));
+    assert!(cache.join(&build_id).join("debuginfo").is_file());
+
+    // Without unwind tables, the trace stops in the object.
+    // The outermost frame has a zero PC.
+    let names = |pcs: &[u64]| -> Vec<String> {
+        pcs.iter()
+            .filter_map(|&pc| symbolizer.resolve(pc.checked_sub(1)?))
+            .map(|symbol| symbol.demangled_name(true))
+            .collect()
+    };
+    let before = names(&caller(call));
+    assert_eq!(before.last().map(String::as_str), Some("twice"));
+    assert!(symbolizer.register_debug_frames() >= 1);
+    let after = names(&caller(call));
+    let twice_index = after.iter().position(|name| name == "twice").unwrap();
+    assert_eq!(after[twice_index + 1], "call");
+    assert!(after[twice_index + 2].ends_with("::caller"), "{:?}", after);
+
+    // Until the object is unloaded.
+    assert!(symbolizer.deregister_debug_frames() >= 1);
+    assert_eq!(names(&caller(call)), before);
+
+    // Servers are only asked when told to.
+    std::env::set_var("DEBUGINFOD_URLS", &server.url);
+    let symbolizer = Symbolizer::new();
+    std::env::remove_var("DEBUGINFOD_URLS");
+    assert_ne!(symbolizer.resolve(twice + 1).and_then(|s| s.name()), Some("twice"));
+    assert_eq!(server.requests(), requests);
+
+    // Found in the cache afterwards.
+    let offline = Client::new(["http://127.0.0.1:1"], &cache);
+    let symbolizer = Symbolizer::with_debuginfod(&offline);
+    assert_eq!(symbolizer.resolve(twice + 1).unwrap().name(), Some("twice"));
+    assert_eq!(server.requests(), requests);
+
+    let path = client.executable(module.build_id().unwrap()).unwrap();
+    assert_eq!(std::fs::read(path).unwrap(), std::fs::read(&unstripped).unwrap());
+    assert_eq!(server.requests().len(), requests.len() + 1);
+
+    // Misses are remembered.
+    assert!(client.debuginfo(&[0xde, 0xad, 0xbe, 0xef]).is_err());
+    assert!(client.debuginfo(&[0xde, 0xad, 0xbe, 0xef]).is_err());
+    assert_eq!(server.requests().len(), requests.len() + 2);
+    assert!(offline.debuginfo(&[0xca, 0xfe]).is_err());
+
+    unsafe { libc::dlclose(handle) };
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,61 @@
+#![cfg(all(feature = "symbolize", target_os = "linux"))]
+
+use unwind::symbolize::demangle;
+
+#[test]
+fn test_corpus() {
+    let corpus = include_str!("demangle.txt");
+    let mut demangled = 0;
+    for line in corpus.lines().filter(|line| !line.starts_with('#')) {
+        let mut fields = line.split('\t');
+        let (mangled, expected, stripped) = (fields.next().unwrap(), fields.next().unwrap(), fields.next().unwrap());
+        assert_eq!(
+            demangle(mangled.as_bytes(), false).as_deref(),
+            Some(expected),
+            "{}",
+            mangled
+        );
+        assert_eq!(
+            demangle(mangled.as_bytes(), true).as_deref(),
+            Some(stripped),
+            "{}",
+            mangled
+        );
+        demangled += 1;
+    }
+    assert!(demangled > 500);
+}
+
+#[test]
+fn test_not_mangled() {
+    for name in [
+        "main",
+        "_start",
+        "__libc_start_main",
+        "_Z",
+        "_ZN",
+        "_R",
+        "_ZN3fooE.cold\u{0}",
+        "_RNvC",
+    ] {
+        assert_eq!(demangle(name.as_bytes(), false), None, "{}", name);
+    }
+    assert_eq!(demangle(b"_ZN3foo\xff3barE", false), None);
+}
+
+#[test]
+fn test_suffixes() {
+    let name = "_ZN4core3fmt5write17h0123456789abcdefE";
+    assert_eq!(
+        demangle(format!("{}.llvm.1234ABCD", name).as_bytes(), true).unwrap(),
+        "core::fmt::write"
+    );
+    assert_eq!(
+        demangle(format!("{}.cold.1", name).as_bytes(), false).unwrap(),
+        "core::fmt::write::h0123456789abcdef.cold.1"
+    );
+    assert_eq!(
+        demangle(b"_ZNK3foo3barEi.cold", false).unwrap(),
+        "foo::bar(int) const [clone .cold]"
+    );
+}
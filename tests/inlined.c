@@ -0,0 +1,9 @@
+/* Built by tests/symbolize.rs with various DWARF options. */
+
+__attribute__((noinline, noipa)) void *where(void) { return __builtin_return_address(0); }
+
+static inline __attribute__((always_inline)) void *inner(void) { return where(); }
+
+static inline __attribute__((always_inline)) void *middle(void) { return inner(); }
+
+void *outer(void) { return middle(); }
@@ -0,0 +1,160 @@
+#![cfg(all(
+    feature = "itanium-abi",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+
+use libc::{c_int, c_void};
+use std::cell::Cell;
+use std::hint::black_box;
+use std::panic::{catch_unwind, resume_unwind};
+use std::process::Command;
+use unwind::itanium::{_Unwind_Exception, _Unwind_Reason_Code};
+
+thread_local! {
+    static DROPS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        DROPS.with(|drops| drops.set(drops.get() + 1));
+    }
+}
+
+fn drops() -> usize {
+    DROPS.with(|drops| drops.replace(0))
+}
+
+#[test]
+fn test_linked() {
+    // What the panic runtime of std resolves to.
+    extern "C" {
+        fn _Unwind_RaiseException(exception_object: *mut _Unwind_Exception) -> _Unwind_Reason_Code;
+    }
+    assert_eq!(
+        _Unwind_RaiseException as *const () as usize,
+        unwind::itanium::_Unwind_RaiseException as *const () as usize
+    );
+}
+
+#[test]
+fn test_panic() {
+    drops();
+    let payload = catch_unwind(outer).unwrap_err();
+    assert_eq!(payload.downcast_ref::<i32>(), Some(&42));
+    assert_eq!(drops(), 2);
+}
+
+#[test]
+fn test_rethrow() {
+    drops();
+    let payload = catch_unwind(|| {
+        let _guard = Guard;
+        let payload = catch_unwind(outer).unwrap_err();
+        resume_unwind(payload);
+    })
+    .unwrap_err();
+    assert_eq!(payload.downcast_ref::<i32>(), Some(&42));
+    assert_eq!(drops(), 3);
+}
+
+#[inline(never)]
+fn outer() {
+    let _guard = Guard;
+    inner();
+    black_box(());
+}
+
+#[inline(never)]
+fn inner() {
+    let _guard = Guard;
+    if black_box(true) {
+        // Unlike `panic!`, doesn't call the panic hook.
+        resume_unwind(Box::new(42i32));
+    }
+}
+
+const CXX_SOURCE: &str = r#"
+#include <unwind.h>
+
+static int destructors;
+
+struct Guard {
+    ~Guard() { destructors++; }
+};
+
+extern "C" void *cxx_raise_exception() {
+    return (void *)&_Unwind_RaiseException;
+}
+
+extern "C" void cxx_throw(int v) {
+    Guard guard;
+    throw v;
+}
+
+extern "C" int cxx_catch(void (*f)(void (*)(int), int), int v) {
+    try {
+        Guard guard;
+        f(cxx_throw, v);
+    } catch (int e) {
+        return e + destructors;
+    }
+    return -1;
+}
+"#;
+
+/// Builds `CXX_SOURCE` into a shared library and loads it. The test needs a
+/// C++ compiler, it fails without one.
+fn load_cxx_library() -> *mut c_void {
+    let dir = std::env::temp_dir().join(format!("unwind-itanium-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("throw.cc");
+    let library = dir.join("libthrow.so");
+    std::fs::write(&source, CXX_SOURCE).unwrap();
+    let status = Command::new("c++")
+        .args(["-shared", "-fPIC", "-O1", "-o"])
+        .arg(&library)
+        .arg(&source)
+        .status()
+        .expect("can't run c++");
+    assert!(status.success());
+    let path = std::ffi::CString::new(library.to_str().unwrap()).unwrap();
+    let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW) };
+    assert!(!handle.is_null());
+    std::fs::remove_dir_all(&dir).unwrap();
+    handle
+}
+
+unsafe fn symbol(handle: *mut c_void, name: &str) -> *mut c_void {
+    let name = std::ffi::CString::new(name).unwrap();
+    let symbol = libc::dlsym(handle, name.as_ptr());
+    assert!(!symbol.is_null());
+    symbol
+}
+
+/// Called by C++, throws a C++ exception through this Rust frame.
+extern "C-unwind" fn call_cxx_throw(throw: extern "C-unwind" fn(c_int), v: c_int) {
+    let _guard = Guard;
+    throw(v);
+    black_box(());
+}
+
+#[test]
+fn test_cxx_exception() {
+    let handle = load_cxx_library();
+    unsafe {
+        // The executable is linked with -rdynamic, its definitions are the
+        // ones libstdc++ uses.
+        let raise: extern "C" fn() -> *mut c_void = std::mem::transmute(symbol(handle, "cxx_raise_exception"));
+        assert_eq!(raise(), unwind::itanium::_Unwind_RaiseException as *mut c_void);
+
+        type Callback = extern "C-unwind" fn(extern "C-unwind" fn(c_int), c_int);
+        let catch: extern "C-unwind" fn(Callback, c_int) -> c_int = std::mem::transmute(symbol(handle, "cxx_catch"));
+        drops();
+        // Both C++ destructors ran.
+        assert_eq!(catch(call_cxx_throw, 40), 42);
+        assert_eq!(drops(), 1);
+    }
+}
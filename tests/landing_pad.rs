@@ -0,0 +1,46 @@
+#![cfg(all(target_os = "linux", not(target_arch = "arm")))]
+
+use std::hint::black_box;
+use std::panic::catch_unwind;
+use unwind::{unwind_init_registers, LandingPad, Registers, UnwindCursor};
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        black_box(self);
+    }
+}
+
+#[test]
+fn test_landing_pad() {
+    let landing_pads = catch_unwind(with_guard).unwrap();
+    // The first frame is `landing_pads` itself.
+    assert_eq!(landing_pads[0], None);
+    let cleanup = landing_pads[1].unwrap();
+    assert!(cleanup.cleanup && !cleanup.catch);
+    // `catch_unwind` catches everything, inlined or not.
+    assert!(landing_pads[2..].iter().flatten().any(|landing_pad| landing_pad.catch));
+}
+
+#[inline(never)]
+fn with_guard() -> Vec<Option<LandingPad>> {
+    let _guard = Guard;
+    let landing_pads = landing_pads();
+    black_box(landing_pads)
+}
+
+#[inline(never)]
+fn landing_pads() -> Vec<Option<LandingPad>> {
+    let mut registers = Registers::default();
+    unsafe { unwind_init_registers(&mut registers as _) };
+    let mut cursor = UnwindCursor::uncached();
+    let mut landing_pads = vec![];
+    loop {
+        landing_pads.push(cursor.landing_pad(&registers).unwrap());
+        if !cursor.step(&mut registers).unwrap() {
+            break;
+        }
+    }
+    landing_pads
+}
@@ -0,0 +1,6 @@
+/* Built by tests/symbolize.rs, stripped with the symbols of its local
+   functions kept in MiniDebugInfo. */
+
+__attribute__((noinline)) static int local(int x) { return x * 3 + 1; }
+
+void *local_address(void) { return (void *)local; }
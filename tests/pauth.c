@@ -0,0 +1,12 @@
+/* Built by build.rs with -mbranch-protection=standard on aarch64, so that
+   these functions sign their return address before saving it, and their CFI
+   marks it as signed with DW_CFA_AARCH64_negate_ra_state. */
+
+__attribute__((noinline)) static int pauth_twice(int (*f)(void)) { return f() * 2; }
+
+int pauth_call(int (*f)(void)) { return pauth_twice(f) + 1; }
+
+/* Signed with the B key, which the augmentation of its CIE records. */
+__attribute__((target("branch-protection=pac-ret+b-key"))) int pauth_call_b_key(int (*f)(void)) {
+    return pauth_twice(f) + 2;
+}
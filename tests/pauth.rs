@@ -0,0 +1,62 @@
+#![cfg(all(target_arch = "aarch64", target_os = "linux"))]
+
+use std::cell::RefCell;
+
+type Call = unsafe extern "C" fn(extern "C" fn() -> libc::c_int) -> libc::c_int;
+
+extern "C" {
+    // Call `f` through a static function, all built from tests/pauth.c with
+    // pointer authentication.
+    fn pauth_call(f: extern "C" fn() -> libc::c_int) -> libc::c_int;
+    fn pauth_call_b_key(f: extern "C" fn() -> libc::c_int) -> libc::c_int;
+}
+
+thread_local! {
+    static PCS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+extern "C" fn callback() -> libc::c_int {
+    let mut pcs = vec![];
+    unwind::trace(|registers| {
+        pcs.push(registers.pc());
+        true
+    })
+    .unwrap();
+    PCS.set(pcs);
+    21
+}
+
+/// Returns the names of the first frames traced from `callback`, called
+/// through `call`.
+#[inline(never)]
+fn trace_through(call: Call) -> Vec<String> {
+    unsafe { call(callback) };
+    let pcs = PCS.take();
+    // The authentication codes are stripped from the return addresses.
+    assert!(pcs.iter().all(|&pc| pc < 1 << 48));
+    let mut names = vec![];
+    for &pc in &pcs[..4] {
+        backtrace::resolve(pc as _, |s| {
+            names.push(s.name().unwrap().as_str().unwrap().to_string());
+        });
+    }
+    names
+}
+
+#[test]
+fn test_unwind_signed_frames() {
+    let names = trace_through(pauth_call);
+    assert!(names[0].contains("callback"));
+    assert!(names[1].contains("pauth_twice"));
+    assert!(names[2].contains("pauth_call"));
+    // Unwound through both signed frames back to Rust.
+    assert!(names[3].contains("trace_through"));
+}
+
+#[test]
+fn test_unwind_b_key_frames() {
+    let names = trace_through(pauth_call_b_key);
+    assert!(names[1].contains("pauth_twice"));
+    assert!(names[2].contains("pauth_call_b_key"));
+    assert!(names[3].contains("trace_through"));
+}
@@ -0,0 +1,90 @@
+#![cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+
+use std::hint::black_box;
+use unwind::{unwind_init_registers, RegisterFile, Registers, UnwindCursor};
+
+#[cfg(target_arch = "x86_64")]
+const RETURN_VALUE: usize = unwind::arch::UNW_X86_64_RAX;
+#[cfg(target_arch = "aarch64")]
+const RETURN_VALUE: usize = unwind::arch::UNW_ARM64_X0;
+
+#[test]
+fn test_resume() {
+    assert_eq!(grandparent(), 42);
+}
+
+#[test]
+fn test_resume_restores_callee_saved_registers() {
+    // The values are live across the call, in callee-saved registers or on
+    // the stack.
+    let a = black_box(7u64);
+    let f = black_box(1.5f64);
+    let r = grandparent();
+    assert_eq!(black_box(a) * r, 7 * 42);
+    assert_eq!(black_box(f) * r as f64, 63.0);
+}
+
+#[inline(never)]
+fn grandparent() -> u64 {
+    let v = parent();
+    black_box(v)
+}
+
+#[inline(never)]
+fn parent() -> u64 {
+    black_box(child()) + 1
+}
+
+#[inline(never)]
+fn child() -> u64 {
+    clobber_callee_saved_registers();
+    let mut registers = Registers::default();
+    unsafe { unwind_init_registers(&mut registers as _) };
+    let mut cursor = UnwindCursor::new();
+    // child -> parent -> grandparent
+    assert!(cursor.step(&mut registers).unwrap());
+    assert!(cursor.step(&mut registers).unwrap());
+    registers.set(RETURN_VALUE, 42).unwrap();
+    // Otherwise the compiler knows the callers never return, and drops
+    // whatever follows the calls.
+    if black_box(true) {
+        unsafe { registers.resume() }
+    }
+    0
+}
+
+/// Makes the caller save and clobber callee-saved registers, so that they
+/// only get their values back by unwinding.
+#[inline(always)]
+fn clobber_callee_saved_registers() {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        std::arch::asm!(
+            "mov r12, -1",
+            "mov r13, -1",
+            "mov r14, -1",
+            "mov r15, -1",
+            out("r12") _,
+            out("r13") _,
+            out("r14") _,
+            out("r15") _,
+        );
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        std::arch::asm!(
+            "mov x20, #-1",
+            "mov x21, #-1",
+            "mov x22, #-1",
+            "mov x23, #-1",
+            "fmov d8, xzr",
+            "fmov d9, xzr",
+            out("x20") _,
+            out("x21") _,
+            out("x22") _,
+            out("x23") _,
+            out("d8") _,
+            out("d9") _,
+        );
+    }
+}
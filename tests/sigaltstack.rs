@@ -0,0 +1,106 @@
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Unoptimized code needs about three times more stack, see the
+/// documentation of `trace_from_ucontext`.
+const SCALE: usize = if cfg!(debug_assertions) { 3 } else { 1 };
+const ALT_STACK_SIZE: usize = 16 * 1024 * SCALE;
+/// The bound on the stack `trace_from_ucontext` uses documented in the
+/// README and in src/lib.rs.
+const MAX_TRACE_USAGE: usize = 6 * 1024 * SCALE;
+const GUARD_SIZE: usize = 4096;
+const MAX_STACK_DEPTH: usize = 64;
+const PATTERN: u8 = 0xa5;
+
+static FRAMES: AtomicUsize = AtomicUsize::new(0);
+/// The stack pointer of the signal handler, below the signal frame.
+static HANDLER_SP: AtomicUsize = AtomicUsize::new(0);
+static TRACED: AtomicBool = AtomicBool::new(false);
+static DONE: AtomicBool = AtomicBool::new(false);
+
+#[test]
+fn test_unwind_on_sigaltstack() {
+    // Resolve the loaded objects outside of the signal handler.
+    unwind::trace(|_| true).unwrap();
+
+    unsafe {
+        // Overflowing the alternate stack hits the guard page below it.
+        let mapping = libc::mmap(
+            std::ptr::null_mut(),
+            GUARD_SIZE + ALT_STACK_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(mapping, libc::MAP_FAILED);
+        assert_eq!(libc::mprotect(mapping, GUARD_SIZE, libc::PROT_NONE), 0);
+        let stack = std::slice::from_raw_parts_mut((mapping as *mut u8).add(GUARD_SIZE), ALT_STACK_SIZE);
+        stack.fill(PATTERN);
+
+        let ss = libc::stack_t {
+            ss_sp: stack.as_mut_ptr() as _,
+            ss_flags: 0,
+            ss_size: ALT_STACK_SIZE,
+        };
+        assert_eq!(libc::sigaltstack(&ss, std::ptr::null_mut()), 0);
+        let mut sa: libc::sigaction = std::mem::zeroed();
+        sa.sa_sigaction = signal_handler as *const () as usize;
+        sa.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        assert_eq!(libc::sigaction(libc::SIGPROF, &sa, std::ptr::null_mut()), 0);
+
+        // Interrupt a busy loop, so that the signal is delivered while the
+        // thread runs our own code.
+        let timer = libc::itimerval {
+            it_interval: libc::timeval { tv_sec: 0, tv_usec: 0 },
+            it_value: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 10000,
+            },
+        };
+        assert_eq!(libc::setitimer(libc::ITIMER_PROF, &timer, std::ptr::null_mut()), 0);
+        let mut n = 0u64;
+        while !DONE.load(Ordering::SeqCst) {
+            n = black_box(n.wrapping_add(1));
+        }
+
+        let ss = libc::stack_t {
+            ss_sp: std::ptr::null_mut(),
+            ss_flags: libc::SS_DISABLE,
+            ss_size: 0,
+        };
+        assert_eq!(libc::sigaltstack(&ss, std::ptr::null_mut()), 0);
+
+        assert!(TRACED.load(Ordering::SeqCst));
+        assert!(FRAMES.load(Ordering::SeqCst) > 2);
+
+        // The stack grows downwards, so the untouched pattern is at the bottom.
+        // This includes the signal frame pushed by the kernel.
+        let unused = stack.iter().take_while(|&&b| b == PATTERN).count();
+        let lowest = stack.as_ptr() as usize + unused;
+        let handler_sp = HANDLER_SP.load(Ordering::SeqCst);
+        assert!(stack.as_ptr_range().contains(&(handler_sp as *const u8)));
+        let trace_usage = handler_sp - lowest;
+        eprintln!(
+            "sigaltstack usage: {} bytes, {} bytes by the trace",
+            ALT_STACK_SIZE - unused,
+            trace_usage
+        );
+        assert!(trace_usage <= MAX_TRACE_USAGE, "{} bytes", trace_usage);
+        libc::munmap(mapping, GUARD_SIZE + ALT_STACK_SIZE);
+    }
+}
+
+extern "C" fn signal_handler(_: libc::c_int, _: *mut libc::siginfo_t, ucontext: *mut libc::c_void) {
+    let mut pcs = [0u64; MAX_STACK_DEPTH];
+    HANDLER_SP.store(black_box(pcs.as_ptr()) as usize, Ordering::SeqCst);
+    let mut n = 0;
+    let result = unwind::trace_from_ucontext(ucontext, |registers| {
+        pcs[n] = registers.pc();
+        n += 1;
+        n < MAX_STACK_DEPTH
+    });
+    TRACED.store(result.is_ok(), Ordering::SeqCst);
+    FRAMES.store(n, Ordering::SeqCst);
+    DONE.store(true, Ordering::SeqCst);
+}
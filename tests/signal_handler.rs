@@ -39,10 +39,10 @@ fn test_unwind_in_signal_handler() {
 fn frequency(v: i64) -> libc::itimerval {
     let interval = 1e6 as i64 / v;
     let it_interval = libc::timeval {
-        tv_sec: interval / 1e6 as i64,
+        tv_sec: (interval / 1e6 as i64) as _,
         tv_usec: (interval % 1e6 as i64) as _,
     };
-    let it_value = it_interval.clone();
+    let it_value = it_interval;
     libc::itimerval { it_interval, it_value }
 }
 
@@ -58,6 +58,6 @@ extern "C" fn perf_signal_handler(_: libc::c_int, _: *mut libc::siginfo_t, ucont
         true
     })
     .unwrap();
-    assert!(pcs.len() > 0);
+    assert!(!pcs.is_empty());
     SAMPLE_COUNT.fetch_add(1, Ordering::SeqCst);
 }
@@ -0,0 +1,402 @@
+#![cfg(all(feature = "symbolize", target_os = "linux"))]
+
+use std::ffi::CString;
+use std::hint::black_box;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use unwind::symbolize::{self, DebugInfo, ModuleMap, NormalizedAddress, Symbolizer};
+
+#[inline(never)]
+fn outer() -> Vec<u64> {
+    black_box(inner())
+}
+
+#[inline(never)]
+fn inner() -> Vec<u64> {
+    let mut pcs = vec![];
+    unwind::trace(|registers| {
+        pcs.push(registers.pc());
+        true
+    })
+    .unwrap();
+    black_box(pcs)
+}
+
+/// Returns the name of the outermost function `backtrace::resolve` finds at
+/// `address`, the one the symbol tables have.
+fn backtrace_name(address: u64) -> Option<Vec<u8>> {
+    let mut name = None;
+    backtrace::resolve(address as usize as _, |symbol| {
+        name = symbol.name().map(|name| name.as_bytes().to_vec());
+    });
+    name
+}
+
+#[test]
+fn test_trace() {
+    let symbolizer = Symbolizer::new();
+    let mut resolved = 0;
+    // The outermost frame returns to nowhere.
+    for pc in outer().into_iter().filter(|&pc| pc != 0) {
+        let Some(symbol) = symbolizer.resolve(pc - 1) else {
+            continue;
+        };
+        // Only the test binary has debug information.
+        if symbol.module().path() != Path::new("/proc/self/exe") {
+            continue;
+        }
+        assert_eq!(Some(symbol.name_bytes().to_vec()), backtrace_name(pc), "{:#x}", pc);
+        let mut demangled = None;
+        backtrace::resolve(pc as usize as _, |s| demangled = s.name().map(|name| name.to_string()));
+        assert_eq!(Some(symbol.demangled_name(false)), demangled, "{:#x}", pc);
+        assert_eq!(symbol.offset(), pc - 1 - symbol.address());
+        resolved += 1;
+    }
+    // outer, inner and the test.
+    assert!(resolved >= 3);
+}
+
+/// The functions and source locations `backtrace::resolve` finds at
+/// `address`, from debug information.
+type BacktraceFrame = (Option<Vec<u8>>, Option<PathBuf>, Option<u32>, Option<u32>);
+
+fn backtrace_frames(address: u64) -> Vec<BacktraceFrame> {
+    let mut frames = vec![];
+    backtrace::resolve(address as usize as _, |symbol| {
+        frames.push((
+            symbol.name().map(|name| name.as_bytes().to_vec()),
+            symbol.filename().map(Path::to_path_buf),
+            symbol.lineno(),
+            symbol.colno(),
+        ));
+    });
+    frames
+}
+
+#[test]
+fn test_frames() {
+    // Test binaries of the release profile have no debug information.
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let symbolizer = Symbolizer::new();
+    let mut inlined = 0;
+    for pc in outer().into_iter().filter(|&pc| pc != 0) {
+        let frames = symbolizer.frames(pc - 1);
+        let theirs = backtrace_frames(pc);
+        if theirs.iter().all(|(_, file, _, _)| file.is_none()) {
+            continue;
+        }
+        let ours: Vec<BacktraceFrame> = frames
+            .iter()
+            .map(|f| {
+                (
+                    f.name_bytes().map(<[u8]>::to_vec),
+                    f.file().map(Path::to_path_buf),
+                    f.line(),
+                    f.column(),
+                )
+            })
+            .collect();
+        assert_eq!(ours, theirs, "{:#x}", pc);
+        inlined += frames.len() - 1;
+    }
+    // The standard library inlines into the outermost frames.
+    assert!(inlined > 0);
+}
+
+#[test]
+fn test_functions() {
+    let symbolizer = Symbolizer::new();
+    for address in [
+        outer as fn() -> Vec<u64> as usize,
+        inner as fn() -> Vec<u64> as usize,
+        backtrace_name as fn(u64) -> Option<Vec<u8>> as usize,
+    ] {
+        let address = address as u64;
+        let symbol = symbolizer.resolve(address).unwrap();
+        assert_eq!(symbol.address(), address);
+        assert_eq!(Some(symbol.name_bytes().to_vec()), backtrace_name(address + 1));
+        symbolize::resolve(address + 1, |s| {
+            assert_eq!(s.name_bytes(), symbol.name_bytes());
+            assert_eq!(s.offset(), 1);
+        });
+    }
+}
+
+#[test]
+fn test_shared_library() {
+    let symbolizer = Symbolizer::new();
+    for address in [libc::getpid as *const () as usize, libc::malloc as *const () as usize] {
+        let address = address as u64;
+        let symbol = symbolizer.resolve(address).unwrap();
+        assert_eq!(symbol.address(), address);
+        assert!(symbol.module().path().to_str().unwrap().contains("libc"));
+        // Either name of aliases, e.g. getpid and __getpid.
+        let name = CString::new(symbol.name_bytes()).unwrap();
+        let found = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+        assert!(found.is_null() || found as u64 == address, "{:?}", name);
+    }
+}
+
+/// Set in the process `test_module_map` runs the test in again.
+const MODULE_MAP_CHILD: &str = "UNWIND_TEST_MODULE_MAP_CHILD";
+
+/// Returns the frames of `inner`, `outer` and this function, made relative
+/// to their objects.
+#[inline(never)]
+fn normalized_stack(map: &ModuleMap) -> Vec<NormalizedAddress> {
+    outer()[..3].iter().map(|&pc| map.normalize(pc - 1).unwrap()).collect()
+}
+
+#[test]
+fn test_module_map() {
+    let map = ModuleMap::new();
+    let stack = normalized_stack(&map);
+    if std::env::var_os(MODULE_MAP_CHILD).is_some() {
+        let hex: String = map.encode().iter().map(|b| format!("{:02x}", b)).collect();
+        // Out of the way of the output of the test harness.
+        eprintln!("map {}", hex);
+        for a in &stack {
+            eprintln!("address {} {} {}", a.module, a.address, a.file_offset);
+        }
+        return;
+    }
+
+    // The same stack, taken in another process.
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "test_module_map", "--nocapture", "--test-threads=1"])
+        .env(MODULE_MAP_CHILD, "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let hex = stderr.lines().find_map(|line| line.strip_prefix("map ")).unwrap();
+    let data: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect();
+    let theirs = ModuleMap::decode(&data).unwrap();
+    let their_stack: Vec<NormalizedAddress> = stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("address "))
+        .map(|line| {
+            let fields: Vec<_> = line.split(' ').map(|field| field.parse().unwrap()).collect();
+            NormalizedAddress {
+                module: fields[0] as usize,
+                address: fields[1],
+                file_offset: fields[2],
+            }
+        })
+        .collect();
+    assert_eq!(theirs.fingerprint(&their_stack), map.fingerprint(&stack));
+    assert_ne!(map.fingerprint(&stack[..2]), map.fingerprint(&stack));
+
+    // Symbolized from the files of their objects only.
+    let names: Vec<String> = their_stack
+        .iter()
+        .map(|address| {
+            let module = theirs.module(address).unwrap();
+            let table = symbolize::SymbolTable::open(module.path()).unwrap();
+            let (name, _, _) = table.lookup(address.address).unwrap();
+            symbolize::demangle(name, true).unwrap()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        ["symbolize::inner", "symbolize::outer", "symbolize::normalized_stack"]
+    );
+}
+
+/// Builds of tests/inlined.c: names, compilers, compiler flags, and the tool
+/// packing the .dwo files into a .dwp file, if any.
+const VARIANTS: [(&str, &str, &[&str], Option<&str>); 6] = [
+    ("dwarf4", "cc", &["-gdwarf-4"], None),
+    // Code in several sections gives units range lists.
+    ("dwarf5", "cc", &["-gdwarf-5", "-ffunction-sections"], None),
+    (
+        "split4",
+        "cc",
+        &["-gdwarf-4", "-gsplit-dwarf", "-ffunction-sections"],
+        None,
+    ),
+    ("split5", "cc", &["-gdwarf-5", "-gsplit-dwarf"], None),
+    ("package4", "cc", &["-gdwarf-4", "-gsplit-dwarf"], Some("dwp")),
+    // The dwp tools can't pack the version 5 .dwo files of gcc 12, but
+    // llvm-dwp packs those of clang.
+    ("package5", "clang", &["-gdwarf-5", "-gsplit-dwarf"], Some("llvm-dwp")),
+];
+
+/// Returns the line of `source` where `callee` is called, and the column of
+/// the call.
+fn call_site(source: &str, callee: &str) -> (u32, u32) {
+    let call = format!("return {}();", callee);
+    let (line, text) = source
+        .lines()
+        .enumerate()
+        .find(|(_, text)| text.contains(&call))
+        .unwrap();
+    (
+        line as u32 + 1,
+        (text.find(&call).unwrap() + "return ".len()) as u32 + 1,
+    )
+}
+
+#[test]
+fn test_inlined() {
+    let source_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/inlined.c");
+    let source = std::fs::read_to_string(&source_path).unwrap();
+    let exe = std::env::current_exe().unwrap();
+    // CI installs all the tools, elsewhere variants may be skipped.
+    let may_skip = std::env::var_os("CI").is_none();
+    let mut ran = vec![];
+    for (name, compiler, flags, packer) in VARIANTS {
+        let dir = exe
+            .parent()
+            .unwrap()
+            .join(format!("inlined-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lib = dir.join(format!("libinlined-{}.so", name));
+        let status = Command::new(compiler)
+            .current_dir(&dir)
+            .args(["-O1", "-shared", "-fPIC", "-o"])
+            .arg(&lib)
+            .args(flags)
+            .arg(&source_path)
+            .status();
+        match status {
+            Ok(status) => assert!(status.success()),
+            Err(err) => {
+                assert!(may_skip, "can't run {}: {}", compiler, err);
+                eprintln!("skipped {}, can't run {}: {}", name, compiler, err);
+                std::fs::remove_dir_all(&dir).unwrap();
+                continue;
+            }
+        }
+        if let Some(packer) = packer {
+            let mut dwp = lib.clone().into_os_string();
+            dwp.push(".dwp");
+            match Command::new(packer).arg("-e").arg(&lib).arg("-o").arg(&dwp).status() {
+                Ok(status) => assert!(status.success()),
+                Err(err) => {
+                    assert!(may_skip, "can't run {}: {}", packer, err);
+                    eprintln!("skipped {}, can't run {}: {}", name, packer, err);
+                    std::fs::remove_dir_all(&dir).unwrap();
+                    continue;
+                }
+            }
+            // Only the package is left.
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension() == Some("dwo".as_ref()) {
+                    std::fs::remove_file(path).unwrap();
+                }
+            }
+        }
+
+        let path = CString::new(lib.as_os_str().as_bytes()).unwrap();
+        let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW) };
+        assert!(!handle.is_null());
+        let outer = unsafe { libc::dlsym(handle, c"outer".as_ptr()) };
+        assert!(!outer.is_null());
+        let outer: extern "C" fn() -> usize = unsafe { std::mem::transmute(outer) };
+        // In `inner`, inlined into `middle`, inlined into `outer`.
+        let pc = outer() as u64 - 1;
+
+        let symbolizer = Symbolizer::new();
+        let frames = symbolizer.frames(pc);
+        let names: Vec<_> = frames.iter().map(|frame| frame.name().unwrap()).collect();
+        assert_eq!(names, ["inner", "middle", "outer"], "{}", name);
+        assert!(
+            frames.iter().all(|frame| frame.file() == Some(&source_path)),
+            "{}",
+            name
+        );
+        assert_eq!(frames[0].line(), Some(call_site(&source, "where").0), "{}", name);
+        assert!(frames[0].column().is_some());
+        for (frame, callee) in frames[1..].iter().zip(["inner", "middle"]) {
+            let (line, column) = call_site(&source, callee);
+            assert_eq!((frame.line(), frame.column()), (Some(line), Some(column)), "{}", name);
+        }
+        let module = symbolizer.modules().find(|module| module.contains(pc)).unwrap();
+        let debug_info = DebugInfo::open(&lib).unwrap();
+        assert_eq!(debug_info.frames(pc - module.base()).unwrap(), frames, "{}", name);
+
+        unsafe { libc::dlclose(handle) };
+        std::fs::remove_dir_all(&dir).unwrap();
+        ran.push((flags, packer));
+    }
+    // At least split DWARF in .dwo files and in a package.
+    assert!(ran
+        .iter()
+        .any(|(flags, packer)| flags.contains(&"-gsplit-dwarf") && packer.is_none()));
+    assert!(ran.iter().any(|(_, packer)| packer.is_some()));
+}
+
+/// The recipe of GDB's manual ("MiniDebugInfo") moving the symbols of local
+/// functions of a stripped object to a compressed .gnu_debugdata section.
+const MINI_DEBUG_INFO_RECIPE: &str = r#"
+set -e
+nm -D "$1" --format=posix --defined-only | awk '{ print $1 }' | sort > dynsyms
+nm "$1" --format=posix --defined-only | awk '{ if ($2 == "T" || $2 == "t" || $2 == "D") print $1 }' | sort > funcsyms
+comm -13 dynsyms funcsyms > keep_symbols
+objcopy --only-keep-debug "$1" debug
+objcopy -S --remove-section .gdb_index --remove-section .comment --keep-symbols=keep_symbols debug mini_debuginfo
+strip --strip-all -R .comment "$1"
+xz mini_debuginfo
+objcopy --add-section .gnu_debugdata=mini_debuginfo.xz "$1"
+"#;
+
+#[test]
+fn test_mini_debug_info() {
+    let source_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/minidebuginfo.c");
+    let exe = std::env::current_exe().unwrap();
+    let dir = exe
+        .parent()
+        .unwrap()
+        .join(format!("minidebuginfo-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join("libminidebuginfo.so");
+    // The test needs cc, nm, objcopy, strip and xz, it fails without them.
+    let status = Command::new("cc")
+        .args(["-O1", "-shared", "-fPIC", "-o"])
+        .arg(&lib)
+        .arg(&source_path)
+        .status()
+        .expect("can't run cc");
+    assert!(status.success());
+    let status = Command::new("sh")
+        .current_dir(&dir)
+        .args(["-c", MINI_DEBUG_INFO_RECIPE, "sh"])
+        .arg(&lib)
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "the MiniDebugInfo recipe failed, missing nm, objcopy or xz?"
+    );
+
+    let path = CString::new(lib.as_os_str().as_bytes()).unwrap();
+    let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW) };
+    assert!(!handle.is_null());
+    let local_address = unsafe { libc::dlsym(handle, c"local_address".as_ptr()) };
+    assert!(!local_address.is_null());
+    let local_address: extern "C" fn() -> usize = unsafe { std::mem::transmute(local_address) };
+    let local = local_address() as u64;
+
+    // The static function is only in MiniDebugInfo, the other in .dynsym.
+    let symbolizer = Symbolizer::new();
+    let symbol = symbolizer.resolve(local + 1).unwrap();
+    assert_eq!(symbol.name(), Some("local"));
+    assert_eq!(symbol.address(), local);
+    assert!(symbol.size() > 1);
+    let symbol = symbolizer.resolve(local_address as usize as u64).unwrap();
+    assert_eq!(symbol.name(), Some("local_address"));
+    let table = symbolize::SymbolTable::open(&lib).unwrap();
+    let (name, _, _) = table.lookup(local - symbol.module().base()).unwrap();
+    assert_eq!(name, b"local");
+
+    unsafe { libc::dlclose(handle) };
+    std::fs::remove_dir_all(&dir).unwrap();
+}